@@ -0,0 +1,626 @@
+use std::ops::Range;
+
+use image::RgbaImage;
+use rayon::prelude::*;
+
+/// Bytes of one 4x4 BC1 block
+const BC1_BLOCK_SIZE: usize = 8;
+
+/// Bytes of one 4x4 BC7 block
+const BC7_BLOCK_SIZE: usize = 16;
+
+/// Share of the second endpoint in each palette entry of BC7's 2-bit
+/// indices, out of 64
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+
+/// Share of the second endpoint in each palette entry of BC7's 4-bit
+/// indices, out of 64
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Whether an image of this size can be stored block-compressed, which
+/// needs whole 4x4 blocks
+pub fn is_block_aligned(size: (u32, u32)) -> bool {
+    size.0 > 0 && size.1 > 0 && size.0.is_multiple_of(4) && size.1.is_multiple_of(4)
+}
+
+/// Whether every pixel of `image` is fully opaque, so BC1 can store it
+pub fn is_opaque(image: &RgbaImage) -> bool {
+    image.pixels().all(|pixel| pixel[3] == 255)
+}
+
+/// Compress a block-aligned image to BC1, 4 bits per texel without alpha
+pub fn encode_bc1(image: &RgbaImage) -> Vec<u8> {
+    encode_blocks(image, BC1_BLOCK_SIZE, |block, output| {
+        output.copy_from_slice(&encode_color_block(block));
+    })
+}
+
+/// Compress a block-aligned image to BC7, 8 bits per texel with alpha.
+/// Blocks are stored in mode 6, sixteen steps along one line through RGBA
+/// that keeps gradients smooth, or in mode 5 with separate lines for color
+/// and alpha, whichever comes closer.
+pub fn encode_bc7(image: &RgbaImage) -> Vec<u8> {
+    encode_blocks(image, BC7_BLOCK_SIZE, |block, output| {
+        output.copy_from_slice(&encode_bc7_block(block));
+    })
+}
+
+/// Run `encode` for every 4x4 block in row-major order, each writing
+/// `block_size` bytes. Blocks are encoded in parallel.
+fn encode_blocks(
+    image: &RgbaImage,
+    block_size: usize,
+    encode: impl Fn(&[[u8; 4]; 16], &mut [u8]) + Sync,
+) -> Vec<u8> {
+    let (blocks_x, blocks_y) = (image.width() / 4, image.height() / 4);
+    let mut output = vec![0; blocks_x as usize * blocks_y as usize * block_size];
+
+    output
+        .par_chunks_mut(block_size)
+        .enumerate()
+        .for_each(|(index, chunk)| {
+            let (block_x, block_y) = (index as u32 % blocks_x, index as u32 / blocks_x);
+            let mut block = [[0; 4]; 16];
+            for (i, texel) in block.iter_mut().enumerate() {
+                let (x, y) = (block_x * 4 + i as u32 % 4, block_y * 4 + i as u32 / 4);
+                *texel = image.get_pixel(x, y).0;
+            }
+            encode(&block, chunk);
+        });
+
+    output
+}
+
+/// Two RGB565 endpoints spanning the block's visible colors and a 2-bit
+/// palette index per texel
+fn encode_color_block(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    // Transparent texels may have any color, ignoring them keeps the
+    // endpoints tight around what is seen
+    let visible: Vec<[u8; 4]> = block.iter().copied().filter(|texel| texel[3] > 0).collect();
+    let texels = if visible.is_empty() {
+        &block[..]
+    } else {
+        &visible
+    };
+
+    let mut min = [255u8; 3];
+    let mut max = [0u8; 3];
+    for texel in texels {
+        for channel in 0..3 {
+            min[channel] = min[channel].min(texel[channel]);
+            max[channel] = max[channel].max(texel[channel]);
+        }
+    }
+
+    let (mut color0, mut color1) = (to_rgb565(max), to_rgb565(min));
+    // The four-color mode needs the first endpoint to be the larger one
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let mut indices = 0u32;
+    if color0 != color1 {
+        let (end0, end1) = (from_rgb565(color0), from_rgb565(color1));
+        let palette = [end0, end1, mix(end0, end1, 2, 1), mix(end0, end1, 1, 2)];
+        for (i, texel) in block.iter().enumerate() {
+            let nearest = (0..4)
+                .min_by_key(|&entry| color_distance(palette[entry], texel))
+                .unwrap_or(0);
+            indices |= (nearest as u32) << (i * 2);
+        }
+    }
+
+    let mut output = [0; 8];
+    output[..2].copy_from_slice(&color0.to_le_bytes());
+    output[2..4].copy_from_slice(&color1.to_le_bytes());
+    output[4..].copy_from_slice(&indices.to_le_bytes());
+    output
+}
+
+/// Fit candidate encodings of a block in modes 6 and 5, keeping the
+/// closer one
+fn encode_bc7_block(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let texels = hide_transparent_colors(block);
+    let (error, mode6) = encode_bc7_mode6(&texels);
+    if error == 0 {
+        return mode6;
+    }
+    let (mode5_error, mode5) = encode_bc7_mode5(&texels);
+    if mode5_error < error { mode5 } else { mode6 }
+}
+
+/// One line through RGBA with 7-bit endpoints, each sharing a low bit
+/// across its channels, and a 4-bit index per texel. Returns the block and
+/// its error.
+fn encode_bc7_mode6(texels: &[[u8; 4]; 16]) -> (u32, [u8; 16]) {
+    let (error, mut ends, mut indices) = fit_line(texels, 0..4, &BC7_WEIGHTS_4, |ends| {
+        [[0, 0], [0, 1], [1, 0], [1, 1]]
+            .into_iter()
+            .map(|p_bits: [u8; 2]| {
+                std::array::from_fn(|end| {
+                    ends[end].map(|value| {
+                        let high = ((value - p_bits[end] as f32) / 2.0).round();
+                        (high.clamp(0.0, 127.0) as u8) << 1 | p_bits[end]
+                    })
+                })
+            })
+            .collect()
+    });
+    flip_for_anchor(&mut ends, &mut indices, 16);
+
+    let mut bits = BlockBits::default();
+    // Mode 6 is marked by six zero bits and a one
+    bits.push(1 << 6, 7);
+    for channel in 0..4 {
+        for end in &ends {
+            bits.push(end[channel] >> 1, 7);
+        }
+    }
+    for end in &ends {
+        bits.push(end[0] & 1, 1);
+    }
+    bits.push_indices(&indices, 4);
+    (error, bits.bits.to_le_bytes())
+}
+
+/// Separate lines for the color, with 7-bit endpoints, and for alpha, with
+/// 8-bit ones, and 2-bit indices into each. Sprite edges going from
+/// transparent to colors that vary fit this far better than one line.
+/// Returns the block and its error.
+fn encode_bc7_mode5(texels: &[[u8; 4]; 16]) -> (u32, [u8; 16]) {
+    let (color_error, mut color_ends, mut color_indices) =
+        fit_line(texels, 0..3, &BC7_WEIGHTS_2, |ends| {
+            // 7-bit values are widened by repeating their top bit
+            vec![ends.map(|end| {
+                end.map(|value| {
+                    let high = (value * 127.0 / 255.0).round().clamp(0.0, 127.0) as u8;
+                    high << 1 | high >> 6
+                })
+            })]
+        });
+    let (alpha_error, mut alpha_ends, mut alpha_indices) =
+        fit_line(texels, 3..4, &BC7_WEIGHTS_2, |ends| {
+            vec![ends.map(|end| end.map(|value| value.round().clamp(0.0, 255.0) as u8))]
+        });
+    flip_for_anchor(&mut color_ends, &mut color_indices, 4);
+    flip_for_anchor(&mut alpha_ends, &mut alpha_indices, 4);
+
+    let mut bits = BlockBits::default();
+    // Mode 5 is marked by five zero bits and a one, then the channel
+    // rotation, which stays off
+    bits.push(1 << 5, 6);
+    bits.push(0, 2);
+    for channel in 0..3 {
+        for end in &color_ends {
+            bits.push(end[channel] >> 1, 7);
+        }
+    }
+    for end in &alpha_ends {
+        bits.push(end[3], 8);
+    }
+    bits.push_indices(&color_indices, 2);
+    bits.push_indices(&alpha_indices, 2);
+    (color_error + alpha_error, bits.bits.to_le_bytes())
+}
+
+/// Fit a line through `channels` of the texels, then refit it to the
+/// palette entries they picked. `quantize` turns ends into the endpoints
+/// the mode can store, the closest of them all is kept. Returns the error,
+/// endpoints and indices, with the other channels of the endpoints zero.
+fn fit_line<const N: usize>(
+    texels: &[[u8; 4]; 16],
+    channels: Range<usize>,
+    weights: &[u32; N],
+    quantize: impl Fn([[f32; 4]; 2]) -> Vec<[[u8; 4]; 2]>,
+) -> (u32, [[u8; 4]; 2], [u8; 16]) {
+    let texels = texels
+        .map(|texel| std::array::from_fn(|c| if channels.contains(&c) { texel[c] } else { 0 }));
+    let mut ends = principal_ends(&texels);
+    let mut best: Option<(u32, [[u8; 4]; 2], [u8; 16])> = None;
+
+    for _ in 0..3 {
+        for quantized in quantize(ends) {
+            let palette = weights.map(|weight| {
+                std::array::from_fn(|c| {
+                    let (end0, end1) = (quantized[0][c] as u32, quantized[1][c] as u32);
+                    (((64 - weight) * end0 + weight * end1 + 32) >> 6) as u8
+                })
+            });
+            let (indices, error) = nearest_indices(&palette, &texels);
+            if best.is_none_or(|(best_error, ..)| error < best_error) {
+                best = Some((error, quantized, indices));
+            }
+        }
+        match best.and_then(|(_, _, indices)| refit_ends(&texels, &indices, weights)) {
+            Some(refit) => ends = refit,
+            None => break,
+        }
+    }
+
+    best.expect("quantizing gives at least one candidate")
+}
+
+/// Swap the endpoints if needed, so the first texel's index fits the bit
+/// less it gets. The weights are symmetric, so texels keep their colors.
+fn flip_for_anchor(ends: &mut [[u8; 4]; 2], indices: &mut [u8; 16], entries: u8) {
+    if indices[0] >= entries / 2 {
+        ends.swap(0, 1);
+        for index in indices {
+            *index = entries - 1 - *index;
+        }
+    }
+}
+
+/// Bits of a BC7 block, filled from the lowest one up
+#[derive(Default)]
+struct BlockBits {
+    bits: u128,
+    position: u32,
+}
+
+impl BlockBits {
+    fn push(&mut self, value: u8, count: u32) {
+        self.bits |= (value as u128) << self.position;
+        self.position += count;
+    }
+
+    /// Add `bits` per index, one less for the first texel's
+    fn push_indices(&mut self, indices: &[u8; 16], bits: u32) {
+        for (i, index) in indices.iter().enumerate() {
+            self.push(*index, if i == 0 { bits - 1 } else { bits });
+        }
+    }
+}
+
+/// The block with transparent texels, which may have any color, given the
+/// mean of the visible ones so they don't stretch the line
+fn hide_transparent_colors(block: &[[u8; 4]; 16]) -> [[u8; 4]; 16] {
+    let visible: Vec<&[u8; 4]> = block.iter().filter(|texel| texel[3] > 0).collect();
+    if visible.is_empty() {
+        return *block;
+    }
+    let mean: [u8; 3] = std::array::from_fn(|channel| {
+        let sum: u32 = visible.iter().map(|texel| texel[channel] as u32).sum();
+        ((sum + visible.len() as u32 / 2) / visible.len() as u32) as u8
+    });
+
+    block.map(|texel| match texel {
+        [_, _, _, 0] => [mean[0], mean[1], mean[2], 0],
+        texel => texel,
+    })
+}
+
+/// Ends of the line through the texels' mean along the direction they
+/// spread the most, reaching as far as the texels do
+fn principal_ends(texels: &[[u8; 4]; 16]) -> [[f32; 4]; 2] {
+    let points = texels.map(|texel| texel.map(f32::from));
+    let mean: [f32; 4] = std::array::from_fn(|channel| {
+        points.iter().map(|point| point[channel]).sum::<f32>() / 16.0
+    });
+    let offsets = points.map(|point| std::array::from_fn::<f32, 4, _>(|c| point[c] - mean[c]));
+    let dot = |a: &[f32; 4], b: &[f32; 4]| (0..4).map(|c| a[c] * b[c]).sum::<f32>();
+
+    // Power iteration from the texel furthest out, which already points
+    // somewhere along the spread
+    let mut axis = offsets
+        .iter()
+        .copied()
+        .max_by(|a, b| dot(a, a).total_cmp(&dot(b, b)))
+        .unwrap_or_default();
+    for _ in 0..8 {
+        let next: [f32; 4] = std::array::from_fn(|row| {
+            offsets
+                .iter()
+                .map(|offset| offset[row] * dot(offset, &axis))
+                .sum()
+        });
+        let length = dot(&next, &next).sqrt();
+        if length < f32::EPSILON {
+            break;
+        }
+        axis = next.map(|value| value / length);
+    }
+
+    let length = dot(&axis, &axis).sqrt();
+    if length < f32::EPSILON {
+        return [mean, mean];
+    }
+    let axis = axis.map(|value| value / length);
+    let (low, high) = offsets
+        .iter()
+        .map(|offset| dot(offset, &axis))
+        .fold((f32::MAX, f32::MIN), |(low, high), position| {
+            (low.min(position), high.max(position))
+        });
+    [low, high].map(|position| std::array::from_fn(|c| mean[c] + axis[c] * position))
+}
+
+/// Index of the closest palette entry for each texel, and the squared
+/// error of the whole block. Alpha counts more than the colors, an opaque
+/// overlay turning slightly see-through shows the desktop through it.
+fn nearest_indices(palette: &[[u8; 4]], texels: &[[u8; 4]; 16]) -> ([u8; 16], u32) {
+    let distance = |a: &[u8; 4], b: &[u8; 4]| -> u32 {
+        let [r, g, b, alpha] = std::array::from_fn(|c| (a[c] as i32 - b[c] as i32).pow(2) as u32);
+        r + g + b + 8 * alpha
+    };
+
+    let mut indices = [0; 16];
+    let mut error = 0;
+    for (index, texel) in indices.iter_mut().zip(texels) {
+        let (nearest, nearest_distance) = palette
+            .iter()
+            .map(|entry| distance(entry, texel))
+            .enumerate()
+            .min_by_key(|&(_, distance)| distance)
+            .unwrap_or_default();
+        *index = nearest as u8;
+        error += nearest_distance;
+    }
+    (indices, error)
+}
+
+/// Least-squares endpoints for texels keeping the palette entries with
+/// `weights` they picked, or `None` if they all picked the same weight
+fn refit_ends(
+    texels: &[[u8; 4]; 16],
+    indices: &[u8; 16],
+    weights: &[u32],
+) -> Option<[[f32; 4]; 2]> {
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0f32; 4], [0.0f32; 4]);
+    for (texel, index) in texels.iter().zip(indices) {
+        let b = weights[*index as usize] as f32 / 64.0;
+        let a = 1.0 - b;
+        aa += a * a;
+        ab += a * b;
+        bb += b * b;
+        for channel in 0..4 {
+            ax[channel] += a * texel[channel] as f32;
+            bx[channel] += b * texel[channel] as f32;
+        }
+    }
+
+    let determinant = aa * bb - ab * ab;
+    if determinant.abs() < 1e-6 {
+        return None;
+    }
+    Some([
+        std::array::from_fn(|c| (bb * ax[c] - ab * bx[c]) / determinant),
+        std::array::from_fn(|c| (aa * bx[c] - ab * ax[c]) / determinant),
+    ])
+}
+
+fn to_rgb565(color: [u8; 3]) -> u16 {
+    let r = (color[0] as u16 * 31 + 127) / 255;
+    let g = (color[1] as u16 * 63 + 127) / 255;
+    let b = (color[2] as u16 * 31 + 127) / 255;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_rgb565(color: u16) -> [u8; 3] {
+    let (r, g, b) = ((color >> 11) & 31, (color >> 5) & 63, color & 31);
+    [
+        ((r * 255 + 15) / 31) as u8,
+        ((g * 255 + 31) / 63) as u8,
+        ((b * 255 + 15) / 31) as u8,
+    ]
+}
+
+/// Weighted average of two colors, `weight0` parts of `a` and `weight1` of `b`
+fn mix(a: [u8; 3], b: [u8; 3], weight0: u32, weight1: u32) -> [u8; 3] {
+    std::array::from_fn(|channel| {
+        ((a[channel] as u32 * weight0 + b[channel] as u32 * weight1) / (weight0 + weight1)) as u8
+    })
+}
+
+fn color_distance(a: [u8; 3], b: &[u8; 4]) -> u32 {
+    (0..3)
+        .map(|channel| (a[channel] as i32 - b[channel] as i32).pow(2) as u32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// Decode the texels of the first block of BC1 data
+    fn decode_bc1_block(data: &[u8]) -> [[u8; 4]; 16] {
+        let color0 = u16::from_le_bytes([data[0], data[1]]);
+        let color1 = u16::from_le_bytes([data[2], data[3]]);
+        let (end0, end1) = (from_rgb565(color0), from_rgb565(color1));
+        let palette = [end0, end1, mix(end0, end1, 2, 1), mix(end0, end1, 1, 2)];
+        let indices = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+
+        std::array::from_fn(|i| {
+            let [r, g, b] = palette[(indices >> (i * 2)) as usize & 3];
+            [r, g, b, 255]
+        })
+    }
+
+    /// Decode the texels of the first block of BC7 data, which has to be
+    /// in mode 5 or 6 like `encode_bc7` writes
+    fn decode_bc7_block(data: &[u8]) -> [[u8; 4]; 16] {
+        let bits = u128::from_le_bytes(data[..16].try_into().unwrap());
+        let mut position = 0;
+        let mut take = |count: u32| {
+            let value = (bits >> position) as u32 & ((1 << count) - 1);
+            position += count;
+            value
+        };
+        let interpolate = |end0: u32, end1: u32, weight: u32| {
+            (((64 - weight) * end0 + weight * end1 + 32) >> 6) as u8
+        };
+
+        match bits.trailing_zeros() {
+            6 => {
+                take(7);
+                let mut ends = [[0u32; 4]; 2];
+                for channel in 0..4 {
+                    for end in &mut ends {
+                        end[channel] = take(7) << 1;
+                    }
+                }
+                for end in &mut ends {
+                    let p_bit = take(1);
+                    end.iter_mut().for_each(|value| *value |= p_bit);
+                }
+                std::array::from_fn(|i| {
+                    let weight = BC7_WEIGHTS_4[take(if i == 0 { 3 } else { 4 }) as usize];
+                    std::array::from_fn(|c| interpolate(ends[0][c], ends[1][c], weight))
+                })
+            }
+            5 => {
+                take(6);
+                assert_eq!(take(2), 0, "rotated channels");
+                let mut ends = [[0u32; 4]; 2];
+                for channel in 0..3 {
+                    for end in &mut ends {
+                        let high = take(7);
+                        end[channel] = high << 1 | high >> 6;
+                    }
+                }
+                for end in &mut ends {
+                    end[3] = take(8);
+                }
+                let mut indices = || {
+                    let mut indices = [0; 16];
+                    for (i, index) in indices.iter_mut().enumerate() {
+                        *index = BC7_WEIGHTS_2[take(if i == 0 { 1 } else { 2 }) as usize];
+                    }
+                    indices
+                };
+                let (color, alpha) = (indices(), indices());
+                std::array::from_fn(|i| {
+                    std::array::from_fn(|c| {
+                        let weight = if c == 3 { alpha[i] } else { color[i] };
+                        interpolate(ends[0][c], ends[1][c], weight)
+                    })
+                })
+            }
+            mode => panic!("mode {} isn't decoded", mode),
+        }
+    }
+
+    /// Largest difference of any channel between the block of `image`
+    /// and the decoded one
+    fn max_error(image: &RgbaImage, decoded: &[[u8; 4]; 16]) -> u8 {
+        image
+            .pixels()
+            .zip(decoded)
+            .flat_map(|(pixel, texel)| (0..4).map(move |c| pixel[c].abs_diff(texel[c])))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_block_sizes() {
+        assert!(is_block_aligned((8, 4)));
+        assert!(!is_block_aligned((6, 4)));
+        assert!(!is_block_aligned((0, 0)));
+
+        let image = RgbaImage::new(8, 12);
+        assert_eq!(encode_bc1(&image).len(), 6 * 8);
+        assert_eq!(encode_bc7(&image).len(), 6 * 16);
+    }
+
+    #[test]
+    fn test_bc1_round_trip() {
+        // Representable in RGB565, so a solid block comes back exactly
+        let solid = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 255, 255]));
+        let decoded = decode_bc1_block(&encode_bc1(&solid));
+        assert!(decoded.iter().all(|texel| *texel == [255, 0, 255, 255]));
+
+        // A gradient lands on the palette between its ends
+        let gradient = RgbaImage::from_fn(4, 4, |x, _| Rgba([x as u8 * 80, 100, 0, 255]));
+        let decoded = decode_bc1_block(&encode_bc1(&gradient));
+        for (i, texel) in decoded.iter().enumerate() {
+            let expected = (i as i32 % 4) * 80;
+            assert!((texel[0] as i32 - expected).abs() <= 16, "{:?}", texel);
+        }
+    }
+
+    #[test]
+    fn test_bc7_round_trip() {
+        // Each endpoint's low bit is shared by its channels, so a solid
+        // block with odd and even channels is off by at most one
+        let solid = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 254, 255]));
+        assert!(max_error(&solid, &decode_bc7_block(&encode_bc7(&solid))) <= 1);
+
+        // A gradient through every channel falls on the sixteen steps
+        let gradient = RgbaImage::from_fn(4, 4, |x, y| {
+            let step = (x + 4 * y) as u8;
+            Rgba([
+                10 + step * 15,
+                200 - step * 12,
+                90 + step * 3,
+                255 - step * 8,
+            ])
+        });
+        assert!(max_error(&gradient, &decode_bc7_block(&encode_bc7(&gradient))) <= 3);
+
+        // An anti-aliased edge blends two colors in uneven steps
+        let coverage = [
+            0, 0, 31, 143, 0, 18, 176, 255, 9, 120, 250, 255, 77, 230, 255, 255,
+        ];
+        let edge = RgbaImage::from_fn(4, 4, |x, y| {
+            let share = coverage[(x + 4 * y) as usize] as u32;
+            let blend = |a: u32, b: u32| ((a * (255 - share) + b * share) / 255) as u8;
+            Rgba([blend(240, 20), blend(180, 40), blend(30, 160), 255])
+        });
+        assert!(max_error(&edge, &decode_bc7_block(&encode_bc7(&edge))) <= 6);
+
+        // Blocks are laid out in row-major order
+        let image = RgbaImage::from_fn(8, 4, |x, _| {
+            Rgba(if x < 4 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            })
+        });
+        let encoded = encode_bc7(&image);
+        let blue = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+        assert!(max_error(&blue, &decode_bc7_block(&encoded[16..])) <= 1);
+    }
+
+    #[test]
+    fn test_bc7_alpha() {
+        let image = RgbaImage::from_fn(4, 4, |x, y| {
+            Rgba([0, 200, 0, if (x + y) % 2 == 0 { 0 } else { 255 }])
+        });
+        assert!(!is_opaque(&image));
+
+        let decoded = decode_bc7_block(&encode_bc7(&image));
+        for (i, texel) in decoded.iter().enumerate() {
+            let expected = if (i % 4 + i / 4) % 2 == 0 { 0 } else { 255 };
+            assert_eq!(texel[3], expected);
+            // Only the visible texels decide the color
+            if expected == 255 {
+                assert!((texel[1] as i32 - 200).abs() <= 4, "{:?}", texel);
+            }
+        }
+
+        // A sprite's edge, where colors vary next to transparent texels,
+        // gets a line for color and one for alpha in mode 5
+        let edge = RgbaImage::from_fn(4, 4, |x, y| {
+            let visible = x + y >= 3;
+            Rgba([
+                40 + 60 * x as u8,
+                200,
+                250 - 60 * x as u8,
+                if visible { 255 } else { 0 },
+            ])
+        });
+        let encoded = encode_bc7(&edge);
+        assert_eq!(encoded[0] & 0x3f, 1 << 5);
+        let decoded = decode_bc7_block(&encoded);
+        for (pixel, texel) in edge.pixels().zip(&decoded) {
+            assert_eq!(texel[3], pixel[3]);
+            if pixel[3] > 0 {
+                for channel in 0..3 {
+                    assert!(pixel[channel].abs_diff(texel[channel]) <= 2, "{:?}", texel);
+                }
+            }
+        }
+    }
+}
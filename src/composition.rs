@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use wgpu::rwh::RawWindowHandle;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::DirectComposition::{
+    DCompositionCreateDevice, IDCompositionDevice, IDCompositionTarget, IDCompositionVisual,
+};
+use windows::Win32::Graphics::Dxgi::IDXGIDevice;
+use windows::core::Interface;
+
+use crate::window_backend::WindowBackend;
+
+/// A DirectComposition visual covering a window. Swapchains presenting to
+/// a window directly are always opaque on Windows, DWM only blends those
+/// presenting to a visual, with premultiplied alpha.
+pub struct Composition {
+    device: IDCompositionDevice,
+    // Keeps the visual attached to the window
+    _target: IDCompositionTarget,
+    visual: IDCompositionVisual,
+}
+
+impl Composition {
+    /// Attach a visual to `window`, which needs to have been created
+    /// without a redirection bitmap so nothing opaque shows underneath
+    pub fn new(window: &dyn WindowBackend) -> Result<Self> {
+        let hwnd = match window.window_handle()?.as_raw() {
+            RawWindowHandle::Win32(handle) => HWND(handle.hwnd.get() as *mut _),
+            _ => return Err(anyhow!("Not a Win32 window")),
+        };
+
+        // The window handle is valid while `window` is, the objects keep
+        // references to what they need
+        unsafe {
+            let device: IDCompositionDevice = DCompositionCreateDevice(None::<&IDXGIDevice>)?;
+            let target = device.CreateTargetForHwnd(hwnd, true)?;
+            let visual = device.CreateVisual()?;
+            target.SetRoot(&visual)?;
+            Ok(Self {
+                device,
+                _target: target,
+                visual,
+            })
+        }
+    }
+
+    /// A surface presenting to the visual. Only DX12 adapters can draw
+    /// into it.
+    pub fn create_surface(&self, instance: &wgpu::Instance) -> Result<wgpu::Surface<'static>> {
+        // The surface holds its own reference to the visual
+        let surface = unsafe {
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::CompositionVisual(
+                self.visual.as_raw(),
+            ))?
+        };
+        Ok(surface)
+    }
+
+    /// Show what the surface presents, once it has been configured for the
+    /// first time
+    pub fn commit(&self) -> Result<()> {
+        unsafe { self.device.Commit()? };
+        Ok(())
+    }
+}
+
+/// A surface for `window` drawn through DirectComposition, and the
+/// composition it needs kept alive. Falls back to an opaque surface of the
+/// window itself where DirectComposition or DX12 aren't available.
+pub fn create_surface(
+    instance: &wgpu::Instance,
+    window: Arc<dyn WindowBackend>,
+) -> Result<(wgpu::Surface<'static>, Option<Composition>)> {
+    let composition = Composition::new(&*window).and_then(|composition| {
+        let surface = composition.create_surface(instance)?;
+        Ok((surface, composition))
+    });
+    match composition {
+        Ok((surface, composition)) => Ok((surface, Some(composition))),
+        Err(e) => {
+            log::warn!(
+                "No DirectComposition, the overlay can't be transparent: {}",
+                e
+            );
+            Ok((instance.create_surface(window)?, None))
+        }
+    }
+}
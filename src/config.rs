@@ -74,7 +74,8 @@ impl Config {
     }
 }
 
-fn get_config_path() -> Result<PathBuf> {
+/// Where the config file is read from, whether or not it exists
+pub fn get_config_path() -> Result<PathBuf> {
     let home_dir = dirs::home_dir()
         .or_else(|| std::env::var("HOME").ok().map(PathBuf::from))
         .ok_or_else(|| anyhow!("Could not determine home directory"))?;
@@ -340,7 +340,7 @@ impl DeltaCompressor {
             // Calculate aligned buffer size for staging buffer (16-bit data now)
             let unpadded_bytes_per_row = width * 8; // 8 bytes per pixel for Rgba16Sint (4 channels * 2 bytes each)
             let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-            let padded_bytes_per_row = ((unpadded_bytes_per_row + align - 1) / align) * align;
+            let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
             let buffer_size = (padded_bytes_per_row * height) as u64;
 
             self.staging_buffer = Some(self.device.create_buffer(&wgpu::BufferDescriptor {
@@ -357,15 +357,15 @@ impl DeltaCompressor {
     fn calculate_aligned_bytes_per_row(width: u32) -> u32 {
         let unpadded_bytes_per_row = width * 8; // 8 bytes per pixel for Rgba16Sint
         let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-        ((unpadded_bytes_per_row + align - 1) / align) * align
+        unpadded_bytes_per_row.div_ceil(align) * align
     }
 
-    pub async fn compress_sequence(&mut self, images: &[RgbaImage]) -> Result<CompressedSequence> {
+    pub async fn compress_sequence(&mut self, images: &[&RgbaImage]) -> Result<CompressedSequence> {
         if images.is_empty() {
             return Err(anyhow::anyhow!("No images to compress"));
         }
 
-        let first_image = &images[0];
+        let first_image = images[0];
         let (width, height) = first_image.dimensions();
 
         log::info!(
@@ -575,8 +575,8 @@ impl DeltaCompressor {
             compute_pass.set_bind_group(0, &bind_group, &[]);
 
             let (width, height) = self.current_dimensions;
-            let workgroup_count_x = (width + 7) / 8;
-            let workgroup_count_y = (height + 7) / 8;
+            let workgroup_count_x = width.div_ceil(8);
+            let workgroup_count_y = height.div_ceil(8);
 
             compute_pass.dispatch_workgroups(workgroup_count_x, workgroup_count_y, 1);
         }
@@ -698,8 +698,8 @@ impl DeltaCompressor {
             compute_pass.set_bind_group(0, &bind_group, &[]);
 
             let (width, height) = self.current_dimensions;
-            let workgroup_count_x = (width + 7) / 8;
-            let workgroup_count_y = (height + 7) / 8;
+            let workgroup_count_x = width.div_ceil(8);
+            let workgroup_count_y = height.div_ceil(8);
 
             compute_pass.dispatch_workgroups(workgroup_count_x, workgroup_count_y, 1);
         }
@@ -786,7 +786,7 @@ impl DeltaCompressor {
     fn calculate_aligned_bytes_per_row_rgba8(width: u32) -> u32 {
         let unpadded_bytes_per_row = width * 4;
         let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-        ((unpadded_bytes_per_row + align - 1) / align) * align
+        unpadded_bytes_per_row.div_ceil(align) * align
     }
 }
 
@@ -0,0 +1,48 @@
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use winit::event_loop::EventLoopProxy;
+
+use crate::overlay::OverlayCommand;
+
+/// A key chord grabbed from the whole desktop that shows and hides the
+/// overlay, whichever application has the focus. Released when dropped.
+pub struct ToggleHotkey {
+    _manager: GlobalHotKeyManager,
+}
+
+impl ToggleHotkey {
+    /// Grab `chord`, modifiers and a key joined by `+` like "ctrl+alt+k",
+    /// sending `ToggleVisible` through `proxy` whenever it is pressed. Has
+    /// to be called on the thread running the event loop.
+    pub fn register(proxy: EventLoopProxy<OverlayCommand>, chord: &str) -> Result<Self> {
+        let hotkey =
+            HotKey::from_str(chord).map_err(|e| anyhow!("'{}' isn't a key chord: {}", chord, e))?;
+
+        // Grabbing keys only works through XWayland there, which never sees
+        // them while a Wayland window has the focus
+        if cfg!(target_os = "linux") && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Err(anyhow!(
+                "Wayland doesn't let applications grab keys, bind {} to a command in the compositor's settings instead",
+                chord
+            ));
+        }
+
+        let manager = GlobalHotKeyManager::new()?;
+        manager
+            .register(hotkey)
+            .map_err(|e| anyhow!("Can't grab {}: {}", chord, e))?;
+
+        let id = hotkey.id();
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.id() == id && event.state() == HotKeyState::Pressed {
+                // Fails only once the event loop is gone
+                let _ = proxy.send_event(OverlayCommand::ToggleVisible);
+            }
+        }));
+
+        Ok(Self { _manager: manager })
+    }
+}
@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use anyhow::Result;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use winit::window::Icon;
+
+/// Width and height of window icons in pixels
+pub const ICON_SIZE: u32 = 64;
+
+/// A window icon from `image`, fitted into a square
+pub fn from_image(image: &RgbaImage) -> Result<Icon> {
+    let icon = fit_square(image, ICON_SIZE);
+    Ok(Icon::from_rgba(icon.into_raw(), ICON_SIZE, ICON_SIZE)?)
+}
+
+/// A window icon from the image file at `path`
+pub fn load(path: &Path) -> Result<Icon> {
+    from_image(&image::open(path)?.to_rgba8())
+}
+
+/// `image` scaled to fit a square of `size`, centered on transparent
+/// padding where it isn't square. Colors are filtered premultiplied, so
+/// transparent pixels don't bleed into the edges.
+pub fn fit_square(image: &RgbaImage, size: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scale = size as f64 / width.max(height).max(1) as f64;
+    let scaled_width = ((width as f64 * scale).round() as u32).clamp(1, size);
+    let scaled_height = ((height as f64 * scale).round() as u32).clamp(1, size);
+
+    let mut premultiplied = image.clone();
+    for pixel in premultiplied.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+    let mut scaled = imageops::resize(
+        &premultiplied,
+        scaled_width,
+        scaled_height,
+        FilterType::Lanczos3,
+    );
+    for pixel in scaled.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = match alpha {
+                0 => 0,
+                _ => ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+            };
+        }
+    }
+
+    let mut icon = RgbaImage::from_pixel(size, size, Rgba([0, 0, 0, 0]));
+    imageops::overlay(
+        &mut icon,
+        &scaled,
+        ((size - scaled_width) / 2) as i64,
+        ((size - scaled_height) / 2) as i64,
+    );
+    icon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_square() {
+        // A wide frame is centered between transparent bands
+        let frame = RgbaImage::from_pixel(200, 100, Rgba([255, 0, 0, 255]));
+        let icon = fit_square(&frame, 64);
+        assert_eq!(icon.dimensions(), (64, 64));
+        assert_eq!(icon.get_pixel(0, 0)[3], 0);
+        assert_eq!(icon.get_pixel(32, 5)[3], 0);
+        assert_eq!(*icon.get_pixel(32, 32), Rgba([255, 0, 0, 255]));
+
+        // Transparent neighbours leave colors alone at the edges
+        let mut frame = RgbaImage::from_pixel(128, 128, Rgba([0, 0, 0, 0]));
+        for y in 0..128 {
+            for x in 64..128 {
+                frame.put_pixel(x, y, Rgba([0, 255, 0, 255]));
+            }
+        }
+        let icon = fit_square(&frame, 64);
+        let edge = icon.get_pixel(32, 32);
+        assert!(edge[3] > 0 && edge[3] < 255, "{:?}", edge);
+        assert!(edge[0] < 8 && edge[1] > 240, "{:?}", edge);
+    }
+
+    #[test]
+    fn test_from_image() {
+        let frame = RgbaImage::from_pixel(3, 7, Rgba([1, 2, 3, 4]));
+        assert!(from_image(&frame).is_ok());
+    }
+}
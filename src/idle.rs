@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+/// Tells whether the user has left keyboard and mouse alone for a while
+pub trait IdleSource {
+    /// Whether there was no input for at least the timeout the source was
+    /// created with
+    fn is_idle(&mut self) -> Result<bool>;
+}
+
+/// Connect to what tells how long the user has been idle: ext-idle-notify
+/// on Wayland, the MIT-SCREEN-SAVER extension on X11
+#[cfg(target_os = "linux")]
+pub fn connect(timeout: Duration) -> Result<Box<dyn IdleSource>> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        Ok(Box::new(wayland::WaylandIdle::new(timeout)?))
+    } else {
+        Ok(Box::new(x11::X11Idle::new(timeout)?))
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn connect(timeout: Duration) -> Result<Box<dyn IdleSource>> {
+    Ok(Box::new(windows::WindowsIdle { timeout }))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn connect(_timeout: Duration) -> Result<Box<dyn IdleSource>> {
+    Err(anyhow::anyhow!(
+        "Idle time can't be queried on this platform"
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::time::Duration;
+
+    use anyhow::{Result, anyhow};
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::screensaver::{self, ConnectionExt};
+    use x11rb::rust_connection::RustConnection;
+
+    use super::IdleSource;
+
+    /// Asks the X server when input last arrived
+    pub struct X11Idle {
+        connection: RustConnection,
+        root: u32,
+        timeout: Duration,
+    }
+
+    impl X11Idle {
+        pub fn new(timeout: Duration) -> Result<Self> {
+            let (connection, screen) = x11rb::connect(None)?;
+            connection
+                .extension_information(screensaver::X11_EXTENSION_NAME)?
+                .ok_or_else(|| anyhow!("The X server has no MIT-SCREEN-SAVER extension"))?;
+            let root = connection.setup().roots[screen].root;
+            Ok(Self {
+                connection,
+                root,
+                timeout,
+            })
+        }
+    }
+
+    impl IdleSource for X11Idle {
+        fn is_idle(&mut self) -> Result<bool> {
+            let info = self.connection.screensaver_query_info(self.root)?.reply()?;
+            Ok(Duration::from_millis(info.ms_since_user_input as u64) >= self.timeout)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod wayland {
+    use std::time::Duration;
+
+    use anyhow::{Result, anyhow};
+    use smithay_client_toolkit::reexports::client::backend::WaylandError;
+    use smithay_client_toolkit::reexports::client::globals::{
+        GlobalListContents, registry_queue_init,
+    };
+    use smithay_client_toolkit::reexports::client::protocol::{wl_registry, wl_seat};
+    use smithay_client_toolkit::reexports::client::{
+        Connection, Dispatch, EventQueue, QueueHandle, delegate_noop,
+    };
+    use smithay_client_toolkit::reexports::protocols::ext::idle_notify::v1::client::{
+        ext_idle_notification_v1, ext_idle_notifier_v1,
+    };
+
+    use super::IdleSource;
+
+    /// Has the compositor tell when the user goes idle and comes back.
+    /// Compositors count inhibitors such as playing videos as activity.
+    pub struct WaylandIdle {
+        queue: EventQueue<IdleState>,
+        state: IdleState,
+        _notification: ext_idle_notification_v1::ExtIdleNotificationV1,
+    }
+
+    struct IdleState {
+        idle: bool,
+    }
+
+    impl WaylandIdle {
+        pub fn new(timeout: Duration) -> Result<Self> {
+            let connection = Connection::connect_to_env()?;
+            let (globals, mut queue) = registry_queue_init::<IdleState>(&connection)?;
+            let handle = queue.handle();
+
+            let seat: wl_seat::WlSeat = globals.bind(&handle, 1..=1, ())?;
+            let notifier: ext_idle_notifier_v1::ExtIdleNotifierV1 = globals
+                .bind(&handle, 1..=1, ())
+                .map_err(|_| anyhow!("The compositor doesn't support ext-idle-notify"))?;
+            let timeout = timeout.as_millis().min(u32::MAX as u128) as u32;
+            let notification = notifier.get_idle_notification(timeout, &seat, &handle, ());
+
+            let mut state = IdleState { idle: false };
+            queue.roundtrip(&mut state)?;
+            Ok(Self {
+                queue,
+                state,
+                _notification: notification,
+            })
+        }
+    }
+
+    impl IdleSource for WaylandIdle {
+        fn is_idle(&mut self) -> Result<bool> {
+            let read = self.queue.flush().and_then(|()| {
+                self.queue
+                    .prepare_read()
+                    .map_or(Ok(0), |guard| guard.read())
+            });
+            match read {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.queue.dispatch_pending(&mut self.state)?;
+            Ok(self.state.idle)
+        }
+    }
+
+    impl Dispatch<ext_idle_notification_v1::ExtIdleNotificationV1, ()> for IdleState {
+        fn event(
+            state: &mut Self,
+            _proxy: &ext_idle_notification_v1::ExtIdleNotificationV1,
+            event: ext_idle_notification_v1::Event,
+            _data: &(),
+            _conn: &Connection,
+            _qh: &QueueHandle<Self>,
+        ) {
+            match event {
+                ext_idle_notification_v1::Event::Idled => state.idle = true,
+                ext_idle_notification_v1::Event::Resumed => state.idle = false,
+                _ => {}
+            }
+        }
+    }
+
+    impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for IdleState {
+        fn event(
+            _state: &mut Self,
+            _proxy: &wl_registry::WlRegistry,
+            _event: wl_registry::Event,
+            _data: &GlobalListContents,
+            _conn: &Connection,
+            _qh: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    delegate_noop!(IdleState: ignore wl_seat::WlSeat);
+    delegate_noop!(IdleState: ext_idle_notifier_v1::ExtIdleNotifierV1);
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::time::Duration;
+
+    use anyhow::{Result, anyhow};
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    use super::IdleSource;
+
+    /// Compares the time of the last input with the system's uptime
+    pub struct WindowsIdle {
+        pub timeout: Duration,
+    }
+
+    impl IdleSource for WindowsIdle {
+        fn is_idle(&mut self) -> Result<bool> {
+            let mut info = LASTINPUTINFO {
+                cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+                dwTime: 0,
+            };
+            // Writes to `info` only
+            if unsafe { GetLastInputInfo(&mut info) } == 0 {
+                return Err(anyhow!("GetLastInputInfo failed"));
+            }
+            // Both wrap around after 49 days
+            let idle = unsafe { GetTickCount() }.wrapping_sub(info.dwTime);
+            Ok(Duration::from_millis(idle as u64) >= self.timeout)
+        }
+    }
+}
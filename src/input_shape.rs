@@ -0,0 +1,177 @@
+use image::RgbaImage;
+
+/// Pixels that are opaque in any frame of a sequence, which clicks go to
+/// while the rest of the window lets them through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlphaMask {
+    width: u32,
+    height: u32,
+    opaque: Vec<bool>,
+}
+
+impl AlphaMask {
+    /// Pixels with an alpha of at least `threshold` in any of `frames`.
+    /// Frames of another size than the first are stretched over it, as
+    /// they are drawn into the same rectangle. None without frames.
+    pub fn union<'a>(
+        frames: impl IntoIterator<Item = &'a RgbaImage>,
+        threshold: u8,
+    ) -> Option<Self> {
+        let threshold = threshold.max(1);
+        let mut frames = frames.into_iter().peekable();
+        let (width, height) = frames.peek()?.dimensions();
+        let mut opaque = vec![false; width as usize * height as usize];
+        for frame in frames {
+            let (frame_width, frame_height) = frame.dimensions();
+            for y in 0..height {
+                let frame_y = (y as u64 * frame_height as u64 / height as u64) as u32;
+                for x in 0..width {
+                    let frame_x = (x as u64 * frame_width as u64 / width as u64) as u32;
+                    if frame.get_pixel(frame_x, frame_y)[3] >= threshold {
+                        opaque[(y * width + x) as usize] = true;
+                    }
+                }
+            }
+        }
+        Some(Self {
+            width,
+            height,
+            opaque,
+        })
+    }
+
+    /// Rectangles as x, y, width and height in window pixels covering the
+    /// opaque pixels of the mask drawn at `rect`, mirrored horizontally and
+    /// vertically as `flip` says. Runs of pixels repeating on the next row
+    /// grow the rectangle above rather than adding another.
+    pub fn rectangles(&self, rect: [f32; 4], flip: (bool, bool)) -> Vec<[i32; 4]> {
+        let [left, top, width, height] = rect;
+        let (scale_x, scale_y) = (
+            width / self.width.max(1) as f32,
+            height / self.height.max(1) as f32,
+        );
+        let mut rectangles: Vec<[i32; 4]> = Vec::new();
+        // Rectangles reaching down to the row above, by their columns
+        let mut open: Vec<usize> = Vec::new();
+        for row in 0..self.height {
+            let mask_row = if flip.1 { self.height - 1 - row } else { row };
+            let start_y = (top + row as f32 * scale_y).floor() as i32;
+            let end_y = (top + (row + 1) as f32 * scale_y).ceil() as i32;
+            if end_y <= start_y {
+                continue;
+            }
+
+            let mut still_open = Vec::new();
+            for (start, end) in self.runs(mask_row) {
+                let (start, end) = if flip.0 {
+                    (self.width - end, self.width - start)
+                } else {
+                    (start, end)
+                };
+                let x = (left + start as f32 * scale_x).floor() as i32;
+                let run_width = (left + end as f32 * scale_x).ceil() as i32 - x;
+                if run_width <= 0 {
+                    continue;
+                }
+                let above = open.iter().copied().find(|&index| {
+                    let [above_x, above_y, above_width, above_height] = rectangles[index];
+                    above_x == x && above_width == run_width && above_y + above_height >= start_y
+                });
+                match above {
+                    Some(index) => {
+                        let rectangle = &mut rectangles[index];
+                        rectangle[3] = end_y - rectangle[1];
+                        still_open.push(index);
+                    }
+                    None => {
+                        still_open.push(rectangles.len());
+                        rectangles.push([x, start_y, run_width, end_y - start_y]);
+                    }
+                }
+            }
+            open = still_open;
+        }
+        rectangles
+    }
+
+    /// Columns where runs of opaque pixels on `row` start and end
+    fn runs(&self, row: u32) -> Vec<(u32, u32)> {
+        let pixels = &self.opaque[(row * self.width) as usize..((row + 1) * self.width) as usize];
+        let mut runs = Vec::new();
+        let mut start = None;
+        for (x, &opaque) in pixels.iter().enumerate() {
+            match (opaque, start) {
+                (true, None) => start = Some(x as u32),
+                (false, Some(run_start)) => {
+                    runs.push((run_start, x as u32));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(run_start) = start {
+            runs.push((run_start, self.width));
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// A `width` by `height` frame, opaque in `rect` given as x, y, width
+    /// and height
+    fn frame(width: u32, height: u32, rect: [u32; 4]) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let inside = (rect[0]..rect[0] + rect[2]).contains(&x)
+                && (rect[1]..rect[1] + rect[3]).contains(&y);
+            Rgba([255, 255, 255, if inside { 255 } else { 10 }])
+        })
+    }
+
+    #[test]
+    fn test_union() {
+        let frames = [frame(8, 4, [0, 0, 2, 2]), frame(8, 4, [6, 2, 2, 2])];
+        let mask = AlphaMask::union(&frames, 128).unwrap();
+        assert_eq!(mask.runs(0), vec![(0, 2)]);
+        assert_eq!(mask.runs(3), vec![(6, 8)]);
+
+        // Below the threshold counts as transparent, at it as opaque
+        let mask = AlphaMask::union(&frames, 10).unwrap();
+        assert_eq!(mask.runs(1), vec![(0, 8)]);
+
+        // Smaller frames are stretched over the first
+        let frames = [frame(8, 4, [0, 0, 0, 0]), frame(4, 2, [2, 1, 2, 1])];
+        let mask = AlphaMask::union(&frames, 128).unwrap();
+        assert_eq!(mask.runs(1), Vec::new());
+        assert_eq!(mask.runs(2), vec![(4, 8)]);
+
+        assert_eq!(AlphaMask::union(std::iter::empty(), 128), None);
+    }
+
+    #[test]
+    fn test_rectangles() {
+        let mask = AlphaMask::union(&[frame(8, 4, [2, 1, 3, 3])], 128).unwrap();
+
+        // Rows with the same run make one rectangle, scaled into the rect
+        assert_eq!(
+            mask.rectangles([10.0, 20.0, 16.0, 8.0], (false, false)),
+            vec![[14, 22, 6, 6]]
+        );
+        // Mirrored horizontally and vertically
+        assert_eq!(
+            mask.rectangles([0.0, 0.0, 8.0, 4.0], (true, true)),
+            vec![[3, 0, 3, 3]]
+        );
+
+        // Runs that differ between rows start rectangles of their own
+        let frames = [frame(4, 2, [0, 0, 4, 1]), frame(4, 2, [1, 1, 1, 1])];
+        let mask = AlphaMask::union(&frames, 128).unwrap();
+        assert_eq!(
+            mask.rectangles([0.0, 0.0, 4.0, 2.0], (false, false)),
+            vec![[0, 0, 4, 1], [1, 1, 1, 1]]
+        );
+    }
+}
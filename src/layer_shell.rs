@@ -0,0 +1,406 @@
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState, Region};
+use smithay_client_toolkit::output::{OutputHandler, OutputState};
+use smithay_client_toolkit::reexports::client::backend::WaylandError;
+use smithay_client_toolkit::reexports::client::globals::registry_queue_init;
+use smithay_client_toolkit::reexports::client::protocol::{wl_output, wl_surface};
+use smithay_client_toolkit::reexports::client::{Connection, EventQueue, Proxy, QueueHandle};
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::shell::WaylandSurface;
+use smithay_client_toolkit::shell::wlr_layer::{
+    Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+    LayerSurfaceConfigure,
+};
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry, registry_handlers,
+};
+use wgpu::rwh::{
+    DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
+    RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, WindowHandle,
+};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::window::WindowId;
+
+use crate::window_backend::{BackendEvent, Corner, Placement, WindowBackend};
+
+/// An overlay surface on the wlr-layer-shell overlay layer, above every
+/// window and outside the compositor's tiling, decorations and taskbars.
+/// Wgpu draws into it through its raw Wayland handles.
+pub struct LayerShellWindow {
+    connection: Connection,
+    /// Creates the regions input is restricted to
+    compositor: CompositorState,
+    layer: LayerSurface,
+    events: Mutex<LayerEvents>,
+}
+
+/// The layer surface's own event queue, dispatched by `poll_events`
+struct LayerEvents {
+    queue: EventQueue<LayerState>,
+    state: LayerState,
+}
+
+struct LayerState {
+    registry: RegistryState,
+    outputs: OutputState,
+    size: PhysicalSize<u32>,
+    configured: bool,
+    pending: Vec<BackendEvent>,
+}
+
+impl LayerShellWindow {
+    /// Create a layer surface of `size` in the corner `options` asks for,
+    /// on the output named `output` or one the compositor picks. Fails when
+    /// not running under Wayland or when the compositor doesn't offer
+    /// wlr-layer-shell. Clicks and keys go to the windows underneath unless
+    /// `interactive`. Compositors see the surface under `namespace`, e.g.
+    /// for layer rules.
+    pub fn new(
+        options: Placement,
+        output: Option<&str>,
+        size: PhysicalSize<u32>,
+        interactive: bool,
+        namespace: &str,
+    ) -> Result<Self> {
+        let connection = Connection::connect_to_env()?;
+        let (globals, mut queue) = registry_queue_init(&connection)?;
+        let handle = queue.handle();
+
+        let compositor = CompositorState::bind(&globals, &handle)?;
+        let layer_shell = LayerShell::bind(&globals, &handle)
+            .map_err(|_| anyhow!("The compositor doesn't support wlr-layer-shell"))?;
+
+        let mut state = LayerState {
+            registry: RegistryState::new(&globals),
+            outputs: OutputState::new(&globals, &handle),
+            size,
+            configured: false,
+            pending: Vec::new(),
+        };
+
+        // Outputs are only named once the compositor has described them
+        let output = match output {
+            Some(name) => {
+                queue.roundtrip(&mut state)?;
+                let found = state.outputs.outputs().find(|output| {
+                    state
+                        .outputs
+                        .info(output)
+                        .is_some_and(|info| info.name.as_deref() == Some(name))
+                });
+                if found.is_none() {
+                    log::warn!(
+                        "No Wayland output named {}, letting the compositor pick",
+                        name
+                    );
+                }
+                found
+            }
+            None => None,
+        };
+
+        let surface = compositor.create_surface(&handle);
+        let layer = layer_shell.create_layer_surface(
+            &handle,
+            surface,
+            Layer::Overlay,
+            Some(namespace),
+            output.as_ref(),
+        );
+        layer.set_anchor(anchor(options.corner));
+        let (margin_x, margin_y) = (options.margin.0 as i32, options.margin.1 as i32);
+        layer.set_margin(margin_y, margin_x, margin_y, margin_x);
+        layer.set_size(size.width, size.height);
+        if interactive {
+            layer.set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
+        } else {
+            layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+            let region = Region::new(&compositor)?;
+            layer.set_input_region(Some(region.wl_region()));
+        }
+        layer.commit();
+
+        // Nothing may be drawn before the first configure
+        while !state.configured {
+            queue.blocking_dispatch(&mut state)?;
+        }
+        // Only sizes changed by later configures are news
+        state.pending.clear();
+
+        Ok(Self {
+            connection,
+            compositor,
+            layer,
+            events: Mutex::new(LayerEvents { queue, state }),
+        })
+    }
+
+    fn events(&self) -> std::sync::MutexGuard<'_, LayerEvents> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl WindowBackend for LayerShellWindow {
+    // Derived from the surface like winit does for its Wayland windows
+    fn id(&self) -> WindowId {
+        WindowId::from(self.layer.wl_surface().id().as_ptr() as u64)
+    }
+
+    fn inner_size(&self) -> PhysicalSize<u32> {
+        self.events().state.size
+    }
+
+    fn request_inner_size(&self, size: PhysicalSize<u32>) {
+        self.layer.set_size(size.width, size.height);
+        self.layer.commit();
+    }
+
+    // Layer surfaces are shown once configured and have no title
+
+    fn set_visible(&self, _visible: bool) {}
+
+    fn set_title(&self, _title: &str) {}
+
+    fn request_redraw(&self) {
+        let pending = &mut self.events().state.pending;
+        if !pending.contains(&BackendEvent::RedrawRequested) {
+            pending.push(BackendEvent::RedrawRequested);
+        }
+    }
+
+    // The compositor places the surface by its anchor and margins
+
+    fn outer_position(&self) -> Option<PhysicalPosition<i32>> {
+        None
+    }
+
+    fn set_outer_position(&self, _position: PhysicalPosition<i32>) {}
+
+    fn set_input_shape(&self, rectangles: Option<&[[i32; 4]]>) -> bool {
+        let region = match rectangles
+            .map(|_| Region::new(&self.compositor))
+            .transpose()
+        {
+            Ok(region) => region,
+            Err(e) => {
+                log::warn!("Can't create an input region: {}", e);
+                return false;
+            }
+        };
+        if let (Some(region), Some(rectangles)) = (&region, rectangles) {
+            for &[x, y, width, height] in rectangles {
+                region.add(x, y, width, height);
+            }
+        }
+        // Applies with the next commit, no region takes input anywhere
+        self.layer
+            .set_input_region(region.as_ref().map(Region::wl_region));
+        self.layer.commit();
+        true
+    }
+
+    fn poll_events(&self) -> Vec<BackendEvent> {
+        let events = &mut *self.events();
+        let read = events.queue.flush().and_then(|()| {
+            events
+                .queue
+                .prepare_read()
+                .map_or(Ok(0), |guard| guard.read())
+        });
+        match read {
+            Ok(_) => {}
+            Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => {
+                log::error!("Lost the Wayland connection: {}", e);
+                return vec![BackendEvent::CloseRequested];
+            }
+        }
+        if let Err(e) = events.queue.dispatch_pending(&mut events.state) {
+            log::error!("Failed to handle Wayland events: {}", e);
+            return vec![BackendEvent::CloseRequested];
+        }
+        std::mem::take(&mut events.state.pending)
+    }
+
+    fn needs_polling(&self) -> bool {
+        true
+    }
+}
+
+impl HasWindowHandle for LayerShellWindow {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        let surface = NonNull::new(self.layer.wl_surface().id().as_ptr().cast())
+            .ok_or(HandleError::Unavailable)?;
+        let raw = RawWindowHandle::Wayland(WaylandWindowHandle::new(surface));
+        // The surface lives as long as `self`
+        Ok(unsafe { WindowHandle::borrow_raw(raw) })
+    }
+}
+
+impl HasDisplayHandle for LayerShellWindow {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        let display = NonNull::new(self.connection.backend().display_ptr().cast())
+            .ok_or(HandleError::Unavailable)?;
+        let raw = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(display));
+        // The connection lives as long as `self`
+        Ok(unsafe { DisplayHandle::borrow_raw(raw) })
+    }
+}
+
+/// Edges of the output a surface in `corner` is anchored to, none
+/// centering it
+fn anchor(corner: Corner) -> Anchor {
+    match corner {
+        Corner::TopLeft => Anchor::TOP | Anchor::LEFT,
+        Corner::TopRight => Anchor::TOP | Anchor::RIGHT,
+        Corner::BottomLeft => Anchor::BOTTOM | Anchor::LEFT,
+        Corner::BottomRight => Anchor::BOTTOM | Anchor::RIGHT,
+        Corner::Center => Anchor::empty(),
+    }
+}
+
+impl LayerShellHandler for LayerState {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
+        self.pending.push(BackendEvent::CloseRequested);
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        // A zero means the size is up to us, which is the one requested
+        let (width, height) = configure.new_size;
+        let size = PhysicalSize::new(
+            if width == 0 { self.size.width } else { width },
+            if height == 0 {
+                self.size.height
+            } else {
+                height
+            },
+        );
+        if size != self.size {
+            self.size = size;
+            self.pending.push(BackendEvent::Resized(size));
+        }
+        self.configured = true;
+        self.pending.push(BackendEvent::RedrawRequested);
+    }
+}
+
+impl CompositorHandler for LayerState {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_factor: i32,
+    ) {
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for LayerState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.outputs
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl ProvidesRegistryState for LayerState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry
+    }
+
+    registry_handlers![OutputState];
+}
+
+delegate_compositor!(LayerState);
+delegate_output!(LayerState);
+delegate_layer!(LayerState);
+delegate_registry!(LayerState);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fails_without_wayland() {
+        // Without a compositor the overlay falls back to a winit window
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return;
+        }
+        let window = LayerShellWindow::new(
+            Placement::default(),
+            None,
+            PhysicalSize::new(64, 64),
+            false,
+            "anibuddy",
+        );
+        assert!(window.is_err());
+    }
+}
@@ -1,38 +1,85 @@
+mod block_compression;
+#[cfg(target_os = "windows")]
+mod composition;
 mod config;
 mod delta_compression;
+#[cfg(feature = "hotkey")]
+mod hotkey;
+mod icon;
+mod idle;
+mod input_shape;
+#[cfg(target_os = "linux")]
+mod layer_shell;
 mod media_loader;
+mod menu;
+mod mipmaps;
+mod movement;
 mod overlay;
+mod pointer;
 mod renderer;
+mod shadow;
+mod state;
+mod supersampling;
+#[cfg(test)]
+mod test_dir;
+mod touch;
+#[cfg(all(feature = "tray", target_os = "linux"))]
+mod tray;
+mod watcher;
+mod window_backend;
 
 use anyhow::{Result, anyhow};
-use clap::{CommandFactory, Parser};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use config::{Config, PresetConfig, is_likely_path};
 use env_logger::Env;
-use media_loader::{MediaSource, detect_media_type};
-use overlay::OverlayApplication;
-use std::path::Path;
+use media_loader::{
+    LoadOptions, LoopMode, MediaSource, ProgressFn, SortOrder, SpriteSheetGrid, detect_media_type,
+};
+use movement::{BounceOptions, FollowOptions, WanderOptions};
+use overlay::{ClickAction, MAIN_SEQUENCE, OverlayApplication, StartFrame};
+use renderer::{
+    AdapterSelection, ChromaKey, ColorEffects, Filtering, Outline, PresentMode, ScaleMode,
+};
+use shadow::Shadow;
+use state::SavedState;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use window_backend::{Corner, CursorStyle, DragModifier, Placement, X11Mode};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 #[derive(Parser)]
 #[command(name = "anibuddy")]
-#[command(about = "An overlay for animated gifs and apngs for the wayland desktop")]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(about = "An overlay for animated gifs, apngs and webps for the wayland desktop")]
 #[command(
     long_about = r#"An overlay application that can display animated sequences from:
 - Directories containing image files (PNG, JPG, JPEG)
 - GIF files
 - APNG files
+- Animated WebP files
 - Named presets from config file
 
 Supports delta compression to reduce memory usage for animations with small changes between frames."#
 )]
 struct Args {
-    /// Path to directory with images, GIF file, APNG file, or preset name
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to directory with images, image file, GIF, APNG, WebP, preset name, or - for stdin
     path_or_preset: Option<String>,
 
-    /// Frames per second (overrides preset FPS if specified)
+    /// Frames per second (overrides preset FPS and per-frame GIF delays if specified)
     #[arg(short, long)]
     fps: Option<u64>,
 
+    /// Play at this many times the frames' own pace, from 0.05 to 20; + and - change it while
+    /// running
+    #[arg(long, value_name = "FACTOR", value_parser = parse_finite, default_value_t = 1.0)]
+    speed: f32,
+
     /// Enable delta compression for memory efficiency (overrides preset compression if specified)
     #[arg(short, long)]
     compress: bool,
@@ -40,37 +87,617 @@ struct Args {
     /// List available presets and exit
     #[arg(long)]
     list_presets: bool,
+
+    /// Neither restore the position and settings of the last run nor remember this run's
+    #[arg(long)]
+    no_persist: bool,
+
+    /// Treat the input image as a sprite sheet with this many columns
+    #[arg(long, requires = "sheet_rows")]
+    sheet_cols: Option<u32>,
+
+    /// Treat the input image as a sprite sheet with this many rows
+    #[arg(long, requires = "sheet_cols")]
+    sheet_rows: Option<u32>,
+
+    /// Number of frames in the sprite sheet (defaults to all non-empty cells)
+    #[arg(long, requires = "sheet_cols")]
+    sheet_frames: Option<usize>,
+
+    /// Order directory frames by plain file name instead of numeric-aware sorting
+    #[arg(long)]
+    lexicographic_sort: bool,
+
+    /// Also load frames from subdirectories of the image directory
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Only load directory frames whose relative path matches this glob (e.g. "dance/*.png")
+    #[arg(long, value_name = "GLOB")]
+    filter: Option<String>,
+
+    /// Start playing right away and decode the remaining directory frames in the background
+    #[arg(long)]
+    lazy: bool,
+
+    /// Crop fully transparent borders shared by all frames
+    #[arg(long)]
+    trim: bool,
+
+    /// Play frames in this order, as comma-separated indices that may repeat (e.g. 0,1,2,2,1)
+    #[arg(long, value_name = "INDICES", value_delimiter = ',')]
+    frame_order: Option<Vec<usize>>,
+
+    /// Keep only every N-th frame
+    #[arg(long, value_name = "N")]
+    frame_step: Option<usize>,
+
+    /// Play the frames back to front
+    #[arg(long)]
+    reverse_frames: bool,
+
+    /// Start out playing backwards, R changes direction while running. Unlike --reverse-frames
+    /// this keeps the frames in order, so it works on lazily loaded sequences.
+    #[arg(long)]
+    reverse: bool,
+
+    /// Start on this frame, counting from 0, or this far into the sequence like 50%
+    #[arg(long, visible_alias = "start", value_name = "FRAME|PERCENT%", value_parser = parse_start_frame)]
+    start_frame: Option<StartFrame>,
+
+    /// Exit when --start-frame is past the last frame, instead of starting on the last one
+    #[arg(long, requires = "start_frame")]
+    strict_start: bool,
+
+    /// Start on a random frame, so several overlays don't dance in step
+    #[arg(long, conflicts_with = "start_frame")]
+    random_start: bool,
+
+    /// What happens after the last frame, instead of what a sequence's meta.toml says
+    /// [default: loop]
+    #[arg(long, value_name = "MODE")]
+    loop_mode: Option<LoopMode>,
+
+    /// Exit after playing through this many times, fading out first; ping-pong counts there and
+    /// back as once. 0 loops forever
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    loops: u32,
+
+    /// Exit, fading out, after the overlay has been shown this long, like 30s, 2m or 1h15m;
+    /// with --loops whichever comes first
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    duration: Option<Duration>,
+
+    /// Show every frame even when running late, rather than skipping ahead to keep time
+    #[arg(long)]
+    no_frame_skip: bool,
+
+    /// Leave out directory frames that fail to decode instead of exiting
+    #[arg(long)]
+    skip_bad_frames: bool,
+
+    /// Fail instead of downscaling frames larger than the GPU's maximum texture size
+    #[arg(long)]
+    no_auto_downscale: bool,
+
+    /// Downscale frames when they would need more texture memory than this (e.g. 512M, 2G)
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_gpu_mem: Option<u64>,
+
+    /// Store frames with 4 bits per channel to halve GPU memory, at the cost of color depth
+    #[arg(long)]
+    low_memory: bool,
+
+    /// Store frames BC1/BC7 compressed on the GPU where supported, for 4-8x less memory
+    #[arg(long)]
+    block_compress: bool,
+
+    /// Pack small frames into a few large textures instead of one texture layer each
+    #[arg(long)]
+    atlas: bool,
+
+    /// Skip generating mipmaps, for faster startup when the window is never shrunk
+    #[arg(long)]
+    no_mipmaps: bool,
+
+    /// Fade each frame into the next instead of cutting, smoothing low-fps sequences
+    #[arg(long)]
+    crossfade: bool,
+
+    /// Texture filtering, nearest keeps pixel art crisp and only scales by whole factors
+    #[arg(long, value_enum, default_value_t = Filtering::Linear)]
+    sampling: Filtering,
+
+    /// How frames are placed in a window of a different size or aspect ratio
+    #[arg(long, value_enum, default_value_t = ScaleMode::Stretch)]
+    scale: ScaleMode,
+
+    /// Draw frames this many times larger and filter them down to the window,
+    /// smoothing large frames shown small (1.0 to 4.0)
+    #[arg(long, value_name = "FACTOR", value_parser = parse_finite, default_value_t = 1.0)]
+    render_scale: f32,
+
+    /// Rotate frames clockwise by this many degrees, the window grows to fit
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    rotate: f32,
+
+    /// Mirror frames horizontally, H toggles this while running
+    #[arg(long)]
+    flip_h: bool,
+
+    /// Mirror frames vertically
+    #[arg(long)]
+    flip_v: bool,
+
+    /// Opacity of the whole overlay from 0.0 to 1.0, [ and ] adjust it while running
+    #[arg(long, value_parser = parse_finite, default_value_t = 1.0)]
+    opacity: f32,
+
+    /// Seconds the overlay takes to fade in on start and out on quit, 0 turns fading off. Quitting
+    /// again while it fades out exits right away.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds, default_value = "0.3")]
+    fade: Duration,
+
+    /// Dim the overlay to this share of its opacity, from 0.0 to 1.0, while the pointer is over it,
+    /// fading back once it leaves
+    #[arg(long, value_name = "OPACITY")]
+    hover_opacity: Option<f32>,
+
+    /// Dim the overlay once the pointer is this many pixels from it (X11 and Windows)
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 0,
+        requires = "hover_opacity"
+    )]
+    hover_radius: u32,
+
+    /// Color multiplied into every frame, e.g. #80a0ff for a blue night look
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color, default_value = "#ffffff")]
+    tint: [u8; 3],
+
+    /// Rotate the hue of frames by this many degrees
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    hue_shift: f32,
+
+    /// Scale color saturation, 0.0 is gray and values above 1.0 are more vivid
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    saturation: f32,
+
+    /// Show frames in shades of gray, combine with --tint for sepia and the like
+    #[arg(long)]
+    grayscale: bool,
+
+    /// Invert the colors of frames
+    #[arg(long)]
+    invert: bool,
+
+    /// Color filling the window behind frames, #rrggbb or #rrggbbaa with alpha
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_rgba, default_value = "#00000000")]
+    background: [u8; 4],
+
+    /// Make this background color transparent, for frames rendered on a green screen
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color)]
+    chroma_key: Option<[u8; 3]>,
+
+    /// How far a color may be from the chroma key and still become transparent (0.0 to 1.7)
+    #[arg(long, default_value_t = 0.25, requires = "chroma_key")]
+    chroma_tolerance: f32,
+
+    /// Width of the fade beyond the chroma key tolerance that softens edges
+    #[arg(long, default_value_t = 0.1, requires = "chroma_key")]
+    chroma_softness: f32,
+
+    /// Draw this many copies of the animation side by side (up to 16)
+    #[arg(long, value_name = "COUNT", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=16))]
+    instances: u64,
+
+    /// Gap between instances in pixels
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 0,
+        requires = "instances"
+    )]
+    instance_spacing: u32,
+
+    /// Frames each instance runs ahead of the previous one, spread evenly by default
+    #[arg(long, value_name = "FRAMES", requires = "instances")]
+    instance_offset: Option<usize>,
+
+    /// Draw an outline this many frame pixels thick around the opaque parts of frames
+    #[arg(long, value_name = "PIXELS")]
+    outline: Option<f32>,
+
+    /// Color of the outline
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color, default_value = "#ffffff", requires = "outline")]
+    outline_color: [u8; 3],
+
+    /// Draw a soft drop shadow behind frames, the window grows to leave room for it
+    #[arg(long)]
+    shadow: bool,
+
+    /// Color of the drop shadow
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color, default_value = "#000000", requires = "shadow")]
+    shadow_color: [u8; 3],
+
+    /// Offset of the drop shadow in pixels, positive values move it right and down
+    #[arg(
+        long,
+        value_name = "DX,DY",
+        value_parser = parse_offset,
+        default_value = "4,4",
+        allow_hyphen_values = true,
+        requires = "shadow"
+    )]
+    shadow_offset: (f32, f32),
+
+    /// How far the drop shadow's edge is blurred, in pixels (up to 64)
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 6.0,
+        requires = "shadow"
+    )]
+    shadow_blur: f32,
+
+    /// Opacity of the drop shadow from 0.0 to 1.0
+    #[arg(long, default_value_t = 0.5, requires = "shadow")]
+    shadow_opacity: f32,
+
+    /// Draw frames with this WGSL fragment shader instead of the built-in one, reloaded when saved
+    #[arg(long, value_name = "FILE")]
+    shader: Option<PathBuf>,
+
+    /// Use the built-in shader if the --shader file fails to compile, instead of exiting
+    #[arg(long, requires = "shader")]
+    shader_fallback: bool,
+
+    /// How frames are presented, mailbox lowers latency where supported
+    #[arg(long, value_enum, default_value_t = PresentMode::Fifo)]
+    present_mode: PresentMode,
+
+    /// Take mouse clicks over the overlay instead of passing them to the windows underneath
+    #[arg(long)]
+    interactive: bool,
+
+    /// With --interactive, take clicks only on pixels opaque in some frame and pass the others
+    /// through (X11 and --layer-shell)
+    #[arg(long, requires = "interactive")]
+    input_shape: bool,
+
+    /// Alpha from 1 to 255 from which pixels count as opaque for --input-shape
+    #[arg(
+        long,
+        value_name = "ALPHA",
+        default_value_t = 128,
+        requires = "input_shape"
+    )]
+    input_threshold: u8,
+
+    /// Show the overlay on this monitor, given as its index or part of its name from --list-monitors
+    #[arg(long, value_name = "INDEX|NAME")]
+    monitor: Option<String>,
+
+    /// Show the overlay on every monitor, each copy in a window of its own. The one on --monitor
+    /// moves around, the others stay centered or at --anchor.
+    #[arg(long)]
+    all_monitors: bool,
+
+    /// Trail the mouse pointer around the desktop (X11 and Windows)
+    #[arg(long, conflicts_with_all = ["interactive", "anchor", "position"])]
+    follow_cursor: bool,
+
+    /// Where the overlay's top left corner sits relative to the pointer it follows
+    #[arg(
+        long,
+        value_name = "DX,DY",
+        value_parser = parse_position,
+        default_value = "24,24",
+        allow_hyphen_values = true,
+        requires = "follow_cursor"
+    )]
+    follow_offset: (i32, i32),
+
+    /// Seconds the overlay lags behind the pointer, 0 sticks to it
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 0.2,
+        requires = "follow_cursor"
+    )]
+    follow_smoothing: f64,
+
+    /// Wander to random places on the monitor, facing the way the overlay walks
+    #[arg(long, conflicts_with_all = ["follow_cursor", "anchor", "position"])]
+    wander: bool,
+
+    /// Top wandering speed in pixels per second
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 80.0,
+        requires = "wander"
+    )]
+    wander_speed: f64,
+
+    /// Seconds between picking where to wander next
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_seconds,
+        default_value = "6",
+        requires = "wander"
+    )]
+    wander_interval: Duration,
+
+    /// Chance of resting instead of wandering on, from 0 to 1
+    #[arg(
+        long,
+        value_name = "CHANCE",
+        default_value_t = 0.3,
+        requires = "wander"
+    )]
+    wander_pause_chance: f64,
+
+    /// Distance kept from the monitor's edges while wandering, as X,Y or one value
+    #[arg(
+        long,
+        value_name = "X,Y",
+        value_parser = parse_margin,
+        default_value = "0",
+        requires = "wander"
+    )]
+    wander_margin: (u32, u32),
+
+    /// Move diagonally and bounce off the monitor's edges like the DVD logo
+    #[arg(long, conflicts_with_all = ["follow_cursor", "wander", "anchor", "position"])]
+    bounce: bool,
+
+    /// Bouncing speed in pixels per second
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 150.0,
+        requires = "bounce"
+    )]
+    bounce_speed: f64,
+
+    /// Bounce around the whole desktop rather than the monitor the overlay starts on
+    #[arg(long, requires = "bounce")]
+    roam: bool,
+
+    /// Keep the overlay in this corner or the center of its monitor, also when the monitor
+    /// layout or the window's size changes
+    #[arg(long, value_enum, conflicts_with = "position")]
+    anchor: Option<Corner>,
+
+    /// Distance of an anchored overlay from the screen edges in pixels, as X,Y or one value
+    #[arg(long, value_name = "X,Y", value_parser = parse_margin, requires = "anchor")]
+    margin: Option<(u32, u32)>,
+
+    /// Show the overlay at this position in physical pixels; negative values count from the
+    /// monitor's right and bottom edges, so -20,-20 is near the bottom right corner
+    #[arg(
+        long,
+        value_name = "X,Y",
+        value_parser = parse_position,
+        allow_hyphen_values = true
+    )]
+    position: Option<(i32, i32)>,
+
+    /// List the monitors --monitor can choose from and exit
+    #[arg(long)]
+    list_monitors: bool,
+
+    /// Size the window like this instead of like the frames, --scale decides how they fill it
+    #[arg(long, value_name = "WxH", value_parser = parse_window_size)]
+    size: Option<(u32, u32)>,
+
+    /// Multiply the window's size by this, Ctrl with + and - change it while running
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    window_scale: f64,
+
+    /// Percent of its size an interactive overlay grows or shrinks by per notch scrolled over it;
+    /// with Ctrl held, scrolling steps through whole multiples of the frames' size
+    #[arg(long, value_name = "PERCENT", default_value_t = 5.0)]
+    scroll_step: f64,
+
+    /// Let the window manager resize the window
+    #[arg(long)]
+    resizable: bool,
+
+    /// List the overlay in the taskbar and alt-tab like any other window
+    #[arg(long)]
+    show_in_taskbar: bool,
+
+    /// Image shown as the window's icon in taskbars, instead of the first frame (X11 and Windows)
+    #[arg(long, value_name = "PATH")]
+    icon: Option<PathBuf>,
+
+    /// Let the overlay take the keyboard focus when it appears and when clicked; without it keys
+    /// reach the overlay while the pointer is over it, on X11, Windows and macOS
+    #[arg(long)]
+    take_focus: bool,
+
+    /// Fill the monitor, fitting frames into it unless --scale says otherwise; any key or click
+    /// quits, F11 toggles it while running
+    #[arg(long, conflicts_with_all = ["layer_shell", "follow_cursor", "wander", "bounce"])]
+    fullscreen: bool,
+
+    /// Color behind frames while fullscreen, #rrggbb or #rrggbbaa with alpha
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_rgba, default_value = "#000000")]
+    fullscreen_background: [u8; 4],
+
+    /// Pause the animation after this many seconds without keyboard or mouse input, until the
+    /// user is back (Wayland with ext-idle-notify, X11 and Windows)
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    idle_timeout: Option<Duration>,
+
+    /// Treat frame sizes as logical pixels, so they look the same size on Hi-DPI displays.
+    /// With nearest sampling or integer scaling, fractional scales round to a whole factor
+    #[arg(long)]
+    logical_size: bool,
+
+    /// Show a tray icon to show, hide, pause and quit the overlay (builds with the tray feature)
+    #[arg(long)]
+    tray: bool,
+
+    /// Show and hide the overlay from any application with this key chord, e.g. ctrl+alt+k (builds with the hotkey feature, not on Wayland)
+    #[arg(long, value_name = "CHORD")]
+    toggle_hotkey: Option<String>,
+
+    /// Ignore keyboard shortcuts, including Escape and Q to quit
+    #[arg(long)]
+    no_hotkeys: bool,
+
+    /// Key to hold while dragging the overlay with the left mouse button, with --interactive
+    #[arg(long, value_enum, default_value_t = DragModifier::Alt)]
+    drag_modifier: DragModifier,
+
+    /// How the pointer looks over the overlay, with --interactive; grab shows a hand while the
+    /// drag modifier is held
+    #[arg(long, value_enum, default_value_t = CursorStyle::Default)]
+    cursor: CursorStyle,
+
+    /// What clicking the overlay does, with --interactive; clicks with the drag modifier held
+    /// still drag it
+    #[arg(long, value_enum, default_value_t = ClickAction::None)]
+    on_click: ClickAction,
+
+    /// How X11 window managers treat the overlay, the other modes keep it out of tiling and alt-tab
+    #[arg(long, value_enum, default_value_t = X11Mode::Managed)]
+    x11_mode: X11Mode,
+
+    /// Window title, followed by the sequence playing and whether it is paused [default: the
+    /// media's title or anibuddy]
+    #[arg(long, value_name = "TITLE")]
+    title: Option<String>,
+
+    /// Application id on Wayland, WM_CLASS on X11 and layer surface namespace, for compositor
+    /// window rules [default: anibuddy]
+    #[arg(long, value_name = "ID")]
+    app_id: Option<String>,
+
+    /// On Wayland, draw on a wlr-layer-shell overlay surface instead of a window, so tiling
+    /// compositors leave it alone; falls back to a window where unsupported
+    #[arg(long)]
+    layer_shell: bool,
+
+    /// Screen corner the layer surface is anchored to, --anchor takes precedence
+    #[arg(long, value_enum, default_value_t = Corner::BottomRight, requires = "layer_shell")]
+    layer_corner: Corner,
+
+    /// Distance of the layer surface from the screen edges in pixels
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 0,
+        requires = "layer_shell"
+    )]
+    layer_margin: u32,
+
+    /// Run on this GPU, given as its index or part of its name from --list-gpus
+    #[arg(long, value_name = "INDEX|NAME")]
+    gpu: Option<String>,
+
+    /// Prefer the integrated GPU to save power, unless --gpu names one
+    #[arg(long, conflicts_with = "gpu")]
+    low_power: bool,
+
+    /// Exit when no GPU is found instead of rendering in software
+    #[arg(long)]
+    no_fallback: bool,
+
+    /// List the GPUs --gpu can choose from and exit
+    #[arg(long)]
+    list_gpus: bool,
+
+    /// Reload frames when files in the image directory change
+    #[arg(long)]
+    watch: bool,
+
+    /// Keep another sequence loaded under NAME, switched to with Tab (repeatable)
+    #[arg(long = "sequence", value_name = "NAME=PATH_OR_PRESET", value_parser = parse_named_sequence)]
+    sequences: Vec<(String, String)>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Forget the position and settings remembered from earlier runs
+    ResetState,
 }
 
 fn main() -> Result<()> {
     // Initialize logger with default level None
     env_logger::Builder::from_env(Env::default().default_filter_or("none")).init();
 
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // Settings restored from the last run only fill in what isn't given
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
 
     // Load config file
     let config = Config::load()?;
 
+    if let Some(Command::ResetState) = args.command {
+        if SavedState::clear()? {
+            println!("Forgot the position and settings of earlier runs");
+        } else {
+            println!("No position or settings were remembered");
+        }
+        return Ok(());
+    }
+
     // Handle list presets command
     if args.list_presets {
         print_presets(&config);
         return Ok(());
     }
 
+    if args.list_gpus {
+        print_gpus();
+        return Ok(());
+    }
+
+    if args.list_monitors {
+        return print_monitors();
+    }
+
+    let sheet = args
+        .sheet_cols
+        .zip(args.sheet_rows)
+        .map(|(columns, rows)| SpriteSheetGrid {
+            columns,
+            rows,
+            frame_count: args.sheet_frames,
+        });
+
     // Determine media source, fps, and compression
+    let source = args.path_or_preset.clone();
     let (media_source, fps, use_compression) = match args.path_or_preset {
         Some(path_or_preset) => {
             let (source, config_fps, config_compress) =
-                resolve_path_or_preset(&config, &path_or_preset, args.fps)?;
-            let final_fps = args.fps.unwrap_or(config_fps);
+                resolve_path_or_preset(&config, &path_or_preset, args.fps, sheet)?;
+            let final_fps = args.fps.or(config_fps);
             let final_compress = if args.compress { true } else { config_compress };
             (source, final_fps, final_compress)
         }
         None => {
             // No path/preset specified, try to use default preset
-            match get_default_preset(&config, args.fps) {
+            match get_default_preset(&config, args.fps, sheet) {
                 Ok((source, config_fps, config_compress)) => {
-                    let final_fps = args.fps.unwrap_or(config_fps);
+                    let final_fps = args.fps.or(config_fps);
                     let final_compress = if args.compress { true } else { config_compress };
                     (source, final_fps, final_compress)
                 }
@@ -105,7 +732,9 @@ fn main() -> Result<()> {
         }
     };
 
-    let frame_interval = create_frame_interval(fps);
+    // Without an explicit FPS, fall back to 30 for sources that don't carry
+    // their own per-frame timing
+    let frame_interval = create_frame_interval(fps.unwrap_or(30));
 
     if use_compression {
         log::info!("Starting application with delta compression enabled");
@@ -113,35 +742,292 @@ fn main() -> Result<()> {
         log::info!("Starting application with standard (uncompressed) mode");
     }
 
-    let mut app = OverlayApplication::new(media_source, frame_interval, use_compression);
+    let reordered = args.frame_order.is_some() || args.frame_step.is_some() || args.reverse_frames;
+    let load_options = LoadOptions {
+        sort_order: if args.lexicographic_sort {
+            SortOrder::Lexicographic
+        } else {
+            SortOrder::Natural
+        },
+        recursive: args.recursive,
+        filter: args.filter,
+        lazy: args.lazy && !use_compression && !args.trim && !reordered,
+        skip_undecodable: args.skip_bad_frames,
+        trim_transparent: args.trim,
+        frame_order: args.frame_order,
+        frame_step: args.frame_step,
+        reverse_frames: args.reverse_frames,
+    };
+
+    if args.lazy && use_compression {
+        log::warn!("Delta compression needs every frame up front, ignoring --lazy");
+    } else if args.lazy && args.trim {
+        log::warn!("Trimming needs every frame up front, ignoring --lazy");
+    } else if args.lazy && reordered {
+        log::warn!("Reordering frames needs every frame up front, ignoring --lazy");
+    }
+
+    if args.on_click != ClickAction::None {
+        if !args.interactive {
+            log::warn!(
+                "Clicks pass through the overlay without --interactive, ignoring --on-click"
+            );
+        } else if args.drag_modifier == DragModifier::None {
+            log::warn!("Every click drags with --drag-modifier none, ignoring --on-click");
+        } else if args.on_click == ClickAction::Hide && !args.tray && args.toggle_hotkey.is_none() {
+            log::warn!(
+                "Nothing shows the overlay again once a click hides it, see --tray and --toggle-hotkey"
+            );
+        }
+    }
+
+    if args.loops > 0 && matches!(args.loop_mode, Some(LoopMode::Once | LoopMode::Hold)) {
+        log::warn!("Sequences play through once with --loop-mode once or hold, ignoring --loops");
+    }
+
+    let restored = if args.no_persist {
+        None
+    } else {
+        SavedState::load()
+    };
+    let window_scale = match &restored {
+        Some(state) if !given("window_scale") => state.window_scale,
+        _ => args.window_scale,
+    };
+    let opacity = match &restored {
+        Some(state) if !given("opacity") => state.opacity,
+        _ => args.opacity,
+    };
+    let speed = match &restored {
+        Some(state) if !given("speed") => state.speed,
+        _ => args.speed,
+    };
+    // Any other placement leaves the remembered position out
+    let placed = args.position.is_some()
+        || args.anchor.is_some()
+        || args.follow_cursor
+        || args.wander
+        || args.bounce
+        || args.fullscreen
+        || args.layer_shell;
+    let restored_position = restored
+        .as_ref()
+        .filter(|_| !placed)
+        .and_then(|state| state.position);
+    let monitor = args
+        .monitor
+        .or_else(|| restored.as_ref().and_then(|state| state.monitor.clone()));
+    // Frame indices only mean something for the same animation
+    let start_frame = if args.random_start {
+        StartFrame::Random
+    } else if let Some(start) = args.start_frame {
+        start
+    } else {
+        restored
+            .as_ref()
+            .filter(|state| state.source == source)
+            .map_or(StartFrame::default(), |state| {
+                StartFrame::Index(state.frame)
+            })
+    };
+
+    let mut app = OverlayApplication::new(media_source, frame_interval, use_compression)
+        .with_media_timing(fps.is_none())
+        .with_load_options(load_options)
+        .with_auto_downscale(!args.no_auto_downscale)
+        .with_gpu_memory_budget(args.max_gpu_mem)
+        .with_low_memory(args.low_memory)
+        .with_block_compression(args.block_compress)
+        .with_atlas(args.atlas)
+        .with_mipmaps(!args.no_mipmaps)
+        .with_crossfade(args.crossfade)
+        .with_filter(args.sampling)
+        .with_scale_mode(args.scale)
+        .with_render_scale(args.render_scale)
+        .with_rotation(args.rotate)
+        .with_flip(args.flip_h, args.flip_v)
+        .with_opacity(opacity)
+        .with_fade(args.fade)
+        .with_hover_dim(args.hover_opacity, args.hover_radius)
+        .with_tint(args.tint)
+        .with_color_effects(ColorEffects {
+            hue_shift: args.hue_shift,
+            saturation: args.saturation,
+            grayscale: args.grayscale,
+            invert: args.invert,
+        })
+        .with_background(args.background)
+        .with_chroma_key(args.chroma_key.map(|color| ChromaKey {
+            color,
+            tolerance: args.chroma_tolerance,
+            softness: args.chroma_softness,
+        }))
+        .with_instances(
+            args.instances as usize,
+            args.instance_spacing,
+            args.instance_offset,
+        )
+        .with_outline(args.outline.map(|width| Outline {
+            color: args.outline_color,
+            width,
+        }))
+        .with_shadow(args.shadow.then_some(Shadow {
+            color: args.shadow_color,
+            offset: args.shadow_offset,
+            blur_radius: args.shadow_blur,
+            opacity: args.shadow_opacity,
+        }))
+        .with_fragment_shader(args.shader, args.shader_fallback)
+        .with_present_mode(args.present_mode)
+        .with_interactive(args.interactive)
+        .with_input_shape(args.input_shape.then_some(args.input_threshold))
+        .with_drag_modifier(args.drag_modifier)
+        .with_cursor_style(args.cursor)
+        .with_on_click(args.on_click)
+        .with_hotkeys(!args.no_hotkeys)
+        .with_tray(args.tray)
+        .with_toggle_hotkey(args.toggle_hotkey)
+        .with_logical_size(args.logical_size)
+        .with_size(
+            args.size
+                .map(|(width, height)| PhysicalSize::new(width, height)),
+        )
+        .with_window_scale(window_scale)
+        .with_scroll_step(args.scroll_step / 100.0)
+        .with_speed(speed)
+        .with_start_frame(start_frame, args.strict_start)
+        .with_reverse(args.reverse)
+        .with_loop_mode(args.loop_mode)
+        .with_loops(args.loops)
+        .with_duration(args.duration)
+        .with_frame_skip(!args.no_frame_skip)
+        .with_resizable(args.resizable)
+        .with_taskbar(args.show_in_taskbar)
+        .with_icon(args.icon)
+        .with_take_focus(args.take_focus)
+        .with_fullscreen(args.fullscreen, args.fullscreen_background)
+        .with_idle_timeout(args.idle_timeout)
+        .with_monitor(monitor)
+        .with_all_monitors(args.all_monitors)
+        .with_position(args.position.map(|(x, y)| PhysicalPosition::new(x, y)))
+        .with_restored_position(restored_position.map(|(x, y)| PhysicalPosition::new(x, y)))
+        .with_x11_mode(args.x11_mode)
+        .with_title(args.title)
+        .with_app_id(args.app_id)
+        .with_follow_cursor(args.follow_cursor.then_some(FollowOptions {
+            offset: args.follow_offset,
+            smoothing: args.follow_smoothing.max(0.0),
+        }))
+        .with_wander(args.wander.then_some(WanderOptions {
+            speed: args.wander_speed.max(1.0),
+            interval: args.wander_interval,
+            pause_chance: args.wander_pause_chance.clamp(0.0, 1.0),
+            margin: args.wander_margin,
+        }))
+        .with_bounce(args.bounce.then_some(BounceOptions {
+            speed: args.bounce_speed.max(1.0),
+            roam: args.roam,
+        }))
+        .with_anchor(args.anchor.map(|corner| Placement {
+            corner,
+            margin: args.margin.unwrap_or_default(),
+        }))
+        .with_layer_shell(
+            args.layer_shell.then_some(Placement {
+                corner: args.anchor.unwrap_or(args.layer_corner),
+                margin: args
+                    .margin
+                    .unwrap_or((args.layer_margin, args.layer_margin)),
+            }),
+        )
+        .with_adapter(AdapterSelection {
+            gpu: args.gpu,
+            low_power: args.low_power,
+            software_fallback: !args.no_fallback,
+        })
+        .with_watch(args.watch);
+
+    // Log lines would land in the middle of the status line
+    if std::io::stderr().is_terminal() && log::max_level() < log::LevelFilter::Info {
+        app = app.with_progress(progress_printer());
+    }
+
+    for (name, path_or_preset) in &args.sequences {
+        if name == MAIN_SEQUENCE {
+            return Err(anyhow!(
+                "'{}' names the sequence given as PATH_OR_PRESET, pick another name",
+                MAIN_SEQUENCE
+            ));
+        }
+
+        let (source, _, _) = resolve_path_or_preset(&config, path_or_preset, None, None)?;
+        app = app.with_sequence(name.clone(), source);
+    }
+
     app.run()?;
 
+    if !args.no_persist {
+        let state = SavedState {
+            // Wayland doesn't tell, the position from before may still fit
+            position: app
+                .window_position()
+                .map(|position| (position.x, position.y))
+                .or(restored.and_then(|state| state.position)),
+            monitor: app.window_monitor().map(str::to_string),
+            window_scale: app.window_scale(),
+            opacity: app.opacity(),
+            speed: app.speed(),
+            source,
+            frame: app.frame_index().unwrap_or(0),
+            ..SavedState::default()
+        };
+        if let Err(e) = state.save() {
+            log::warn!("This run's position and settings won't be restored: {}", e);
+        }
+    }
+
+    if let Some(position) = app.window_position() {
+        log::info!("The overlay was last at {},{}", position.x, position.y);
+    }
+
     Ok(())
 }
 
+/// Path argument that reads a single image from standard input
+const STDIN_ARG: &str = "-";
+
 /// Resolve a path or preset name to a MediaSource, FPS, and compression setting
+///
+/// The FPS is `None` when neither the CLI nor the preset specify one.
 fn resolve_path_or_preset(
     config: &Option<Config>,
     path_or_preset: &str,
     fps_override: Option<u64>,
-) -> Result<(MediaSource, u64, bool)> {
-    if is_likely_path(path_or_preset) {
+    sheet: Option<SpriteSheetGrid>,
+) -> Result<(MediaSource, Option<u64>, bool)> {
+    if path_or_preset == STDIN_ARG {
+        if sheet.is_some() {
+            return Err(anyhow!("Sprite sheets cannot be read from standard input"));
+        }
+        log::info!("Reading a single image from standard input");
+        Ok((MediaSource::Stdin, fps_override, false))
+    } else if is_likely_path(path_or_preset) {
         // Treat as path
-        let media_source = create_media_source_from_path(path_or_preset)?;
-        let fps = fps_override.unwrap_or(30);
+        let media_source = create_media_source_from_path(path_or_preset, sheet)?;
+        let fps = fps_override;
         let compress = false; // Default to no compression for direct paths
         log::info!(
             "Using path: {} (fps: {}, compress: {})",
             path_or_preset,
-            fps,
+            fps_label(fps),
             compress
         );
         Ok((media_source, fps, compress))
     } else if let Some(cfg) = config {
         // Try as preset first
         if let Some(preset) = cfg.get_preset(path_or_preset) {
-            let media_source = create_media_source_from_preset(preset)?;
-            let fps = fps_override.unwrap_or(preset.fps.unwrap_or(30));
+            let media_source = create_media_source_from_preset(preset, sheet)?;
+            let fps = fps_override.or(preset.fps);
             let compress = preset.use_compression();
 
             if fps_override.is_some() {
@@ -149,7 +1035,7 @@ fn resolve_path_or_preset(
                     "Using preset '{}': {} (fps: {} - overridden, compress: {})",
                     path_or_preset,
                     preset.path,
-                    fps,
+                    fps_label(fps),
                     compress
                 );
             } else {
@@ -157,7 +1043,7 @@ fn resolve_path_or_preset(
                     "Using preset '{}': {} (fps: {}, compress: {})",
                     path_or_preset,
                     preset.path,
-                    fps,
+                    fps_label(fps),
                     compress
                 );
             }
@@ -165,17 +1051,17 @@ fn resolve_path_or_preset(
             Ok((media_source, fps, compress))
         } else {
             // Not a preset, try as path
-            try_path_fallback(cfg, path_or_preset, fps_override)
+            try_path_fallback(cfg, path_or_preset, fps_override, sheet)
         }
     } else {
         // No config file, treat as path
-        let media_source = create_media_source_from_path(path_or_preset)?;
-        let fps = fps_override.unwrap_or(30);
+        let media_source = create_media_source_from_path(path_or_preset, sheet)?;
+        let fps = fps_override;
         let compress = false; // Default to no compression when no config
         log::info!(
             "Using path: {} (fps: {}, compress: {})",
             path_or_preset,
-            fps,
+            fps_label(fps),
             compress
         );
         Ok((media_source, fps, compress))
@@ -186,25 +1072,26 @@ fn resolve_path_or_preset(
 fn get_default_preset(
     config: &Option<Config>,
     fps_override: Option<u64>,
-) -> Result<(MediaSource, u64, bool)> {
+    sheet: Option<SpriteSheetGrid>,
+) -> Result<(MediaSource, Option<u64>, bool)> {
     if let Some(cfg) = config {
         if let Some(default_preset) = cfg.get_default() {
-            let media_source = create_media_source_from_preset(default_preset)?;
-            let fps = fps_override.unwrap_or(default_preset.fps.unwrap_or(30));
+            let media_source = create_media_source_from_preset(default_preset, sheet)?;
+            let fps = fps_override.or(default_preset.fps);
             let compress = default_preset.use_compression();
 
             if fps_override.is_some() {
                 log::info!(
                     "Using default preset: {} (fps: {} - overridden, compress: {})",
                     default_preset.path,
-                    fps,
+                    fps_label(fps),
                     compress
                 );
             } else {
                 log::info!(
                     "Using default preset: {} (fps: {}, compress: {})",
                     default_preset.path,
-                    fps,
+                    fps_label(fps),
                     compress
                 );
             }
@@ -223,7 +1110,8 @@ fn try_path_fallback(
     config: &Config,
     arg: &str,
     fps_override: Option<u64>,
-) -> Result<(MediaSource, u64, bool)> {
+    sheet: Option<SpriteSheetGrid>,
+) -> Result<(MediaSource, Option<u64>, bool)> {
     let path = Path::new(arg);
     if !path.exists() {
         let available_presets = config.list_presets();
@@ -242,15 +1130,23 @@ fn try_path_fallback(
         return Err(anyhow!(error_msg));
     }
 
-    let media_source = detect_media_type(path)?;
-    let fps = fps_override.unwrap_or(30);
+    let media_source = media_source_for(path, sheet)?;
+    let fps = fps_override;
     let compress = false; // Default to no compression for fallback paths
-    log::info!("Using path: {} (fps: {}, compress: {})", arg, fps, compress);
+    log::info!(
+        "Using path: {} (fps: {}, compress: {})",
+        arg,
+        fps_label(fps),
+        compress
+    );
     Ok((media_source, fps, compress))
 }
 
 /// Create a MediaSource from a preset configuration
-fn create_media_source_from_preset(preset: &PresetConfig) -> Result<MediaSource> {
+fn create_media_source_from_preset(
+    preset: &PresetConfig,
+    sheet: Option<SpriteSheetGrid>,
+) -> Result<MediaSource> {
     let path = Path::new(&preset.path);
     if !path.exists() {
         return Err(anyhow!(
@@ -258,16 +1154,244 @@ fn create_media_source_from_preset(preset: &PresetConfig) -> Result<MediaSource>
             preset.path
         ));
     }
-    detect_media_type(path)
+    media_source_for(path, sheet)
 }
 
 /// Create a MediaSource from a path string
-fn create_media_source_from_path(path_str: &str) -> Result<MediaSource> {
+fn create_media_source_from_path(
+    path_str: &str,
+    sheet: Option<SpriteSheetGrid>,
+) -> Result<MediaSource> {
     let path = Path::new(path_str);
     if !path.exists() {
         return Err(anyhow!("Path '{}' does not exist", path_str));
     }
-    detect_media_type(path)
+    media_source_for(path, sheet)
+}
+
+/// Detect the media type of an existing path, or treat it as a sprite sheet
+/// when a grid was given
+fn media_source_for(path: &Path, sheet: Option<SpriteSheetGrid>) -> Result<MediaSource> {
+    match sheet {
+        Some(grid) if path.is_file() => Ok(MediaSource::SpriteSheet(path.to_path_buf(), grid)),
+        Some(_) => Err(anyhow!(
+            "Sprite sheet path '{}' is not a file",
+            path.display()
+        )),
+        None => detect_media_type(path),
+    }
+}
+
+/// Build a progress hook that keeps a single status line updated on stderr
+fn progress_printer() -> Arc<ProgressFn> {
+    // Frames are decoded in parallel, so reports can arrive out of order
+    let last = Mutex::new((0, 0));
+
+    Arc::new(move |done, total, name| {
+        let mut last = last.lock().unwrap();
+        if *last == (done, total) || (last.1 == total && last.0 > done) {
+            return;
+        }
+        // A stage left unfinished, like the first frame of a lazily decoded
+        // directory, keeps its line when the next one starts
+        if last.1 != total && last.0 < last.1 {
+            eprintln!();
+        }
+        *last = (done, total);
+
+        eprint!("\r\x1b[2K[{}/{}] {}", done, total, name);
+        if done == total {
+            eprintln!();
+        }
+    })
+}
+
+/// Split a `--sequence` value into its name and path or preset
+fn parse_named_sequence(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), path.to_string()))
+        }
+        _ => Err(format!("expected NAME=PATH_OR_PRESET, got '{}'", value)),
+    }
+}
+
+/// Parse a byte count with an optional K, M or G suffix (powers of 1024)
+fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let (number, multiplier) = match trimmed
+        .char_indices()
+        .last()
+        .map(|(i, c)| (i, c.to_ascii_uppercase()))
+    {
+        Some((i, 'K')) => (&trimmed[..i], 1024),
+        Some((i, 'M')) => (&trimmed[..i], 1024 * 1024),
+        Some((i, 'G')) => (&trimmed[..i], 1024 * 1024 * 1024),
+        _ => (trimmed, 1),
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|bytes| *bytes > 0)
+        .ok_or_else(|| format!("expected a size like 512M or 2G, got '{}'", value))
+}
+
+/// Parse an RGB color written as #rrggbb, the # being optional
+fn parse_hex_color(value: &str) -> Result<[u8; 3], String> {
+    let hex = value.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+    };
+
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(format!("expected a color like #80a0ff, got '{}'", value)),
+    }
+}
+
+/// Parse a color written as #rrggbbaa, or #rrggbb for an opaque one
+fn parse_hex_rgba(value: &str) -> Result<[u8; 4], String> {
+    let hex = value.trim().trim_start_matches('#');
+    let error = || {
+        format!(
+            "expected a color like #80a0ff or #80a0ff80, got '{}'",
+            value
+        )
+    };
+    match hex.len() {
+        6 => parse_hex_color(hex).map(|[r, g, b]| [r, g, b, 255]),
+        8 => {
+            let (rgb, alpha) = hex.split_at_checked(6).ok_or_else(error)?;
+            let [r, g, b] = parse_hex_color(rgb).map_err(|_| error())?;
+            let a = u8::from_str_radix(alpha, 16).map_err(|_| error())?;
+            Ok([r, g, b, a])
+        }
+        _ => Err(error()),
+    }
+}
+
+/// Parse a pixel offset written as dx,dy
+fn parse_offset(value: &str) -> Result<(f32, f32), String> {
+    let parse = |part: &str| part.trim().parse::<f32>().ok().filter(|v| v.is_finite());
+    match value.split_once(',') {
+        Some((dx, dy)) => parse(dx)
+            .zip(parse(dy))
+            .ok_or_else(|| format!("expected an offset like 4,4, got '{}'", value)),
+        None => Err(format!("expected an offset like 4,4, got '{}'", value)),
+    }
+}
+
+/// Parse a window size written as WxH
+fn parse_window_size(value: &str) -> Result<(u32, u32), String> {
+    let parse = |part: &str| part.trim().parse::<u32>().ok().filter(|v| *v > 0);
+    value
+        .split_once(['x', 'X'])
+        .and_then(|(width, height)| parse(width).zip(parse(height)))
+        .ok_or_else(|| format!("expected a size like 256x256, got '{}'", value))
+}
+
+/// Parse a frame index, or a percentage of the sequence written like 50%
+fn parse_start_frame(value: &str) -> Result<StartFrame, String> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => percent
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .map(StartFrame::Percent)
+            .ok_or_else(|| format!("expected a percentage from 0% to 100%, got '{}'", value)),
+        None => value.trim().parse().map(StartFrame::Index).map_err(|_| {
+            format!(
+                "expected a frame number or a percentage like 50%, got '{}'",
+                value
+            )
+        }),
+    }
+}
+
+/// Parse a duration written as amounts of hours, minutes, seconds and
+/// milliseconds like 1h15m, 1h 15m, 2m, 30s or 1.5s. A bare number counts
+/// seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let error = || format!("expected a duration like 30s, 2m or 1h15m, got '{}'", value);
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| error());
+    }
+
+    if value.is_empty() {
+        return Err(error());
+    }
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(error)?;
+        let amount: f64 = rest[..number_end].parse().map_err(|_| error())?;
+        rest = rest[number_end..].trim_start();
+        let unit_end = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" | "min" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return Err(error()),
+        };
+        total += amount * seconds;
+        rest = rest[unit_end..].trim_start();
+    }
+    Duration::try_from_secs_f64(total).map_err(|_| error())
+}
+
+/// Parse a number, turning away NaN and infinities
+fn parse_finite(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("expected a number like 1.5, got '{}'", value))
+}
+
+/// Parse a number of seconds like 0.3 or 10
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("expected a number of seconds like 0.3, got '{}'", value))
+}
+
+/// Parse margins written as x,y, or as one value for both
+fn parse_margin(value: &str) -> Result<(u32, u32), String> {
+    let parse = |part: &str| part.trim().parse::<u32>().ok();
+    match value.split_once(',') {
+        Some((x, y)) => parse(x).zip(parse(y)),
+        None => parse(value).map(|margin| (margin, margin)),
+    }
+    .ok_or_else(|| format!("expected margins like 24,16 or 24, got '{}'", value))
+}
+
+/// Parse a window position written as x,y
+fn parse_position(value: &str) -> Result<(i32, i32), String> {
+    let parse = |part: &str| part.trim().parse::<i32>().ok();
+    value
+        .split_once(',')
+        .and_then(|(x, y)| parse(x).zip(parse(y)))
+        .ok_or_else(|| {
+            format!(
+                "expected a position like 100,50 or -20,-20, got '{}'",
+                value
+            )
+        })
 }
 
 /// Create a Duration for the frame interval based on FPS
@@ -279,6 +1403,14 @@ fn create_frame_interval(fps: u64) -> Duration {
     }
 }
 
+/// Describe an optional FPS setting for log output
+fn fps_label(fps: Option<u64>) -> String {
+    match fps {
+        Some(fps) => fps.to_string(),
+        None => "from media".to_string(),
+    }
+}
+
 /// Print available presets
 fn print_presets(config: &Option<Config>) {
     if let Some(cfg) = config {
@@ -305,57 +1437,95 @@ fn print_presets(config: &Option<Config>) {
     }
 }
 
-/// Print usage hints and examples
-fn print_usage_hint(config: &Option<Config>) {
-    println!("Examples:");
-    println!("  anibuddy ./frames              # Use frames directory");
-    println!("  anibuddy --compress ./frames   # Use frames directory with compression");
-    println!("  anibuddy animation.gif         # Use GIF file");
-    println!("  anibuddy -c animation.gif      # Use GIF file with compression");
-    println!("  anibuddy konata                # Use 'konata' preset");
-    println!(
-        "  anibuddy --compress konata     # Use 'konata' preset with compression (overrides config)"
-    );
-    println!("  anibuddy ./frames --fps 60     # Use frames directory at 60 FPS");
-    println!("  anibuddy -c ./frames --fps 60  # Use frames directory at 60 FPS with compression");
-    println!();
-    println!("Controls:");
-    println!();
-
-    if let Some(config) = config {
-        let presets = config.list_presets();
-        if !presets.is_empty() {
-            println!("Available presets: {}", presets.join(", "));
-            println!("Use --list-presets to see preset details.");
-        } else {
-            println!("No presets configured.");
+fn print_gpus() {
+    let adapters = renderer::list_adapters();
+    if adapters.is_empty() {
+        println!("No GPUs found.");
+        return;
+    }
+
+    println!("Available GPUs:");
+    for adapter in adapters {
+        println!("  {}", adapter);
+    }
+}
+
+fn print_monitors() -> Result<()> {
+    let monitors = window_backend::list_monitors()?;
+    if monitors.is_empty() {
+        println!("No monitors found.");
+        return Ok(());
+    }
+
+    println!("Available monitors:");
+    for monitor in monitors {
+        println!("  {}", monitor);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let seconds = Duration::from_secs;
+        assert_eq!(parse_duration("30s"), Ok(seconds(30)));
+        assert_eq!(parse_duration("2m"), Ok(seconds(120)));
+        assert_eq!(parse_duration("2min"), Ok(seconds(120)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+
+        // Several units, with or without spaces between them
+        assert_eq!(parse_duration("1h15m"), Ok(seconds(4500)));
+        assert_eq!(parse_duration("1h 15m"), Ok(seconds(4500)));
+        assert_eq!(parse_duration(" 1h 15m 30s "), Ok(seconds(4530)));
+        assert_eq!(parse_duration("5 s"), Ok(seconds(5)));
+
+        // A bare number counts seconds
+        assert_eq!(parse_duration("45"), Ok(seconds(45)));
+        assert_eq!(parse_duration("0.5"), Ok(Duration::from_millis(500)));
+
+        for invalid in [
+            "", " ", "s", "1h15", "1x", "1.2.3s", "-5", "-5s", "nan", "inf", "h1",
+        ] {
+            assert!(parse_duration(invalid).is_err(), "'{}'", invalid);
         }
-    } else {
-        println!("No config file found. Create ~/.config/anibuddy/config.toml to use presets.");
-    }
-
-    println!();
-    println!("Config file format (~/.config/anibuddy/config.toml):");
-    println!("[default]");
-    println!("path = \"/path/to/default/animation\"");
-    println!("fps = 30");
-    println!("compress = false");
-    println!();
-    println!("[konata]");
-    println!("path = \"/path/to/konata/frames\"");
-    println!("fps = 24");
-    println!("compress = true");
-    println!();
-    println!("[1]");
-    println!("path = \"/path/to/animation1.gif\"");
-    println!("fps = 60");
-    println!("compress = false");
-    println!();
-    println!("Delta Compression:");
-    println!("Delta compression reduces memory usage by storing only the differences");
-    println!("between consecutive frames. This is especially effective for animations");
-    println!("with small changes between frames, potentially reducing memory usage");
-    println!("by 50-90% depending on the content.");
-    println!();
-    println!("The --compress CLI flag overrides the preset's compression setting.");
+    }
+
+    #[test]
+    fn test_parse_finite() {
+        assert_eq!(parse_finite("1.5"), Ok(1.5));
+        assert_eq!(parse_finite(" 0 "), Ok(0.0));
+        assert_eq!(parse_finite("-2"), Ok(-2.0));
+
+        for invalid in ["", "x", "NaN", "nan", "inf", "-inf", "1e40"] {
+            assert!(parse_finite(invalid).is_err(), "'{}'", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds("0.3"), Ok(Duration::from_millis(300)));
+        assert_eq!(parse_seconds(" 10 "), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_seconds("0"), Ok(Duration::ZERO));
+
+        for invalid in ["", "s", "-1", "nan", "inf", "1e300", "1s"] {
+            assert!(parse_seconds(invalid).is_err(), "'{}'", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_start_frame() {
+        assert_eq!(parse_start_frame("12"), Ok(StartFrame::Index(12)));
+        assert_eq!(parse_start_frame(" 0 "), Ok(StartFrame::Index(0)));
+        assert_eq!(parse_start_frame("50%"), Ok(StartFrame::Percent(50.0)));
+        assert_eq!(parse_start_frame("12.5 %"), Ok(StartFrame::Percent(12.5)));
+        assert_eq!(parse_start_frame("100%"), Ok(StartFrame::Percent(100.0)));
+
+        for invalid in ["", "%", "-1", "1.5", "101%", "-1%", "nan%", "half"] {
+            assert!(parse_start_frame(invalid).is_err(), "'{}'", invalid);
+        }
+    }
 }
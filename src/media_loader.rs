@@ -1,50 +1,558 @@
 use anyhow::{Result, anyhow};
-use glob::glob;
-use image::{Rgba, RgbaImage};
+use glob::{MatchOptions, Pattern};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, ImageDecoder, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::fs::File as StdFile;
+use std::io::{BufReader, IsTerminal, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum MediaSource {
     Directory(PathBuf),
     GifFile(PathBuf),
     ApngFile(PathBuf),
+    WebpFile(PathBuf),
+    SpriteSheet(PathBuf, SpriteSheetGrid),
+    /// A single still image
+    ImageFile(PathBuf),
+    /// A single still image piped to standard input
+    Stdin,
+}
+
+/// Optional files next to a directory's frames mapping file names to
+/// display durations in milliseconds
+const DURATION_MANIFESTS: [&str; 2] = ["frames.toml", "frames.json"];
+
+/// Optional file inside an image directory with playback defaults
+const METADATA_FILE: &str = "meta.toml";
+
+/// File extensions picked up when loading a directory of frames. WebP files
+/// in a directory contribute their first frame only.
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "bmp", "tif", "tiff", "webp"];
+
+/// Smallest scale `SequenceSet::fit_memory_budget` will shrink frames to
+/// before giving up
+const MIN_BUDGET_SCALE: f64 = 0.125;
+
+/// Called while loading with the number of frames done, the total and the
+/// file or step being worked on
+pub type ProgressFn = dyn Fn(usize, usize, &str) + Send + Sync;
+
+/// Display time of each frame, `None` where the global frame interval applies
+pub type FrameDurations = Vec<Option<Duration>>;
+
+/// Layout of a sprite sheet, frames are read in row-major order
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteSheetGrid {
+    pub columns: u32,
+    pub rows: u32,
+    /// Number of cells that hold frames, when `None` all cells are used
+    /// except trailing fully transparent ones
+    pub frame_count: Option<usize>,
+}
+
+/// What happens after the last frame has been shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoopMode {
+    /// Start over from the first frame
+    #[default]
+    Loop,
+    /// Play back to the first frame and forth again, showing the frames at
+    /// either end once
+    #[serde(alias = "pingpong")]
+    PingPong,
+    /// Play through once and exit
+    Once,
+    /// Play through once and stay on the last frame
+    Hold,
+}
+
+/// Where playback goes after the frame on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advance {
+    /// Show this frame next, playing in reverse from there if set.
+    /// `wrapped` is set when that goes past an end of the sequence, around
+    /// to the other end or turning back.
+    To {
+        index: usize,
+        reverse: bool,
+        wrapped: bool,
+    },
+    /// The last frame has been shown and the sequence doesn't loop
+    Finished,
+}
+
+impl LoopMode {
+    /// Where playback goes after frame `index` of `count`, playing from
+    /// the last frame to the first if `reverse`
+    pub fn advance(self, index: usize, count: usize, reverse: bool) -> Advance {
+        if count <= 1 {
+            return Advance::To {
+                index: 0,
+                reverse,
+                wrapped: true,
+            };
+        }
+        let index = index.min(count - 1);
+        let at_end = if reverse {
+            index == 0
+        } else {
+            index == count - 1
+        };
+        let step = |reverse: bool| if reverse { index - 1 } else { index + 1 };
+        match self {
+            _ if !at_end => Advance::To {
+                index: step(reverse),
+                reverse,
+                wrapped: false,
+            },
+            LoopMode::Loop => Advance::To {
+                index: if reverse { count - 1 } else { 0 },
+                reverse,
+                wrapped: true,
+            },
+            // Turning around, the end frame was just shown
+            LoopMode::PingPong => Advance::To {
+                index: step(!reverse),
+                reverse: !reverse,
+                wrapped: true,
+            },
+            LoopMode::Once | LoopMode::Hold => Advance::Finished,
+        }
+    }
+
+    /// Frame one step forward or back from `index` of `count`, when
+    /// stepping through them by hand. Only looping sequences wrap around
+    /// their ends, None where others would.
+    pub fn step(self, index: usize, count: usize, forward: bool) -> Option<usize> {
+        let index = index.min(count.checked_sub(1)?);
+        match (forward, self) {
+            (true, _) if index + 1 < count => Some(index + 1),
+            (false, _) if index > 0 => Some(index - 1),
+            (true, LoopMode::Loop) => Some(0),
+            (false, LoopMode::Loop) => Some(count - 1),
+            _ => None,
+        }
+    }
+}
+
+/// Playback defaults shipped with a sequence in its meta.toml. Unknown keys
+/// are ignored so the format can grow.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SequenceMetadata {
+    pub fps: Option<u64>,
+    #[serde(rename = "loop")]
+    pub loop_mode: Option<LoopMode>,
+    /// Human-readable name, used as the window title
+    pub title: Option<String>,
+}
+
+/// How files inside an image directory are ordered into frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Numeric runs are compared by value, so frame_2 comes before frame_10
+    #[default]
+    Natural,
+    /// Plain byte-wise ordering, for zero-padded names
+    Lexicographic,
+}
+
+/// Settings that affect how a media source is decoded into frames
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub sort_order: SortOrder,
+    /// Descend into subdirectories of an image directory
+    pub recursive: bool,
+    /// Glob matched against each image's path relative to the directory,
+    /// e.g. "dance/*.png"
+    pub filter: Option<String>,
+    /// Decode only the first frame of an image directory up front and the
+    /// rest on a background thread
+    pub lazy: bool,
+    /// Leave out directory frames that fail to decode instead of aborting
+    pub skip_undecodable: bool,
+    /// Crop all frames to the smallest box holding every non-transparent
+    /// pixel of the sequence, needs all frames decoded up front
+    pub trim_transparent: bool,
+    /// Play the decoded frames in this order, indices may repeat
+    pub frame_order: Option<Vec<usize>>,
+    /// Keep only every n-th frame
+    pub frame_step: Option<usize>,
+    /// Play the frames back to front
+    pub reverse_frames: bool,
 }
 
 pub struct MediaSequence {
     images: Vec<RgbaImage>,
+    /// Per-frame display time taken from the source file or frame manifest
+    frame_durations: FrameDurations,
+    /// Whether the durations come from a user-written manifest rather than
+    /// delays embedded in an animated file
+    has_duration_manifest: bool,
+    /// Frames still being decoded in the background, in playback order
+    pending_frames: Option<Receiver<Result<RgbaImage>>>,
+    /// Number of frames decoded so far, including ones already handed out
+    /// through `take_decoded_frames`
+    decoded_count: usize,
+    /// Largest frame dimension allowed and whether bigger frames are
+    /// downscaled (`true`) or rejected, applied to frames decoded later too
+    dimension_limit: Option<(u32, bool)>,
+    /// Factor applied to the size of every frame to fit a memory budget,
+    /// including frames decoded later
+    scale: f64,
+    /// Whether frames the background decoder fails on are left out rather
+    /// than ending the sequence
+    skip_undecodable: bool,
+    /// Top-left corner of the trimmed frames within the original ones
+    trim_offset: (u32, u32),
+    /// Image directory the frames were read from, kept for reloading
+    directory: Option<PathBuf>,
+    /// File behind each frame, empty unless loaded from a directory
+    frame_paths: Vec<PathBuf>,
+    /// Playback order as indices into `images`, `None` plays them as decoded
+    order: Option<Vec<usize>>,
+    /// Size of every frame in `images` once their pixels were dropped by
+    /// `release_pixels`
+    released_sizes: Option<Vec<(u32, u32)>>,
+    metadata: SequenceMetadata,
     current_index: usize,
 }
 
+/// What had to be decoded again after the files of a directory changed
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReloadOutcome {
+    /// Same files in the same order, only the frames at these indices changed
+    Frames(Vec<usize>),
+    /// Files were added, removed or reordered, or frames changed size, so
+    /// every frame is new
+    Sequence,
+}
+
 impl MediaSequence {
-    pub fn load(source: MediaSource) -> Result<Self> {
-        let images = match source {
-            MediaSource::Directory(path) => Self::load_image_directory(&path)?,
+    /// Decode a media source, reporting each decoded directory frame to
+    /// `progress`. Single-file sources report once when they are done.
+    pub fn load(source: MediaSource, options: &LoadOptions, progress: &ProgressFn) -> Result<Self> {
+        let file_label = match &source {
+            MediaSource::Directory(_) => None,
+            MediaSource::GifFile(path)
+            | MediaSource::ApngFile(path)
+            | MediaSource::WebpFile(path)
+            | MediaSource::SpriteSheet(path, _)
+            | MediaSource::ImageFile(path) => Some(file_name(path)),
+            MediaSource::Stdin => Some("stdin".to_string()),
+        };
+
+        let mut has_duration_manifest = false;
+        let mut pending_frames = None;
+        let mut directory = None;
+        let mut frame_paths = Vec::new();
+        let mut metadata = SequenceMetadata::default();
+
+        let (images, frame_durations) = match source {
+            MediaSource::Directory(path) => {
+                metadata = load_metadata(&path)?;
+                let (mut image_paths, manifest) = Self::list_image_directory(&path, options)?;
+                has_duration_manifest = manifest.is_some();
+                let mut durations = manifest.unwrap_or_else(|| vec![None; image_paths.len()]);
+
+                let images = if options.lazy && image_paths.len() > 1 {
+                    // Only the first frame is needed to size the window
+                    log::info!("Decoding the first frame now and the rest in the background");
+                    let first = decode_image(&image_paths[0])?;
+                    progress(1, image_paths.len(), &file_name(&image_paths[0]));
+                    pending_frames = Some(spawn_frame_decoder(
+                        image_paths[1..].to_vec(),
+                        options.skip_undecodable,
+                    ));
+                    vec![first]
+                } else {
+                    let decoded = decode_images(&image_paths, progress, options.skip_undecodable)?;
+                    keep_decoded(decoded, &mut image_paths, &mut durations)
+                };
+
+                directory = Some(path);
+                frame_paths = image_paths;
+                (images, durations)
+            }
             MediaSource::GifFile(path) => Self::load_gif(&path)?,
             MediaSource::ApngFile(path) => Self::load_apng(&path)?,
+            MediaSource::WebpFile(path) => Self::load_webp(&path)?,
+            MediaSource::SpriteSheet(path, grid) => {
+                let images = Self::load_sprite_sheet(&path, grid)?;
+                let durations = vec![None; images.len()];
+                (images, durations)
+            }
+            MediaSource::ImageFile(path) => (vec![decode_image(&path)?], vec![None]),
+            MediaSource::Stdin => (vec![Self::load_stdin()?], vec![None]),
         };
 
         if images.is_empty() {
             return Err(anyhow!("No images loaded from source"));
         }
 
-        Ok(Self {
+        if let Some(label) = file_label {
+            progress(images.len(), images.len(), &label);
+        }
+
+        let mut images = images;
+        let trim_offset = if options.trim_transparent && pending_frames.is_none() {
+            trim_transparent_borders(&mut images)
+        } else {
+            (0, 0)
+        };
+
+        let sequence = Self {
+            decoded_count: images.len(),
             images,
+            frame_durations,
+            has_duration_manifest,
+            pending_frames,
+            dimension_limit: None,
+            scale: 1.0,
+            skip_undecodable: options.skip_undecodable,
+            trim_offset,
+            directory,
+            frame_paths,
+            order: None,
+            released_sizes: None,
+            metadata,
             current_index: 0,
-        })
+        };
+
+        sequence.arranged(options)
     }
 
-    fn load_image_directory(directory: &Path) -> Result<Vec<RgbaImage>> {
-        let patterns = ["*.png", "*.jpg", "*.jpeg"];
-        let mut image_paths = Vec::new();
+    /// Play the frames back to front
+    pub fn reversed(mut self) -> Result<Self> {
+        let mut order = self.playback_order()?;
+        order.reverse();
+        self.order = Some(order);
+        Ok(self)
+    }
 
-        for pattern in &patterns {
-            let full_pattern = directory.join(pattern).to_string_lossy().to_string();
-            let paths: Vec<PathBuf> = glob(&full_pattern)?.filter_map(Result::ok).collect();
-            image_paths.extend(paths);
+    /// Keep every `step`-th frame, starting with the first
+    pub fn subsampled(mut self, step: usize) -> Result<Self> {
+        self.order = Some(subsample_order(&self.playback_order()?, step)?);
+        Ok(self)
+    }
+
+    /// Play the current frames in the given order. Indices refer to the
+    /// current playback order and may repeat.
+    pub fn reorder(mut self, indices: &[usize]) -> Result<Self> {
+        self.order = Some(reorder_indices(&self.playback_order()?, indices)?);
+        Ok(self)
+    }
+
+    /// Indices into `images` in playback order, only known once every frame
+    /// is decoded
+    fn playback_order(&self) -> Result<Vec<usize>> {
+        if self.is_loading() {
+            return Err(anyhow!("Frames can't be reordered while still decoding"));
         }
 
-        image_paths.sort();
+        Ok(self
+            .order
+            .clone()
+            .unwrap_or_else(|| (0..self.images.len()).collect()))
+    }
+
+    /// Apply the playback order asked for in `options`: custom order first,
+    /// then the step, then reversal
+    fn arranged(mut self, options: &LoadOptions) -> Result<Self> {
+        if let Some(indices) = &options.frame_order {
+            self = self.reorder(indices)?;
+        }
+        if let Some(step) = options.frame_step {
+            self = self.subsampled(step)?;
+        }
+        if options.reverse_frames {
+            self = self.reversed()?;
+        }
+
+        if self.order.is_some() {
+            log::info!(
+                "Playing {} of {} decoded frames in custom order",
+                self.count(),
+                self.images.len()
+            );
+        }
+
+        Ok(self)
+    }
+
+    /// Re-read the image directory after the files in `changed` were written.
+    ///
+    /// When the directory still lists the same files only the changed frames
+    /// are decoded again, otherwise the whole sequence is. On error the
+    /// sequence is left as it was.
+    pub fn reload(&mut self, changed: &[PathBuf], options: &LoadOptions) -> Result<ReloadOutcome> {
+        let directory = self
+            .directory
+            .clone()
+            .ok_or_else(|| anyhow!("Only sequences loaded from a directory can be reloaded"))?;
+        if self.released_sizes.is_some() {
+            return Err(anyhow!(
+                "The frames were released after upload, the sequence can't be reloaded"
+            ));
+        }
+
+        let (mut image_paths, manifest) = Self::list_image_directory(&directory, options)?;
+        let mut durations = manifest
+            .clone()
+            .unwrap_or_else(|| vec![None; image_paths.len()]);
+        let fully_decoded =
+            self.pending_frames.is_none() && self.images.len() == self.frame_paths.len();
+
+        // A changed frame can grow the trimmed box of the whole sequence
+        if fully_decoded
+            && image_paths == self.frame_paths
+            && !options.trim_transparent
+            && self.order.is_none()
+        {
+            // Watchers report absolute paths, the frame paths may be relative
+            let changed: HashSet<PathBuf> = changed
+                .iter()
+                .filter_map(|path| fs::canonicalize(path).ok())
+                .collect();
+            let indices: Vec<usize> = image_paths
+                .iter()
+                .enumerate()
+                .filter(|(_, path)| {
+                    fs::canonicalize(path).is_ok_and(|path| changed.contains(&path))
+                })
+                .map(|(index, _)| index)
+                .collect();
+
+            let paths: Vec<PathBuf> = indices
+                .iter()
+                .map(|&index| image_paths[index].clone())
+                .collect();
+            // A changed frame that fails to decode, like one that is still
+            // being written, keeps its previous image
+            let mut replaced = Vec::new();
+            for (&index, image) in indices
+                .iter()
+                .zip(decode_images(&paths, &|_, _, _| {}, true)?)
+            {
+                if let Some(mut image) = image {
+                    self.fit_frame(&mut image, index)?;
+                    replaced.push((index, image));
+                }
+            }
+
+            let resized = replaced
+                .iter()
+                .any(|(index, image)| image.dimensions() != self.images[*index].dimensions());
+
+            let indices: Vec<usize> = replaced.iter().map(|(index, _)| *index).collect();
+            for (index, image) in replaced {
+                self.images[index] = image;
+            }
+            self.frame_durations = durations;
+            self.has_duration_manifest = manifest.is_some();
+
+            log::info!("Reloaded {} changed frames", indices.len());
+            return Ok(if resized {
+                ReloadOutcome::Sequence
+            } else {
+                ReloadOutcome::Frames(indices)
+            });
+        }
+
+        let decoded = decode_images(&image_paths, &|_, _, _| {}, options.skip_undecodable)?;
+        let mut images = keep_decoded(decoded, &mut image_paths, &mut durations);
+        if images.is_empty() {
+            return Err(anyhow!(
+                "No frame in {} could be decoded",
+                directory.display()
+            ));
+        }
+
+        let trim_offset = if options.trim_transparent {
+            trim_transparent_borders(&mut images)
+        } else {
+            (0, 0)
+        };
+
+        for (index, image) in images.iter_mut().enumerate() {
+            self.fit_frame(image, index)?;
+        }
+
+        let metadata = load_metadata(&directory)?;
+        let reloaded = Self {
+            decoded_count: images.len(),
+            images,
+            frame_durations: durations,
+            has_duration_manifest: manifest.is_some(),
+            pending_frames: None,
+            dimension_limit: self.dimension_limit,
+            scale: self.scale,
+            skip_undecodable: self.skip_undecodable,
+            trim_offset,
+            directory: Some(directory),
+            frame_paths: image_paths,
+            order: None,
+            released_sizes: None,
+            metadata,
+            current_index: 0,
+        };
+
+        // The custom order may no longer fit the new frames
+        *self = reloaded.arranged(options)?;
+
+        log::info!("Reloaded all {} frames", self.images.len());
+        Ok(ReloadOutcome::Sequence)
+    }
+
+    /// Apply the scale set by `scale_frames` and the limit set by
+    /// `limit_dimensions` to a newly decoded frame
+    fn fit_frame(&self, image: &mut RgbaImage, index: usize) -> Result<()> {
+        if self.scale != 1.0 {
+            *image = scale_image(image, self.scale);
+        }
+
+        match self.dimension_limit {
+            Some((max_dimension, downscale)) => fit_within(image, index, max_dimension, downscale),
+            None => Ok(()),
+        }
+    }
+
+    /// Find the frames of an image directory in playback order, along with
+    /// their durations if a manifest is present
+    fn list_image_directory(
+        directory: &Path,
+        options: &LoadOptions,
+    ) -> Result<(Vec<PathBuf>, Option<FrameDurations>)> {
+        let filter = options
+            .filter
+            .as_deref()
+            .map(|filter| {
+                Pattern::new(filter)
+                    .map_err(|e| anyhow!("Invalid frame filter '{}': {}", filter, e))
+            })
+            .transpose()?;
+
+        let mut image_paths = collect_image_paths(directory, options.recursive, filter.as_ref())?;
+
+        match options.sort_order {
+            SortOrder::Natural => {
+                image_paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()))
+            }
+            SortOrder::Lexicographic => image_paths.sort(),
+        }
 
         if image_paths.is_empty() {
             return Err(anyhow!("No image files found in {}", directory.display()));
@@ -52,17 +560,116 @@ impl MediaSequence {
 
         log::info!("Found {} images in directory", image_paths.len());
 
-        let mut images = Vec::with_capacity(image_paths.len());
-        for path in image_paths {
-            log::debug!("Loading {}", path.display());
-            let img = image::open(&path)?.to_rgba8();
-            images.push(img);
+        // Frames are named by their path relative to the directory, which for
+        // non-recursive loads is just the file name
+        let frame_names: Vec<String> = image_paths
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(directory).unwrap_or(path);
+                relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+
+        let durations = Self::load_duration_manifest(directory, &frame_names)?;
+
+        Ok((image_paths, durations))
+    }
+
+    /// Read the optional frames.toml / frames.json next to the images and
+    /// resolve it into one entry per frame name
+    fn load_duration_manifest(
+        directory: &Path,
+        frame_names: &[String],
+    ) -> Result<Option<FrameDurations>> {
+        let Some(manifest_path) = DURATION_MANIFESTS
+            .iter()
+            .map(|name| directory.join(name))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+
+        log::info!("Loading frame durations from {}", manifest_path.display());
+
+        let content = fs::read_to_string(&manifest_path).map_err(|e| {
+            anyhow!(
+                "Failed to read frame manifest {}: {}",
+                manifest_path.display(),
+                e
+            )
+        })?;
+
+        let is_json = manifest_path.extension().is_some_and(|ext| ext == "json");
+        let expected_format = if is_json {
+            r#"{"file.png": milliseconds}"#
+        } else {
+            r#""file.png" = milliseconds"#
+        };
+
+        let parsed: Result<HashMap<String, u64>> = if is_json {
+            serde_json::from_str(&content).map_err(|e| anyhow!(e))
+        } else {
+            toml::from_str(&content).map_err(|e| anyhow!(e))
+        };
+
+        let entries = parsed.map_err(|e| {
+            anyhow!(
+                "Failed to parse frame manifest {} (expected {}): {}",
+                manifest_path.display(),
+                expected_format,
+                e
+            )
+        })?;
+
+        if let Some((name, _)) = entries.iter().find(|(_, ms)| **ms == 0) {
+            return Err(anyhow!(
+                "Frame manifest {} gives '{}' a duration of 0ms",
+                manifest_path.display(),
+                name
+            ));
         }
 
-        Ok(images)
+        for name in entries.keys() {
+            if !frame_names.contains(name) {
+                log::warn!(
+                    "Frame manifest {} lists '{}' which is not a loaded frame",
+                    manifest_path.display(),
+                    name
+                );
+            }
+        }
+
+        let durations = frame_names
+            .iter()
+            .map(|name| entries.get(name).map(|ms| Duration::from_millis(*ms)))
+            .collect();
+
+        Ok(Some(durations))
+    }
+
+    fn load_stdin() -> Result<RgbaImage> {
+        let mut stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            return Err(anyhow!("Expected an image piped to standard input"));
+        }
+
+        let mut data = Vec::new();
+        stdin
+            .read_to_end(&mut data)
+            .map_err(|e| anyhow!("Failed to read standard input: {}", e))?;
+
+        log::info!("Read {} bytes from standard input", data.len());
+
+        Ok(image::load_from_memory(&data)
+            .map_err(|e| anyhow!("Failed to decode image from standard input: {}", e))?
+            .to_rgba8())
     }
 
-    fn load_gif(path: &Path) -> Result<Vec<RgbaImage>> {
+    fn load_gif(path: &Path) -> Result<(Vec<RgbaImage>, FrameDurations)> {
         log::info!("Loading GIF file: {}", path.display());
 
         let file = StdFile::open(path)?;
@@ -80,6 +687,7 @@ impl MediaSequence {
         log::info!("GIF canvas size: {}x{}", canvas_width, canvas_height);
 
         let mut images = Vec::new();
+        let mut durations = Vec::new();
         let mut canvas = RgbaImage::new(canvas_width, canvas_height);
         let mut previous_canvas: Option<RgbaImage> = None;
 
@@ -93,12 +701,13 @@ impl MediaSequence {
             let frame_top = frame.top as u32;
 
             log::debug!(
-                "Frame: {}x{} at ({}, {}) dispose: {:?}",
+                "Frame: {}x{} at ({}, {}) dispose: {:?} delay: {}0ms",
                 frame_width,
                 frame_height,
                 frame_left,
                 frame_top,
-                frame.dispose
+                frame.dispose,
+                frame.delay
             );
 
             // Create frame image from buffer
@@ -113,8 +722,11 @@ impl MediaSequence {
 
                     if canvas_x < canvas_width && canvas_y < canvas_height {
                         let pixel = frame_image.get_pixel(x, y);
-                        // Always composite the pixel (including transparent ones for proper clearing)
-                        canvas.put_pixel(canvas_x, canvas_y, *pixel);
+                        // Transparent pixels let the previous canvas show through,
+                        // clearing is handled by the disposal method instead
+                        if pixel[3] != 0 {
+                            canvas.put_pixel(canvas_x, canvas_y, *pixel);
+                        }
                     }
                 }
             }
@@ -122,6 +734,10 @@ impl MediaSequence {
             // Save the current canvas state as this frame's output
             images.push(canvas.clone());
 
+            // GIF delays are in hundredths of a second, 0 means "unspecified"
+            durations
+                .push((frame.delay > 0).then(|| Duration::from_millis(frame.delay as u64 * 10)));
+
             // Now handle disposal method to prepare canvas for the next frame
             match frame.dispose {
                 gif::DisposalMethod::Keep => {
@@ -153,105 +769,461 @@ impl MediaSequence {
         }
 
         log::info!("Loaded {} frames from GIF", images.len());
-        Ok(images)
+        Ok((images, durations))
     }
 
-    fn load_apng(path: &Path) -> Result<Vec<RgbaImage>> {
+    fn load_apng(path: &Path) -> Result<(Vec<RgbaImage>, FrameDurations)> {
         log::info!("Loading APNG file: {}", path.display());
 
-        let file = StdFile::open(path)?;
-        let decoder = png::Decoder::new(file);
-        let mut reader = decoder
-            .read_info()
-            .map_err(|e| anyhow!("Failed to read PNG info: {}", e))?;
+        let file = BufReader::new(StdFile::open(path)?);
+        let decoder =
+            PngDecoder::new(file).map_err(|e| anyhow!("Failed to read PNG info: {}", e))?;
 
-        // Get the full image dimensions from the PNG info
-        let canvas_width = reader.info().width;
-        let canvas_height = reader.info().height;
+        // Not animated, just load as single image
+        if !decoder.is_apng()? {
+            log::info!("PNG is not animated, loading as single frame");
+            let img = image::open(path)?.to_rgba8();
+            return Ok((vec![img], vec![None]));
+        }
 
+        let (canvas_width, canvas_height) = decoder.dimensions();
         log::info!("APNG canvas size: {}x{}", canvas_width, canvas_height);
 
-        let mut images = Vec::new();
+        // The APNG decoder composites each fcTL region onto the full canvas,
+        // applying the frame's blend and dispose ops
+        let frames = decoder
+            .apng()?
+            .into_frames()
+            .collect_frames()
+            .map_err(|e| anyhow!("Error reading APNG frame: {}", e))?;
 
-        // Check if it's animated
-        if let Some(animation_control) = reader.info().animation_control() {
-            log::info!("APNG has {} frames", animation_control.num_frames);
-
-            let buffer_size = reader.output_buffer_size();
-            let mut buffer = vec![0; buffer_size];
-
-            // Read all frames
-            loop {
-                match reader.next_frame(&mut buffer) {
-                    Ok(output_info) => {
-                        // Always use the full canvas dimensions, not the frame output dimensions
-                        let width = canvas_width;
-                        let height = canvas_height;
-
-                        let rgba_buffer = match output_info.color_type {
-                            png::ColorType::Rgba => buffer.clone(),
-                            png::ColorType::Rgb => {
-                                let mut rgba = Vec::with_capacity((width * height * 4) as usize);
-                                for chunk in buffer.chunks(3) {
-                                    rgba.extend_from_slice(chunk);
-                                    rgba.push(255);
-                                }
-                                rgba
-                            }
-                            _ => {
-                                return Err(anyhow!(
-                                    "Unsupported PNG color type: {:?}",
-                                    output_info.color_type
-                                ));
-                            }
-                        };
-
-                        // Ensure we have the right buffer size for the full canvas
-                        let expected_size = (width * height * 4) as usize;
-                        let mut full_buffer = rgba_buffer;
-                        if full_buffer.len() != expected_size {
-                            // If the frame buffer doesn't match canvas size, pad or crop as needed
-                            full_buffer.resize(expected_size, 0);
-                        }
+        let mut images = Vec::with_capacity(frames.len());
+        let mut durations = Vec::with_capacity(frames.len());
 
-                        let rgba_image = RgbaImage::from_raw(width, height, full_buffer)
-                            .ok_or_else(|| anyhow!("Failed to create image from APNG frame"))?;
+        for frame in frames {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            log::debug!("APNG frame delay: {}/{}ms", numerator, denominator);
 
-                        images.push(rgba_image);
-                    }
-                    Err(e) if format!("{}", e).contains("End of image has been reached") => {
-                        // Gracefully end loop
-                        break;
-                    }
-                    Err(png::DecodingError::IoError(ref io_err))
-                        if io_err.kind() == std::io::ErrorKind::UnexpectedEof =>
-                    {
-                        break;
-                    }
-                    Err(e) => return Err(anyhow!("Error reading APNG frame: {}", e)),
+            // A zero delay means the frame should use the global frame interval
+            durations.push((numerator > 0).then(|| Duration::from(frame.delay())));
+            images.push(frame.into_buffer());
+        }
+
+        log::info!("Loaded {} frames from APNG", images.len());
+        Ok((images, durations))
+    }
+
+    fn load_webp(path: &Path) -> Result<(Vec<RgbaImage>, FrameDurations)> {
+        log::info!("Loading WebP file: {}", path.display());
+
+        let file = BufReader::new(StdFile::open(path)?);
+        let decoder =
+            WebPDecoder::new(file).map_err(|e| anyhow!("Failed to read WebP info: {}", e))?;
+
+        // Not animated, just load as single image
+        if !decoder.has_animation() {
+            log::info!("WebP is not animated, loading as single frame");
+            let img = image::open(path)?.to_rgba8();
+            return Ok((vec![img], vec![None]));
+        }
+
+        let (canvas_width, canvas_height) = decoder.dimensions();
+        log::info!("WebP canvas size: {}x{}", canvas_width, canvas_height);
+
+        // The WebP demuxer composites each ANMF frame onto the canvas
+        // according to its blend and dispose flags
+        let frames = decoder
+            .into_frames()
+            .collect_frames()
+            .map_err(|e| anyhow!("Error reading WebP frame: {}", e))?;
+
+        let mut images = Vec::with_capacity(frames.len());
+        let mut durations = Vec::with_capacity(frames.len());
+
+        for frame in frames {
+            let (numerator, _) = frame.delay().numer_denom_ms();
+
+            // A zero duration means the frame should use the global frame interval
+            durations.push((numerator > 0).then(|| Duration::from(frame.delay())));
+            images.push(frame.into_buffer());
+        }
+
+        log::info!("Loaded {} frames from WebP", images.len());
+        Ok((images, durations))
+    }
+
+    fn load_sprite_sheet(path: &Path, grid: SpriteSheetGrid) -> Result<Vec<RgbaImage>> {
+        log::info!(
+            "Loading {}x{} sprite sheet: {}",
+            grid.columns,
+            grid.rows,
+            path.display()
+        );
+
+        if grid.columns == 0 || grid.rows == 0 {
+            return Err(anyhow!(
+                "Sprite sheet grid must have at least one column and row"
+            ));
+        }
+
+        let cell_count = (grid.columns * grid.rows) as usize;
+        if let Some(frame_count) = grid.frame_count
+            && (frame_count == 0 || frame_count > cell_count)
+        {
+            return Err(anyhow!(
+                "Sprite sheet frame count {} must be between 1 and {}",
+                frame_count,
+                cell_count
+            ));
+        }
+
+        let sheet = image::open(path)?.to_rgba8();
+        let (sheet_width, sheet_height) = sheet.dimensions();
+
+        let cell_width = sheet_width / grid.columns;
+        let cell_height = sheet_height / grid.rows;
+
+        if cell_width == 0 || cell_height == 0 {
+            return Err(anyhow!(
+                "Sprite sheet {}x{} is too small for a {}x{} grid",
+                sheet_width,
+                sheet_height,
+                grid.columns,
+                grid.rows
+            ));
+        }
+
+        if sheet_width % grid.columns != 0 || sheet_height % grid.rows != 0 {
+            log::warn!(
+                "Sprite sheet {}x{} doesn't divide evenly into {}x{} cells, ignoring the remainder",
+                sheet_width,
+                sheet_height,
+                grid.columns,
+                grid.rows
+            );
+        }
+
+        log::info!("Sprite sheet cell size: {}x{}", cell_width, cell_height);
+
+        let mut images: Vec<RgbaImage> = (0..cell_count as u32)
+            .map(|cell| {
+                let x = (cell % grid.columns) * cell_width;
+                let y = (cell / grid.columns) * cell_height;
+                image::imageops::crop_imm(&sheet, x, y, cell_width, cell_height).to_image()
+            })
+            .collect();
+
+        match grid.frame_count {
+            Some(frame_count) => images.truncate(frame_count),
+            None => {
+                // Drop empty cells at the end of a partially filled sheet
+                while images.len() > 1
+                    && images
+                        .last()
+                        .is_some_and(|cell| cell.pixels().all(|pixel| pixel[3] == 0))
+                {
+                    images.pop();
                 }
             }
-        } else {
-            // Not animated, just load as single image
-            log::info!("PNG is not animated, loading as single frame");
-            let img = image::open(path)?.to_rgba8();
-            images.push(img);
         }
 
-        log::info!("Loaded {} frames from APNG", images.len());
+        log::info!("Loaded {} frames from sprite sheet", images.len());
         Ok(images)
     }
 
     pub fn current_image(&self) -> Option<&RgbaImage> {
-        self.images.get(self.current_index)
+        self.frame(self.current_index)
+    }
+
+    /// Frame at `index` in playback order
+    pub fn frame(&self, index: usize) -> Option<&RgbaImage> {
+        match &self.order {
+            Some(order) => order.get(index).and_then(|&index| self.images.get(index)),
+            None => self.images.get(index),
+        }
+    }
+
+    /// Size of the current frame, also known after `release_pixels`
+    pub fn current_frame_size(&self) -> Option<(u32, u32)> {
+        let index = match &self.order {
+            Some(order) => *order.get(self.current_index)?,
+            None => self.current_index,
+        };
+        match &self.released_sizes {
+            Some(sizes) => sizes.get(index).copied(),
+            None => self.images.get(index).map(RgbaImage::dimensions),
+        }
     }
 
+    /// Drop the decoded pixels once the frames are on the GPU, keeping the
+    /// frame count, sizes, timing and metadata. Frames read afterwards are
+    /// missing and the sequence can't be reloaded. Frames the background
+    /// decoder hands out later were never kept anyway.
+    pub fn release_pixels(&mut self) {
+        if self.released_sizes.is_none() {
+            self.released_sizes = Some(self.images.iter().map(RgbaImage::dimensions).collect());
+            self.images = Vec::new();
+        }
+    }
+
+    /// Total number of frames, including ones still being decoded
     pub fn count(&self) -> usize {
-        self.images.len()
+        match &self.order {
+            Some(order) => order.len(),
+            None => self.frame_durations.len(),
+        }
+    }
+
+    /// Number of frames decoded so far, always a prefix of the sequence
+    pub fn decoded_count(&self) -> usize {
+        match &self.order {
+            Some(order) => order.len(),
+            None => self.decoded_count,
+        }
     }
 
-    pub fn get_all_images(&self) -> &[RgbaImage] {
-        &self.images
+    pub fn is_loading(&self) -> bool {
+        self.pending_frames.is_some()
+    }
+
+    /// Collect frames the background decoder has finished since the last call.
+    ///
+    /// These frames are not kept by the sequence. If a frame fails to decode
+    /// the sequence is cut short at that point.
+    pub fn take_decoded_frames(&mut self) -> Vec<(usize, RgbaImage)> {
+        let mut decoded = Vec::new();
+        let Some(receiver) = &self.pending_frames else {
+            return decoded;
+        };
+
+        let mut finished = false;
+        loop {
+            let error = match receiver.try_recv() {
+                Ok(Ok(mut image)) => match self.fit_frame(&mut image, self.decoded_count) {
+                    Ok(()) => {
+                        decoded.push((self.decoded_count, image));
+                        self.decoded_count += 1;
+                        continue;
+                    }
+                    Err(e) => e,
+                },
+                Ok(Err(e)) if self.skip_undecodable => {
+                    log::warn!("Skipping frame: {}", e);
+                    self.frame_durations.remove(self.decoded_count);
+                    if self.decoded_count < self.frame_paths.len() {
+                        self.frame_paths.remove(self.decoded_count);
+                    }
+                    continue;
+                }
+                Ok(Err(e)) => e,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    log::info!("Finished decoding {} frames", self.decoded_count);
+                    finished = true;
+                    break;
+                }
+            };
+
+            log::error!(
+                "{}, stopping the sequence after {} frames",
+                error,
+                self.decoded_count
+            );
+            self.frame_durations.truncate(self.decoded_count);
+            finished = true;
+            break;
+        }
+
+        if finished {
+            self.pending_frames = None;
+        }
+
+        decoded
+    }
+
+    /// Make sure no frame is wider or taller than `max_dimension`, either by
+    /// downscaling it or, when `downscale` is false, by failing
+    pub fn limit_dimensions(&mut self, max_dimension: u32, downscale: bool) -> Result<()> {
+        self.dimension_limit = Some((max_dimension, downscale));
+
+        for (index, image) in self.images.iter_mut().enumerate() {
+            fit_within(image, index, max_dimension, downscale)?;
+        }
+
+        Ok(())
+    }
+
+    /// Resize every frame by `factor`, keeping the aspect ratio
+    pub fn scale_frames(&mut self, factor: f64) {
+        self.scale *= factor;
+
+        for image in &mut self.images {
+            *image = scale_image(image, factor);
+        }
+    }
+
+    /// Texture memory needed to upload every frame uncompressed. Frames still
+    /// being decoded are assumed to be as large as the first.
+    pub fn estimated_memory(&self) -> u64 {
+        let frame_size = |image: &RgbaImage| image.width() as u64 * image.height() as u64 * 4;
+        let decoded: u64 = self.images.iter().map(frame_size).sum();
+        let pending = self.count().saturating_sub(self.images.len()) as u64;
+
+        decoded + pending * self.images.first().map_or(0, frame_size)
+    }
+
+    /// Where the trimmed frames start within the frames as stored on disk,
+    /// `(0, 0)` when nothing was trimmed
+    pub fn trim_offset(&self) -> (u32, u32) {
+        self.trim_offset
+    }
+
+    /// Every decoded frame in playback order, repeated frames appear once
+    /// per occurrence but share their data. Empty after `release_pixels`.
+    pub fn get_all_images(&self) -> Vec<&RgbaImage> {
+        match &self.order {
+            Some(order) => order
+                .iter()
+                .filter_map(|&index| self.images.get(index))
+                .collect(),
+            None => self.images.iter().collect(),
+        }
+    }
+
+    /// Display time of each frame in playback order
+    pub fn frame_durations(&self) -> FrameDurations {
+        match &self.order {
+            Some(order) => order
+                .iter()
+                .map(|&index| self.frame_durations.get(index).copied().flatten())
+                .collect(),
+            None => self.frame_durations.clone(),
+        }
+    }
+
+    pub fn has_duration_manifest(&self) -> bool {
+        self.has_duration_manifest
+    }
+
+    /// Playback defaults from the sequence's meta.toml
+    pub fn metadata(&self) -> &SequenceMetadata {
+        &self.metadata
+    }
+
+    /// Directory the frames were loaded from, if any
+    pub fn source_directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+}
+
+/// Several sequences loaded side by side and addressed by name, in the order
+/// they were added
+#[derive(Default)]
+pub struct SequenceSet {
+    sequences: Vec<(String, MediaSequence)>,
+}
+
+impl SequenceSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: String, sequence: MediaSequence) -> Result<()> {
+        if self.index_of(&name).is_some() {
+            return Err(anyhow!("A sequence named '{}' is already loaded", name));
+        }
+
+        self.sequences.push((name, sequence));
+        Ok(())
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.sequences
+            .iter()
+            .position(|(sequence_name, _)| sequence_name == name)
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.sequences.get(index).map(|(name, _)| name.as_str())
+    }
+
+    pub fn at(&self, index: usize) -> Option<&MediaSequence> {
+        self.sequences.get(index).map(|(_, sequence)| sequence)
+    }
+
+    pub fn at_mut(&mut self, index: usize) -> Option<&mut MediaSequence> {
+        self.sequences.get_mut(index).map(|(_, sequence)| sequence)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sequences.len()
+    }
+
+    /// Uniformly downscale all sequences when uploading them would need more
+    /// than `budget` bytes of texture memory
+    pub fn fit_memory_budget(&mut self, budget: u64) -> Result<()> {
+        let estimate: u64 = self.iter().map(MediaSequence::estimated_memory).sum();
+        if estimate <= budget {
+            log::info!(
+                "Estimated texture memory of {} fits the budget of {}",
+                format_bytes(estimate),
+                format_bytes(budget)
+            );
+            return Ok(());
+        }
+
+        // Memory grows with the square of the side length
+        let factor = (budget as f64 / estimate as f64).sqrt();
+        if factor < MIN_BUDGET_SCALE {
+            return Err(anyhow!(
+                "Frames need {} of texture memory and would have to shrink to {:.1}% to fit the budget of {}, below the minimum of {:.1}%",
+                format_bytes(estimate),
+                factor * 100.0,
+                format_bytes(budget),
+                MIN_BUDGET_SCALE * 100.0
+            ));
+        }
+
+        let original_size = self
+            .iter()
+            .next()
+            .and_then(MediaSequence::current_image)
+            .map(|image| image.dimensions());
+
+        for sequence in self.iter_mut() {
+            sequence.scale_frames(factor);
+        }
+
+        let scaled_size = self
+            .iter()
+            .next()
+            .and_then(MediaSequence::current_image)
+            .map(|image| image.dimensions());
+        let final_estimate: u64 = self.iter().map(MediaSequence::estimated_memory).sum();
+
+        if let (Some(original), Some(scaled)) = (original_size, scaled_size) {
+            log::warn!(
+                "Scaled frames by {:.1}% from {}x{} to {}x{} so the estimated texture memory of {} fits the budget of {} (now {})",
+                factor * 100.0,
+                original.0,
+                original.1,
+                scaled.0,
+                scaled.1,
+                format_bytes(estimate),
+                format_bytes(budget),
+                format_bytes(final_estimate)
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MediaSequence> {
+        self.sequences.iter().map(|(_, sequence)| sequence)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut MediaSequence> {
+        self.sequences.iter_mut().map(|(_, sequence)| sequence)
     }
 }
 
@@ -272,19 +1244,12 @@ pub fn detect_media_type(path: &Path) -> Result<MediaSource> {
                 if is_apng(path)? {
                     Ok(MediaSource::ApngFile(path.to_path_buf()))
                 } else {
-                    // Single PNG, treat as directory with one file
-                    let parent = path
-                        .parent()
-                        .ok_or_else(|| anyhow!("Cannot get parent directory"))?;
-                    Ok(MediaSource::Directory(parent.to_path_buf()))
+                    Ok(MediaSource::ImageFile(path.to_path_buf()))
                 }
             }
-            Some("jpg") | Some("jpeg") => {
-                // Single image, treat as directory
-                let parent = path
-                    .parent()
-                    .ok_or_else(|| anyhow!("Cannot get parent directory"))?;
-                Ok(MediaSource::Directory(parent.to_path_buf()))
+            Some("webp") => Ok(MediaSource::WebpFile(path.to_path_buf())),
+            Some("jpg") | Some("jpeg") | Some("bmp") | Some("tif") | Some("tiff") => {
+                Ok(MediaSource::ImageFile(path.to_path_buf()))
             }
             _ => Err(anyhow!("Unsupported file type: {:?}", extension)),
         }
@@ -293,9 +1258,1152 @@ pub fn detect_media_type(path: &Path) -> Result<MediaSource> {
     }
 }
 
+/// Scale a frame down, keeping its aspect ratio, so that neither side is
+/// larger than `max_dimension`
+fn fit_within(
+    image: &mut RgbaImage,
+    index: usize,
+    max_dimension: u32,
+    downscale: bool,
+) -> Result<()> {
+    let (width, height) = image.dimensions();
+    if width <= max_dimension && height <= max_dimension {
+        return Ok(());
+    }
+
+    if !downscale {
+        return Err(anyhow!(
+            "Frame {} is {}x{}, larger than the GPU's maximum texture size of {}",
+            index,
+            width,
+            height,
+            max_dimension
+        ));
+    }
+
+    let scale = max_dimension as f64 / width.max(height) as f64;
+    let new_width = ((width as f64 * scale).round() as u32).clamp(1, max_dimension);
+    let new_height = ((height as f64 * scale).round() as u32).clamp(1, max_dimension);
+
+    log::warn!(
+        "Downscaling frame {} from {}x{} to {}x{} to fit the GPU's maximum texture size of {}",
+        index,
+        width,
+        height,
+        new_width,
+        new_height,
+        max_dimension
+    );
+
+    *image = image::imageops::resize(image, new_width, new_height, FilterType::Lanczos3);
+    Ok(())
+}
+
+/// Read the optional meta.toml of an image directory
+fn load_metadata(directory: &Path) -> Result<SequenceMetadata> {
+    let path = directory.join(METADATA_FILE);
+    if !path.is_file() {
+        return Ok(SequenceMetadata::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let metadata: SequenceMetadata = toml::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+    if metadata.fps == Some(0) {
+        return Err(anyhow!("{} sets fps to 0", path.display()));
+    }
+
+    log::info!(
+        "Loaded sequence metadata from {}: {:?}",
+        path.display(),
+        metadata
+    );
+    Ok(metadata)
+}
+
+/// Pick `indices` out of `order`, making sure every index exists
+fn reorder_indices(order: &[usize], indices: &[usize]) -> Result<Vec<usize>> {
+    if indices.is_empty() {
+        return Err(anyhow!("A frame order needs at least one frame"));
+    }
+
+    indices
+        .iter()
+        .map(|&index| {
+            order.get(index).copied().ok_or_else(|| {
+                anyhow!(
+                    "Frame order refers to frame {} but the sequence has {} frames",
+                    index,
+                    order.len()
+                )
+            })
+        })
+        .collect()
+}
+
+fn subsample_order(order: &[usize], step: usize) -> Result<Vec<usize>> {
+    if step == 0 {
+        return Err(anyhow!("Frame step must be at least 1"));
+    }
+
+    Ok(order.iter().copied().step_by(step).collect())
+}
+
+/// Crop every frame to the union of their non-transparent areas and return
+/// the offset of that box. Sequences without any visible pixel are left as
+/// they are.
+fn trim_transparent_borders(images: &mut [RgbaImage]) -> (u32, u32) {
+    let Some((left, top, right, bottom)) = images
+        .iter()
+        .filter_map(opaque_bounds)
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+    else {
+        log::warn!("Every frame is fully transparent, nothing to trim");
+        return (0, 0);
+    };
+
+    let (width, height) = images[0].dimensions();
+    log::info!(
+        "Trimming transparent borders from {}x{} to {}x{} at offset ({}, {})",
+        width,
+        height,
+        right - left,
+        bottom - top,
+        left,
+        top
+    );
+
+    for image in images.iter_mut() {
+        // Frames may differ in size, keep the box within each one
+        let crop_width = right.min(image.width()).saturating_sub(left).max(1);
+        let crop_height = bottom.min(image.height()).saturating_sub(top).max(1);
+        let x = left.min(image.width() - 1);
+        let y = top.min(image.height() - 1);
+        *image = image::imageops::crop_imm(image, x, y, crop_width, crop_height).to_image();
+    }
+
+    (left, top)
+}
+
+/// Bounding box of the pixels with non-zero alpha as (left, top, right,
+/// bottom), right and bottom exclusive
+fn opaque_bounds(image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+
+        bounds = Some(match bounds {
+            Some((left, top, right, bottom)) => {
+                (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+            }
+            None => (x, y, x + 1, y + 1),
+        });
+    }
+
+    bounds
+}
+
+/// Resize a frame by `factor`, rounding down so the result never takes more
+/// memory than intended
+fn scale_image(image: &RgbaImage, factor: f64) -> RgbaImage {
+    let width = ((image.width() as f64 * factor) as u32).max(1);
+    let height = ((image.height() as f64 * factor) as u32).max(1);
+    image::imageops::resize(image, width, height, FilterType::Lanczos3)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Name shown for a file in progress reports
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn decode_image(path: &Path) -> Result<RgbaImage> {
+    log::debug!("Loading {}", path.display());
+    Ok(image::open(path)
+        .map_err(|e| anyhow!("Failed to decode {}: {}", path.display(), e))?
+        .to_rgba8())
+}
+
+/// Decode frames in parallel, keeping their order. Every failing file is
+/// reported instead of just the first one, and with `skip_undecodable` the
+/// failing frames come back as `None` instead of being an error.
+fn decode_images(
+    paths: &[PathBuf],
+    progress: &ProgressFn,
+    skip_undecodable: bool,
+) -> Result<Vec<Option<RgbaImage>>> {
+    let done = AtomicUsize::new(0);
+    let results: Vec<Result<RgbaImage>> = paths
+        .par_iter()
+        .map(|path| {
+            let result = decode_image(path);
+            let done = done.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+            progress(done, paths.len(), &file_name(path));
+            result
+        })
+        .collect();
+
+    let mut images = Vec::with_capacity(results.len());
+    let mut errors = Vec::new();
+
+    for result in results {
+        match result {
+            Ok(image) => images.push(Some(image)),
+            Err(e) if skip_undecodable => {
+                log::warn!("Skipping frame: {}", e);
+                images.push(None);
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "Failed to decode {} of {} frames:\n  {}",
+            errors.len(),
+            paths.len(),
+            errors.join("\n  ")
+        ));
+    }
+
+    Ok(images)
+}
+
+/// Drop the paths and durations of frames `decode_images` skipped
+fn keep_decoded(
+    decoded: Vec<Option<RgbaImage>>,
+    paths: &mut Vec<PathBuf>,
+    durations: &mut FrameDurations,
+) -> Vec<RgbaImage> {
+    let mut kept = decoded.iter().map(Option::is_some);
+    paths.retain(|_| kept.next().unwrap_or(false));
+    let mut kept = decoded.iter().map(Option::is_some);
+    durations.retain(|_| kept.next().unwrap_or(false));
+
+    decoded.into_iter().flatten().collect()
+}
+
+/// Decode frames on a background thread, sending them back in order. A
+/// failing frame ends the decoding unless `skip_undecodable` is set.
+fn spawn_frame_decoder(paths: Vec<PathBuf>, skip_undecodable: bool) -> Receiver<Result<RgbaImage>> {
+    // A small bound keeps the decoder from racing far ahead of the GPU upload
+    let (sender, receiver) = mpsc::sync_channel(8);
+
+    thread::spawn(move || {
+        for path in paths {
+            let result = decode_image(&path);
+            let failed = result.is_err() && !skip_undecodable;
+            if sender.send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+/// List the image files of a directory, optionally descending into
+/// subdirectories and keeping only paths matching `filter`
+fn collect_image_paths(
+    directory: &Path,
+    recursive: bool,
+    filter: Option<&Pattern>,
+) -> Result<Vec<PathBuf>> {
+    let match_options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::default()
+    };
+
+    let mut image_paths = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![directory.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        // Resolving symlinks means every real directory is read at most once,
+        // even when a link points back up the tree
+        if !visited.insert(fs::canonicalize(&dir)?) {
+            log::debug!("Skipping already visited directory {}", dir.display());
+            continue;
+        }
+
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            let is_image = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if !is_image {
+                continue;
+            }
+
+            if let Some(pattern) = filter {
+                let relative = path.strip_prefix(directory).unwrap_or(&path);
+                if !pattern.matches_path_with(relative, match_options) {
+                    log::debug!("Skipping {} (doesn't match filter)", path.display());
+                    continue;
+                }
+            }
+
+            image_paths.push(path);
+        }
+    }
+
+    Ok(image_paths)
+}
+
+/// Compare two strings treating runs of ASCII digits as numbers, so that
+/// "img2.png" sorts before "img10.png"
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_digits = take_digits(&mut a_chars);
+                let b_digits = take_digits(&mut b_chars);
+
+                let a_value = a_digits.trim_start_matches('0');
+                let b_value = b_digits.trim_start_matches('0');
+
+                // Longer runs without leading zeros are larger numbers, equal
+                // lengths compare digit by digit. Ties fall back to the number
+                // of leading zeros to keep the order deterministic.
+                let ordering = a_value
+                    .len()
+                    .cmp(&b_value.len())
+                    .then_with(|| a_value.cmp(b_value))
+                    .then_with(|| a_digits.len().cmp(&b_digits.len()));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
 fn is_apng(path: &Path) -> Result<bool> {
     let file = StdFile::open(path)?;
     let decoder = png::Decoder::new(file);
     let reader = decoder.read_info()?;
     Ok(reader.info().animation_control().is_some())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    /// Encode a 2x1 PNG with the given color layout into `directory`
+    fn write_png(
+        directory: &Path,
+        name: &str,
+        color: png::ColorType,
+        depth: png::BitDepth,
+        palette: Option<(&[u8], &[u8])>,
+        data: &[u8],
+    ) -> PathBuf {
+        let path = directory.join(format!("{}.png", name));
+        let file = StdFile::create(&path).unwrap();
+
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), 2, 1);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some((palette, trns)) = palette {
+            encoder.set_palette(palette);
+            encoder.set_trns(trns);
+        }
+
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_reload_directory() {
+        let directory = TestDir::new("reload");
+
+        let frame = |value: u8| RgbaImage::from_pixel(2, 2, Rgba([value, 0, 0, 255]));
+        frame(1).save(directory.join("1.png")).unwrap();
+        frame(2).save(directory.join("2.png")).unwrap();
+
+        let options = LoadOptions::default();
+        let mut sequence = MediaSequence::load(
+            MediaSource::Directory(directory.to_path_buf()),
+            &options,
+            &|_, _, _| {},
+        )
+        .unwrap();
+
+        frame(3).save(directory.join("2.png")).unwrap();
+        let outcome = sequence
+            .reload(&[directory.join("2.png")], &options)
+            .unwrap();
+        assert_eq!(outcome, ReloadOutcome::Frames(vec![1]));
+        assert_eq!(sequence.frame(1).unwrap().get_pixel(0, 0)[0], 3);
+
+        // A frame caught half written keeps the image it had
+        frame(5).save(directory.join("1.png")).unwrap();
+        fs::write(directory.join("2.png"), b"not a png yet").unwrap();
+        let outcome = sequence
+            .reload(
+                &[directory.join("1.png"), directory.join("2.png")],
+                &options,
+            )
+            .unwrap();
+        assert_eq!(outcome, ReloadOutcome::Frames(vec![0]));
+        assert_eq!(sequence.frame(0).unwrap().get_pixel(0, 0)[0], 5);
+        assert_eq!(sequence.frame(1).unwrap().get_pixel(0, 0)[0], 3);
+        frame(3).save(directory.join("2.png")).unwrap();
+
+        frame(4).save(directory.join("3.png")).unwrap();
+        let outcome = sequence
+            .reload(&[directory.join("3.png")], &options)
+            .unwrap();
+        assert_eq!(outcome, ReloadOutcome::Sequence);
+        assert_eq!(sequence.count(), 3);
+
+        RgbaImage::new(4, 4).save(directory.join("1.png")).unwrap();
+        let outcome = sequence
+            .reload(&[directory.join("1.png")], &options)
+            .unwrap();
+        assert_eq!(outcome, ReloadOutcome::Sequence);
+    }
+
+    #[test]
+    fn test_fit_memory_budget() {
+        let directory = TestDir::new("budget");
+
+        for name in ["1.png", "2.png"] {
+            RgbaImage::from_pixel(100, 100, Rgba([0, 0, 0, 255]))
+                .save(directory.join(name))
+                .unwrap();
+        }
+
+        let load = || {
+            let mut set = SequenceSet::new();
+            let source = MediaSource::Directory(directory.to_path_buf());
+            let sequence =
+                MediaSequence::load(source, &LoadOptions::default(), &|_, _, _| {}).unwrap();
+            set.insert("main".to_string(), sequence).unwrap();
+            set
+        };
+
+        let mut set = load();
+        set.fit_memory_budget(20_000).unwrap();
+        let sequence = set.at(0).unwrap();
+        assert_eq!(sequence.current_image().unwrap().dimensions(), (50, 50));
+        assert!(sequence.estimated_memory() <= 20_000);
+
+        assert!(load().fit_memory_budget(100).is_err());
+    }
+
+    #[test]
+    fn test_load_mixed_formats() {
+        let directory = TestDir::new("mixed");
+
+        let frame = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 255]));
+        frame.save(directory.join("frame_1.png")).unwrap();
+        image::DynamicImage::ImageRgba8(frame.clone())
+            .to_rgb8()
+            .save(directory.join("frame_2.jpg"))
+            .unwrap();
+        frame.save(directory.join("frame_3.bmp")).unwrap();
+        frame.save(directory.join("frame_10.tiff")).unwrap();
+        fs::write(directory.join("frame_4.png"), b"not a png").unwrap();
+
+        let source = || MediaSource::Directory(directory.to_path_buf());
+        let strict = LoadOptions::default();
+        let error = MediaSequence::load(source(), &strict, &|_, _, _| {})
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("frame_4.png"));
+
+        let lenient = LoadOptions {
+            skip_undecodable: true,
+            ..LoadOptions::default()
+        };
+        let sequence = MediaSequence::load(source(), &lenient, &|_, _, _| {}).unwrap();
+        assert_eq!(sequence.count(), 4);
+        let names: Vec<String> = sequence
+            .frame_paths
+            .iter()
+            .map(|path| file_name(path))
+            .collect();
+        assert_eq!(
+            names,
+            ["frame_1.png", "frame_2.jpg", "frame_3.bmp", "frame_10.tiff"]
+        );
+    }
+
+    #[test]
+    fn test_collect_image_paths() {
+        let directory = TestDir::new("collect");
+        let frame = RgbaImage::new(1, 1);
+        fs::create_dir(directory.join("dance")).unwrap();
+        frame.save(directory.join("idle.png")).unwrap();
+        frame.save(directory.join("dance/1.png")).unwrap();
+        frame.save(directory.join("dance/2.bmp")).unwrap();
+        fs::write(directory.join("dance/notes.txt"), "not a frame").unwrap();
+        // A link back up the tree is followed once, not forever
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&*directory, directory.join("dance/loop")).unwrap();
+
+        let collect = |recursive, filter: Option<&str>| {
+            let filter = filter.map(|filter| Pattern::new(filter).unwrap());
+            let mut paths = collect_image_paths(&directory, recursive, filter.as_ref()).unwrap();
+            paths.sort();
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(&*directory).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        assert_eq!(collect(false, None), paths(&["idle.png"]));
+        assert_eq!(
+            collect(true, None),
+            paths(&["dance/1.png", "dance/2.bmp", "idle.png"])
+        );
+        // The filter matches paths relative to the directory, * stays
+        // within one directory
+        assert_eq!(collect(true, Some("dance/*.png")), paths(&["dance/1.png"]));
+        assert_eq!(collect(true, Some("*.png")), paths(&["idle.png"]));
+        assert!(collect(false, Some("dance/*")).is_empty());
+    }
+
+    #[test]
+    fn test_lazy_decoding() {
+        let directory = TestDir::new("lazy");
+        for value in 0..3u8 {
+            RgbaImage::from_pixel(2, 2, Rgba([value, 0, 0, 255]))
+                .save(directory.join(format!("{}.png", value)))
+                .unwrap();
+        }
+
+        let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let report = reported.clone();
+        let options = LoadOptions {
+            lazy: true,
+            ..LoadOptions::default()
+        };
+        let mut sequence = MediaSequence::load(
+            MediaSource::Directory(directory.to_path_buf()),
+            &options,
+            &move |done, total, _| report.lock().unwrap().push((done, total)),
+        )
+        .unwrap();
+
+        // Only the first frame is decoded up front, the count covers all
+        assert_eq!(*reported.lock().unwrap(), [(1, 3)]);
+        assert_eq!(sequence.count(), 3);
+        assert_eq!(sequence.decoded_count(), 1);
+        assert!(sequence.is_loading());
+        assert_eq!(sequence.frame(0).unwrap().get_pixel(0, 0)[0], 0);
+
+        // The rest arrive in order as the background decoder finishes them
+        let mut decoded = Vec::new();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while sequence.is_loading() && std::time::Instant::now() < deadline {
+            for (index, image) in sequence.take_decoded_frames() {
+                decoded.push((index, image.get_pixel(0, 0)[0]));
+            }
+            assert_eq!(sequence.decoded_count(), 1 + decoded.len());
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!sequence.is_loading());
+        assert_eq!(decoded, [(1, 1), (2, 2)]);
+        assert_eq!(sequence.decoded_count(), 3);
+        // Handed out frames aren't kept
+        assert!(sequence.frame(1).is_none());
+    }
+
+    /// A sequence whose frames are 1x1 images with red set to their index
+    fn numbered_sequence(count: u8) -> MediaSequence {
+        MediaSequence {
+            images: (0..count)
+                .map(|i| RgbaImage::from_pixel(1, 1, Rgba([i, 0, 0, 255])))
+                .collect(),
+            frame_durations: (0..count)
+                .map(|i| Some(Duration::from_millis(i as u64 + 1)))
+                .collect(),
+            has_duration_manifest: true,
+            pending_frames: None,
+            decoded_count: count as usize,
+            dimension_limit: None,
+            scale: 1.0,
+            skip_undecodable: false,
+            trim_offset: (0, 0),
+            directory: None,
+            frame_paths: Vec::new(),
+            order: None,
+            released_sizes: None,
+            metadata: SequenceMetadata::default(),
+            current_index: 0,
+        }
+    }
+
+    fn frame_numbers(sequence: &MediaSequence) -> Vec<u8> {
+        sequence
+            .get_all_images()
+            .iter()
+            .map(|image| image.get_pixel(0, 0)[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_reversed_odd_length() {
+        let sequence = numbered_sequence(5).reversed().unwrap();
+        assert_eq!(frame_numbers(&sequence), [4, 3, 2, 1, 0]);
+        assert_eq!(sequence.count(), 5);
+        assert_eq!(sequence.current_image().unwrap().get_pixel(0, 0)[0], 4);
+        assert_eq!(
+            sequence.frame_durations()[0],
+            Some(Duration::from_millis(5))
+        );
+    }
+
+    #[test]
+    fn test_reorder_with_repeats() {
+        let sequence = numbered_sequence(4).reorder(&[0, 1, 1, 3, 0]).unwrap();
+        assert_eq!(frame_numbers(&sequence), [0, 1, 1, 3, 0]);
+        assert_eq!(sequence.count(), 5);
+
+        // Indices refer to the order that is already in place
+        let sequence = sequence.subsampled(2).unwrap().reorder(&[2, 2]).unwrap();
+        assert_eq!(frame_numbers(&sequence), [0, 0]);
+
+        assert!(numbered_sequence(3).reorder(&[0, 3]).is_err());
+        assert!(numbered_sequence(3).reorder(&[]).is_err());
+        assert!(numbered_sequence(3).subsampled(0).is_err());
+    }
+
+    #[test]
+    fn test_release_pixels() {
+        let mut sequence = numbered_sequence(3).reversed().unwrap();
+        sequence.images[2] = RgbaImage::new(4, 2);
+        sequence.release_pixels();
+
+        assert!(sequence.get_all_images().is_empty());
+        assert!(sequence.current_image().is_none());
+        // Counting, sizing and timing still work
+        assert_eq!(sequence.count(), 3);
+        assert_eq!(sequence.current_frame_size(), Some((4, 2)));
+        assert_eq!(
+            sequence.frame_durations()[0],
+            Some(Duration::from_millis(3))
+        );
+    }
+
+    #[test]
+    fn test_load_metadata() {
+        let directory = TestDir::new("metadata");
+
+        assert!(load_metadata(&directory).unwrap().fps.is_none());
+
+        fs::write(
+            directory.join(METADATA_FILE),
+            "fps = 12\nloop = \"hold\"\ntitle = \"Dance\"\nauthor = \"someone\"\n",
+        )
+        .unwrap();
+        let metadata = load_metadata(&directory).unwrap();
+        assert_eq!(metadata.fps, Some(12));
+        assert_eq!(metadata.loop_mode, Some(LoopMode::Hold));
+        assert_eq!(metadata.title.as_deref(), Some("Dance"));
+
+        fs::write(directory.join(METADATA_FILE), "loop = \"ping-pong\"\n").unwrap();
+        let metadata = load_metadata(&directory).unwrap();
+        assert_eq!(metadata.loop_mode, Some(LoopMode::PingPong));
+
+        fs::write(directory.join(METADATA_FILE), "loop = \"sideways\"\n").unwrap();
+        assert!(load_metadata(&directory).is_err());
+    }
+
+    #[test]
+    fn test_load_duration_manifest() {
+        let directory = TestDir::new("manifest");
+        let names = ["1.png".to_string(), "2.png".to_string()];
+        let load = || MediaSequence::load_duration_manifest(&directory, &names);
+
+        // No manifest leaves the timing to the frame interval
+        assert!(load().unwrap().is_none());
+
+        fs::write(directory.join("frames.toml"), "\"1.png\" = 250\n").unwrap();
+        assert_eq!(
+            load().unwrap(),
+            Some(vec![Some(Duration::from_millis(250)), None])
+        );
+
+        // Frames the manifest lists but which weren't loaded are only
+        // warned about
+        fs::write(
+            directory.join("frames.toml"),
+            "\"2.png\" = 40\n\"missing.png\" = 80\n",
+        )
+        .unwrap();
+        assert_eq!(
+            load().unwrap(),
+            Some(vec![None, Some(Duration::from_millis(40))])
+        );
+
+        for bad in [
+            "\"1.png\" = 0\n",
+            "\"1.png\" = \"fast\"\n",
+            "\"1.png\" = -5\n",
+        ] {
+            fs::write(directory.join("frames.toml"), bad).unwrap();
+            let error = load().unwrap_err().to_string();
+            assert!(error.contains("frames.toml"), "{}", error);
+        }
+        fs::remove_file(directory.join("frames.toml")).unwrap();
+
+        fs::write(
+            directory.join("frames.json"),
+            r#"{"1.png": 30, "2.png": 60}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            load().unwrap(),
+            Some(vec![
+                Some(Duration::from_millis(30)),
+                Some(Duration::from_millis(60))
+            ])
+        );
+        fs::write(directory.join("frames.json"), r#"{"1.png": 30,"#).unwrap();
+        let error = load().unwrap_err().to_string();
+        assert!(error.contains("frames.json"), "{}", error);
+    }
+
+    /// Frames `mode` shows after frame `start` of `count`, until it
+    /// finishes or `steps` have been taken
+    fn play(mode: LoopMode, start: usize, count: usize, reverse: bool, steps: usize) -> Vec<usize> {
+        let (mut index, mut reverse) = (start, reverse);
+        let mut shown = Vec::new();
+        for _ in 0..steps {
+            match mode.advance(index, count, reverse) {
+                Advance::To {
+                    index: next,
+                    reverse: next_reverse,
+                    ..
+                } => {
+                    (index, reverse) = (next, next_reverse);
+                    shown.push(index);
+                }
+                Advance::Finished => break,
+            }
+        }
+        shown
+    }
+
+    #[test]
+    fn test_loop_advance() {
+        assert_eq!(play(LoopMode::Loop, 0, 3, false, 5), vec![1, 2, 0, 1, 2]);
+        // Wrapping around backwards from the first frame
+        assert_eq!(play(LoopMode::Loop, 1, 3, true, 4), vec![0, 2, 1, 0]);
+        // A single frame stays put
+        assert_eq!(play(LoopMode::Loop, 0, 1, false, 2), vec![0, 0]);
+        assert_eq!(
+            LoopMode::Loop.advance(2, 3, false),
+            Advance::To {
+                index: 0,
+                reverse: false,
+                wrapped: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_ping_pong_advance() {
+        // The ends are shown once per turn
+        assert_eq!(
+            play(LoopMode::PingPong, 0, 4, false, 8),
+            vec![1, 2, 3, 2, 1, 0, 1, 2]
+        );
+        assert_eq!(play(LoopMode::PingPong, 3, 4, true, 5), vec![2, 1, 0, 1, 2]);
+        assert_eq!(play(LoopMode::PingPong, 0, 2, false, 4), vec![1, 0, 1, 0]);
+        assert_eq!(
+            LoopMode::PingPong.advance(3, 4, false),
+            Advance::To {
+                index: 2,
+                reverse: true,
+                wrapped: true
+            }
+        );
+        assert_eq!(
+            LoopMode::PingPong.advance(1, 4, false),
+            Advance::To {
+                index: 2,
+                reverse: false,
+                wrapped: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_once_and_hold_advance() {
+        for mode in [LoopMode::Once, LoopMode::Hold] {
+            assert_eq!(play(mode, 0, 3, false, 5), vec![1, 2]);
+            assert_eq!(play(mode, 2, 3, true, 5), vec![1, 0]);
+            assert_eq!(mode.advance(2, 3, false), Advance::Finished);
+        }
+        // Indices past the end count as the last frame
+        assert_eq!(LoopMode::Once.advance(7, 3, false), Advance::Finished);
+    }
+
+    #[test]
+    fn test_step() {
+        assert_eq!(LoopMode::Loop.step(1, 3, true), Some(2));
+        assert_eq!(LoopMode::Loop.step(1, 3, false), Some(0));
+        // Only looping wraps around
+        assert_eq!(LoopMode::Loop.step(2, 3, true), Some(0));
+        assert_eq!(LoopMode::Loop.step(0, 3, false), Some(2));
+        for mode in [LoopMode::PingPong, LoopMode::Once, LoopMode::Hold] {
+            assert_eq!(mode.step(2, 3, true), None);
+            assert_eq!(mode.step(0, 3, false), None);
+            assert_eq!(mode.step(2, 3, false), Some(1));
+        }
+        // Past the frames decoded so far, stepping goes on from the last
+        assert_eq!(LoopMode::Hold.step(5, 3, false), Some(1));
+        assert_eq!(LoopMode::Loop.step(0, 0, true), None);
+    }
+
+    #[test]
+    fn test_trim_transparent_borders() {
+        let mut first = RgbaImage::new(10, 8);
+        first.put_pixel(2, 3, Rgba([255, 0, 0, 255]));
+        let mut second = RgbaImage::new(10, 8);
+        second.put_pixel(6, 5, Rgba([0, 255, 0, 10]));
+        let empty = RgbaImage::new(10, 8);
+
+        let mut images = vec![first, second, empty.clone()];
+        assert_eq!(trim_transparent_borders(&mut images), (2, 3));
+        for image in &images {
+            assert_eq!(image.dimensions(), (5, 3));
+        }
+        assert_eq!(images[0].get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(images[1].get_pixel(4, 2), &Rgba([0, 255, 0, 10]));
+
+        let mut all_empty = vec![empty.clone(), empty];
+        assert_eq!(trim_transparent_borders(&mut all_empty), (0, 0));
+        assert_eq!(all_empty[0].dimensions(), (10, 8));
+    }
+
+    #[test]
+    fn test_decode_palette_png() {
+        let directory = TestDir::new("palette");
+        let path = write_png(
+            &directory,
+            "palette",
+            png::ColorType::Indexed,
+            png::BitDepth::Eight,
+            Some((&[255, 0, 0, 0, 0, 255], &[255, 128])),
+            &[0, 1],
+        );
+
+        let image = decode_image(&path).unwrap();
+        assert_eq!(image.dimensions(), (2, 1));
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 255, 128]));
+    }
+
+    #[test]
+    fn test_load_apng() {
+        let directory = TestDir::new("apng");
+        let path = directory.join("animated.png");
+        let file = StdFile::create(&path).unwrap();
+
+        // Red for 1/10 of a second, then blue with no delay of its own
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(2, 0).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.set_frame_delay(1, 10).unwrap();
+        writer
+            .write_image_data(&[255, 0, 0, 255, 255, 0, 0, 255])
+            .unwrap();
+        writer.set_frame_delay(0, 1).unwrap();
+        writer
+            .write_image_data(&[0, 0, 255, 255, 0, 0, 255, 128])
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert!(is_apng(&path).unwrap());
+        let (images, durations) = MediaSequence::load_apng(&path).unwrap();
+        assert_eq!(durations, [Some(Duration::from_millis(100)), None]);
+        assert_eq!(images[0].get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(images[1].get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(images[1].get_pixel(1, 0), &Rgba([0, 0, 255, 128]));
+
+        // A PNG without animation is one still frame
+        let still = write_png(
+            &directory,
+            "still",
+            png::ColorType::Rgba,
+            png::BitDepth::Eight,
+            None,
+            &[10, 20, 30, 255, 40, 50, 60, 255],
+        );
+        assert!(!is_apng(&still).unwrap());
+        let (images, durations) = MediaSequence::load_apng(&still).unwrap();
+        assert_eq!(durations, [None]);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].get_pixel(1, 0), &Rgba([40, 50, 60, 255]));
+    }
+
+    #[test]
+    fn test_load_webp() {
+        let directory = TestDir::new("webp");
+        let path = directory.join("still.webp");
+        let image = RgbaImage::from_fn(3, 2, |x, y| {
+            Rgba([x as u8 * 100, y as u8 * 200, 50, 255 - x as u8 * 60])
+        });
+        image::codecs::webp::WebPEncoder::new_lossless(StdFile::create(&path).unwrap())
+            .encode(image.as_raw(), 3, 2, image::ExtendedColorType::Rgba8)
+            .unwrap();
+
+        // A WebP without animation is one still frame, exact when lossless
+        let sequence = MediaSequence::load(
+            detect_media_type(&path).unwrap(),
+            &LoadOptions::default(),
+            &|_, _, _| {},
+        )
+        .unwrap();
+        assert_eq!(sequence.count(), 1);
+        assert_eq!(sequence.frame_durations(), [None]);
+        assert_eq!(sequence.current_image(), Some(&image));
+    }
+
+    #[test]
+    fn test_load_sprite_sheet() {
+        let directory = TestDir::new("sprite-sheet");
+        let path = directory.join("sheet.png");
+
+        // 3x2 cells of 2x2 pixels, each cell red by its index, the last
+        // one left empty
+        let sheet = RgbaImage::from_fn(6, 4, |x, y| {
+            let cell = x / 2 + y / 2 * 3;
+            if cell == 5 {
+                Rgba([0, 0, 0, 0])
+            } else {
+                Rgba([cell as u8 * 40, x as u8, y as u8, 255])
+            }
+        });
+        sheet.save(&path).unwrap();
+
+        let grid = |frame_count| SpriteSheetGrid {
+            columns: 3,
+            rows: 2,
+            frame_count,
+        };
+        let images = MediaSequence::load_sprite_sheet(&path, grid(None)).unwrap();
+        assert_eq!(images.len(), 5);
+        for (cell, image) in images.iter().enumerate() {
+            let (left, top) = (cell as u32 % 3 * 2, cell as u32 / 3 * 2);
+            let expected = image::imageops::crop_imm(&sheet, left, top, 2, 2).to_image();
+            assert_eq!(image, &expected, "cell {}", cell);
+            assert_eq!(image.get_pixel(0, 0)[0], cell as u8 * 40);
+        }
+
+        // A frame count keeps the empty cell, or leaves out more
+        let images = MediaSequence::load_sprite_sheet(&path, grid(Some(6))).unwrap();
+        assert_eq!(images.len(), 6);
+        assert!(images[5].pixels().all(|pixel| pixel[3] == 0));
+        let images = MediaSequence::load_sprite_sheet(&path, grid(Some(2))).unwrap();
+        assert_eq!(images.len(), 2);
+
+        assert!(MediaSequence::load_sprite_sheet(&path, grid(Some(7))).is_err());
+        assert!(MediaSequence::load_sprite_sheet(&path, grid(Some(0))).is_err());
+    }
+
+    #[test]
+    fn test_decode_grayscale_alpha_png() {
+        let directory = TestDir::new("grayscale-alpha");
+        let path = write_png(
+            &directory,
+            "grayscale-alpha",
+            png::ColorType::GrayscaleAlpha,
+            png::BitDepth::Eight,
+            None,
+            &[200, 255, 50, 0],
+        );
+
+        let image = decode_image(&path).unwrap();
+        assert_eq!(image.get_pixel(0, 0), &Rgba([200, 200, 200, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([50, 50, 50, 0]));
+    }
+
+    #[test]
+    fn test_decode_rgb16_png() {
+        // 16-bit big-endian samples: full red, then mid gray
+        let directory = TestDir::new("rgb16");
+        let path = write_png(
+            &directory,
+            "rgb16",
+            png::ColorType::Rgb,
+            png::BitDepth::Sixteen,
+            None,
+            &[0xff, 0xff, 0, 0, 0, 0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80],
+        );
+
+        let image = decode_image(&path).unwrap();
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn test_load_gif() {
+        let directory = TestDir::new("gif");
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+        let clear = Rgba([0, 0, 0, 0]);
+
+        // image's encoder clears every frame's area once it was shown
+        let path = directory.join("cleared.gif");
+        let mut encoder = image::codecs::gif::GifEncoder::new(StdFile::create(&path).unwrap());
+        let frame = |image: RgbaImage, ms: u32| {
+            image::Frame::from_parts(image, 0, 0, image::Delay::from_numer_denom_ms(ms, 1))
+        };
+        let mut holed = RgbaImage::from_pixel(2, 2, blue);
+        holed.put_pixel(1, 1, clear);
+        encoder
+            .encode_frames([
+                frame(RgbaImage::from_pixel(4, 4, red), 100),
+                frame(holed, 0),
+            ])
+            .unwrap();
+        drop(encoder);
+
+        let (images, durations) = MediaSequence::load_gif(&path).unwrap();
+        assert_eq!(durations, [Some(Duration::from_millis(100)), None]);
+        assert!(images[0].pixels().all(|pixel| *pixel == red));
+        // Nothing of the cleared first frame shows through the hole
+        let second = &images[1];
+        assert_eq!(second.dimensions(), (4, 4));
+        assert_eq!(second.get_pixel(0, 0), &blue);
+        assert_eq!(second.get_pixel(1, 1), &clear);
+        assert_eq!(second.get_pixel(3, 3), &clear);
+
+        // Frames kept or restored after showing, written with the gif crate
+        // to place them and pick their disposal
+        let path = directory.join("kept.gif");
+        let mut encoder = gif::Encoder::new(StdFile::create(&path).unwrap(), 4, 4, &[]).unwrap();
+        let green = Rgba([0, 255, 0, 255]);
+        let mut holed = RgbaImage::from_pixel(2, 2, green);
+        holed.put_pixel(0, 0, clear);
+        for (image, (left, top), dispose, delay) in [
+            (
+                RgbaImage::from_pixel(4, 4, red),
+                (0, 0),
+                gif::DisposalMethod::Keep,
+                5,
+            ),
+            (holed, (1, 1), gif::DisposalMethod::Previous, 10),
+            (
+                RgbaImage::from_pixel(1, 1, blue),
+                (3, 3),
+                gif::DisposalMethod::Keep,
+                20,
+            ),
+        ] {
+            let (width, height) = (image.width() as u16, image.height() as u16);
+            let mut frame = gif::Frame::from_rgba(width, height, &mut image.into_raw());
+            (frame.left, frame.top) = (left, top);
+            frame.dispose = dispose;
+            frame.delay = delay;
+            encoder.write_frame(&frame).unwrap();
+        }
+        drop(encoder);
+
+        let (images, durations) = MediaSequence::load_gif(&path).unwrap();
+        let milliseconds = |ms| Some(Duration::from_millis(ms));
+        assert_eq!(
+            durations,
+            [milliseconds(50), milliseconds(100), milliseconds(200)]
+        );
+        // The kept first frame shows through the second one's hole
+        assert_eq!(images[1].get_pixel(1, 1), &red);
+        assert_eq!(images[1].get_pixel(2, 2), &green);
+        assert_eq!(images[1].get_pixel(0, 0), &red);
+        // The second frame is undone before the third
+        assert_eq!(images[2].get_pixel(2, 2), &red);
+        assert_eq!(images[2].get_pixel(3, 3), &blue);
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("img2.png", "img10.png"), Ordering::Less);
+        assert_eq!(natural_cmp("img10.png", "img2.png"), Ordering::Greater);
+        assert_eq!(
+            natural_cmp("konata_001.png", "konata_2.png"),
+            Ordering::Less
+        );
+        assert_eq!(
+            natural_cmp("konata_010.png", "konata_9.png"),
+            Ordering::Greater
+        );
+        assert_eq!(natural_cmp("frame_1.png", "frame_1.png"), Ordering::Equal);
+        assert_eq!(natural_cmp("a1b2.png", "a1b10.png"), Ordering::Less);
+        assert_eq!(natural_cmp("a.png", "b.png"), Ordering::Less);
+        assert_eq!(natural_cmp("frame.png", "frame_1.png"), Ordering::Less);
+
+        let mut names = vec![
+            "frame_100.png",
+            "frame_10.png",
+            "frame_2.png",
+            "frame_1.png",
+            "frame_101.png",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "frame_1.png",
+                "frame_2.png",
+                "frame_10.png",
+                "frame_100.png",
+                "frame_101.png",
+            ]
+        );
+    }
+}
@@ -0,0 +1,646 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+const MENU_SHADER: &str = r#"
+struct MenuParams {
+    target_size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_font: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> params: MenuParams;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Position within the quad, 0 to 1 along each axis
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) glyph: i32,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) rect: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) glyph: i32,
+) -> VertexOutput {
+    // Triangle strip over the corners of the quad
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u));
+    let pixel = rect.xy + corner * rect.zw;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        pixel.x / params.target_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / params.target_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.local = corner;
+    out.color = color;
+    out.glyph = glyph;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if in.glyph >= 0 {
+        let texel = vec2<i32>(
+            in.glyph + min(i32(in.local.x * 5.0), 4),
+            min(i32(in.local.y * 7.0), 6),
+        );
+        if textureLoad(t_font, texel, 0).r < 0.5 {
+            discard;
+        }
+    }
+    return in.color;
+}
+"#;
+
+/// Glyphs of the menu font from space to underscore, 5 pixels wide and 7
+/// high. Bit 4 of each row is its leftmost pixel.
+const FONT: [[u8; 7]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+];
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Distance between the left edges of neighbouring glyphs
+const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+// Layout in pixels of the font, multiplied by the menu's scale
+const BORDER: u32 = 1;
+const PADDING: u32 = 3;
+/// Side of the square marking checked entries
+const MARK_SIZE: u32 = 3;
+const ROW_HEIGHT: u32 = GLYPH_HEIGHT + 2 * PADDING;
+/// Where labels start, right of the column of marks
+const LABEL_LEFT: u32 = BORDER + PADDING + MARK_SIZE + PADDING;
+
+const BORDER_COLOR: [f32; 4] = [0.45, 0.45, 0.45, 1.0];
+const BACKGROUND_COLOR: [f32; 4] = [0.12, 0.12, 0.12, 1.0];
+const HOVER_COLOR: [f32; 4] = [0.24, 0.36, 0.58, 1.0];
+const TEXT_COLOR: [f32; 4] = [0.92, 0.92, 0.92, 1.0];
+
+/// What picking a menu entry does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuAction {
+    TogglePause,
+    /// Hold the animation on its first frame
+    Stop,
+    /// Play at this speed
+    Speed(f32),
+    /// Play the sequence at this index
+    Sequence(usize),
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuEntry {
+    /// Shown in capitals, characters the font lacks as question marks
+    pub label: String,
+    pub action: MenuAction,
+    /// Marked as the current choice
+    pub checked: bool,
+}
+
+/// A quad `MenuPainter` draws, either filled with its color or showing a
+/// glyph of the font in it
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct MenuQuad {
+    /// Left, top, width and height in window pixels
+    pub rect: [f32; 4],
+    /// Opaque sRGB color
+    pub color: [f32; 4],
+    /// Column of the glyph in the font texture, negative fills the quad
+    pub glyph: i32,
+}
+
+/// A context menu open at some place in a window, one entry per row
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextMenu {
+    entries: Vec<MenuEntry>,
+    /// Top left corner in window pixels
+    origin: (u32, u32),
+    /// Window pixels per pixel of the font
+    scale: u32,
+    hovered: Option<usize>,
+}
+
+impl ContextMenu {
+    /// A menu of `entries` opened by a click at `position` in a window of
+    /// `window_size`, its font magnified `scale` times. It is moved left and
+    /// up, then drawn smaller, until it fits the window if it can.
+    pub fn new(
+        entries: Vec<MenuEntry>,
+        position: (f64, f64),
+        window_size: (u32, u32),
+        scale: u32,
+    ) -> Self {
+        let fits = |scale: u32| {
+            let (width, height) = menu_size(&entries, scale);
+            width <= window_size.0 && height <= window_size.1
+        };
+        let scale = (1..=scale.max(1))
+            .rev()
+            .find(|&scale| fits(scale))
+            .unwrap_or(1);
+
+        let (width, height) = menu_size(&entries, scale);
+        let place = |position: f64, size: u32, window: u32| {
+            (position.max(0.0) as u32).min(window.saturating_sub(size))
+        };
+        Self {
+            origin: (
+                place(position.0, width, window_size.0),
+                place(position.1, height, window_size.1),
+            ),
+            entries,
+            scale,
+            hovered: None,
+        }
+    }
+
+    /// Width and height in window pixels
+    pub fn size(&self) -> (u32, u32) {
+        menu_size(&self.entries, self.scale)
+    }
+
+    /// Index of the entry at `position` in the window
+    pub fn entry_at(&self, position: (f64, f64)) -> Option<usize> {
+        let (width, height) = self.size();
+        let border = (BORDER * self.scale) as f64;
+        let x = position.0 - self.origin.0 as f64;
+        let y = position.1 - self.origin.1 as f64;
+        if x < border || x >= width as f64 - border || y < border || y >= height as f64 - border {
+            return None;
+        }
+        let row = ((y - border) / (ROW_HEIGHT * self.scale) as f64) as usize;
+        (row < self.entries.len()).then_some(row)
+    }
+
+    /// What the entry at `position` does
+    pub fn action_at(&self, position: (f64, f64)) -> Option<MenuAction> {
+        self.entry_at(position)
+            .map(|index| self.entries[index].action)
+    }
+
+    /// Highlight the entry at `position`, none if it is outside the menu or
+    /// unknown. Returns whether the highlight moved.
+    pub fn hover(&mut self, position: Option<(f64, f64)>) -> bool {
+        let hovered = position.and_then(|position| self.entry_at(position));
+        let changed = hovered != self.hovered;
+        self.hovered = hovered;
+        changed
+    }
+
+    /// Everything drawn for the menu, back to front
+    pub fn quads(&self) -> Vec<MenuQuad> {
+        let scale = self.scale;
+        let (width, height) = self.size();
+        let quad =
+            |x: u32, y: u32, width: u32, height: u32, color: [f32; 4], glyph: i32| MenuQuad {
+                rect: [
+                    (self.origin.0 + x) as f32,
+                    (self.origin.1 + y) as f32,
+                    width as f32,
+                    height as f32,
+                ],
+                color,
+                glyph,
+            };
+
+        let border = BORDER * scale;
+        let mut quads = vec![
+            quad(0, 0, width, height, BORDER_COLOR, -1),
+            quad(
+                border,
+                border,
+                width - 2 * border,
+                height - 2 * border,
+                BACKGROUND_COLOR,
+                -1,
+            ),
+        ];
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let top = border + index as u32 * ROW_HEIGHT * scale;
+            if self.hovered == Some(index) {
+                quads.push(quad(
+                    border,
+                    top,
+                    width - 2 * border,
+                    ROW_HEIGHT * scale,
+                    HOVER_COLOR,
+                    -1,
+                ));
+            }
+            if entry.checked {
+                let offset = (ROW_HEIGHT - MARK_SIZE) / 2 * scale;
+                quads.push(quad(
+                    (BORDER + PADDING) * scale,
+                    top + offset,
+                    MARK_SIZE * scale,
+                    MARK_SIZE * scale,
+                    TEXT_COLOR,
+                    -1,
+                ));
+            }
+            for (column, character) in entry.label.chars().enumerate() {
+                let glyph = glyph_index(character);
+                // Spaces have nothing to draw
+                if glyph == 0 {
+                    continue;
+                }
+                quads.push(quad(
+                    (LABEL_LEFT + column as u32 * GLYPH_ADVANCE) * scale,
+                    top + PADDING * scale,
+                    GLYPH_WIDTH * scale,
+                    GLYPH_HEIGHT * scale,
+                    TEXT_COLOR,
+                    (glyph * GLYPH_WIDTH) as i32,
+                ));
+            }
+        }
+        quads
+    }
+}
+
+/// Width and height of a menu of `entries` in window pixels, drawn `scale`
+/// times the font's size
+fn menu_size(entries: &[MenuEntry], scale: u32) -> (u32, u32) {
+    let label_width = entries
+        .iter()
+        .map(|entry| (entry.label.chars().count() as u32 * GLYPH_ADVANCE).saturating_sub(1))
+        .max()
+        .unwrap_or(0);
+    (
+        (LABEL_LEFT + label_width + PADDING + BORDER) * scale,
+        (2 * BORDER + entries.len() as u32 * ROW_HEIGHT) * scale,
+    )
+}
+
+/// Index in `FONT` of the glyph drawn for `character`. Lower case letters
+/// are drawn as capitals, characters the font lacks as question marks.
+fn glyph_index(character: char) -> u32 {
+    match character.to_ascii_uppercase() {
+        character @ ' '..='_' => character as u32 - ' ' as u32,
+        _ => '?' as u32 - ' ' as u32,
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct MenuParams {
+    target_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// Draws the quads of a context menu over what is already in the target
+pub struct MenuPainter {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    quads: Option<(wgpu::Buffer, u32)>,
+}
+
+impl MenuPainter {
+    /// Set up drawing into `format` targets
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Menu Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Menu Shader"),
+            source: wgpu::ShaderSource::Wgsl(MENU_SHADER.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Menu Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Menu Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<MenuQuad>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Float32x4,
+                        2 => Sint32,
+                    ],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                // Everything is opaque, glyphs discard what isn't theirs
+                targets: &[Some(format.into())],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        let font = font_pixels();
+        let font_view = device
+            .create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("Menu Font Texture"),
+                    size: wgpu::Extent3d {
+                        width: FONT.len() as u32 * GLYPH_WIDTH,
+                        height: GLYPH_HEIGHT,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::R8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                &font,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Menu Params Buffer"),
+            contents: bytemuck::cast_slice(&[MenuParams::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Menu Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&font_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            params,
+            quads: None,
+        }
+    }
+
+    /// Draw `quads` from now on, nothing if there are none
+    pub fn set_quads(&mut self, device: &wgpu::Device, quads: &[MenuQuad]) {
+        self.quads = (!quads.is_empty()).then(|| {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Menu Quad Buffer"),
+                contents: bytemuck::cast_slice(quads),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            (buffer, quads.len() as u32)
+        });
+    }
+
+    /// Draw the quads over the contents of `target`, which is `target_size`
+    /// pixels large
+    pub fn draw(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        target_size: (u32, u32),
+    ) {
+        let Some((quads, count)) = &self.quads else {
+            return;
+        };
+        let params = MenuParams {
+            target_size: [target_size.0.max(1) as f32, target_size.1.max(1) as f32],
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&[params]));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Menu Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, quads.slice(..));
+        render_pass.draw(0..4, 0..*count);
+    }
+}
+
+/// `FONT` as one row of glyphs in an 8-bit texture, 255 where glyphs are
+/// drawn
+fn font_pixels() -> Vec<u8> {
+    let width = FONT.len() * GLYPH_WIDTH as usize;
+    let mut pixels = vec![0; width * GLYPH_HEIGHT as usize];
+    for (index, glyph) in FONT.iter().enumerate() {
+        for (y, row) in glyph.iter().enumerate() {
+            for x in 0..GLYPH_WIDTH as usize {
+                if row & (0x10 >> x) != 0 {
+                    pixels[y * width + index * GLYPH_WIDTH as usize + x] = 255;
+                }
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::validate_wgsl;
+
+    fn entries(labels: &[&str]) -> Vec<MenuEntry> {
+        labels
+            .iter()
+            .map(|label| MenuEntry {
+                label: label.to_string(),
+                action: MenuAction::Quit,
+                checked: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_menu_layout() {
+        // 5 characters are 29 font pixels wide, 10 to their left and 4 to
+        // their right, two rows of 13 and the border above and below
+        let menu = ContextMenu::new(entries(&["Pause", "Quit"]), (10.0, 20.0), (800, 600), 2);
+        assert_eq!(menu.size(), (86, 56));
+        assert_eq!(menu.origin, (10, 20));
+
+        // Moved to stay inside the window
+        let menu = ContextMenu::new(entries(&["Pause", "Quit"]), (790.0, 590.0), (800, 600), 2);
+        assert_eq!(menu.origin, (714, 544));
+
+        // Drawn smaller where it doesn't fit otherwise, and at the smallest
+        // size where nothing does
+        let menu = ContextMenu::new(entries(&["Pause", "Quit"]), (0.0, 0.0), (60, 40), 3);
+        assert_eq!(menu.size(), (43, 28));
+        let menu = ContextMenu::new(entries(&["Pause", "Quit"]), (5.0, 5.0), (20, 20), 3);
+        assert_eq!((menu.size(), menu.origin), ((43, 28), (0, 0)));
+    }
+
+    #[test]
+    fn test_menu_entry_at() {
+        let mut menu = ContextMenu::new(entries(&["Pause", "Quit"]), (10.0, 20.0), (800, 600), 2);
+        assert_eq!(menu.entry_at((12.0, 22.0)), Some(0));
+        assert_eq!(menu.entry_at((93.0, 47.9)), Some(0));
+        assert_eq!(menu.entry_at((50.0, 48.0)), Some(1));
+        assert_eq!(menu.entry_at((50.0, 73.9)), Some(1));
+        // The border and everything around the menu
+        assert_eq!(menu.entry_at((11.0, 30.0)), None);
+        assert_eq!(menu.entry_at((50.0, 74.0)), None);
+        assert_eq!(menu.entry_at((5.0, 30.0)), None);
+
+        assert!(menu.hover(Some((50.0, 50.0))));
+        assert!(!menu.hover(Some((60.0, 60.0))));
+        assert!(menu.hover(None));
+    }
+
+    #[test]
+    fn test_glyph_index() {
+        assert_eq!(glyph_index(' '), 0);
+        assert_eq!(glyph_index('A'), glyph_index('a'));
+        assert_eq!(glyph_index('_'), 63);
+        assert_eq!(glyph_index('é'), glyph_index('?'));
+        assert_eq!(glyph_index('{'), glyph_index('?'));
+    }
+
+    #[test]
+    fn test_font_pixels() {
+        let pixels = font_pixels();
+        let width = FONT.len() * GLYPH_WIDTH as usize;
+        // The bottom row of the underscore, the last glyph
+        assert!(pixels[6 * width + width - 5..].iter().all(|&p| p == 255));
+        // The top of the 1 only has its middle column set
+        let one = glyph_index('1') as usize * GLYPH_WIDTH as usize;
+        assert_eq!(&pixels[one..one + 5], &[0, 0, 255, 0, 0]);
+    }
+
+    #[test]
+    fn test_menu_shader_validates() {
+        if let Err(message) = validate_wgsl(MENU_SHADER, "menu") {
+            panic!("{}", message);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::ops::Range;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+use crate::renderer::FULLSCREEN_VERTEX_SHADER;
+
+const BLIT_FRAGMENT_SHADER: &str = r#"
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Linear filtering halfway between texels averages each 2x2 block
+    return textureSample(t_source, s_source, in.tex_coords);
+}
+"#;
+
+// The GL backend cannot view one layer of an array as a 2D texture, so
+// arrays are sampled whole with the layer passed alongside
+const BLIT_ARRAY_FRAGMENT_SHADER: &str = r#"
+@group(0) @binding(0)
+var t_source: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> layer: u32;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_source, in.tex_coords, layer);
+}
+"#;
+
+/// Format of every texture that gets a mip chain
+pub const MIPMAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Fills the smaller mip levels of frame textures by repeatedly drawing each
+/// level into the next one at half the size
+pub struct MipmapGenerator {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    single_layer: BlitPipeline,
+    array: BlitPipeline,
+    sampler: wgpu::Sampler,
+}
+
+/// Pipeline drawing one mip level from a texture bound as 2D or as an array
+struct BlitPipeline {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl BlitPipeline {
+    fn new(device: &wgpu::Device, array: bool) -> Self {
+        let (fragment_source, view_dimension) = if array {
+            (
+                BLIT_ARRAY_FRAGMENT_SHADER,
+                wgpu::TextureViewDimension::D2Array,
+            )
+        } else {
+            (BLIT_FRAGMENT_SHADER, wgpu::TextureViewDimension::D2)
+        };
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{}{}", FULLSCREEN_VERTEX_SHADER, fragment_source).into(),
+            ),
+        });
+
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+        if array {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &entries,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(MIPMAP_FORMAT.into())],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+}
+
+impl MipmapGenerator {
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        let single_layer = BlitPipeline::new(&device, false);
+        let array = BlitPipeline::new(&device, true);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            device,
+            queue,
+            single_layer,
+            array,
+            sampler,
+        }
+    }
+
+    /// Regenerate every level below the first for the given array layers of
+    /// `texture`, which has to be a `MIPMAP_FORMAT` render attachment
+    pub fn generate(&self, texture: &wgpu::Texture, layers: Range<u32>) {
+        if texture.mip_level_count() < 2 || layers.is_empty() {
+            return;
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Mipmap Encoder"),
+            });
+
+        let array = texture.depth_or_array_layers() > 1;
+        let blit = if array {
+            &self.array
+        } else {
+            &self.single_layer
+        };
+
+        for layer in layers {
+            let layer_buffer = array.then(|| {
+                self.device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Mipmap Layer Buffer"),
+                        contents: bytemuck::cast_slice(&[layer, 0, 0, 0]),
+                        usage: wgpu::BufferUsages::UNIFORM,
+                    })
+            });
+
+            for level in 1..texture.mip_level_count() {
+                // Sampling reads the whole array at the previous level
+                let source = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(if array {
+                        wgpu::TextureViewDimension::D2Array
+                    } else {
+                        wgpu::TextureViewDimension::D2
+                    }),
+                    base_mip_level: level - 1,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+                let target = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+
+                let mut entries = vec![
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ];
+                if let Some(layer_buffer) = &layer_buffer {
+                    entries.push(wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: layer_buffer.as_entire_binding(),
+                    });
+                }
+
+                let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Mipmap Bind Group"),
+                    layout: &blit.bind_group_layout,
+                    entries: &entries,
+                });
+
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Mipmap Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                render_pass.set_pipeline(&blit.pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+/// Number of levels in a full mip chain for a texture of this size, down to
+/// 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::validate_wgsl;
+
+    #[test]
+    fn test_mip_level_count() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(2, 1), 2);
+        assert_eq!(mip_level_count(200, 200), 8);
+        assert_eq!(mip_level_count(256, 100), 9);
+        assert_eq!(mip_level_count(4096, 4096), 13);
+    }
+
+    #[test]
+    fn test_blit_shader_validates() {
+        for fragment_source in [BLIT_FRAGMENT_SHADER, BLIT_ARRAY_FRAGMENT_SHADER] {
+            let source = format!("{}{}", FULLSCREEN_VERTEX_SHADER, fragment_source);
+            if let Err(message) = validate_wgsl(&source, "blit") {
+                panic!("{}", message);
+            }
+        }
+    }
+}
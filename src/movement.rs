@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// How the window trails the mouse pointer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FollowOptions {
+    /// Where the window's top left corner sits relative to the pointer
+    pub offset: (i32, i32),
+    /// Seconds the window takes to cover about two thirds of the way to
+    /// the pointer, 0 sticks to it
+    pub smoothing: f64,
+}
+
+/// How the window wanders around its monitor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WanderOptions {
+    /// Top speed in pixels per second
+    pub speed: f64,
+    /// Time between picking destinations
+    pub interval: Duration,
+    /// Chance of resting instead of picking a new destination, 0 to 1
+    pub pause_chance: f64,
+    /// Horizontal and vertical distance kept from the monitor's edges
+    pub margin: (u32, u32),
+}
+
+/// How the window bounces around like the DVD logo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BounceOptions {
+    /// Speed in pixels per second
+    pub speed: f64,
+    /// Bounce off the edges of the desktop spanning every monitor rather
+    /// than those of the monitor the window started on
+    pub roam: bool,
+}
+
+/// `position` of a window of `size` moved along `velocity` in pixels per
+/// second for `elapsed` seconds, and the velocity it continues with. It
+/// reflects off the edges of the area at `origin`, and is moved back onto
+/// it if it was off it, as after the area shrank.
+pub fn bounce(
+    position: (f64, f64),
+    velocity: (f64, f64),
+    size: PhysicalSize<u32>,
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    elapsed: f64,
+) -> ((f64, f64), (f64, f64)) {
+    let axis = |position: f64, velocity: f64, size: u32, origin: i32, area: u32| {
+        let low = origin as f64;
+        let high = (low + area as f64 - size as f64).max(low);
+        let next = position.clamp(low, high) + velocity * elapsed;
+        if next < low {
+            ((low + (low - next)).min(high), velocity.abs())
+        } else if next > high {
+            ((high - (next - high)).max(low), -velocity.abs())
+        } else {
+            (next, velocity)
+        }
+    };
+    let (x, dx) = axis(position.0, velocity.0, size.width, origin.x, area.width);
+    let (y, dy) = axis(position.1, velocity.1, size.height, origin.y, area.height);
+    ((x, y), (dx, dy))
+}
+
+/// `current` moved toward `target` over `elapsed` seconds, closing the
+/// distance exponentially with `time_constant`
+pub fn ease_toward(
+    current: (f64, f64),
+    target: (f64, f64),
+    time_constant: f64,
+    elapsed: f64,
+) -> (f64, f64) {
+    if time_constant <= 0.0 {
+        return target;
+    }
+    let progress = 1.0 - (-elapsed / time_constant).exp();
+    (
+        current.0 + (target.0 - current.0) * progress,
+        current.1 + (target.1 - current.1) * progress,
+    )
+}
+
+/// `current` moved toward `target` over `elapsed` seconds at up to `speed`
+/// pixels per second, slowing down over the last second of the way
+pub fn approach(current: (f64, f64), target: (f64, f64), speed: f64, elapsed: f64) -> (f64, f64) {
+    let (dx, dy) = (target.0 - current.0, target.1 - current.1);
+    let distance = dx.hypot(dy);
+    if distance < 0.5 {
+        return target;
+    }
+    let step = (speed.min(distance) * elapsed).min(distance);
+    (
+        current.0 + dx / distance * step,
+        current.1 + dy / distance * step,
+    )
+}
+
+/// A random position for a window of `size` on the monitor at `origin` of
+/// `area`, `margin` away from its edges where the window fits
+pub fn wander_target(
+    rng: &mut fastrand::Rng,
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    size: PhysicalSize<u32>,
+    margin: (u32, u32),
+) -> (f64, f64) {
+    let mut pick = |origin: i32, area: u32, size: u32, margin: u32| {
+        let start = origin as f64 + margin as f64;
+        let room = area as f64 - size as f64 - 2.0 * margin as f64;
+        if room > 0.0 {
+            start + rng.f64() * room
+        } else {
+            origin as f64 + (area as f64 - size as f64) / 2.0
+        }
+    };
+    (
+        pick(origin.x, area.width, size.width, margin.0),
+        pick(origin.y, area.height, size.height, margin.1),
+    )
+}
+
+/// `position` moved just far enough for a window of `size` to be inside
+/// the monitor at `origin` of `area`, or aligned with its top left corner
+/// if it is larger
+pub fn clamp_to_area(
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let clamp = |position: i32, size: u32, origin: i32, area: u32| {
+        position.min(origin + area as i32 - size as i32).max(origin)
+    };
+    PhysicalPosition::new(
+        clamp(position.x, size.width, origin.x, area.width),
+        clamp(position.y, size.height, origin.y, area.height),
+    )
+}
+
+/// Whether `position` lies on the monitor at `origin` of `area`
+pub fn contains(
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    position: PhysicalPosition<i32>,
+) -> bool {
+    (origin.x..origin.x + area.width as i32).contains(&position.x)
+        && (origin.y..origin.y + area.height as i32).contains(&position.y)
+}
+
+/// How many pixels `position` is from the nearest edge of the area at
+/// `origin` of `area`, 0 on it
+pub fn distance_to_area(
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    position: PhysicalPosition<i32>,
+) -> f64 {
+    let outside = |position: i32, origin: i32, size: u32| {
+        (origin - position)
+            .max(position - (origin + size as i32 - 1))
+            .max(0) as f64
+    };
+    outside(position.x, origin.x, area.width).hypot(outside(position.y, origin.y, area.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ease_toward() {
+        assert_eq!(
+            ease_toward((0.0, 0.0), (100.0, 50.0), 0.0, 0.016),
+            (100.0, 50.0)
+        );
+
+        // One time constant covers about 63% of the way
+        let (x, y) = ease_toward((0.0, 0.0), (100.0, 50.0), 0.2, 0.2);
+        assert!((x - 63.2).abs() < 0.1, "{}", x);
+        assert!((y - 31.6).abs() < 0.1, "{}", y);
+
+        // Small steps add up to the same as one large step
+        let mut position = (0.0, 0.0);
+        for _ in 0..10 {
+            position = ease_toward(position, (100.0, 50.0), 0.2, 0.02);
+        }
+        assert!((position.0 - x).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_approach() {
+        // Full speed far away
+        let (x, y) = approach((0.0, 0.0), (300.0, 400.0), 100.0, 0.5);
+        assert!((x - 30.0).abs() < 1e-9 && (y - 40.0).abs() < 1e-9);
+
+        // Slower within a second's travel, and never past the target
+        let (x, _) = approach((0.0, 0.0), (50.0, 0.0), 100.0, 0.5);
+        assert!((x - 25.0).abs() < 1e-9);
+        assert_eq!(approach((0.0, 0.0), (50.0, 0.0), 100.0, 5.0), (50.0, 0.0));
+        assert_eq!(approach((0.0, 0.0), (0.3, 0.0), 100.0, 0.01), (0.3, 0.0));
+    }
+
+    #[test]
+    fn test_wander_target() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let origin = PhysicalPosition::new(1920, 0);
+        let area = PhysicalSize::new(1280, 720);
+        let size = PhysicalSize::new(100, 200);
+        for _ in 0..100 {
+            let (x, y) = wander_target(&mut rng, origin, area, size, (40, 20));
+            assert!((1960.0..=3200.0 - 140.0).contains(&x), "{}", x);
+            assert!((20.0..=720.0 - 220.0).contains(&y), "{}", y);
+        }
+
+        // Windows without room to wander stay centered
+        let wide = PhysicalSize::new(1280, 200);
+        let (x, _) = wander_target(&mut rng, origin, area, wide, (40, 20));
+        assert_eq!(x, 1920.0);
+    }
+
+    #[test]
+    fn test_bounce() {
+        let origin = PhysicalPosition::new(1920, 0);
+        let area = PhysicalSize::new(1280, 720);
+        let size = PhysicalSize::new(100, 100);
+        let step =
+            |position, velocity, elapsed| bounce(position, velocity, size, origin, area, elapsed);
+
+        assert_eq!(
+            step((2000.0, 300.0), (100.0, -50.0), 0.5),
+            ((2050.0, 275.0), (100.0, -50.0))
+        );
+        // Reflected off the right and top edges
+        assert_eq!(
+            step((3090.0, 10.0), (100.0, -100.0), 0.5),
+            ((3060.0, 40.0), (-100.0, 100.0))
+        );
+        // Off a shrunken monitor, it comes back heading inward
+        assert_eq!(
+            step((3500.0, 300.0), (100.0, 0.0), 0.1),
+            ((3090.0, 300.0), (-100.0, 0.0))
+        );
+        // Larger than the monitor keeps the top left corner on it
+        let large = PhysicalSize::new(2000, 100);
+        let (position, _) = bounce((1920.0, 0.0), (100.0, 0.0), large, origin, area, 1.0);
+        assert_eq!(position, (1920.0, 0.0));
+    }
+
+    #[test]
+    fn test_clamp_to_area() {
+        let origin = PhysicalPosition::new(1920, 0);
+        let area = PhysicalSize::new(1280, 720);
+        let size = PhysicalSize::new(100, 100);
+        let clamp = |x, y| clamp_to_area(PhysicalPosition::new(x, y), size, origin, area);
+
+        assert_eq!(clamp(2000, 300), PhysicalPosition::new(2000, 300));
+        assert_eq!(clamp(1900, -20), PhysicalPosition::new(1920, 0));
+        assert_eq!(clamp(3190, 700), PhysicalPosition::new(3100, 620));
+
+        // Larger than the monitor keeps the top left corner on it
+        let large = PhysicalSize::new(2000, 100);
+        assert_eq!(
+            clamp_to_area(PhysicalPosition::new(2500, 0), large, origin, area),
+            origin
+        );
+    }
+
+    #[test]
+    fn test_contains() {
+        let origin = PhysicalPosition::new(-1280, 0);
+        let area = PhysicalSize::new(1280, 1024);
+        assert!(contains(origin, area, PhysicalPosition::new(-1, 1023)));
+        assert!(!contains(origin, area, PhysicalPosition::new(0, 10)));
+        assert!(!contains(origin, area, PhysicalPosition::new(-10, 1024)));
+    }
+
+    #[test]
+    fn test_distance_to_area() {
+        let origin = PhysicalPosition::new(100, 100);
+        let area = PhysicalSize::new(50, 20);
+        let distance = |x, y| distance_to_area(origin, area, PhysicalPosition::new(x, y));
+        assert_eq!(distance(100, 100), 0.0);
+        assert_eq!(distance(149, 119), 0.0);
+        assert_eq!(distance(160, 110), 11.0);
+        assert_eq!(distance(100, 95), 5.0);
+        assert_eq!(distance(96, 122), 5.0);
+    }
+}
@@ -1,292 +1,4020 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
-use winit::dpi::PhysicalSize;
-use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::window::{Window, WindowAttributes, WindowId};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceEvent, DeviceId, ElementState, MouseButton, MouseScrollDelta, RawKeyEvent, Touch,
+    TouchPhase,
+};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, DeviceEvents, EventLoop};
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey};
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Icon, WindowAttributes, WindowId};
 
-use crate::media_loader::{MediaSequence, MediaSource};
-use crate::renderer::Renderer;
+use crate::idle::IdleSource;
+use crate::input_shape::AlphaMask;
+use crate::media_loader::{
+    Advance, FrameDurations, LoadOptions, LoopMode, MediaSequence, MediaSource, ProgressFn,
+    ReloadOutcome, SequenceSet,
+};
+use crate::menu::{ContextMenu, MenuAction, MenuEntry};
+use crate::movement::{
+    BounceOptions, FollowOptions, WanderOptions, approach, bounce, clamp_to_area, contains,
+    distance_to_area, ease_toward, wander_target,
+};
+use crate::pointer::GlobalPointer;
+use crate::renderer::{
+    AdapterSelection, ChromaKey, ColorEffects, Filtering, Gpu, MAX_INSTANCES, Outline, PresentMode,
+    Renderer, ScaleMode, SharedFrames, display_scale, rotated_size,
+};
+use crate::shadow::Shadow;
+use crate::touch::{Gesture, Touches};
+use crate::watcher::DirectoryWatcher;
+use crate::window_backend::{
+    BackendEvent, CursorStyle, DragModifier, Placement, WindowBackend, X11Mode, anchored_position,
+    as_accessory, centered_position, find_monitor, held_modifiers, hide_from_taskbar, is_wayland,
+    key_for_code, monitor_name, prevent_focus, resolve_position, show_inactive, with_app_id,
+    with_macos_style, with_x11_mode,
+};
+
+/// Name of the sequence given on the command line or in a preset
+pub const MAIN_SEQUENCE: &str = "main";
+
+/// Window title, application id and WM_CLASS unless asked otherwise
+const DEFAULT_NAME: &str = "anibuddy";
+
+/// How often a still image wakes up to check the watched directory
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the event loop wakes up for events of window backends winit
+/// doesn't see, see `WindowBackend::needs_polling`
+const BACKEND_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long after showing the window moves by the window manager are
+/// undone, for unmanaged X11 windows
+const MAP_SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// How often an anchored window checks whether its monitor's resolution
+/// changed
+const ANCHOR_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the idle source is asked whether the user is still idle
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a window on the move is moved a step further
+const MOVEMENT_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Longest time a bouncing window moves on for at once, so it doesn't
+/// jump after the event loop slept through being paused or hidden
+const MAX_BOUNCE_STEP: Duration = Duration::from_millis(100);
+
+/// How much [ and ] change the opacity per press
+const OPACITY_STEP: f32 = 0.05;
+
+/// How long the overlay takes to dim when the pointer comes near, and to
+/// come back once it leaves
+const HOVER_FADE_DURATION: Duration = Duration::from_millis(250);
+
+/// How often the pointer's position is checked for dimming the overlay
+/// when it is near
+const HOVER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Factor + and - change the playback speed by per press
+const SPEED_STEP: f32 = 1.1;
+
+/// Slowest and fastest playback speeds
+const SPEED_RANGE: (f32, f32) = (0.05, 20.0);
+
+/// Speeds offered by the context menu
+const SPEED_PRESETS: [f32; 3] = [0.5, 1.0, 2.0];
+
+/// Factor Ctrl with + and - change the window's size by per press
+const WINDOW_SCALE_STEP: f64 = 1.1;
+
+/// Smallest and largest window scales Ctrl with + and - and scrolling
+/// reach
+const WINDOW_SCALE_RANGE: (f64, f64) = (0.1, 8.0);
+
+/// Pixels touchpads scroll by that count as one notch of a mouse wheel
+const PIXELS_PER_NOTCH: f64 = 40.0;
+
+/// What a click on an interactive overlay does, without the drag modifier
+/// held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ClickAction {
+    /// Nothing
+    #[default]
+    None,
+    /// Quit, after fading out
+    Exit,
+    /// Hide the overlay until the tray or the toggle hotkey shows it again
+    Hide,
+    /// Pause or resume the animation
+    Pause,
+    /// Switch to the next sequence loaded with --sequence
+    NextSequence,
+}
+
+/// Frame of the main sequence playback starts on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartFrame {
+    /// This frame, counting from 0
+    Index(usize),
+    /// This far into the sequence, from 0 to 100
+    Percent(f64),
+    /// Any of the frames, so several overlays don't dance in step
+    Random,
+}
+
+impl Default for StartFrame {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+/// Whether the animation advances
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackState {
+    /// Frames follow each other at their intervals
+    #[default]
+    Playing,
+    /// Held on the current frame, playing continues from it
+    Paused,
+    /// Back on the first frame, playing starts over from it
+    Stopped,
+}
+
+/// Requests sent to the running overlay from outside its window, e.g. by
+/// the tray icon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(all(feature = "tray", target_os = "linux")), allow(dead_code))]
+pub enum OverlayCommand {
+    ToggleVisible,
+    TogglePause,
+    Stop,
+    OpenConfig,
+    Quit,
+}
+
+/// Where fading the overlay in on start or out on quit is
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fade {
+    /// Fading in once the windows are shown, invisible until then
+    Pending,
+    /// Fading in since then
+    In(Instant),
+    /// Fading out since then, the application exits once it's done
+    Out(Instant),
+}
+
+/// A window showing the overlay and the renderer drawing into it
+struct OverlayWindow {
+    window: Arc<dyn WindowBackend>,
+    renderer: Renderer,
+    /// Physical pixels per logical pixel of the display showing the window
+    scale_factor: f64,
+}
 
 pub struct OverlayApplication {
-    window: Option<Arc<Window>>,
-    renderer: Option<Renderer>,
-    media_sequence: Option<MediaSequence>,
-    media_source: Option<MediaSource>,
+    /// Every window showing the overlay, one per monitor with
+    /// `all_monitors`
+    windows: HashMap<WindowId, OverlayWindow>,
+    /// The window on the target monitor, the one that moves around and
+    /// gets captured. Another one takes over when it closes.
+    primary: Option<WindowId>,
+    /// Show the overlay on every monitor instead of only the target one
+    all_monitors: bool,
+    sequences: SequenceSet,
+    /// Sources loaded in `run`, the first one plays on start
+    media_sources: Vec<(String, MediaSource)>,
+    active_sequence: usize,
+    load_options: LoadOptions,
+    /// When the frame on screen was due, or shown without frame skipping
     last_frame_time: Instant,
+    /// Skip frames to catch up after late wakeups, rather than showing
+    /// every one of them late
+    frame_skip: bool,
     frame_interval: Duration,
+    /// Interval used when the active sequence has no fps of its own
+    default_frame_interval: Duration,
+    frame_durations: FrameDurations,
+    use_media_timing: bool,
     current_frame_index: usize,
+    /// Frame of the main sequence shown first
+    start_frame: StartFrame,
+    /// Fail to start on frames past the end, rather than on the last one
+    strict_start: bool,
+    /// Play from the last frame to the first, R flips it while running
+    reverse: bool,
+    /// Set while ping-pong plays the other way than `reverse` says
+    turned_around: bool,
+    /// Times to play sequences through before exiting, 0 for forever
+    loops: u32,
+    /// Times sequences have been played through
+    loops_played: u32,
     frame_count: usize,
+    loop_mode: LoopMode,
+    /// What happens at the end of every sequence, instead of what their
+    /// metadata asks for
+    loop_override: Option<LoopMode>,
+    /// Set once a sequence that doesn't loop has shown its last frame
+    playback_finished: bool,
+    /// Whether Space, S and the menus left the animation advancing
+    playback: PlaybackState,
+    /// Playback speed relative to the frame intervals, changed with + and -
+    speed: f32,
+    /// Whether keyboard shortcuts are handled
+    hotkeys: bool,
+    /// Set while the window is hidden, nothing is drawn then
+    hidden: bool,
+    /// Treat frame sizes as logical pixels, scaled by the display's scale
+    /// factor
+    logical_size: bool,
+    /// Window size to use instead of the frames' size
+    size: Option<PhysicalSize<u32>>,
+    /// Factor the window's size is multiplied by
+    window_scale: f64,
+    /// Fraction of the window's size a notch of the mouse wheel adds or
+    /// takes away
+    scroll_step: f64,
+    /// Notches scrolled with Ctrl held that didn't add up to a whole one
+    /// yet
+    scroll_notches: f64,
+    /// Let the window be resized through the window manager
+    resizable: bool,
+    /// List the window in taskbars and alt-tab
+    taskbar: bool,
+    /// Set while the windows fill their monitors, any key or click quits
+    /// then
+    fullscreen: bool,
+    /// Color behind frames while fullscreen
+    fullscreen_background: [u8; 4],
+    /// Show a tray icon with a menu controlling the overlay
+    tray: bool,
+    /// Key chord showing and hiding the overlay from any application
+    toggle_hotkey: Option<String>,
     use_compression: bool,
+    auto_downscale: bool,
+    gpu_memory_budget: Option<u64>,
+    low_memory: bool,
+    block_compression: bool,
+    atlas: bool,
+    mipmaps: bool,
+    crossfade: bool,
+    filter: Filtering,
+    scale_mode: ScaleMode,
+    render_scale: f32,
+    rotation: f32,
+    flip: (bool, bool),
+    opacity: f32,
+    /// How long fading in and out takes, zero shows and closes the overlay
+    /// right away
+    fade_duration: Duration,
+    fade: Option<Fade>,
+    /// Share of the opacity left while the pointer is near, not dimming
+    /// without it
+    hover_opacity: Option<f32>,
+    /// Pixels around the windows the pointer counts as near within
+    hover_radius: u32,
+    /// How far dimmed for the pointer, from 0 to 1, and when that was
+    hover_dim: (f32, Instant),
+    tint: [u8; 3],
+    color_effects: ColorEffects,
+    background: [u8; 4],
+    chroma_key: Option<ChromaKey>,
+    outline: Option<Outline>,
+    shadow: Option<Shadow>,
+    /// Copies of the sequence drawn side by side
+    instances: usize,
+    instance_spacing: u32,
+    /// Frames between neighbouring instances, `None` spreads them evenly
+    /// over the sequence
+    instance_offset: Option<usize>,
+    fragment_shader: Option<PathBuf>,
+    shader_fallback: bool,
+    /// Watches the directory of `fragment_shader` to recompile it on save
+    shader_watcher: Option<DirectoryWatcher>,
+    present_mode: PresentMode,
+    adapter: AdapterSelection,
+    /// Take mouse input instead of letting clicks through to the windows
+    /// underneath
+    interactive: bool,
+    /// Draw on a wlr-layer-shell surface placed like this where available
+    layer_shell: Option<Placement>,
+    x11_mode: X11Mode,
+    /// Window title instead of the sequence's or `DEFAULT_NAME`
+    title: Option<String>,
+    /// Wayland application id, X11 WM_CLASS and layer surface namespace
+    app_id: String,
+    /// Image file to use as the window's icon instead of the first frame
+    icon: Option<PathBuf>,
+    /// Icon taskbars show for the windows, once the frames are loaded
+    window_icon: Option<Icon>,
+    /// Index or part of the name of the monitor to show the overlay on
+    monitor: Option<String>,
+    /// Where the window is shown, see `resolve_position`
+    position: Option<PhysicalPosition<i32>>,
+    /// Where the window was when the last run exited, used without
+    /// `position` or `anchor`
+    restored_position: Option<PhysicalPosition<i32>>,
+    /// Corner of its monitor the window is kept in
+    anchor: Option<Placement>,
+    /// When the anchored window's monitor was last looked at
+    anchor_checked_at: Instant,
+    /// Trail the mouse pointer like this
+    follow: Option<FollowOptions>,
+    pointer: Option<GlobalPointer>,
+    /// Where a moving window is, unrounded for smooth easing, and when it
+    /// got there
+    movement: Option<((f64, f64), Instant)>,
+    /// Wander around the monitor like this
+    wander: Option<WanderOptions>,
+    /// Where a wandering window is headed and when that was picked
+    wander_target: Option<((f64, f64), Instant)>,
+    /// Set while wandering left, mirroring frames to face that way
+    facing_left: bool,
+    bounce: Option<BounceOptions>,
+    /// Pixels per second a bouncing window moves by, across and down
+    bounce_velocity: (f64, f64),
+    /// Monitor a bouncing window stays on unless roaming
+    bounce_monitor: Option<MonitorHandle>,
+    rng: fastrand::Rng,
+    /// Where an unmanaged window was before it was shown and when that was,
+    /// window managers moving it on map are undone until `MAP_SETTLE_TIME`
+    /// has passed
+    pinned_position: Option<(PhysicalPosition<i32>, Instant)>,
+    /// Where the window was last seen, kept after it closes
+    last_position: Option<PhysicalPosition<i32>>,
+    /// Name of the monitor the window was on when it closed
+    last_monitor: Option<String>,
+    /// Key that has to be held to drag an interactive overlay
+    drag_modifier: DragModifier,
+    modifiers: ModifiersState,
+    /// Let the windows take the keyboard focus when shown and clicked
+    take_focus: bool,
+    /// Set while one of the windows has the keyboard focus
+    focused: bool,
+    /// Keys held down according to the keyboard's own events
+    held_keys: HashSet<KeyCode>,
+    /// Pointer position within the window
+    cursor: Option<PhysicalPosition<f64>>,
+    /// How the pointer looks over an interactive overlay
+    cursor_style: CursorStyle,
+    /// Alpha from which pixels of an interactive overlay take clicks, the
+    /// others let them through
+    input_threshold: Option<u8>,
+    /// Pixels taking clicks in any frame of each sequence, empty where
+    /// clicks go anywhere in the window
+    input_masks: Vec<Option<AlphaMask>>,
+    /// What clicks on an interactive overlay do
+    on_click: ClickAction,
+    /// Window the left button was pressed in for a click, rather than to
+    /// drag it
+    click_pressed: Option<WindowId>,
+    /// Window the pointer is over
+    hovered: Option<WindowId>,
+    /// Window being moved by hand and where the pointer grabbed it
+    drag_anchor: Option<(WindowId, PhysicalPosition<f64>)>,
+    /// Fingers on an interactive overlay, by device and touch id
+    touches: Touches<(DeviceId, u64)>,
+    /// Window being dragged by a finger and where the finger holds it
+    touch_anchor: Option<(WindowId, PhysicalPosition<f64>)>,
+    /// Context menu open in a window of an interactive overlay
+    menu: Option<(WindowId, ContextMenu)>,
+    watch: bool,
+    watcher: Option<DirectoryWatcher>,
+    progress: Arc<ProgressFn>,
     frame_update_in_progress: bool,
+    /// Set while the application is suspended, to when that happened
+    suspended_at: Option<Instant>,
+    /// Quit once the overlay has been up this long
+    duration: Option<Duration>,
+    /// When the first window was shown
+    shown_at: Option<Instant>,
+    /// Pause after the user has been idle this long
+    idle_timeout: Option<Duration>,
+    idle: Option<Box<dyn IdleSource>>,
+    /// Set while paused for the user being idle, to when that started
+    idle_since: Option<Instant>,
+    /// When `idle` was last asked
+    idle_checked_at: Instant,
     is_shutting_down: bool,
 }
 
 impl OverlayApplication {
     pub fn new(source: MediaSource, frame_interval: Duration, use_compression: bool) -> Self {
         Self {
-            window: None,
-            renderer: None,
-            media_sequence: None,
-            media_source: Some(source),
+            windows: HashMap::new(),
+            primary: None,
+            all_monitors: false,
+            sequences: SequenceSet::new(),
+            media_sources: vec![(MAIN_SEQUENCE.to_string(), source)],
+            active_sequence: 0,
+            load_options: LoadOptions::default(),
             last_frame_time: Instant::now(),
+            frame_skip: true,
             frame_interval,
+            default_frame_interval: frame_interval,
+            frame_durations: Vec::new(),
+            use_media_timing: true,
             current_frame_index: 0,
+            start_frame: StartFrame::default(),
+            strict_start: false,
+            reverse: false,
+            turned_around: false,
+            loops: 0,
+            loops_played: 0,
+            loop_override: None,
             frame_count: 0,
+            loop_mode: LoopMode::default(),
+            playback_finished: false,
+            playback: PlaybackState::default(),
+            speed: 1.0,
+            hotkeys: true,
+            hidden: false,
+            logical_size: false,
+            size: None,
+            window_scale: 1.0,
+            scroll_step: 0.05,
+            scroll_notches: 0.0,
+            resizable: false,
+            taskbar: false,
+            fullscreen: false,
+            fullscreen_background: [0, 0, 0, 255],
+            tray: false,
+            toggle_hotkey: None,
             use_compression,
+            auto_downscale: true,
+            gpu_memory_budget: None,
+            low_memory: false,
+            block_compression: false,
+            atlas: false,
+            mipmaps: true,
+            crossfade: false,
+            filter: Filtering::default(),
+            scale_mode: ScaleMode::default(),
+            render_scale: 1.0,
+            rotation: 0.0,
+            flip: (false, false),
+            opacity: 1.0,
+            fade_duration: Duration::ZERO,
+            fade: None,
+            hover_opacity: None,
+            hover_radius: 0,
+            hover_dim: (0.0, Instant::now()),
+            tint: [255; 3],
+            color_effects: ColorEffects::default(),
+            background: [0; 4],
+            chroma_key: None,
+            outline: None,
+            shadow: None,
+            instances: 1,
+            instance_spacing: 0,
+            instance_offset: None,
+            fragment_shader: None,
+            shader_fallback: false,
+            shader_watcher: None,
+            present_mode: PresentMode::default(),
+            adapter: AdapterSelection::default(),
+            interactive: false,
+            layer_shell: None,
+            x11_mode: X11Mode::default(),
+            title: None,
+            app_id: DEFAULT_NAME.to_string(),
+            icon: None,
+            window_icon: None,
+            monitor: None,
+            position: None,
+            restored_position: None,
+            anchor: None,
+            anchor_checked_at: Instant::now(),
+            follow: None,
+            pointer: None,
+            movement: None,
+            wander: None,
+            wander_target: None,
+            facing_left: false,
+            bounce: None,
+            bounce_velocity: (0.0, 0.0),
+            bounce_monitor: None,
+            rng: fastrand::Rng::new(),
+            pinned_position: None,
+            last_position: None,
+            last_monitor: None,
+            drag_modifier: DragModifier::default(),
+            modifiers: ModifiersState::empty(),
+            cursor_style: CursorStyle::Default,
+            input_threshold: None,
+            input_masks: Vec::new(),
+            on_click: ClickAction::None,
+            click_pressed: None,
+            hovered: None,
+            take_focus: false,
+            focused: false,
+            held_keys: HashSet::new(),
+            cursor: None,
+            drag_anchor: None,
+            touches: Touches::default(),
+            touch_anchor: None,
+            menu: None,
+            watch: false,
+            watcher: None,
+            progress: Arc::new(|_, _, _| {}),
             frame_update_in_progress: false,
+            suspended_at: None,
+            duration: None,
+            shown_at: None,
+            idle_timeout: None,
+            idle: None,
+            idle_since: None,
+            idle_checked_at: Instant::now(),
             is_shutting_down: false,
         }
     }
 
-    pub fn run(&mut self) -> Result<()> {
-        let event_loop = EventLoop::new()?;
+    /// Whether per-frame delays embedded in animated files (e.g. GIF frame
+    /// delays) and the fps from a sequence's meta.toml take precedence over
+    /// the fixed frame interval. Durations from a frame manifest are always
+    /// honored.
+    pub fn with_media_timing(mut self, enabled: bool) -> Self {
+        self.use_media_timing = enabled;
+        self
+    }
+
+    /// Whether frames larger than the GPU's maximum texture size are
+    /// downscaled to fit instead of failing to load
+    pub fn with_auto_downscale(mut self, enabled: bool) -> Self {
+        self.auto_downscale = enabled;
+        self
+    }
+
+    /// Downscale frames before upload when they would need more than this
+    /// many bytes of texture memory
+    pub fn with_gpu_memory_budget(mut self, budget: Option<u64>) -> Self {
+        self.gpu_memory_budget = budget;
+        self
+    }
+
+    /// Store frames with 4 bits per channel on the GPU. Halves texture
+    /// memory for uncompressed sequences, delta compression ignores it.
+    pub fn with_low_memory(mut self, enabled: bool) -> Self {
+        self.low_memory = enabled;
+        self
+    }
+
+    /// Store frames BC1 or BC7 compressed on GPUs that support it, a quarter
+    /// to an eighth of the memory. Ignored for delta compression, low
+    /// memory and atlas modes.
+    pub fn with_block_compression(mut self, enabled: bool) -> Self {
+        self.block_compression = enabled;
+        self
+    }
+
+    /// Pack the frames of uncompressed sequences into a few large atlas
+    /// textures, which saves per-texture overhead for many small frames
+    pub fn with_atlas(mut self, enabled: bool) -> Self {
+        self.atlas = enabled;
+        self
+    }
+
+    /// Generate mipmaps for uploaded frames so they stay smooth when drawn
+    /// smaller than their native size
+    pub fn with_mipmaps(mut self, enabled: bool) -> Self {
+        self.mipmaps = enabled;
+        self
+    }
+
+    /// Fade each frame into the next over its display time instead of
+    /// cutting, for smoother playback of sequences with few frames
+    pub fn with_crossfade(mut self, enabled: bool) -> Self {
+        self.crossfade = enabled;
+        self
+    }
+
+    /// How frames are filtered when the window isn't the frame size.
+    /// Nearest filtering keeps pixel art crisp by only magnifying frames by
+    /// whole factors.
+    pub fn with_filter(mut self, filter: Filtering) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// How frames are placed when the window's size or aspect ratio differs
+    /// from theirs
+    pub fn with_scale_mode(mut self, scale_mode: ScaleMode) -> Self {
+        self.scale_mode = scale_mode;
+        self
+    }
+
+    /// Draw frames at a multiple of the window's size and filter them down,
+    /// for smoother results when large frames are shown small
+    pub fn with_render_scale(mut self, render_scale: f32) -> Self {
+        self.render_scale = render_scale;
+        self
+    }
+
+    /// Rotate frames clockwise by `degrees`, sizing the window to fit the
+    /// rotated frame
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// Mirror frames horizontally and/or vertically. H toggles the
+    /// horizontal flip while running.
+    pub fn with_flip(mut self, horizontal: bool, vertical: bool) -> Self {
+        self.flip = (horizontal, vertical);
+        self
+    }
+
+    /// Fade the whole overlay, from 0 for invisible to 1 for opaque. [ and ]
+    /// adjust it while running.
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Fade the overlay in when it appears and out before quitting, over
+    /// `duration`. Quitting again during the fade out exits right away.
+    pub fn with_fade(mut self, duration: Duration) -> Self {
+        self.fade_duration = duration;
+        self
+    }
+
+    /// Dim the overlay to `opacity` of its own while the pointer is over
+    /// it or within `radius` pixels of it, fading back once it leaves.
+    /// The radius and click-through overlays need to ask the platform
+    /// where the pointer is, which Wayland doesn't tell.
+    pub fn with_hover_dim(mut self, opacity: Option<f32>, radius: u32) -> Self {
+        self.hover_opacity = opacity.map(|opacity| opacity.clamp(0.0, 1.0));
+        self.hover_radius = radius;
+        self
+    }
+
+    /// Multiply frames by an sRGB color, white leaves them unchanged
+    pub fn with_tint(mut self, tint: [u8; 3]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Shift the hue, scale the saturation, gray out or invert frames, see
+    /// `ColorEffects` for the order they apply in
+    pub fn with_color_effects(mut self, effects: ColorEffects) -> Self {
+        self.color_effects = effects;
+        self
+    }
+
+    /// sRGB color and alpha filling the window behind frames, shown over
+    /// black on surfaces that can't be transparent
+    pub fn with_background(mut self, background: [u8; 4]) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Make a solid background color transparent in frames rendered
+    /// without alpha
+    pub fn with_chroma_key(mut self, chroma_key: Option<ChromaKey>) -> Self {
+        self.chroma_key = chroma_key;
+        self
+    }
+
+    /// Draw a colored border around the opaque parts of frames, so they
+    /// stand out against busy backgrounds
+    pub fn with_outline(mut self, outline: Option<Outline>) -> Self {
+        self.outline = outline;
+        self
+    }
+
+    /// Draw a blurred drop shadow behind frames, growing the window by the
+    /// shadow's margin on every side so it isn't clipped
+    pub fn with_shadow(mut self, shadow: Option<Shadow>) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    /// Draw `count` copies of the sequence side by side in one window,
+    /// `spacing` pixels apart. Instance `i` shows the frame `i * offset`
+    /// after the current one, or with no offset the instances are spread
+    /// evenly over the sequence. The window grows to fit them all.
+    pub fn with_instances(mut self, count: usize, spacing: u32, offset: Option<usize>) -> Self {
+        self.instances = count.clamp(1, MAX_INSTANCES);
+        self.instance_spacing = spacing;
+        self.instance_offset = offset;
+        self
+    }
+
+    /// Draw frames with the WGSL fragment shader at `path`, recompiling it
+    /// whenever the file changes. With `fallback` a shader that fails to
+    /// compile at startup is logged and the built-in one used, otherwise the
+    /// application exits.
+    pub fn with_fragment_shader(mut self, path: Option<PathBuf>, fallback: bool) -> Self {
+        self.fragment_shader = path;
+        self.shader_fallback = fallback;
+        self
+    }
+
+    /// How finished frames are presented, falls back to Fifo if the
+    /// surface doesn't support it
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Run on the GPU `selection` names instead of the one wgpu prefers
+    pub fn with_adapter(mut self, selection: AdapterSelection) -> Self {
+        self.adapter = selection;
+        self
+    }
+
+    /// Report decoding and GPU upload progress while the sequences load
+    pub fn with_progress(mut self, progress: Arc<ProgressFn>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Receive mouse input over the overlay. Otherwise clicks pass through
+    /// to whatever is underneath, leaving the overlay unfocusable by mouse.
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// Draw on a Wayland layer surface in the overlay layer instead of a
+    /// window, so compositors don't tile, decorate or list it. Falls back to
+    /// a window when the compositor doesn't support wlr-layer-shell.
+    pub fn with_layer_shell(mut self, options: Option<Placement>) -> Self {
+        self.layer_shell = options;
+        self
+    }
+
+    /// Title the window with `title` rather than the sequence's title,
+    /// followed by what is playing and whether it is paused
+    pub fn with_title(mut self, title: Option<String>) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Show the image at `path` as the window's icon where taskbars show
+    /// it, rather than the first frame
+    pub fn with_icon(mut self, path: Option<PathBuf>) -> Self {
+        self.icon = path;
+        self
+    }
+
+    /// Identify the window by `app_id` to Wayland compositors, as WM_CLASS
+    /// to X11 window managers and as namespace of layer surfaces, for
+    /// their window rules
+    pub fn with_app_id(mut self, app_id: Option<String>) -> Self {
+        self.app_id = app_id.unwrap_or_else(|| DEFAULT_NAME.to_string());
+        self
+    }
+
+    /// Ask X11 window managers to leave the overlay window alone instead of
+    /// managing it like any other window
+    pub fn with_x11_mode(mut self, mode: X11Mode) -> Self {
+        self.x11_mode = mode;
+        self
+    }
+
+    /// Show the overlay on the monitor at this index or whose name contains
+    /// this, the primary monitor if none matches
+    pub fn with_monitor(mut self, monitor: Option<String>) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Show the overlay on every connected monitor, each in a window of its
+    /// own. Only the one on the target monitor moves around, the others
+    /// stay anchored or centered on theirs.
+    pub fn with_all_monitors(mut self, enabled: bool) -> Self {
+        self.all_monitors = enabled;
+        self
+    }
+
+    /// Show the window at this position in physical pixels instead of where
+    /// the platform puts it. Negative coordinates are distances of the
+    /// window from the monitor's right and bottom edges. Wayland doesn't
+    /// allow choosing it.
+    pub fn with_position(mut self, position: Option<PhysicalPosition<i32>>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Show the window where it was at the end of an earlier run, moved onto
+    /// its monitor if that has shrunk since. `with_position` and
+    /// `with_anchor` take precedence.
+    pub fn with_restored_position(mut self, position: Option<PhysicalPosition<i32>>) -> Self {
+        self.restored_position = position;
+        self
+    }
+
+    /// Keep the window in a corner or the center of its monitor, moving it
+    /// when the monitor's resolution or the window's size changes. Layer
+    /// surfaces use this too, anchored by the compositor.
+    pub fn with_anchor(mut self, anchor: Option<Placement>) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Move the window along with the mouse pointer, easing toward it and
+    /// staying on the pointer's monitor. Needs X11 or Windows, Wayland
+    /// doesn't tell where the pointer is.
+    pub fn with_follow_cursor(mut self, follow: Option<FollowOptions>) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// Wander to random places on the monitor, facing the way the window
+    /// moves. Needs a platform that lets windows choose their position.
+    pub fn with_wander(mut self, wander: Option<WanderOptions>) -> Self {
+        self.wander = wander;
+        self
+    }
+
+    /// Hide the pointer over an interactive overlay, or show a hand while
+    /// it can be dragged
+    pub fn with_cursor_style(mut self, style: CursorStyle) -> Self {
+        self.cursor_style = style;
+        self
+    }
+
+    /// Take clicks on an `interactive` overlay only where some frame of the
+    /// playing sequence has an alpha of at least `threshold`, letting them
+    /// through to the windows underneath elsewhere. Needs X11 or a layer
+    /// surface, rotated frames take them anywhere.
+    pub fn with_input_shape(mut self, threshold: Option<u8>) -> Self {
+        self.input_threshold = threshold;
+        self
+    }
+
+    /// Do `action` when an `interactive` overlay is clicked, without the
+    /// drag modifier held
+    pub fn with_on_click(mut self, action: ClickAction) -> Self {
+        self.on_click = action;
+        self
+    }
+
+    /// Move diagonally and bounce off the monitor's edges like the DVD
+    /// logo, facing the way the window moves. Needs a platform that lets
+    /// windows choose their position.
+    pub fn with_bounce(mut self, bounce: Option<BounceOptions>) -> Self {
+        self.bounce = bounce;
+        self
+    }
+
+    /// Key that has to be held to drag an `interactive` overlay with the
+    /// left mouse button, so clicks don't move it by accident
+    pub fn with_drag_modifier(mut self, modifier: DragModifier) -> Self {
+        self.drag_modifier = modifier;
+        self
+    }
+
+    /// Where the window was last seen on the desktop, also after `run`
+    /// returns. Unknown on Wayland.
+    pub fn window_position(&self) -> Option<PhysicalPosition<i32>> {
+        self.last_position
+    }
+
+    /// Name of the monitor the window was on when it closed
+    pub fn window_monitor(&self) -> Option<&str> {
+        self.last_monitor.as_deref()
+    }
+
+    /// Factor the window's size is multiplied by, as Ctrl with + and -
+    /// left it
+    pub fn window_scale(&self) -> f64 {
+        self.window_scale
+    }
+
+    /// Opacity of the whole overlay, as [ and ] left it
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Whether the animation is playing, paused or stopped
+    pub fn playback_state(&self) -> PlaybackState {
+        self.playback
+    }
+
+    /// Playback speed, as + and - left it
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Frame of the main sequence on screen, `None` while another sequence
+    /// plays
+    pub fn frame_index(&self) -> Option<usize> {
+        (self.active_sequence == 0).then_some(self.current_frame_index)
+    }
+
+    /// Handle keyboard shortcuts, on by default. Without them keys do
+    /// nothing, not even Escape.
+    pub fn with_hotkeys(mut self, enabled: bool) -> Self {
+        self.hotkeys = enabled;
+        self
+    }
+
+    /// Size the window in logical pixels, so frames appear the same size on
+    /// displays of any scale factor instead of one screen pixel per pixel
+    pub fn with_logical_size(mut self, enabled: bool) -> Self {
+        self.logical_size = enabled;
+        self
+    }
+
+    /// Size the window like this instead of like the frames, the scale mode
+    /// decides how frames fill it
+    pub fn with_size(mut self, size: Option<PhysicalSize<u32>>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Play at `speed` times the frames' own pace, + and - change it while
+    /// running. A speed that isn't a number is ignored.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        if speed.is_finite() {
+            self.speed = speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1);
+        }
+        self
+    }
+
+    /// Start on another frame of the main sequence than the first. Frames
+    /// past the end make `run` fail if `strict`, otherwise it starts on
+    /// the last one. Delta-compressed sequences always start on the first
+    /// frame, each of their frames builds on the one before.
+    pub fn with_start_frame(mut self, start: StartFrame, strict: bool) -> Self {
+        self.start_frame = start;
+        self.strict_start = strict;
+        self
+    }
+
+    /// Play sequences from their last frame to their first, each frame
+    /// still shown for its own duration. Delta-compressed sequences only
+    /// play forward.
+    pub fn with_reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Keep to the frames' schedule by skipping those a late wakeup missed,
+    /// on by default. Without it every frame is shown, counting its
+    /// duration from when it was, so playback falls behind under load.
+    pub fn with_frame_skip(mut self, frame_skip: bool) -> Self {
+        self.frame_skip = frame_skip;
+        self
+    }
+
+    /// Exit after playing through `loops` times, fading out first if
+    /// fading is on. Ping-pong plays through once there and back. 0 loops
+    /// forever.
+    pub fn with_loops(mut self, loops: u32) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    /// Loop, ping-pong, play once or hold the last frame at the end of
+    /// every sequence, rather than as their meta.toml says
+    pub fn with_loop_mode(mut self, mode: Option<LoopMode>) -> Self {
+        self.loop_override = mode;
+        self
+    }
+
+    /// Multiply the window's size by `scale`, Ctrl with + and - change it
+    /// while running
+    pub fn with_window_scale(mut self, scale: f64) -> Self {
+        self.window_scale = scale.clamp(WINDOW_SCALE_RANGE.0, WINDOW_SCALE_RANGE.1);
+        self
+    }
+
+    /// Grow and shrink an interactive overlay by `step` of its size per
+    /// notch scrolled over it
+    pub fn with_scroll_step(mut self, step: f64) -> Self {
+        self.scroll_step = step.max(0.0);
+        self
+    }
+
+    /// Let window managers resize the window, frames are scaled to fit
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// List the window in taskbars, pagers and alt-tab like any other
+    /// instead of keeping it out of them
+    pub fn with_taskbar(mut self, enabled: bool) -> Self {
+        self.taskbar = enabled;
+        self
+    }
+
+    /// Let the window take the keyboard focus when it appears and when it
+    /// is clicked, like any other. Without it, keys reach the overlay
+    /// while the pointer is over it where the platform reports them.
+    pub fn with_take_focus(mut self, enabled: bool) -> Self {
+        self.take_focus = enabled;
+        self
+    }
+
+    /// Start out filling the monitor, with frames fitted into it in front
+    /// of `background` unless the scale mode says otherwise. Any key or
+    /// click quits while fullscreen.
+    pub fn with_fullscreen(mut self, enabled: bool, background: [u8; 4]) -> Self {
+        self.fullscreen = enabled;
+        self.fullscreen_background = background;
+        self
+    }
+
+    /// Quit, fading out, once the overlay has been shown for `duration`,
+    /// whether it is playing, paused or hidden by then
+    pub fn with_duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Pause the animation once there was no keyboard or mouse input for
+    /// this long, where the platform tells
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Show a tray icon whose menu shows, hides, pauses and quits the
+    /// overlay. Needs the `tray` feature.
+    pub fn with_tray(mut self, enabled: bool) -> Self {
+        self.tray = enabled;
+        self
+    }
+
+    /// Show and hide the overlay with `chord`, e.g. "ctrl+alt+k", whichever
+    /// application has the focus. Needs the `hotkey` feature and a platform
+    /// that lets applications grab keys, which Wayland doesn't.
+    pub fn with_toggle_hotkey(mut self, chord: Option<String>) -> Self {
+        self.toggle_hotkey = chord;
+        self
+    }
+
+    /// Reload frames whenever the files of the image directory change
+    pub fn with_watch(mut self, enabled: bool) -> Self {
+        self.watch = enabled;
+        self
+    }
+
+    /// Keep another named sequence on the GPU next to the main one, see
+    /// `switch_sequence`
+    pub fn with_sequence(mut self, name: String, source: MediaSource) -> Self {
+        self.media_sources.push((name, source));
+        self
+    }
+
+    pub fn with_load_options(mut self, options: LoadOptions) -> Self {
+        self.load_options = options;
+        self
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        let event_loop = as_accessory(
+            &mut EventLoop::<OverlayCommand>::with_user_event(),
+            self.take_focus,
+        )
+        .build()?;
+
+        if self.media_sources.is_empty() {
+            return Err(anyhow!("No media source specified"));
+        }
+
+        for (name, source) in std::mem::take(&mut self.media_sources) {
+            let sequence = MediaSequence::load(source, &self.load_options, &*self.progress)?;
+            log::info!("Loaded {} frames in sequence '{}'", sequence.count(), name);
+            self.sequences.insert(name, sequence)?;
+        }
+
+        if self.use_compression && self.sequences.len() > 1 {
+            log::warn!("Delta compression supports a single sequence, loading uncompressed");
+            self.use_compression = false;
+        }
+
+        if self.use_compression && self.sequences.iter().all(|sequence| sequence.count() <= 1) {
+            log::info!("Nothing to delta-compress in a single frame, loading uncompressed");
+            self.use_compression = false;
+        }
+
+        if self.low_memory && self.use_compression {
+            log::warn!("Delta-compressed frames are stored at full color depth");
+            self.low_memory = false;
+        }
+
+        if let Some(budget) = self.gpu_memory_budget {
+            // Packed frames take half the memory the estimate assumes
+            let budget = if self.low_memory { budget * 2 } else { budget };
+            self.sequences.fit_memory_budget(budget)?;
+        }
+
+        self.refresh_sequence_info();
+        if self.reverse && self.use_compression {
+            log::warn!("Delta-compressed sequences only play forward, not reversing");
+            self.reverse = false;
+        }
+        self.skip_to_start_frame()?;
+        self.window_icon = self.load_window_icon();
+        // Built while every frame's pixels are still there
+        if let Some(threshold) = self.input_threshold.filter(|_| self.interactive) {
+            self.input_masks = self
+                .sequences
+                .iter()
+                .map(|sequence| AlphaMask::union(sequence.get_all_images(), threshold))
+                .collect();
+        }
+
+        if self.watch
+            && let Some(sequence) = self.sequences.at(0)
+        {
+            match sequence.source_directory() {
+                Some(directory) => {
+                    self.watcher = Some(DirectoryWatcher::new(
+                        directory,
+                        self.load_options.recursive,
+                    )?);
+                }
+                None => log::warn!("--watch only applies to image directories, ignoring it"),
+            }
+        }
+
+        if let Some(shader) = &self.fragment_shader {
+            // Editors often replace the file instead of writing to it, so
+            // its directory is watched rather than the file itself
+            let directory = shader
+                .parent()
+                .filter(|directory| !directory.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            match DirectoryWatcher::new(directory, false) {
+                Ok(watcher) => self.shader_watcher = Some(watcher),
+                Err(e) => log::warn!("Shader changes won't be picked up: {}", e),
+            }
+        }
+
+        // Dropping the handle removes the icon
+        let _tray = self.tray.then(|| self.spawn_tray(&event_loop)).flatten();
+        let _hotkey = self
+            .toggle_hotkey
+            .as_deref()
+            .and_then(|chord| self.register_hotkey(&event_loop, chord));
+
+        event_loop.run_app(self)?;
+
+        Ok(())
+    }
+
+    #[cfg(all(feature = "tray", target_os = "linux"))]
+    fn spawn_tray(
+        &self,
+        event_loop: &EventLoop<OverlayCommand>,
+    ) -> Option<ksni::blocking::Handle<crate::tray::OverlayTray>> {
+        crate::tray::OverlayTray::spawn(event_loop.create_proxy(), self.window_title())
+            .inspect_err(|e| log::warn!("No tray icon: {}", e))
+            .ok()
+    }
+
+    #[cfg(not(all(feature = "tray", target_os = "linux")))]
+    fn spawn_tray(&self, _event_loop: &EventLoop<OverlayCommand>) -> Option<()> {
+        log::warn!("No tray icon, this build doesn't support it");
+        None
+    }
+
+    #[cfg(feature = "hotkey")]
+    fn register_hotkey(
+        &self,
+        event_loop: &EventLoop<OverlayCommand>,
+        chord: &str,
+    ) -> Option<crate::hotkey::ToggleHotkey> {
+        crate::hotkey::ToggleHotkey::register(event_loop.create_proxy(), chord)
+            .inspect(|_| log::info!("{} shows and hides the overlay", chord))
+            .inspect_err(|e| log::warn!("No global hotkey: {}", e))
+            .ok()
+    }
+
+    #[cfg(not(feature = "hotkey"))]
+    fn register_hotkey(&self, _event_loop: &EventLoop<OverlayCommand>, _chord: &str) -> Option<()> {
+        log::warn!("No global hotkey, this build doesn't support it");
+        None
+    }
+
+    /// Make the start frame the current one, before any window shows it.
+    /// Fails on frames past the end with `strict_start`.
+    fn skip_to_start_frame(&mut self) -> Result<()> {
+        if self.start_frame == StartFrame::default()
+            && let Some(last) = self.reversed_start()
+        {
+            self.current_frame_index = last;
+            return Ok(());
+        }
+        if self.frame_count == 0 {
+            return Ok(());
+        }
+        // Lazily decoded frames may not be there yet
+        let decoded = self
+            .sequences
+            .at(self.active_sequence)
+            .map_or(0, |sequence| sequence.decoded_count())
+            .min(self.frame_count);
+        let last = self.frame_count - 1;
+        let index = match self.start_frame {
+            StartFrame::Index(index) if index > last && self.strict_start => {
+                return Err(anyhow!(
+                    "Can't start on frame {}, the last frame is {}",
+                    index,
+                    last
+                ));
+            }
+            StartFrame::Index(index) => index.min(last),
+            StartFrame::Percent(percent) => {
+                ((percent.clamp(0.0, 100.0) / 100.0 * self.frame_count as f64) as usize).min(last)
+            }
+            StartFrame::Random => self.rng.usize(..decoded.max(1)),
+        };
+        if index == 0 {
+            return Ok(());
+        }
+        if self.use_compression {
+            log::info!("Delta-compressed sequences start on their first frame");
+            return Ok(());
+        }
+        if index < decoded {
+            log::info!("Starting on frame {}", index);
+            self.current_frame_index = index;
+        } else {
+            log::info!("Frame {} isn't decoded yet, starting on the first", index);
+        }
+        Ok(())
+    }
+
+    /// Cleanup resources before shutdown
+    fn cleanup(&mut self) {
+        if self.is_shutting_down {
+            return;
+        }
+
+        log::info!("Starting application cleanup");
+        self.is_shutting_down = true;
+
+        // Remembered for `window_position` and `window_monitor`
+        if let Some(window) = self.window().cloned() {
+            self.last_position = window.outer_position().or(self.last_position);
+            self.last_monitor = window
+                .current_monitor()
+                .map(|monitor| monitor_name(&monitor));
+        }
+
+        self.release_cursor();
+        for overlay in self.windows.values_mut() {
+            overlay.renderer.cleanup();
+        }
+
+        self.sequences = SequenceSet::new();
+        self.windows.clear();
+        self.primary = None;
+
+        log::info!("Application cleanup complete");
+    }
+
+    /// Play the sequence called `name` from its first frame. Its frames are
+    /// already on the GPU, so nothing is uploaded.
+    pub fn switch_sequence(&mut self, name: &str) -> Result<()> {
+        let index = self
+            .sequences
+            .index_of(name)
+            .ok_or_else(|| anyhow!("No sequence named '{}' is loaded", name))?;
+
+        for overlay in self.windows.values_mut() {
+            overlay.renderer.select_sequence(index)?;
+        }
+
+        self.active_sequence = index;
+        self.current_frame_index = 0;
+        self.last_frame_time = Instant::now();
+        self.playback_finished = false;
+        self.refresh_sequence_info();
+        if let Some(last) = self.reversed_start()
+            && let Err(e) = self.show_frame(last)
+        {
+            log::error!("Failed to show the last frame: {}", e);
+        }
+        self.update_title();
+        self.update_input_shapes();
+        self.request_redraw();
+
+        log::info!("Switched to sequence '{}'", name);
+        Ok(())
+    }
+
+    /// Cycle to the sequence loaded after the active one
+    fn switch_to_next_sequence(&mut self) {
+        let next = (self.active_sequence + 1) % self.sequences.len().max(1);
+        if let Some(name) = self.sequences.name(next).map(str::to_string)
+            && let Err(e) = self.switch_sequence(&name)
+        {
+            log::error!("Failed to switch sequence: {}", e);
+        }
+    }
+
+    /// Take frame count and timing from the active sequence
+    fn refresh_sequence_info(&mut self) {
+        let Some(sequence) = self.sequences.at(self.active_sequence) else {
+            return;
+        };
+
+        self.frame_count = sequence.count();
+        self.frame_durations = if self.use_media_timing || sequence.has_duration_manifest() {
+            sequence.frame_durations()
+        } else {
+            Vec::new()
+        };
+
+        // An fps given on the command line wins over the sequence's own
+        let metadata = sequence.metadata();
+        self.frame_interval = match metadata.fps {
+            Some(fps) if self.use_media_timing => Duration::from_secs_f64(1.0 / fps as f64),
+            _ => self.default_frame_interval,
+        };
+        self.loop_mode = self
+            .loop_override
+            .or(metadata.loop_mode)
+            .unwrap_or_default();
+        if self.loop_mode == LoopMode::PingPong && self.use_compression {
+            log::warn!("Delta-compressed sequences only play forward, looping instead");
+            self.loop_mode = LoopMode::Loop;
+        }
+        self.turned_around = false;
+    }
+
+    /// Frame playback moves on to from the current one in its direction,
+    /// and the direction it goes on in from there
+    fn next_frame(&self) -> Advance {
+        self.loop_mode.advance(
+            self.current_frame_index,
+            self.frame_count,
+            self.reverse != self.turned_around,
+        )
+    }
+
+    /// Whether moving on as `advance` says would complete the last of
+    /// `loops`: wrapping around, or for ping-pong turning back to the
+    /// direction it started in
+    fn ends_last_loop(&self, advance: Advance) -> bool {
+        let Advance::To {
+            reverse,
+            wrapped: true,
+            ..
+        } = advance
+        else {
+            return false;
+        };
+        self.loops > 0 && reverse == self.reverse && self.loops_played + 1 >= self.loops
+    }
+
+    /// Whether every one of `loops` has been played
+    fn loops_finished(&self) -> bool {
+        self.loops > 0 && self.loops_played >= self.loops
+    }
+
+    /// Last frame of the active sequence when playing in reverse, where
+    /// it starts rather than wrapping around to it right away. None while
+    /// playing forward or before the last frame is decoded.
+    fn reversed_start(&self) -> Option<usize> {
+        let decoded = self
+            .sequences
+            .at(self.active_sequence)
+            .map_or(0, |sequence| sequence.decoded_count());
+        (self.reverse && self.frame_count > 1 && decoded >= self.frame_count)
+            .then(|| self.frame_count - 1)
+    }
+
+    /// Play the other way from the frame on screen
+    fn toggle_reverse(&mut self) {
+        if self.use_compression {
+            log::info!("Delta-compressed sequences only play forward");
+            return;
+        }
+        self.reverse = !self.reverse;
+        log::info!(
+            "Playing {}",
+            if self.reverse {
+                "in reverse"
+            } else {
+                "forward"
+            }
+        );
+        // A held last frame is where reversing starts from
+        if self.playback_finished && self.loop_mode == LoopMode::Hold {
+            self.playback_finished = false;
+            self.last_frame_time = Instant::now();
+            self.request_redraw();
+        }
+    }
+
+    /// Whether new frames still have to be shown
+    fn is_animating(&self) -> bool {
+        self.frame_count > 1 && !self.playback_finished && self.playback == PlaybackState::Playing
+    }
+
+    /// Window size fitting every instance of a frame of `size` once rotated,
+    /// with room for the shadow around them, unless `--size` overrides it,
+    /// on a display scaled by `scale_factor`
+    fn window_size(&self, size: (u32, u32), scale_factor: f64) -> PhysicalSize<u32> {
+        let size = self.size.unwrap_or_else(|| {
+            let (width, height) = rotated_size(size, self.rotation);
+            let margin = self.shadow.map_or(0, |shadow| shadow.margin());
+            let instances = self.instances as u32;
+            let row = width * instances + self.instance_spacing * (instances - 1);
+            PhysicalSize::new(row + 2 * margin, height + 2 * margin)
+        });
+        let size: PhysicalSize<u32> =
+            LogicalSize::new(size.width, size.height).to_physical(self.layout_scale(scale_factor));
+        PhysicalSize::new(size.width.max(1), size.height.max(1))
+    }
+
+    /// Physical pixels per pixel of frames and the layout around them, on a
+    /// display scaled by `scale_factor`
+    fn layout_scale(&self, scale_factor: f64) -> f64 {
+        let whole = self.filter == Filtering::Nearest || self.scale_mode == ScaleMode::IntegerScale;
+        let display = if self.logical_size {
+            display_scale(scale_factor, whole)
+        } else {
+            1.0
+        };
+        display * self.window_scale
+    }
+
+    /// Multiply the window's size by `factor`, within `WINDOW_SCALE_RANGE`
+    fn change_window_scale(&mut self, factor: f64) {
+        self.window_scale =
+            (self.window_scale * factor).clamp(WINDOW_SCALE_RANGE.0, WINDOW_SCALE_RANGE.1);
+        log::info!("Window scale {:.0}%", self.window_scale * 100.0);
+
+        let frame_size = self
+            .sequences
+            .at(self.active_sequence)
+            .and_then(|sequence| sequence.current_frame_size());
+        let ids: Vec<WindowId> = self.windows.keys().copied().collect();
+        for id in ids {
+            let scale_factor = self.windows[&id].scale_factor;
+            let layout_scale = self.layout_scale(scale_factor);
+            let size = frame_size.map(|size| self.window_size(size, scale_factor));
+            let Some(overlay) = self.windows.get_mut(&id) else {
+                continue;
+            };
+            overlay.renderer.set_scale_factor(layout_scale);
+            // The surface follows once the window reports its new size
+            if let Some(size) = size {
+                overlay.window.request_inner_size(size);
+            }
+        }
+    }
+
+    /// Grow or shrink the window for the mouse wheel turned by `delta`,
+    /// from one whole multiple of the frames' size to the next with Ctrl
+    /// held
+    fn scroll(&mut self, delta: MouseScrollDelta) {
+        let notches = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y / PIXELS_PER_NOTCH,
+        };
+        if !self.modifiers.control_key() {
+            self.scroll_notches = 0.0;
+            self.change_window_scale((1.0 + self.scroll_step).powf(notches));
+            return;
+        }
+
+        self.scroll_notches += notches;
+        let steps = self.scroll_notches.trunc();
+        if steps == 0.0 {
+            return;
+        }
+        self.scroll_notches -= steps;
+        // Off a whole multiple, the first step goes to the nearest one.
+        // Rounded first, as scaling by factors leaves it just off them.
+        let current = (self.window_scale * 1e6).round() / 1e6;
+        let scale = if steps > 0.0 {
+            current.floor() + steps
+        } else {
+            (current.ceil() + steps).max(1.0)
+        };
+        self.change_window_scale(scale / self.window_scale);
+    }
+
+    /// `title` or the active sequence's, with the sequence's name where
+    /// there are several and whether the animation is paused
+    fn window_title(&self) -> String {
+        let mut title = self
+            .title
+            .as_deref()
+            .or_else(|| {
+                self.sequences
+                    .at(self.active_sequence)
+                    .and_then(|sequence| sequence.metadata().title.as_deref())
+            })
+            .unwrap_or(DEFAULT_NAME)
+            .to_string();
+        if self.sequences.len() > 1
+            && let Some(name) = self.sequences.name(self.active_sequence)
+        {
+            title = format!("{} - {}", title, name);
+        }
+        match self.playback_state() {
+            PlaybackState::Playing => {}
+            PlaybackState::Paused => title.push_str(&format!(
+                " (paused on frame {} of {})",
+                self.current_frame_index + 1,
+                self.frame_count
+            )),
+            PlaybackState::Stopped => title.push_str(" (stopped)"),
+        }
+        title
+    }
+
+    /// The icon taskbars show for the windows: the image `icon` names, or
+    /// the first frame while its pixels are still there
+    fn load_window_icon(&self) -> Option<Icon> {
+        if let Some(path) = &self.icon {
+            match crate::icon::load(path) {
+                Ok(icon) => return Some(icon),
+                Err(e) => log::warn!(
+                    "Can't use {} as icon, showing the first frame: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        let frame = self.sequences.at(self.active_sequence)?.frame(0)?;
+        crate::icon::from_image(frame)
+            .inspect_err(|e| log::warn!("No icon from the first frame: {}", e))
+            .ok()
+    }
+
+    /// Show what is playing in the windows' titles
+    fn update_title(&self) {
+        let title = self.window_title();
+        for overlay in self.windows.values() {
+            overlay.window.set_title(&title);
+        }
+    }
+
+    /// How long the currently displayed frame should stay on screen
+    fn current_frame_interval(&self) -> Duration {
+        self.frame_durations
+            .get(self.current_frame_index)
+            .copied()
+            .flatten()
+            .unwrap_or(self.frame_interval)
+            .div_f32(self.speed)
+    }
+
+    /// Advance the animation again, from the frame it was paused on or
+    /// from the first one after stopping
+    pub fn play(&mut self) {
+        self.set_playback(PlaybackState::Playing);
+    }
+
+    /// Hold the animation on its current frame. Windows still redraw it,
+    /// as when they are resized.
+    pub fn pause(&mut self) {
+        self.set_playback(PlaybackState::Paused);
+    }
+
+    /// Pause a playing animation, or play a paused or stopped one
+    pub fn toggle(&mut self) {
+        match self.playback {
+            PlaybackState::Playing => self.pause(),
+            PlaybackState::Paused | PlaybackState::Stopped => self.play(),
+        }
+    }
+
+    /// Hold the animation on the frame it starts from, the last one when
+    /// playing in reverse
+    pub fn stop(&mut self) {
+        if let Err(e) = self.show_frame(self.reversed_start().unwrap_or(0)) {
+            log::error!("Failed to show the first frame: {}", e);
+        }
+        self.playback_finished = false;
+        self.turned_around = false;
+        self.set_playback(PlaybackState::Stopped);
+    }
+
+    fn set_playback(&mut self, state: PlaybackState) {
+        if self.playback == state {
+            return;
+        }
+        log::info!("Playback {:?}", state);
+        self.playback = state;
+        self.update_title();
+        // The frame gets its full time once playing again, rather than
+        // the ones missed while held being caught up on
+        self.last_frame_time = Instant::now();
+        self.request_redraw();
+    }
+
+    /// Redraw every window
+    fn request_redraw(&self) {
+        for overlay in self.windows.values() {
+            overlay.window.request_redraw();
+        }
+    }
+
+    /// The primary window, see `primary`
+    fn window(&self) -> Option<&Arc<dyn WindowBackend>> {
+        self.primary
+            .and_then(|id| self.windows.get(&id))
+            .map(|overlay| &overlay.window)
+    }
+
+    /// Show frame `index` of the active sequence in every window
+    fn show_frame(&mut self, index: usize) -> Result<()> {
+        for overlay in self.windows.values_mut() {
+            pollster::block_on(overlay.renderer.set_current_texture_index(index))?;
+        }
+        self.current_frame_index = index;
+        Ok(())
+    }
+
+    /// Show the next decoded frame while paused or stopped
+    pub fn step_forward(&mut self) {
+        self.step_frame(true);
+    }
+
+    /// Show the previous decoded frame while paused or stopped
+    pub fn step_backward(&mut self) {
+        self.step_frame(false);
+    }
+
+    /// Show the next or the previous decoded frame while paused, wrapping
+    /// around the ends only for looping sequences. The windows are redrawn
+    /// right away, as nothing else redraws them while paused.
+    fn step_frame(&mut self, forward: bool) {
+        let count = self
+            .sequences
+            .at(self.active_sequence)
+            .map_or(self.frame_count, |sequence| sequence.decoded_count())
+            .min(self.frame_count);
+        if self.playback == PlaybackState::Playing || count <= 1 {
+            return;
+        }
+        if self.use_compression && !forward {
+            log::info!("Delta-compressed sequences only step forward");
+            return;
+        }
+        let Some(index) = self
+            .loop_mode
+            .step(self.current_frame_index, count, forward)
+        else {
+            return;
+        };
+        // Stepping off the first frame after stopping pauses there
+        self.pause();
+        // A held last frame plays on once stepped away from
+        self.playback_finished = false;
+
+        if let Err(e) = self.show_frame(index) {
+            log::error!("Failed to show frame {}: {}", index, e);
+        }
+        log::info!("Frame {} of {}", index + 1, self.frame_count);
+        self.update_title();
+        self.request_redraw();
+    }
+
+    /// Hide the window and stop drawing, or show it again and continue from
+    /// the frame it was hidden on
+    fn toggle_visible(&mut self) {
+        if self.windows.is_empty() {
+            return;
+        }
+        self.hidden = !self.hidden;
+        for overlay in self.windows.values() {
+            if self.hidden {
+                overlay.window.set_visible(false);
+            } else {
+                self.show_window(&*overlay.window);
+            }
+        }
+        if !self.hidden {
+            self.last_frame_time = Instant::now();
+            self.request_redraw();
+        }
+    }
+
+    /// How frames are placed in their windows, fitted into the monitor
+    /// rather than stretched over it while fullscreen
+    fn current_scale_mode(&self) -> ScaleMode {
+        match self.scale_mode {
+            ScaleMode::Stretch if self.fullscreen => ScaleMode::Fit,
+            mode => mode,
+        }
+    }
+
+    /// Color filling the windows behind frames
+    fn current_background(&self) -> [u8; 4] {
+        if self.fullscreen {
+            self.fullscreen_background
+        } else {
+            self.background
+        }
+    }
+
+    /// Fill every window's monitor, or go back to being an overlay of the
+    /// frames' size
+    fn toggle_fullscreen(&mut self) {
+        // The windows are all of one kind, either all of them can or none
+        let fullscreen = !self.fullscreen;
+        for overlay in self.windows.values() {
+            if !overlay.window.set_fullscreen(fullscreen) {
+                log::warn!("Layer surfaces can't go fullscreen");
+                return;
+            }
+            overlay
+                .window
+                .set_click_through(!fullscreen && !self.interactive);
+        }
+        self.fullscreen = fullscreen;
+        self.update_input_shapes();
+        log::info!(
+            "{}",
+            if fullscreen {
+                "Fullscreen, any key or click quits"
+            } else {
+                "Left fullscreen"
+            }
+        );
+
+        let scale_mode = self.current_scale_mode();
+        let [r, g, b, a] = self
+            .current_background()
+            .map(|channel| channel as f32 / 255.0);
+        for overlay in self.windows.values_mut() {
+            overlay.renderer.set_scale_mode(scale_mode);
+            overlay.renderer.set_background(r, g, b, a);
+        }
+
+        // Not every platform restores the size the window had before, the
+        // surface follows through `resized` either way
+        if !fullscreen
+            && let Some(size) = self
+                .sequences
+                .at(self.active_sequence)
+                .and_then(|sequence| sequence.current_frame_size())
+        {
+            for overlay in self.windows.values() {
+                overlay
+                    .window
+                    .request_inner_size(self.window_size(size, overlay.scale_factor));
+            }
+        }
+        // Moving around goes on from wherever the window ends up
+        self.movement = None;
+        self.request_redraw();
+    }
+
+    /// Open the config file in the desktop's default application
+    fn open_config(&self) {
+        let path = match crate::config::get_config_path() {
+            Ok(path) => path,
+            Err(e) => {
+                log::error!("Can't open the config: {}", e);
+                return;
+            }
+        };
+        let opener = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(target_os = "windows") {
+            "explorer"
+        } else {
+            "xdg-open"
+        };
+        if let Err(e) = std::process::Command::new(opener).arg(&path).spawn() {
+            log::error!("Failed to open {}: {}", path.display(), e);
+        }
+    }
+
+    /// Multiply the playback speed by `factor`
+    fn change_speed(&mut self, factor: f32) {
+        self.set_speed(self.speed * factor);
+    }
+
+    /// Play at `speed` times the frames' own pace, within `SPEED_RANGE`.
+    /// The frame on screen keeps the share of its time it already had, so
+    /// the change neither skips it nor shows it twice as long. A speed
+    /// that isn't a number is ignored.
+    pub fn set_speed(&mut self, speed: f32) {
+        if !speed.is_finite() {
+            return;
+        }
+        let now = Instant::now();
+        let shown = now.duration_since(self.last_frame_time).as_secs_f64()
+            / self
+                .current_frame_interval()
+                .as_secs_f64()
+                .max(f64::EPSILON);
+        self.speed = speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1);
+        log::info!("Playback speed {:.0}%", self.speed * 100.0);
+        let elapsed = self.current_frame_interval().mul_f64(shown.min(1.0));
+        self.last_frame_time = now.checked_sub(elapsed).unwrap_or(now);
+    }
+
+    /// Hand frames finished by the background decoder over to the GPU
+    fn upload_decoded_frames(&mut self) {
+        let mut decoded_any = false;
+        for (sequence_index, sequence) in self.sequences.iter_mut().enumerate() {
+            if !sequence.is_loading() {
+                continue;
+            }
+
+            decoded_any = true;
+            for (index, image) in sequence.take_decoded_frames() {
+                upload_once(&mut self.windows, |renderer| {
+                    if let Err(e) = renderer.upload_image(sequence_index, index, &image) {
+                        log::error!("Failed to upload frame {}: {}", index, e);
+                    }
+                });
+            }
+        }
+
+        // A decode failure cuts the sequence short
+        if decoded_any {
+            self.refresh_sequence_info();
+        }
+    }
+
+    /// Recompile the custom fragment shader once it has been saved, keeping
+    /// the previous pipeline if the new version doesn't compile
+    fn reload_changed_shader(&mut self) {
+        let Some(changed) = self
+            .shader_watcher
+            .as_mut()
+            .and_then(|watcher| watcher.poll())
+        else {
+            return;
+        };
+        let Some(shader) = &self.fragment_shader else {
+            return;
+        };
+        if !changed
+            .iter()
+            .any(|path| path.file_name() == shader.file_name())
+        {
+            return;
+        }
+
+        for overlay in self.windows.values_mut() {
+            if let Err(e) = overlay.renderer.set_fragment_shader(shader) {
+                log::error!("{}", e);
+                return;
+            }
+        }
+        log::info!("Reloaded shader {}", shader.display());
+        self.request_redraw();
+    }
+
+    /// Pick up changes to the watched image directory and swap the affected
+    /// textures without recreating the window
+    fn reload_changed_frames(&mut self) {
+        let Some(changed) = self.watcher.as_mut().and_then(|watcher| watcher.poll()) else {
+            return;
+        };
+        // Only the main sequence is watched
+        let Some(sequence) = self.sequences.at_mut(0) else {
+            return;
+        };
+
+        log::info!("{} files changed, reloading", changed.len());
+
+        let previous_trim_offset = sequence.trim_offset();
+        let outcome = match sequence.reload(&changed, &self.load_options) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                log::error!("Failed to reload frames, keeping the previous ones: {}", e);
+                return;
+            }
+        };
+
+        match outcome {
+            // Delta-compressed frames depend on each other, so any change
+            // means compressing the whole sequence again
+            ReloadOutcome::Frames(indices) if !self.use_compression => {
+                for index in indices {
+                    let Some(image) = sequence.frame(index) else {
+                        continue;
+                    };
+                    upload_once(&mut self.windows, |renderer| {
+                        if let Err(e) = renderer.upload_image(0, index, image) {
+                            log::error!("Failed to upload frame {}: {}", index, e);
+                        }
+                    });
+                }
+            }
+            _ => {
+                // Keep the visible sprite in place when the trimmed box moved
+                let (x, y) = sequence.trim_offset();
+                if (x, y) != previous_trim_offset && self.active_sequence == 0 {
+                    for overlay in self.windows.values() {
+                        if let Some(position) = overlay.window.outer_position() {
+                            overlay.window.set_outer_position(PhysicalPosition::new(
+                                position.x + x as i32 - previous_trim_offset.0 as i32,
+                                position.y + y as i32 - previous_trim_offset.1 as i32,
+                            ));
+                        }
+                    }
+                }
+
+                upload_once(&mut self.windows, |renderer| {
+                    pollster::block_on(preload_sequences(
+                        renderer,
+                        &self.sequences,
+                        self.use_compression,
+                        &|_, _, _| {},
+                    ));
+                });
+                for overlay in self.windows.values_mut() {
+                    if let Err(e) = overlay.renderer.select_sequence(self.active_sequence) {
+                        log::error!("Failed to restore the active sequence: {}", e);
+                    }
+                }
+
+                if let Some(size) = self
+                    .sequences
+                    .at(self.active_sequence)
+                    .and_then(|sequence| sequence.current_frame_size())
+                {
+                    for overlay in self.windows.values() {
+                        let size = self.window_size(size, overlay.scale_factor);
+                        if overlay.window.inner_size() != size {
+                            overlay.window.request_inner_size(size);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.refresh_sequence_info();
+
+        // The sequence may have become shorter
+        let index = self.current_frame_index.min(self.frame_count - 1);
+        if let Err(e) = self.show_frame(index) {
+            log::error!("Failed to show frame {}: {}", index, e);
+        }
+
+        self.request_redraw();
+    }
+
+    fn update(&mut self) {
+        if self.is_shutting_down {
+            return;
+        }
+
+        self.upload_decoded_frames();
+        self.apply_fade();
+
+        // A still image never advances
+        if !self.is_animating() {
+            return;
+        }
+
+        let now = Instant::now();
+        if self.frame_update_in_progress || self.frame_count == 0 {
+            return;
+        }
+        // Each frame is due when the one before was, plus its duration, so
+        // late wakeups skip ahead to the frame due now instead of falling
+        // behind. Delta-compressed frames each build on the one before and
+        // can't be skipped.
+        let skip = self.frame_skip && !self.use_compression;
+        let mut new_frame_index = None;
+        for _ in 0..self.frame_count {
+            let due = self.last_frame_time + self.current_frame_interval();
+            if now < due {
+                break;
+            }
+            self.last_frame_time = if skip { due } else { now };
+            match self.advance_frame() {
+                Some(index) => {
+                    self.current_frame_index = index;
+                    new_frame_index = Some(index);
+                }
+                // Try again an interval from now while the decoder catches up
+                None => {
+                    self.last_frame_time = now;
+                    break;
+                }
+            }
+            if !skip {
+                break;
+            }
+        }
+        // A whole cycle behind, as after the system slept, starts over
+        // from now
+        if now.duration_since(self.last_frame_time) >= self.current_frame_interval() {
+            self.last_frame_time = now;
+        }
+
+        if let Some(index) = new_frame_index {
+            self.frame_update_in_progress = true;
+            if let Err(e) = self.show_frame(index) {
+                if self.use_compression {
+                    log::error!("Failed to update compressed frame: {}", e);
+                } else {
+                    log::error!("Failed to update frame: {}", e);
+                }
+            }
+            self.frame_update_in_progress = false;
+        }
+    }
+
+    /// Move playback on by one frame and return the frame to show, or None
+    /// where it finished or has to wait for the background decoder
+    fn advance_frame(&mut self) -> Option<usize> {
+        let advance = self.next_frame();
+        let (index, reverse, wrapped) = match advance {
+            Advance::To {
+                index,
+                reverse,
+                wrapped,
+            } => (index, reverse, wrapped),
+            Advance::Finished => {
+                log::info!("Reached the last frame, playback finished");
+                self.playback_finished = true;
+                return None;
+            }
+        };
+
+        // Hold the last decoded frame until the background decoder catches up
+        let decoded_count = self
+            .sequences
+            .at(self.active_sequence)
+            .map_or(self.frame_count, |sequence| sequence.decoded_count());
+        if index >= decoded_count {
+            return None;
+        }
+        if self.ends_last_loop(advance) {
+            log::info!("Played {} times, playback finished", self.loops);
+            self.loops_played = self.loops;
+            self.playback_finished = true;
+            return None;
+        }
+        // Ping-pong turns around at either end
+        self.turned_around = reverse != self.reverse;
+        if wrapped && !self.turned_around {
+            self.loops_played += 1;
+        }
+        Some(index)
+    }
+
+    /// Free the decoded frames now that the GPU holds them. Watching the
+    /// directory needs them to tell which frames changed and to upload
+    /// everything again, so they are kept then.
+    fn release_uploaded_pixels(&mut self) {
+        if self.watch {
+            return;
+        }
+
+        #[cfg(debug_assertions)]
+        let before = resident_memory();
+
+        for sequence in self.sequences.iter_mut() {
+            sequence.release_pixels();
+        }
+
+        #[cfg(debug_assertions)]
+        if let (Some(before), Some(after)) = (before, resident_memory()) {
+            let megabytes = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+            log::debug!(
+                "Resident memory {:.1} MB before releasing frame pixels, {:.1} MB after",
+                megabytes(before),
+                megabytes(after)
+            );
+        }
+    }
+
+    /// Draw the first frame into the still hidden window, then show it. The
+    /// frames are uploaded by then, so the window never appears black or
+    /// with leftover contents. Wayland can't hide windows, there it only
+    /// saves showing an empty surface for longer.
+    fn show_first_frame(&mut self, id: WindowId) {
+        if let Err(err) = self.render(id) {
+            // Drawing again on the redraw below reports it properly
+            log::warn!("Failed to draw the first frame: {}", err);
+        }
+        let Some(window) = self.windows.get(&id).map(|overlay| overlay.window.clone()) else {
+            return;
+        };
+        if self.primary == Some(id) {
+            self.last_position = window.outer_position();
+            if self.x11_mode != X11Mode::Managed {
+                self.pinned_position = self
+                    .last_position
+                    .map(|position| (position, Instant::now()));
+            }
+        }
+        self.update_input_shapes();
+        self.show_window(&*window);
+        window.request_redraw();
+        self.shown_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Quit once the overlay has been shown for `duration`, waking up for
+    /// it however long the animation has nothing to draw
+    fn check_duration(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(duration), Some(shown_at)) = (self.duration, self.shown_at) else {
+            return;
+        };
+        let remaining = duration.saturating_sub(shown_at.elapsed());
+        if !remaining.is_zero() {
+            wake_within(event_loop, remaining);
+        } else if !matches!(self.fade, Some(Fade::Out(_))) {
+            log::info!("Shown for {:?}, quitting", duration);
+            self.quit(event_loop);
+        }
+    }
+
+    /// Show `window`, leaving the focus where it is unless asked otherwise
+    fn show_window(&self, window: &dyn WindowBackend) {
+        if self.take_focus {
+            window.set_visible(true);
+        } else {
+            show_inactive(window);
+        }
+    }
+
+    /// Follow the window onto a display with another scale factor. Returns
+    /// the size the window needs there, so frames keep their size in
+    /// logical pixels or in physical pixels, whichever they are sized in,
+    /// rather than being stretched.
+    fn scale_factor_changed(
+        &mut self,
+        id: WindowId,
+        scale_factor: f64,
+    ) -> Option<PhysicalSize<u32>> {
+        log::info!("Scale factor changed to {}", scale_factor);
+        let layout_scale = self.layout_scale(scale_factor);
+        let overlay = self.windows.get_mut(&id)?;
+        overlay.scale_factor = scale_factor;
+        overlay.renderer.set_scale_factor(layout_scale);
+        self.sequences
+            .at(self.active_sequence)
+            .and_then(|sequence| sequence.current_frame_size())
+            .map(|size| self.window_size(size, scale_factor))
+    }
+
+    /// Act on a key pressed over the overlay, see the README's Controls
+    fn key_pressed(&mut self, key: &Key, repeat: bool, event_loop: &ActiveEventLoop) {
+        let text = key.to_text();
+        match key {
+            // F11 fills the monitor and goes back to the overlay
+            Key::Named(NamedKey::F11) if self.hotkeys && !repeat => self.toggle_fullscreen(),
+            // Any other key quits while fullscreen
+            _ if self.fullscreen => self.quit(event_loop),
+            // Escape closes the context menu
+            Key::Named(NamedKey::Escape) if self.menu.is_some() => self.close_menu(),
+            _ if !self.hotkeys => {}
+            // Escape and Q quit
+            Key::Named(NamedKey::Escape) => self.quit(event_loop),
+            _ if text == Some("q") => self.quit(event_loop),
+            // Space pauses and resumes the animation
+            Key::Named(NamedKey::Space) if !repeat => self.toggle(),
+            // S stops on the first frame
+            _ if !repeat && text == Some("s") => self.stop(),
+            // R plays the other way
+            _ if !repeat && text == Some("r") => self.toggle_reverse(),
+            // Left and Right step through the frames while paused
+            Key::Named(NamedKey::ArrowLeft) => self.step_backward(),
+            Key::Named(NamedKey::ArrowRight) => self.step_forward(),
+            // Ctrl with + and - grows and shrinks the window, Ctrl+0 resets it
+            _ if self.modifiers.control_key() && matches!(text, Some("+" | "=" | "-" | "0")) => {
+                let factor = match text {
+                    Some("-") => WINDOW_SCALE_STEP.recip(),
+                    Some("0") => self.window_scale.recip(),
+                    _ => WINDOW_SCALE_STEP,
+                };
+                self.change_window_scale(factor);
+            }
+            // + and - speed the animation up and slow it down
+            _ if matches!(text, Some("+" | "=" | "-")) => {
+                self.change_speed(if text == Some("-") {
+                    SPEED_STEP.recip()
+                } else {
+                    SPEED_STEP
+                });
+            }
+            // Tab cycles through the loaded sequences
+            Key::Named(NamedKey::Tab) if !repeat && self.sequences.len() > 1 => {
+                self.switch_to_next_sequence();
+            }
+            // F12 saves what the overlay shows to a PNG
+            Key::Named(NamedKey::F12) if !repeat => self.capture_frame(),
+            // H mirrors the overlay horizontally
+            _ if !repeat && text == Some("h") => {
+                self.flip.0 = !self.flip.0;
+                self.apply_flip();
+            }
+            // [ and ] fade the overlay out and back in
+            _ if matches!(text, Some("[" | "]")) => {
+                let step = if text == Some("[") {
+                    -OPACITY_STEP
+                } else {
+                    OPACITY_STEP
+                };
+                self.opacity = (self.opacity + step).clamp(0.0, 1.0);
+                log::info!("Opacity {:.0}%", self.opacity * 100.0);
+                let opacity = self.current_opacity();
+                for overlay in self.windows.values_mut() {
+                    overlay.renderer.set_opacity(opacity);
+                }
+                self.request_redraw();
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys from the keyboard itself, which are all an overlay without the
+    /// focus gets. They count as pressed over the overlay while the pointer
+    /// is on it, read as on a US layout. Those keys are typed into whichever
+    /// window has the focus too, so only keys that type no text count.
+    fn raw_key(&mut self, event: RawKeyEvent, event_loop: &ActiveEventLoop) {
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return;
+        };
+        // Platforms don't tell held keys repeating apart from new presses
+        let repeat = match event.state {
+            ElementState::Pressed => !self.held_keys.insert(code),
+            ElementState::Released => {
+                self.held_keys.remove(&code);
+                false
+            }
+        };
+        // Window events carry the keys while a window has the focus
+        if self.focused {
+            return;
+        }
+        let modifiers = held_modifiers(&self.held_keys);
+        if modifiers != self.modifiers {
+            self.modifiers = modifiers;
+            self.update_cursor();
+        }
+        if event.state.is_pressed()
+            && self.cursor.is_some()
+            && let Some(key) = key_for_code(code, self.modifiers.shift_key())
+            && key.to_text().is_none()
+        {
+            self.key_pressed(&key, repeat, event_loop);
+        }
+    }
+
+    /// Start or stop dragging an interactive overlay with the left button,
+    /// or click it by releasing the button over the window it was pressed
+    /// in
+    fn left_button(&mut self, id: WindowId, state: ElementState, event_loop: &ActiveEventLoop) {
+        if state == ElementState::Released {
+            self.drag_anchor = None;
+            if self.click_pressed.take() == Some(id) && self.hovered == Some(id) {
+                self.clicked(event_loop);
+            }
+            return;
+        }
+        if !self.interactive {
+            return;
+        }
+        if !self.drag_modifier.is_held(self.modifiers) {
+            self.click_pressed = Some(id);
+            return;
+        }
+        let Some(window) = self.windows.get(&id).map(|overlay| overlay.window.clone()) else {
+            return;
+        };
+
+        self.moved_by_hand();
+        // The platform moves the window itself where it can
+        if !window.drag_window() {
+            self.drag_anchor = self.cursor.map(|cursor| (id, cursor));
+        }
+    }
+
+    /// Stop keeping the window where it was put, as moves by the user are
+    /// never undone. Wandering goes on from where it ends up.
+    fn moved_by_hand(&mut self) {
+        self.pinned_position = None;
+        self.anchor = None;
+        self.movement = None;
+    }
+
+    /// Drag an interactive overlay with one finger, or pinch it bigger and
+    /// smaller with two, within the same limits as scrolling. Any touch
+    /// quits while fullscreen.
+    fn touched(&mut self, id: WindowId, touch: Touch, event_loop: &ActiveEventLoop) {
+        if self.fullscreen && touch.phase == TouchPhase::Started {
+            self.quit(event_loop);
+            return;
+        }
+        let gesture = self
+            .touches
+            .update((touch.device_id, touch.id), touch.phase, touch.location);
+        match gesture {
+            Some(Gesture::Hold(position)) => {
+                self.moved_by_hand();
+                self.touch_anchor = Some((id, position));
+            }
+            Some(Gesture::Drag(position)) => {
+                if let Some(anchor) = self.touch_anchor {
+                    self.drag_to(id, anchor, position);
+                }
+            }
+            Some(Gesture::Release) => self.touch_anchor = None,
+            Some(Gesture::Pinch(factor)) => {
+                self.scroll_notches = 0.0;
+                self.change_window_scale(factor);
+            }
+            None => {}
+        }
+    }
+
+    /// Do what `on_click` asks for
+    fn clicked(&mut self, event_loop: &ActiveEventLoop) {
+        match self.on_click {
+            ClickAction::None => {}
+            ClickAction::Exit => self.quit(event_loop),
+            ClickAction::Hide if !self.hidden => self.toggle_visible(),
+            ClickAction::Hide => {}
+            ClickAction::Pause => self.toggle(),
+            ClickAction::NextSequence => self.switch_to_next_sequence(),
+        }
+    }
+
+    /// Move a window dragged by hand along with the pointer
+    fn cursor_moved(&mut self, id: WindowId, position: PhysicalPosition<f64>) {
+        self.cursor = Some(position);
+        self.hover_menu(id);
+        if let Some(anchor) = self.drag_anchor {
+            self.drag_to(id, anchor, position);
+        }
+    }
+
+    /// Move the window of `anchor` so the point of it that was grabbed is
+    /// at `position`, for the pointer or finger that moved in window `id`
+    fn drag_to(
+        &mut self,
+        id: WindowId,
+        anchor: (WindowId, PhysicalPosition<f64>),
+        position: PhysicalPosition<f64>,
+    ) {
+        let (dragged, anchor) = anchor;
+        let Some(overlay) = self.windows.get(&id).filter(|_| dragged == id) else {
+            return;
+        };
+        let window = &overlay.window;
+        let Some(origin) = window.outer_position() else {
+            return;
+        };
+
+        let moved = PhysicalPosition::new(
+            origin.x + (position.x - anchor.x).round() as i32,
+            origin.y + (position.y - anchor.y).round() as i32,
+        );
+        window.set_outer_position(moved);
+        if self.primary == Some(id) {
+            self.last_position = Some(moved);
+        }
+    }
+
+    /// Open the context menu of an interactive overlay where the pointer is
+    /// in window `id`, replacing one that is already open
+    fn open_menu(&mut self, id: WindowId) {
+        self.close_menu();
+        let entries = self.menu_entries();
+        let (Some(cursor), Some(overlay)) = (self.cursor, self.windows.get_mut(&id)) else {
+            return;
+        };
+        let size = overlay.window.inner_size();
+        // Two pixels per pixel of the font at a scale factor of 1
+        let scale = (2.0 * overlay.scale_factor).round().max(1.0) as u32;
+        let mut menu = ContextMenu::new(
+            entries,
+            (cursor.x, cursor.y),
+            (size.width, size.height),
+            scale,
+        );
+        menu.hover(Some((cursor.x, cursor.y)));
+        overlay.renderer.set_menu(&menu.quads());
+        overlay.window.request_redraw();
+        self.menu = Some((id, menu));
+        self.update_cursor();
+        self.update_input_shapes();
+    }
+
+    /// Close the context menu if one is open
+    fn close_menu(&mut self) {
+        let Some((id, _)) = self.menu.take() else {
+            return;
+        };
+        if let Some(overlay) = self.windows.get_mut(&id) {
+            overlay.renderer.set_menu(&[]);
+            overlay.window.request_redraw();
+        }
+        self.update_cursor();
+        self.update_input_shapes();
+    }
+
+    /// Show the pointer `cursor_style` asks for over the hovered window,
+    /// the platform's arrow over the context menu
+    fn update_cursor(&self) {
+        let Some(overlay) = self.hovered.and_then(|id| self.windows.get(&id)) else {
+            return;
+        };
+        let style = match self.cursor_style {
+            _ if self.menu.is_some() => CursorStyle::Default,
+            CursorStyle::Grab if !self.drag_modifier.is_held(self.modifiers) => {
+                CursorStyle::Default
+            }
+            style => style,
+        };
+        overlay.window.set_cursor(style);
+    }
+
+    /// Give the pointer its usual look back, before the hovered window
+    /// closes
+    fn release_cursor(&mut self) {
+        if let Some(overlay) = self.hovered.take().and_then(|id| self.windows.get(&id)) {
+            overlay.window.set_cursor(CursorStyle::Default);
+        }
+    }
+
+    /// Highlight the menu entry under the pointer in window `id`
+    fn hover_menu(&mut self, id: WindowId) {
+        let cursor = self.cursor.map(|cursor| (cursor.x, cursor.y));
+        let Some((menu_id, menu)) = &mut self.menu else {
+            return;
+        };
+        if *menu_id != id || !menu.hover(cursor) {
+            return;
+        }
+        if let Some(overlay) = self.windows.get_mut(&id) {
+            overlay.renderer.set_menu(&menu.quads());
+            overlay.window.request_redraw();
+        }
+    }
+
+    /// Do what the menu entry clicked in window `id` does, any click closes
+    /// the menu
+    fn click_menu(&mut self, id: WindowId, event_loop: &ActiveEventLoop) {
+        let action = self
+            .menu
+            .as_ref()
+            .filter(|(menu_id, _)| *menu_id == id)
+            .zip(self.cursor)
+            .and_then(|((_, menu), cursor)| menu.action_at((cursor.x, cursor.y)));
+        self.close_menu();
+
+        match action {
+            Some(MenuAction::TogglePause) => self.toggle(),
+            Some(MenuAction::Stop) => self.stop(),
+            Some(MenuAction::Speed(speed)) => self.set_speed(speed),
+            Some(MenuAction::Sequence(index)) => {
+                if let Some(name) = self.sequences.name(index).map(str::to_string)
+                    && let Err(e) = self.switch_sequence(&name)
+                {
+                    log::error!("Failed to switch sequence: {}", e);
+                }
+            }
+            Some(MenuAction::Quit) => self.quit(event_loop),
+            None => {}
+        }
+    }
+
+    /// Entries of the context menu: pausing and stopping, speed presets, the loaded
+    /// sequences if there are several, and quitting
+    fn menu_entries(&self) -> Vec<MenuEntry> {
+        let entry = |label: String, action, checked| MenuEntry {
+            label,
+            action,
+            checked,
+        };
+        let pause = match self.playback_state() {
+            PlaybackState::Playing => "Pause",
+            PlaybackState::Paused => "Resume",
+            PlaybackState::Stopped => "Play",
+        };
+        let mut entries = vec![
+            entry(pause.to_string(), MenuAction::TogglePause, false),
+            entry(
+                "Stop".to_string(),
+                MenuAction::Stop,
+                self.playback == PlaybackState::Stopped,
+            ),
+        ];
+        entries.extend(SPEED_PRESETS.iter().map(|&speed| {
+            entry(
+                format!("Speed {:.0}%", speed * 100.0),
+                MenuAction::Speed(speed),
+                (self.speed - speed).abs() < 1e-3,
+            )
+        }));
+        if self.sequences.len() > 1 {
+            entries.extend((0..self.sequences.len()).filter_map(|index| {
+                let name = self.sequences.name(index)?;
+                Some(entry(
+                    name.to_string(),
+                    MenuAction::Sequence(index),
+                    index == self.active_sequence,
+                ))
+            }));
+        }
+        entries.push(entry("Quit".to_string(), MenuAction::Quit, false));
+        entries
+    }
+
+    /// Put an unmanaged window back where it was if the window manager
+    /// moved it when it was shown
+    fn moved(&mut self, id: WindowId, position: PhysicalPosition<i32>) {
+        if self.primary != Some(id) {
+            return;
+        }
+        self.last_position = Some(position);
+        let Some((pinned, shown_at)) = self.pinned_position else {
+            return;
+        };
+        if shown_at.elapsed() > MAP_SETTLE_TIME {
+            self.pinned_position = None;
+        } else if position != pinned
+            && let Some(window) = self.window()
+        {
+            log::debug!(
+                "Window manager moved the overlay to {},{}, moving it back",
+                position.x,
+                position.y
+            );
+            window.set_outer_position(pinned);
+        }
+    }
+
+    /// Write the frame on screen in the primary window to
+    /// konata_capture_<timestamp>.png next to the executable. Encoding
+    /// happens on another thread so the animation keeps going.
+    fn capture_frame(&self) {
+        let Some(renderer) = self
+            .primary
+            .and_then(|id| self.windows.get(&id))
+            .map(|overlay| &overlay.renderer)
+        else {
+            return;
+        };
+
+        let capture = renderer
+            .capture_frame()
+            .and_then(|image| Ok((image, capture_path()?)));
+        let (image, path) = match capture {
+            Ok(capture) => capture,
+            Err(err) => {
+                log::error!("Failed to capture frame: {}", err);
+                return;
+            }
+        };
+
+        std::thread::spawn(move || match image.save(&path) {
+            Ok(()) => log::info!("Saved capture to {}", path.display()),
+            Err(err) => log::error!("Failed to save capture to {}: {}", path.display(), err),
+        });
+    }
+
+    /// Frame shown by each instance after the first, `instance_offset`
+    /// frames apart
+    fn instance_frames(&self) -> Vec<usize> {
+        let count = self.frame_count.max(1);
+        let step = self
+            .instance_offset
+            .unwrap_or(count / self.instances.max(1));
+        (1..self.instances)
+            .map(|instance| (self.current_frame_index + instance * step) % count)
+            .collect()
+    }
+
+    /// The frame the current one is fading into and how far along the fade
+    /// is, from the time the current frame has been shown
+    fn crossfade_target(&self) -> (Option<usize>, f32) {
+        if !self.is_animating() {
+            return (None, 0.0);
+        }
+
+        let decoded_count = self
+            .sequences
+            .at(self.active_sequence)
+            .map_or(self.frame_count, |sequence| sequence.decoded_count());
+        // Sequences that don't loop stop on their last frame, as does the
+        // last of `loops`
+        let next = match self.next_frame() {
+            advance if self.ends_last_loop(advance) => return (None, 0.0),
+            Advance::To { index, .. } if index < decoded_count => index,
+            _ => return (None, 0.0),
+        };
+
+        let progress = self.last_frame_time.elapsed().as_secs_f32()
+            / self.current_frame_interval().as_secs_f32();
+        (Some(next), progress.min(1.0))
+    }
+
+    /// Create the surface frames are drawn on: a layer surface if asked for
+    /// and supported, a hidden winit window otherwise
+    fn create_window(
+        &self,
+        event_loop: &ActiveEventLoop,
+        monitor: Option<MonitorHandle>,
+        size: PhysicalSize<u32>,
+    ) -> Result<Arc<dyn WindowBackend>> {
+        if let Some(options) = self.layer_shell {
+            #[cfg(target_os = "linux")]
+            match crate::layer_shell::LayerShellWindow::new(
+                options,
+                monitor.as_ref().and_then(MonitorHandle::name).as_deref(),
+                size,
+                self.interactive,
+                &self.app_id,
+            ) {
+                Ok(window) => {
+                    log::info!("Drawing on a wlr-layer-shell overlay surface");
+                    if self.position.is_some() {
+                        log::warn!(
+                            "Layer surfaces are placed by --anchor or --layer-corner, ignoring --position"
+                        );
+                    }
+                    return Ok(Arc::new(window));
+                }
+                Err(e) => log::warn!("No layer surface, falling back to a window: {}", e),
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = options;
+                log::warn!("Layer surfaces need Wayland, falling back to a window");
+            }
+        }
+
+        let mut window_attributes = WindowAttributes::default()
+            .with_title(self.window_title())
+            .with_transparent(true)
+            .with_decorations(false)
+            .with_resizable(self.resizable)
+            .with_inner_size(size)
+            .with_window_icon(self.window_icon.clone())
+            .with_active(self.take_focus)
+            // Shown by show_first_frame once there is something to show
+            .with_visible(false);
+        // Drawn through DirectComposition, with nothing opaque underneath,
+        // and with the icon in the taskbar too
+        #[cfg(target_os = "windows")]
+        {
+            use winit::platform::windows::WindowAttributesExtWindows;
+            window_attributes = window_attributes
+                .with_no_redirection_bitmap(true)
+                .with_taskbar_icon(self.window_icon.clone());
+        }
+        let placed = monitor.clone().or_else(|| event_loop.primary_monitor());
+        // Only the first window goes where --position asks, the others are
+        // on monitors of their own
+        let requested = self.position.filter(|_| self.windows.is_empty());
+        let restored = self.restored_position.filter(|_| self.windows.is_empty());
+        let position = match (requested, self.anchor, &placed) {
+            (Some(requested), _, Some(monitor)) => Some(resolve_position(
+                requested,
+                monitor.position(),
+                monitor.size(),
+                size,
+            )),
+            (Some(requested), _, None) => Some(requested),
+            (None, Some(anchor), Some(monitor)) => Some(anchored_position(
+                anchor,
+                monitor.position(),
+                monitor.size(),
+                size,
+            )),
+            (None, None, Some(monitor)) if restored.is_some() => restored
+                .map(|restored| clamp_to_area(restored, size, monitor.position(), monitor.size())),
+            (None, None, None) if restored.is_some() => restored,
+            _ => monitor
+                .as_ref()
+                .map(|monitor| centered_position(monitor.position(), monitor.size(), size)),
+        };
+        let explicit = requested.is_some() || self.anchor.is_some();
+        if explicit && is_wayland(event_loop) {
+            log::warn!(
+                "Wayland doesn't let windows choose their position, ignoring --position and --anchor; --layer-shell places the overlay in a screen corner"
+            );
+        } else if let Some(position) = position {
+            window_attributes = window_attributes.with_position(position);
+        }
+        if self.fullscreen {
+            window_attributes =
+                window_attributes.with_fullscreen(Some(Fullscreen::Borderless(placed)));
+        }
+        let window = event_loop.create_window(with_macos_style(with_app_id(
+            with_x11_mode(window_attributes, self.x11_mode),
+            &self.app_id,
+        )))?;
+        // Window managers may ignore the initial position but not a move
+        if (explicit || restored.is_some())
+            && let Some(position) = position
+        {
+            window.set_outer_position(position);
+        }
+
+        // Clicks go to the windows underneath unless asked otherwise, or
+        // quit while fullscreen
+        if !self.interactive
+            && !self.fullscreen
+            && let Err(e) = window.set_cursor_hittest(false)
+        {
+            log::warn!("Can't let clicks through the overlay: {}", e);
+        }
+
+        if !self.taskbar
+            && let Err(e) = hide_from_taskbar(&window)
+        {
+            log::info!("The overlay stays in the taskbar: {}", e);
+        }
+
+        if !self.take_focus
+            && let Err(e) = prevent_focus(&window)
+        {
+            log::info!("The overlay may take the focus: {}", e);
+        }
+
+        Ok(Arc::new(window))
+    }
+
+    /// Create a window on `monitor` with a renderer holding every sequence
+    /// and add it to `windows`, still hidden
+    fn open_window(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        monitor: Option<MonitorHandle>,
+    ) -> Result<WindowId> {
+        // Sizes in logical pixels need the scale before there is a window
+        let scale_factor = monitor
+            .clone()
+            .or_else(|| event_loop.primary_monitor())
+            .map_or(1.0, |monitor| monitor.scale_factor());
+
+        let size = if let Some(sequence) = self.sequences.at(self.active_sequence) {
+            if let Some(size) = sequence.current_frame_size() {
+                let dimensions = self.window_size(size, scale_factor);
+                log::info!(
+                    "Using image dimensions for window: {}x{}",
+                    dimensions.width,
+                    dimensions.height
+                );
+                dimensions
+            } else {
+                log::info!("No image found, using default dimensions");
+                PhysicalSize::new(800, 600)
+            }
+        } else {
+            log::info!("No media sequence found, using default dimensions");
+            PhysicalSize::new(800, 600)
+        };
+
+        let window = self
+            .create_window(event_loop, monitor, size)
+            .map_err(|err| anyhow!("Failed to create window: {}", err))?;
+        let scale_factor = window.scale_factor();
+
+        // Windows share the GPU of the first one, which holds their frames
+        let shared = self
+            .windows
+            .values()
+            .next()
+            .map(|overlay| overlay.renderer.gpu().clone());
+        let renderer = pollster::block_on(async {
+            match self.create_renderer(&window, shared.clone()).await {
+                // The monitor may be driven by another GPU
+                Err(err) if shared.is_some() => {
+                    log::warn!("{}, uploading the frames once more for it", err);
+                    self.create_renderer(&window, None).await
+                }
+                result => result,
+            }
+        });
+        let mut renderer = renderer.map_err(|err| anyhow!("Failed to create renderer: {}", err))?;
+
+        renderer.set_present_mode(self.present_mode);
+        renderer.set_low_memory(self.low_memory);
+        renderer.set_block_compression(self.block_compression);
+        renderer.set_atlas(self.atlas);
+        // Generating mipmaps takes long on the CPU
+        if renderer.is_software() && self.mipmaps {
+            log::info!("Skipping mipmaps with software rendering");
+        }
+        renderer.set_mipmaps(self.mipmaps && !renderer.is_software());
+        renderer.set_crossfade(self.crossfade);
+        renderer.set_filter(self.filter);
+        renderer.set_scale_mode(self.current_scale_mode());
+        renderer.set_render_scale(self.render_scale);
+        renderer.set_rotation(self.rotation);
+        renderer.set_flip(self.flip.0 != self.facing_left, self.flip.1);
+        renderer.set_opacity(self.current_opacity());
+        let [r, g, b] = self.tint.map(|channel| channel as f32 / 255.0);
+        renderer.set_tint(r, g, b);
+        renderer.set_color_effects(self.color_effects);
+        let [r, g, b, a] = self
+            .current_background()
+            .map(|channel| channel as f32 / 255.0);
+        renderer.set_background(r, g, b, a);
+        renderer.set_chroma_key(self.chroma_key);
+        renderer.set_outline(self.outline);
+        renderer.set_shadow(self.shadow);
+        renderer.set_instances(self.instances, self.instance_spacing as f32);
+        renderer.set_scale_factor(self.layout_scale(scale_factor));
+
+        for sequence in self.sequences.iter_mut() {
+            sequence.limit_dimensions(renderer.max_texture_dimension(), self.auto_downscale)?;
+        }
+
+        // Downscaled frames and displays scaled differently than expected
+        // need a matching window
+        if let Some(frame_size) = self
+            .sequences
+            .at(self.active_sequence)
+            .and_then(|sequence| sequence.current_frame_size())
+            && self.window_size(frame_size, scale_factor) != size
+        {
+            window.request_inner_size(self.window_size(frame_size, scale_factor));
+        }
+
+        // Frames are uploaded once per GPU
+        let frames = self
+            .windows
+            .values()
+            .find(|overlay| Arc::ptr_eq(overlay.renderer.gpu(), renderer.gpu()))
+            .map(|overlay| overlay.renderer.frames());
+        match frames {
+            Some(frames) => renderer.share_frames(frames)?,
+            None => pollster::block_on(preload_sequences(
+                &mut renderer,
+                &self.sequences,
+                self.use_compression,
+                &*self.progress,
+            )),
+        }
+        if let Err(e) = renderer.select_sequence(self.active_sequence) {
+            log::error!("Failed to select the active sequence: {}", e);
+        }
+        if let Err(e) =
+            pollster::block_on(renderer.set_current_texture_index(self.current_frame_index))
+        {
+            log::error!("Failed to show frame {}: {}", self.current_frame_index, e);
+        }
+
+        let id = window.id();
+        self.windows.insert(
+            id,
+            OverlayWindow {
+                window,
+                renderer,
+                scale_factor,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Create a renderer drawing into `window` with `gpu`, or on the
+    /// adapter asked for without one, falling back to the built-in shader
+    /// if asked to
+    async fn create_renderer(
+        &self,
+        window: &Arc<dyn WindowBackend>,
+        gpu: Option<Arc<Gpu>>,
+    ) -> Result<Renderer> {
+        match Renderer::new(
+            window.clone(),
+            self.fragment_shader.as_deref(),
+            gpu.clone(),
+            &self.adapter,
+        )
+        .await
+        {
+            Err(err) if self.fragment_shader.is_some() && self.shader_fallback => {
+                log::error!("{}", err);
+                log::warn!("Falling back to the built-in shader");
+                Renderer::new(window.clone(), None, gpu, &self.adapter).await
+            }
+            result => result,
+        }
+    }
+
+    /// Monitors to open a window on, the target one first. With
+    /// `all_monitors` that is every connected monitor, otherwise only the
+    /// target one, which may be `None` to let the platform pick.
+    fn overlay_monitors(&self, event_loop: &ActiveEventLoop) -> Vec<Option<MonitorHandle>> {
+        let target = self.target_monitor(event_loop);
+        if !self.all_monitors {
+            return vec![target];
+        }
+        // Only layer surfaces get to pick their output there
+        if is_wayland(event_loop) && self.layer_shell.is_none() {
+            log::warn!(
+                "Wayland doesn't let windows choose their monitor, --all-monitors needs --layer-shell"
+            );
+            return vec![target];
+        }
+
+        let Some(target) = target
+            .or_else(|| event_loop.primary_monitor())
+            .or_else(|| event_loop.available_monitors().next())
+        else {
+            return vec![None];
+        };
+        let others: Vec<Option<MonitorHandle>> = event_loop
+            .available_monitors()
+            .filter(|monitor| *monitor != target)
+            .map(Some)
+            .collect();
+        std::iter::once(Some(target)).chain(others).collect()
+    }
+
+    /// The monitor `--monitor` asks for, or the primary one if it isn't
+    /// connected. `None` leaves placing the overlay to the platform.
+    fn target_monitor(&self, event_loop: &ActiveEventLoop) -> Option<MonitorHandle> {
+        let query = self.monitor.as_deref()?;
+        let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
+        let names: Vec<String> = monitors.iter().map(monitor_name).collect();
+        match find_monitor(&names, query) {
+            Some(index) => {
+                log::info!(
+                    "Showing the overlay on monitor {} ({})",
+                    index,
+                    names[index]
+                );
+                Some(monitors[index].clone())
+            }
+            None => {
+                log::warn!("No monitor matches '{}', using the primary one", query);
+                event_loop
+                    .primary_monitor()
+                    .or_else(|| monitors.into_iter().next())
+            }
+        }
+    }
+
+    /// Handle the events of window backends outside winit's event loop
+    fn handle_backend_events(&mut self, event_loop: &ActiveEventLoop) {
+        let windows: Vec<(WindowId, Arc<dyn WindowBackend>)> = self
+            .windows
+            .iter()
+            .filter(|(_, overlay)| overlay.window.needs_polling())
+            .map(|(id, overlay)| (*id, overlay.window.clone()))
+            .collect();
+        if windows.is_empty() {
+            return;
+        }
+
+        for (id, window) in windows {
+            for event in window.poll_events() {
+                match event {
+                    BackendEvent::Resized(size) => self.resized(id, size),
+                    BackendEvent::RedrawRequested => self.redraw(id, event_loop),
+                    BackendEvent::CloseRequested => self.close_window(id, event_loop),
+                }
+                if self.is_shutting_down || !self.windows.contains_key(&id) {
+                    break;
+                }
+            }
+            if self.is_shutting_down {
+                return;
+            }
+        }
+
+        // Nothing wakes the event loop up for the backend's events
+        wake_within(event_loop, BACKEND_POLL_INTERVAL);
+    }
+
+    /// Connect to what tells whether the user is idle, or explain why the
+    /// animation won't pause for it
+    fn start_idle_detection(&mut self) {
+        let Some(timeout) = self.idle_timeout else {
+            return;
+        };
+        match crate::idle::connect(timeout) {
+            Ok(idle) => self.idle = Some(idle),
+            Err(e) => log::warn!("Won't pause while the user is idle: {}", e),
+        }
+    }
+
+    /// Pause while the user is idle, and continue from the same frame once
+    /// they are back
+    fn check_idle(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(idle) = &mut self.idle else {
+            return;
+        };
+        if self.idle_checked_at.elapsed() >= IDLE_POLL_INTERVAL {
+            self.idle_checked_at = Instant::now();
+            let result = idle.is_idle();
+            if let Err(e) = &result {
+                log::warn!("Stopped checking whether the user is idle: {}", e);
+                self.idle = None;
+            }
+            match (result, self.idle_since) {
+                (Ok(true), None) => {
+                    log::info!("User is idle, pausing");
+                    self.idle_since = Some(Instant::now());
+                }
+                (Ok(false) | Err(_), Some(idle_since)) => {
+                    log::info!("User is back, resuming");
+                    self.idle_since = None;
+                    // The frame that was showing gets the rest of its time
+                    self.last_frame_time += idle_since.elapsed();
+                    self.request_redraw();
+                }
+                _ => {}
+            }
+        }
+        if self.idle.is_some() {
+            wake_within(
+                event_loop,
+                IDLE_POLL_INTERVAL.saturating_sub(self.idle_checked_at.elapsed()),
+            );
+        }
+    }
+
+    /// Give up on wandering and following the pointer where the platform
+    /// doesn't allow them, and connect to what tells where the pointer is
+    /// for following it or dimming when it comes near
+    fn start_moving(&mut self, event_loop: &ActiveEventLoop) {
+        if self.wander.is_some() && is_wayland(event_loop) {
+            log::warn!("Wayland doesn't let windows move themselves, not wandering");
+            self.wander = None;
+        }
+        if let Some(options) = self.bounce {
+            if is_wayland(event_loop) {
+                log::warn!("Wayland doesn't let windows move themselves, not bouncing");
+                self.bounce = None;
+            } else {
+                // Off diagonally in a random direction
+                let speed = options.speed / std::f64::consts::SQRT_2;
+                let mut sign = || if self.rng.bool() { 1.0 } else { -1.0 };
+                self.bounce_velocity = (speed * sign(), speed * sign());
+                self.bounce_monitor = self.window().and_then(|window| window.current_monitor());
+                self.facing_left = self.bounce_velocity.0 < 0.0;
+                self.apply_flip();
+            }
+        }
+        // Winit only reports the pointer over interactive windows
+        let hover_near =
+            self.hover_opacity.is_some() && (self.hover_radius > 0 || !self.interactive);
+        if self.follow.is_none() && !hover_near {
+            return;
+        }
+        let pointer = if is_wayland(event_loop) {
+            Err(anyhow!("Wayland doesn't tell where the pointer is"))
+        } else {
+            GlobalPointer::new()
+        };
+        match pointer {
+            Ok(pointer) => self.pointer = Some(pointer),
+            Err(e) => {
+                if self.follow.take().is_some() {
+                    log::warn!("Not following the pointer: {}", e);
+                }
+                if hover_near && self.interactive {
+                    log::warn!("Dimming only while the pointer is over the overlay: {}", e);
+                } else if hover_near {
+                    log::warn!(
+                        "Not dimming near the pointer, run with --interactive: {}",
+                        e
+                    );
+                    self.hover_opacity = None;
+                }
+            }
+        }
+    }
+
+    /// Move a wandering window a step closer to its destination, picking a
+    /// new one or resting every `WanderOptions::interval`
+    fn wander(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(options), Some(window)) = (self.wander, self.window().cloned()) else {
+            return;
+        };
+        if self.hidden
+            || self.fullscreen
+            || self.suspended_at.is_some()
+            || self.idle_since.is_some()
+        {
+            return;
+        }
+        let (Some(monitor), Some(position)) = (
+            window
+                .current_monitor()
+                .or_else(|| event_loop.primary_monitor()),
+            window.outer_position(),
+        ) else {
+            return;
+        };
 
-        // Load the media sequence
-        if let Some(source) = self.media_source.take() {
-            self.media_sequence = Some(MediaSequence::load(source)?);
-        } else {
-            return Err(anyhow::format_err!("No media source specified"));
+        let now = Instant::now();
+        let (current, moved_at) = self
+            .movement
+            .unwrap_or(((position.x as f64, position.y as f64), now));
+        let interval = options.interval.max(Duration::from_millis(100));
+        let target = match self.wander_target {
+            Some((target, picked_at)) if now.duration_since(picked_at) < interval => target,
+            _ => {
+                let target = if self.rng.f64() < options.pause_chance {
+                    current
+                } else {
+                    wander_target(
+                        &mut self.rng,
+                        monitor.position(),
+                        monitor.size(),
+                        window.inner_size(),
+                        options.margin,
+                    )
+                };
+                self.wander_target = Some((target, now));
+                target
+            }
         };
 
-        if let Some(sequence) = &self.media_sequence {
-            self.frame_count = sequence.count();
-            log::info!("Loaded {} frames in sequence", self.frame_count);
+        let next = approach(
+            current,
+            target,
+            options.speed,
+            now.duration_since(moved_at).as_secs_f64(),
+        );
+        self.movement = Some((next, now));
+
+        // Face the way the window walks
+        if (target.0 - current.0).abs() >= 1.0 {
+            let left = target.0 < current.0;
+            if left != self.facing_left {
+                self.facing_left = left;
+                self.apply_flip();
+            }
+        }
+
+        let rounded = PhysicalPosition::new(next.0.round() as i32, next.1.round() as i32);
+        if rounded != position {
+            window.set_outer_position(rounded);
+        }
+        // At rest nothing happens until the next destination is picked
+        let picked_at = self.wander_target.map_or(now, |(_, picked_at)| picked_at);
+        wake_within(
+            event_loop,
+            if next == target {
+                interval.saturating_sub(now.duration_since(picked_at))
+            } else {
+                MOVEMENT_INTERVAL
+            },
+        );
+    }
+
+    /// Move a bouncing window on for the time since its last step,
+    /// reflecting off the edges of its monitor, or of the whole desktop
+    /// when roaming
+    fn bounce_around(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(options), Some(window)) = (self.bounce, self.window().cloned()) else {
+            return;
+        };
+        if self.hidden
+            || self.fullscreen
+            || self.suspended_at.is_some()
+            || self.idle_since.is_some()
+        {
+            return;
+        }
+        let Some(position) = window.outer_position() else {
+            return;
+        };
+        let area = if options.roam {
+            desktop_area(event_loop)
         } else {
-            log::error!("Failed to load media sequence");
-            return Ok(());
+            // The monitor it started on, unless that one went away
+            self.bounce_monitor
+                .clone()
+                .filter(|monitor| {
+                    event_loop
+                        .available_monitors()
+                        .any(|other| other == *monitor)
+                })
+                .or_else(|| window.current_monitor())
+                .or_else(|| event_loop.primary_monitor())
+                .map(|monitor| (monitor.position(), monitor.size()))
+        };
+        let Some((origin, size)) = area else {
+            return;
+        };
+
+        let now = Instant::now();
+        let (current, moved_at) = self
+            .movement
+            .unwrap_or(((position.x as f64, position.y as f64), now));
+        let elapsed = now.duration_since(moved_at).min(MAX_BOUNCE_STEP);
+        let (next, velocity) = bounce(
+            current,
+            self.bounce_velocity,
+            window.inner_size(),
+            origin,
+            size,
+            elapsed.as_secs_f64(),
+        );
+        self.movement = Some((next, now));
+        self.bounce_velocity = velocity;
+
+        // Face the way the window moves, turning on the left and right edges
+        let left = velocity.0 < 0.0;
+        if left != self.facing_left {
+            self.facing_left = left;
+            self.apply_flip();
         }
 
-        event_loop.run_app(self)?;
+        let rounded = PhysicalPosition::new(next.0.round() as i32, next.1.round() as i32);
+        if rounded != position {
+            window.set_outer_position(rounded);
+        }
+        wake_within(event_loop, MOVEMENT_INTERVAL);
+    }
 
-        Ok(())
+    /// Mirror frames as asked for, and the other way while moving left
+    fn apply_flip(&mut self) {
+        for overlay in self.windows.values_mut() {
+            overlay
+                .renderer
+                .set_flip(self.flip.0 != self.facing_left, self.flip.1);
+        }
+        self.update_input_shapes();
+        self.request_redraw();
     }
 
-    /// Cleanup resources before shutdown
-    fn cleanup(&mut self) {
-        if self.is_shutting_down {
+    /// Take clicks only on the pixels of the playing sequence's frames in
+    /// `input_masks`, or anywhere while fullscreen, rotated or showing the
+    /// context menu. Gives up on the masks where the platform can't.
+    fn update_input_shapes(&mut self) {
+        if self.input_masks.is_empty() {
             return;
         }
+        let mask = self
+            .input_masks
+            .get(self.active_sequence)
+            .and_then(Option::as_ref)
+            .filter(|_| {
+                self.menu.is_none() && !self.fullscreen && self.rotation.rem_euclid(360.0) == 0.0
+            });
+        let flip = (self.flip.0 != self.facing_left, self.flip.1);
+        let supported = self.windows.values().all(|overlay| {
+            let rectangles: Option<Vec<[i32; 4]>> = mask.map(|mask| {
+                overlay
+                    .renderer
+                    .frame_rects()
+                    .into_iter()
+                    .flat_map(|rect| mask.rectangles(rect, flip))
+                    .collect()
+            });
+            overlay.window.set_input_shape(rectangles.as_deref())
+        });
+        if !supported {
+            log::warn!(
+                "The overlay takes clicks anywhere, only X11 and layer surfaces can limit them to its pixels"
+            );
+            self.input_masks.clear();
+        }
+    }
 
-        log::info!("Starting application cleanup");
-        self.is_shutting_down = true;
+    /// Move the window a step closer to the pointer
+    fn follow_cursor(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(follow), Some(pointer), Some(window)) =
+            (self.follow, &self.pointer, self.window().cloned())
+        else {
+            return;
+        };
+        if self.hidden
+            || self.fullscreen
+            || self.suspended_at.is_some()
+            || self.idle_since.is_some()
+        {
+            return;
+        }
 
-        if let Some(mut renderer) = self.renderer.take() {
-            renderer.cleanup();
+        let cursor = match pointer.position() {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                log::warn!("Stopped following the pointer: {}", e);
+                self.follow = None;
+                return;
+            }
+        };
+        let mut target =
+            PhysicalPosition::new(cursor.x + follow.offset.0, cursor.y + follow.offset.1);
+        // Stay on the monitor the pointer is on, wherever it jumps
+        if let Some(monitor) = event_loop
+            .available_monitors()
+            .find(|monitor| contains(monitor.position(), monitor.size(), cursor))
+        {
+            target = clamp_to_area(
+                target,
+                window.inner_size(),
+                monitor.position(),
+                monitor.size(),
+            );
         }
+        let target = (target.x as f64, target.y as f64);
 
-        self.media_sequence = None;
-        self.window = None;
+        let now = Instant::now();
+        let (current, moved_at) = self.movement.unwrap_or_else(|| {
+            let start = window
+                .outer_position()
+                .map_or(target, |position| (position.x as f64, position.y as f64));
+            (start, now)
+        });
+        let next = ease_toward(
+            current,
+            target,
+            follow.smoothing,
+            now.duration_since(moved_at).as_secs_f64(),
+        );
+        self.movement = Some((next, now));
 
-        log::info!("Application cleanup complete");
+        let position = PhysicalPosition::new(next.0.round() as i32, next.1.round() as i32);
+        if window.outer_position() != Some(position) {
+            window.set_outer_position(position);
+        }
+        wake_within(event_loop, MOVEMENT_INTERVAL);
     }
 
-    fn update(&mut self) {
-        if self.is_shutting_down {
+    fn close(&mut self, event_loop: &ActiveEventLoop) {
+        log::info!("Window close requested");
+
+        self.cleanup();
+
+        event_loop.exit();
+    }
+
+    /// Fade out, then close. Closes right away when fading is off, nothing
+    /// would show the fade, or it is already fading out, so asking twice
+    /// doesn't wait.
+    fn quit(&mut self, event_loop: &ActiveEventLoop) {
+        if self.fade_duration.is_zero()
+            || matches!(self.fade, Some(Fade::Out(_)))
+            || self.windows.is_empty()
+            || self.hidden
+            || self.idle_since.is_some()
+            || self.suspended_at.is_some()
+        {
+            self.close(event_loop);
             return;
         }
 
+        // Fading out from however far a fade in got, the curve is
+        // symmetric so that level is reached as far from the end
+        let remaining = self.fade_duration.mul_f32(1.0 - self.fade_progress());
+        let now = Instant::now();
+        self.fade = Some(Fade::Out(now.checked_sub(remaining).unwrap_or(now)));
+        log::info!("Fading out, close again to quit right away");
+        self.request_redraw();
+    }
+
+    /// How far the current fade is, from 0 to 1. Done when not fading.
+    fn fade_progress(&self) -> f32 {
+        match self.fade {
+            None => 1.0,
+            Some(Fade::Pending) => 0.0,
+            Some(Fade::In(started) | Fade::Out(started)) if !self.fade_duration.is_zero() => {
+                (started.elapsed().as_secs_f32() / self.fade_duration.as_secs_f32()).min(1.0)
+            }
+            Some(_) => 1.0,
+        }
+    }
+
+    /// How much of the overlay's opacity the current fade lets through
+    fn fade_level(&self) -> f32 {
+        let level = ease_in_out(self.fade_progress());
+        match self.fade {
+            Some(Fade::Out(_)) => 1.0 - level,
+            _ => level,
+        }
+    }
+
+    /// How much of the overlay's opacity dimming for the pointer lets
+    /// through
+    fn hover_level(&self) -> f32 {
+        let dimmed = ease_in_out(self.hover_dim.0);
+        let opacity = self.hover_opacity.unwrap_or(1.0);
+        1.0 - dimmed * (1.0 - opacity)
+    }
+
+    /// Opacity windows are drawn with, the user's own faded in or out and
+    /// dimmed for the pointer
+    fn current_opacity(&self) -> f32 {
+        self.opacity * self.fade_level() * self.hover_level()
+    }
+
+    /// Whether the pointer is within `hover_radius` of a window, or over
+    /// one where the platform can't tell where else it is. Never while
+    /// the context menu is open, so it stays readable.
+    fn pointer_near(&self) -> bool {
+        if self.menu.is_some() || self.fullscreen || self.hidden {
+            return false;
+        }
+        let Some(pointer) = &self.pointer else {
+            return self.hovered.is_some();
+        };
+        let cursor = match pointer.position() {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                log::debug!("Can't tell where the pointer is: {}", e);
+                return self.hovered.is_some();
+            }
+        };
+        self.windows.values().any(|overlay| {
+            overlay.window.outer_position().is_some_and(|position| {
+                distance_to_area(position, overlay.window.inner_size(), cursor)
+                    <= self.hover_radius as f64
+            })
+        })
+    }
+
+    /// Dim the overlay a step further while the pointer is near, or bring
+    /// it a step back once it left
+    fn dim_on_hover(&mut self, event_loop: &ActiveEventLoop) {
+        if self.hover_opacity.is_none() {
+            return;
+        }
         let now = Instant::now();
-        if now.duration_since(self.last_frame_time) >= self.frame_interval
-            && !self.frame_update_in_progress
+        let (dimmed, stepped_at) = self.hover_dim;
+        let target = if self.pointer_near() { 1.0 } else { 0.0 };
+        // Not jumping ahead after the event loop slept while nothing moved
+        let step = now
+            .duration_since(stepped_at)
+            .min(HOVER_POLL_INTERVAL)
+            .as_secs_f32()
+            / HOVER_FADE_DURATION.as_secs_f32();
+        let next = if target > dimmed {
+            (dimmed + step).min(target)
+        } else {
+            (dimmed - step).max(target)
+        };
+        self.hover_dim = (next, now);
+
+        if next != dimmed {
+            let opacity = self.current_opacity();
+            for overlay in self.windows.values_mut() {
+                overlay.renderer.set_opacity(opacity);
+                overlay.window.request_redraw();
+            }
+        }
+        if next != target {
+            wake_within(event_loop, MOVEMENT_INTERVAL);
+        } else if self.pointer.is_some() && !self.hidden {
+            wake_within(event_loop, HOVER_POLL_INTERVAL);
+        }
+    }
+
+    /// Apply the current fade to every window, ending a finished fade in
+    fn apply_fade(&mut self) {
+        let Some(fade) = self.fade else {
+            return;
+        };
+        let opacity = self.current_opacity();
+        for overlay in self.windows.values_mut() {
+            overlay.renderer.set_opacity(opacity);
+        }
+        if matches!(fade, Fade::In(_)) && self.fade_progress() >= 1.0 {
+            self.fade = None;
+        }
+    }
+
+    /// Close one window, and the application along with the last one
+    fn close_window(&mut self, id: WindowId, event_loop: &ActiveEventLoop) {
+        if !self.windows.contains_key(&id) {
+            return;
+        }
+        if self.windows.len() == 1 {
+            self.quit(event_loop);
+            return;
+        }
+
+        if self.hovered == Some(id) {
+            self.release_cursor();
+        }
+        if let Some(mut overlay) = self.windows.remove(&id) {
+            overlay.renderer.cleanup();
+        }
+        log::info!("Window closed, {} left", self.windows.len());
+        if self.drag_anchor.is_some_and(|(dragged, _)| dragged == id) {
+            self.drag_anchor = None;
+        }
+        if self.touch_anchor.is_some_and(|(dragged, _)| dragged == id) {
+            self.touch_anchor = None;
+            self.touches.clear();
+        }
+        if self
+            .menu
+            .as_ref()
+            .is_some_and(|(menu_id, _)| *menu_id == id)
         {
-            self.last_frame_time = now;
+            self.menu = None;
+        }
+        // Another window takes over moving around, from where it is
+        if self.primary == Some(id) {
+            self.primary = self.windows.keys().next().copied();
+            self.movement = None;
+            self.pinned_position = None;
+            self.last_position = self.window().and_then(|window| window.outer_position());
+        }
+    }
 
-            if self.frame_count > 0 {
-                let new_frame_index = (self.current_frame_index + 1) % self.frame_count;
-
-                if let Some(renderer) = &mut self.renderer {
-                    self.frame_update_in_progress = true;
-
-                    // For compressed sequences, we need to handle async frame reconstruction
-                    if self.use_compression {
-                        // Create a future to update the frame
-                        let renderer_ptr = renderer as *mut Renderer;
-                        let frame_index = new_frame_index;
-
-                        // This could probably be done better
-                        // For now, we'll use pollster to block on the async operation
-                        match pollster::block_on(async {
-                            let renderer = unsafe { &mut *renderer_ptr };
-                            renderer.set_current_texture_index(frame_index).await
-                        }) {
-                            Ok(_) => {
-                                self.current_frame_index = new_frame_index;
-                            }
-                            Err(e) => {
-                                log::error!("Failed to update compressed frame: {}", e);
-                            }
-                        }
-                    } else {
-                        // For uncompressed sequences, this is synchronous
-                        match pollster::block_on(
-                            renderer.set_current_texture_index(new_frame_index),
-                        ) {
-                            Ok(_) => {
-                                self.current_frame_index = new_frame_index;
-                            }
-                            Err(e) => {
-                                log::error!("Failed to update frame: {}", e);
-                            }
-                        }
-                    }
+    fn resized(&mut self, id: WindowId, size: PhysicalSize<u32>) {
+        log::info!("Window resized to {}x{}", size.width, size.height);
+        let Some(overlay) = self.windows.get_mut(&id) else {
+            return;
+        };
+        overlay.renderer.resize(size.width, size.height);
+        overlay.window.request_redraw();
+        // The menu was placed to fit the old size
+        if self
+            .menu
+            .as_ref()
+            .is_some_and(|(menu_id, _)| *menu_id == id)
+        {
+            self.close_menu();
+        }
+        self.update_input_shapes();
+        self.keep_anchored();
+    }
 
-                    self.frame_update_in_progress = false;
-                }
+    /// Move anchored windows back into their corner, after their size or
+    /// their monitor's resolution changed
+    fn keep_anchored(&mut self) {
+        self.anchor_checked_at = Instant::now();
+        let Some(anchor) = self.anchor.filter(|_| !self.fullscreen) else {
+            return;
+        };
+
+        for overlay in self.windows.values() {
+            let window = &overlay.window;
+            let Some(monitor) = window.current_monitor() else {
+                continue;
+            };
+
+            let position = anchored_position(
+                anchor,
+                monitor.position(),
+                monitor.size(),
+                window.inner_size(),
+            );
+            if window
+                .outer_position()
+                .is_some_and(|current| current != position)
+            {
+                log::debug!(
+                    "Moving the anchored overlay to {},{}",
+                    position.x,
+                    position.y
+                );
+                window.set_outer_position(position);
+            }
+        }
+    }
+
+    /// Advance the animation and draw it, asking for the next redraw while
+    /// it plays
+    fn redraw(&mut self, id: WindowId, event_loop: &ActiveEventLoop) {
+        if self.is_shutting_down
+            || self.suspended_at.is_some()
+            || self.idle_since.is_some()
+            || self.hidden
+        {
+            return;
+        }
+
+        // Frames only advance once their time is up, however many windows
+        // are redrawn
+        self.update();
+
+        // Transient surface problems are handled by the renderer, what
+        // remains can't be recovered from
+        let retry = match self.render(id) {
+            Ok(retry) => retry,
+            Err(err) => {
+                log::error!("Render error: {}", err);
+                self.close_window(id, event_loop);
+                return;
             }
+        };
+
+        // Only animations and fades need to keep presenting new frames
+        if (retry || self.is_animating() || self.fade.is_some())
+            && let Some(overlay) = self.windows.get(&id)
+        {
+            overlay.window.request_redraw();
         }
     }
 
-    fn render(&mut self) -> Result<()> {
+    /// Draw the current frame into window `id`, returning whether it has to
+    /// be retried
+    fn render(&mut self, id: WindowId) -> Result<bool> {
         if self.is_shutting_down {
-            return Ok(());
+            return Ok(false);
         }
 
-        if let Some(renderer) = &mut self.renderer {
-            renderer.render()?;
+        let crossfade = self.crossfade.then(|| self.crossfade_target());
+        let frames = (self.instances > 1).then(|| self.instance_frames());
+        let Some(overlay) = self.windows.get_mut(&id) else {
+            return Ok(false);
+        };
+
+        if let Some((next, progress)) = crossfade {
+            overlay.renderer.set_crossfade_target(next, progress);
+        }
+        if let Some(frames) = frames {
+            overlay.renderer.set_instance_frames(&frames);
         }
 
-        Ok(())
+        Ok(!overlay.renderer.render()?)
     }
 }
 
-impl ApplicationHandler for OverlayApplication {
+impl ApplicationHandler<OverlayCommand> for OverlayApplication {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let (width, height) = if let Some(sequence) = &self.media_sequence {
-            if let Some(image) = sequence.current_image() {
-                let dimensions = image.dimensions();
-                log::info!(
-                    "Using image dimensions for window: {}x{}",
-                    dimensions.0,
-                    dimensions.1
-                );
-                (dimensions.0, dimensions.1)
-            } else {
-                log::info!("No image found, using default dimensions");
-                (800, 600)
+        // Coming back from a suspend only needs new surfaces, the frames
+        // are still decoded and on the GPU
+        if !self.windows.is_empty() {
+            for overlay in self.windows.values_mut() {
+                if let Err(e) = overlay.renderer.resume(overlay.window.clone()) {
+                    log::error!("Failed to recreate the surface: {}", e);
+                    self.cleanup();
+                    event_loop.exit();
+                    return;
+                }
             }
-        } else {
-            log::info!("No media sequence found, using default dimensions");
-            (800, 600)
-        };
 
-        let window_attributes = WindowAttributes::default()
-            .with_title(if self.use_compression {
-                "PNG Overlay (Delta Compressed)"
-            } else {
-                "PNG Overlay"
-            })
-            .with_transparent(true)
-            .with_decorations(false)
-            .with_resizable(false)
-            .with_inner_size(PhysicalSize::new(width, height));
-
-        match event_loop.create_window(window_attributes) {
-            Ok(window) => {
-                let window_arc = Arc::new(window);
-                self.window = Some(window_arc.clone());
-
-                pollster::block_on(async {
-                    match Renderer::new(window_arc).await {
-                        Ok(mut renderer) => {
-                            if let Some(sequence) = &self.media_sequence {
-                                let all_images = sequence.get_all_images();
-
-                                if self.use_compression {
-                                    log::info!(
-                                        "Loading {} images with delta compression",
-                                        all_images.len()
-                                    );
-                                    match renderer.preload_images_compressed(&all_images).await {
-                                        Ok(_) => {
-                                            log::info!("Successfully loaded compressed sequence");
-                                        }
-                                        Err(e) => {
-                                            log::error!(
-                                                "Failed to load compressed sequence: {}, falling back to uncompressed",
-                                                e
-                                            );
-                                            renderer.preload_images(&all_images);
-                                        }
-                                    }
-                                } else {
-                                    log::info!(
-                                        "Loading {} images without compression",
-                                        all_images.len()
-                                    );
-                                    renderer.preload_images(&all_images);
-                                }
-                            }
-
-                            self.renderer = Some(renderer);
-                        }
-                        Err(err) => {
-                            log::error!("Failed to create renderer: {}", err);
-                            event_loop.exit();
-                        }
-                    }
-                });
+            // Continue the frame that was showing for the rest of its time
+            if let Some(suspended_at) = self.suspended_at.take() {
+                self.last_frame_time += suspended_at.elapsed();
             }
-            Err(err) => {
-                log::error!("Failed to create window: {}", err);
-                event_loop.exit();
+            self.request_redraw();
+            return;
+        }
+
+        if !self.fade_duration.is_zero() {
+            self.fade = Some(Fade::Pending);
+        }
+        for (index, monitor) in self.overlay_monitors(event_loop).into_iter().enumerate() {
+            match self.open_window(event_loop, monitor) {
+                Ok(id) => {
+                    if index == 0 {
+                        self.primary = Some(id);
+                        self.start_moving(event_loop);
+                        self.start_idle_detection();
+                    }
+                    self.show_first_frame(id);
+                }
+                // The other monitors can do without their copy
+                Err(err) if index > 0 => log::error!("{}", err),
+                Err(err) => {
+                    log::error!("{}", err);
+                    event_loop.exit();
+                    return;
+                }
             }
         }
+
+        // Keys only reach windows without the focus from the keyboard
+        if !self.take_focus {
+            event_loop.listen_device_events(DeviceEvents::Always);
+        }
+
+        // Every window draws the uploaded frames by now
+        self.release_uploaded_pixels();
+        if self.fade == Some(Fade::Pending) {
+            self.fade = Some(Fade::In(Instant::now()));
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, command: OverlayCommand) {
+        match command {
+            OverlayCommand::ToggleVisible => self.toggle_visible(),
+            OverlayCommand::TogglePause => self.toggle(),
+            OverlayCommand::Stop => self.stop(),
+            OverlayCommand::OpenConfig => self.open_config(),
+            OverlayCommand::Quit => self.quit(event_loop),
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        log::info!("Suspended");
+        self.suspended_at = Some(Instant::now());
+        for overlay in self.windows.values_mut() {
+            overlay.renderer.suspend();
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
+        window_id: WindowId,
         event: winit::event::WindowEvent,
     ) {
         match event {
-            winit::event::WindowEvent::CloseRequested => {
-                log::info!("Window close requested");
-
-                self.cleanup();
-
-                event_loop.exit();
-            }
-            winit::event::WindowEvent::Resized(size) => {
-                log::info!("Window resized to {}x{}", size.width, size.height);
-                if let Some(renderer) = &mut self.renderer {
-                    renderer.resize(size.width, size.height);
+            winit::event::WindowEvent::CloseRequested => self.close_window(window_id, event_loop),
+            winit::event::WindowEvent::Resized(size) => self.resized(window_id, size),
+            winit::event::WindowEvent::Moved(position) => self.moved(window_id, position),
+            winit::event::WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } => {
+                if let Some(size) = self.scale_factor_changed(window_id, scale_factor) {
+                    let _ = inner_size_writer.request_inner_size(size);
+                    // Not every platform follows up with a Resized event
+                    self.resized(window_id, size);
                 }
             }
-            winit::event::WindowEvent::RedrawRequested => {
-                if !self.is_shutting_down {
-                    self.update();
-
-                    if let Err(err) = self.render() {
-                        log::error!("Render error: {}", err);
-                    }
-
-                    if let Some(window) = &self.window {
-                        window.request_redraw();
-                    }
+            winit::event::WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() => {
+                self.key_pressed(&event.logical_key, event.repeat, event_loop);
+            }
+            // Any click quits while fullscreen
+            winit::event::WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            } if self.fullscreen => self.quit(event_loop),
+            // Clicks anywhere close the context menu, clicks on its entries
+            // pick them
+            winit::event::WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.menu.is_some() => self.click_menu(window_id, event_loop),
+            winit::event::WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } if self.interactive => self.open_menu(window_id),
+            // Scrolling grows and shrinks the overlay, a middle click
+            // restores its size
+            winit::event::WindowEvent::MouseWheel { delta, .. } if self.interactive => {
+                self.scroll(delta);
+            }
+            winit::event::WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Middle,
+                ..
+            } if self.interactive => self.change_window_scale(self.window_scale.recip()),
+            // Fingers drag and pinch it, unless touches pass through
+            winit::event::WindowEvent::Touch(touch) if self.interactive || self.fullscreen => {
+                self.touched(window_id, touch, event_loop);
+            }
+            // Clicks outside the overlay take the focus away
+            winit::event::WindowEvent::Focused(focused) => {
+                self.focused = focused;
+                if !focused {
+                    self.close_menu();
                 }
             }
+            winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                self.update_cursor();
+            }
+            winit::event::WindowEvent::CursorEntered { .. } => {
+                self.hovered = Some(window_id);
+                self.update_cursor();
+            }
+            winit::event::WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_moved(window_id, position)
+            }
+            winit::event::WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.release_cursor();
+                self.hover_menu(window_id);
+            }
+            winit::event::WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => self.left_button(window_id, state, event_loop),
+            winit::event::WindowEvent::RedrawRequested => self.redraw(window_id, event_loop),
             _ => {}
         }
     }
 
+    fn device_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let DeviceEvent::Key(event) = event {
+            self.raw_key(event, event_loop);
+        }
+    }
+
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         if self.is_shutting_down {
             return;
         }
 
+        self.schedule_redraw(event_loop);
+        self.handle_backend_events(event_loop);
+        if self.anchor_checked_at.elapsed() >= ANCHOR_CHECK_INTERVAL {
+            self.keep_anchored();
+        }
+        self.follow_cursor(event_loop);
+        self.wander(event_loop);
+        self.bounce_around(event_loop);
+        self.dim_on_hover(event_loop);
+        self.check_idle(event_loop);
+        self.check_duration(event_loop);
+    }
+}
+
+impl OverlayApplication {
+    /// Request the next frame's redraw once it is due and wake up for it
+    fn schedule_redraw(&mut self, event_loop: &ActiveEventLoop) {
+        if matches!(self.fade, Some(Fade::Out(_))) && self.fade_progress() >= 1.0 {
+            self.close(event_loop);
+            return;
+        }
+
+        // Nothing can be shown until the surface is back, the window is
+        // shown again or the user is back
+        if self.suspended_at.is_some() || self.idle_since.is_some() || self.hidden {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+
+        self.reload_changed_frames();
+        self.reload_changed_shader();
+
+        if self.playback_finished && self.loop_mode == LoopMode::Once {
+            self.cleanup();
+            event_loop.exit();
+            return;
+        }
+        // Fading out first, as when quit by hand
+        if self.playback_finished && self.loops_finished() {
+            if !matches!(self.fade, Some(Fade::Out(_))) {
+                self.quit(event_loop);
+            }
+            return;
+        }
+
         let now = Instant::now();
-        if now.duration_since(self.last_frame_time) >= self.frame_interval {
-            if let Some(window) = &self.window {
-                window.request_redraw();
-                event_loop.set_control_flow(ControlFlow::WaitUntil(now + self.frame_interval));
+
+        // A still image is only redrawn when something changes, but the
+        // watcher still needs a chance to report changes
+        if !self.is_animating() {
+            event_loop.set_control_flow(
+                if self.watcher.is_some() || self.shader_watcher.is_some() {
+                    ControlFlow::WaitUntil(now + WATCH_POLL_INTERVAL)
+                } else {
+                    ControlFlow::Wait
+                },
+            );
+            return;
+        }
+
+        let frame_interval = self.current_frame_interval();
+        let due = self.last_frame_time + frame_interval;
+        if now >= due && !self.windows.is_empty() {
+            self.request_redraw();
+            event_loop.set_control_flow(ControlFlow::WaitUntil(now + frame_interval));
+        } else {
+            // Waking when the frame is due rather than an interval after
+            // the last check
+            event_loop.set_control_flow(ControlFlow::WaitUntil(due));
+        }
+    }
+}
+
+/// Smoothstep from 0 to 1 over `progress` from 0 to 1, starting and ending
+/// slowly. `1 - ease_in_out(t)` equals `ease_in_out(1 - t)`.
+fn ease_in_out(progress: f32) -> f32 {
+    let t = progress.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Top left corner and size of the rectangle spanning every monitor
+fn desktop_area(
+    event_loop: &ActiveEventLoop,
+) -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+    event_loop
+        .available_monitors()
+        .map(|monitor| {
+            let (origin, size) = (monitor.position(), monitor.size());
+            (
+                origin,
+                PhysicalPosition::new(origin.x + size.width as i32, origin.y + size.height as i32),
+            )
+        })
+        .reduce(|(low, high), (origin, end)| {
+            (
+                PhysicalPosition::new(low.x.min(origin.x), low.y.min(origin.y)),
+                PhysicalPosition::new(high.x.max(end.x), high.y.max(end.y)),
+            )
+        })
+        .map(|(low, high)| {
+            (
+                low,
+                PhysicalSize::new((high.x - low.x) as u32, (high.y - low.y) as u32),
+            )
+        })
+}
+
+/// Make sure the event loop wakes up within `interval`, without delaying
+/// an earlier wake up
+fn wake_within(event_loop: &ActiveEventLoop, interval: Duration) {
+    let deadline = Instant::now() + interval;
+    match event_loop.control_flow() {
+        ControlFlow::WaitUntil(wake) if wake <= deadline => {}
+        ControlFlow::Poll => {}
+        _ => event_loop.set_control_flow(ControlFlow::WaitUntil(deadline)),
+    }
+}
+
+/// Change the frames on the GPU with `upload` through one renderer per
+/// GPU, the renderers of the other windows on it share the result
+fn upload_once(
+    windows: &mut HashMap<WindowId, OverlayWindow>,
+    mut upload: impl FnMut(&mut Renderer),
+) {
+    let mut uploaded: Vec<SharedFrames> = Vec::new();
+    for overlay in windows.values_mut() {
+        let renderer = &mut overlay.renderer;
+        match uploaded.iter().find(|frames| frames.is_on(renderer.gpu())) {
+            Some(frames) => {
+                if let Err(e) = renderer.share_frames(frames.clone()) {
+                    log::error!("{}", e);
+                }
+            }
+            None => {
+                upload(renderer);
+                uploaded.push(renderer.frames());
+            }
+        }
+    }
+}
+
+/// Upload every decoded frame of all sequences, replacing whatever the
+/// renderer held before
+async fn preload_sequences(
+    renderer: &mut Renderer,
+    sequences: &SequenceSet,
+    use_compression: bool,
+    progress: &ProgressFn,
+) {
+    if use_compression && let Some(sequence) = sequences.at(0) {
+        let all_images = sequence.get_all_images();
+        log::info!("Loading {} images with delta compression", all_images.len());
+        match renderer.preload_images_compressed(&all_images).await {
+            Ok(_) => {
+                log::info!("Successfully loaded compressed sequence");
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to load compressed sequence: {}, falling back to uncompressed",
+                    e
+                );
+                renderer.preload_sequences(&[&all_images], progress);
             }
         }
+    } else {
+        let all_images: Vec<_> = sequences
+            .iter()
+            .map(|sequence| sequence.get_all_images())
+            .collect();
+        log::info!(
+            "Loading {} images without compression",
+            all_images.iter().map(|images| images.len()).sum::<usize>()
+        );
+        let all_images: Vec<&[&RgbaImage]> = all_images.iter().map(Vec::as_slice).collect();
+        renderer.preload_sequences(&all_images, progress);
     }
 }
 
+/// Resident set size of this process in bytes, where /proc tells it
+#[cfg(debug_assertions)]
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Where a capture taken now is saved, next to the executable
+fn capture_path() -> Result<PathBuf> {
+    let executable = std::env::current_exe()?;
+    let directory = executable
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent directory", executable.display()))?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis();
+    Ok(directory.join(format!("konata_capture_{}.png", timestamp)))
+}
+
 impl Drop for OverlayApplication {
     fn drop(&mut self) {
         log::debug!("Dropping OverlayApplication");
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use winit::dpi::PhysicalPosition;
+
+/// Asks the platform where the mouse pointer is on the desktop, wherever
+/// it is. Winit only reports it over the window, and not at all while
+/// clicks pass through.
+pub struct GlobalPointer {
+    #[cfg(target_os = "linux")]
+    x11: (x11rb::rust_connection::RustConnection, u32),
+}
+
+impl GlobalPointer {
+    /// Connect to the X server. Wayland doesn't tell clients where the
+    /// pointer is, so this fails there.
+    #[cfg(target_os = "linux")]
+    pub fn new() -> Result<Self> {
+        use x11rb::connection::Connection;
+
+        let (connection, screen) = x11rb::connect(None)?;
+        let root = connection.setup().roots[screen].root;
+        Ok(Self {
+            x11: (connection, root),
+        })
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn new() -> Result<Self> {
+        Ok(Self {})
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub fn new() -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "The pointer position can't be queried on this platform"
+        ))
+    }
+
+    /// Where the pointer is in physical desktop pixels
+    #[cfg(target_os = "linux")]
+    pub fn position(&self) -> Result<PhysicalPosition<i32>> {
+        use x11rb::protocol::xproto::ConnectionExt;
+
+        let (connection, root) = &self.x11;
+        let reply = connection.query_pointer(*root)?.reply()?;
+        Ok(PhysicalPosition::new(
+            reply.root_x as i32,
+            reply.root_y as i32,
+        ))
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn position(&self) -> Result<PhysicalPosition<i32>> {
+        use windows_sys::Win32::Foundation::POINT;
+        use windows_sys::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+        let mut point = POINT { x: 0, y: 0 };
+        // Writes to `point` only
+        if unsafe { GetCursorPos(&mut point) } == 0 {
+            return Err(anyhow::anyhow!("GetCursorPos failed"));
+        }
+        Ok(PhysicalPosition::new(point.x, point.y))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub fn position(&self) -> Result<PhysicalPosition<i32>> {
+        Err(anyhow::anyhow!(
+            "The pointer position can't be queried on this platform"
+        ))
+    }
+}
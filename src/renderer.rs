@@ -1,11 +1,22 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use bytemuck::{Pod, Zeroable};
 use image::RgbaImage;
-use std::sync::Arc;
-use wgpu::util::DeviceExt;
-use winit::window::Window;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
+use crate::block_compression::{encode_bc1, encode_bc7, is_block_aligned, is_opaque};
+#[cfg(target_os = "windows")]
+use crate::composition::Composition;
 use crate::delta_compression::{CompressedSequence, DeltaCompressor};
+use crate::media_loader::ProgressFn;
+use crate::menu::{MenuPainter, MenuQuad};
+use crate::mipmaps::{MIPMAP_FORMAT, MipmapGenerator, mip_level_count};
+use crate::shadow::{SILHOUETTE_FORMAT, Shadow, ShadowPass};
+use crate::supersampling::{Supersampler, scaled_size};
+use crate::window_backend::WindowBackend;
 
 const VERTEX_SHADER: &str = r#"
 @vertex
@@ -29,668 +40,4998 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<
 }
 "#;
 
-const FRAGMENT_SHADER: &str = r#"
-@group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
-@group(0) @binding(1)
-var s_diffuse: sampler;
-@group(0) @binding(2)
-var<uniform> dimensions: vec4<f32>; // window_width, window_height, image_width, image_height
+/// Vertex shader drawing one triangle over the whole target, for the passes
+/// that filter or combine whole textures. Their fragment shaders are
+/// appended to it and take its `VertexOutput`.
+pub const FULLSCREEN_VERTEX_SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
 
-@fragment
-fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
-    // Calculate texture coordinates based on actual dimensions
-    let tex_coords = vec2<f32>(
-        pos.x / dimensions.x,
-        pos.y / dimensions.y
-    );
-    
-    // Sample the texture
-    return textureSample(t_diffuse, s_diffuse, tex_coords);
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // One triangle covering the whole target
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
 }
 "#;
 
-#[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
-struct Dimensions {
-    window_width: f32,
-    window_height: f32,
-    image_width: f32,
-    image_height: f32,
+/// Frame texture binding for textures with a single layer. GL picks a
+/// texture's type from its layer count, so these can't be bound as arrays.
+const SINGLE_LAYER_BINDING: &str = r#"
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+
+fn sample_frame(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, uv);
 }
 
-pub enum SequenceType {
-    Uncompressed {
-        texture_bind_groups: Vec<wgpu::BindGroup>,
-    },
-    Compressed {
-        compressed_sequence: CompressedSequence,
-        current_frame_texture: wgpu::Texture,
-        current_frame_bind_group: wgpu::BindGroup,
-        reconstructed_frame: Option<RgbaImage>,
-    },
+fn load_frame(texel: vec2<i32>) -> vec4<f32> {
+    return textureLoad(t_diffuse, texel, 0);
 }
+"#;
 
-pub struct Renderer {
-    device: Arc<wgpu::Device>,
-    queue: Arc<wgpu::Queue>,
-    surface: Option<wgpu::Surface<'static>>,
-    pipeline: wgpu::RenderPipeline,
-    bind_group_layout: wgpu::BindGroupLayout,
-    sequence_type: Option<SequenceType>,
-    current_texture_index: usize,
-    config: wgpu::SurfaceConfiguration,
-    dimensions_buffer: wgpu::Buffer,
-    current_dimensions: Dimensions,
+/// Frame texture binding for texture arrays, reading the current layer
+const ARRAY_BINDING: &str = r#"
+@group(0) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
 
-    delta_compressor: Option<DeltaCompressor>,
-    sampler: wgpu::Sampler,
-}
+fn sample_frame(uv: vec2<f32>) -> vec4<f32> {
+    let current = textureSample(t_diffuse, s_diffuse, uv, dimensions.layer);
+    if dimensions.blend == 0.0 {
+        return current;
+    }
 
-impl Renderer {
-    pub async fn new(window: Arc<Window>) -> Result<Self> {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
+    // Mixing premultiplied keeps transparent texels' color out of the fade
+    let next = textureSample(t_diffuse, s_diffuse, uv, dimensions.next_layer);
+    let mixed = mix(
+        vec4<f32>(current.rgb * current.a, current.a),
+        vec4<f32>(next.rgb * next.a, next.a),
+        dimensions.blend
+    );
+    return vec4<f32>(mixed.rgb / max(mixed.a, 1e-5), mixed.a);
+}
 
-        let surface = instance.create_surface(window.clone())?;
+fn load_frame(texel: vec2<i32>) -> vec4<f32> {
+    return textureLoad(t_diffuse, texel, dimensions.layer, 0);
+}
+"#;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .map_err(|_| anyhow::anyhow!("Failed to find an appropriate adapter"))?;
+/// Declarations shared by every fragment shader, prepended to them along
+/// with one of the texture bindings when the pipelines are built
+const FRAGMENT_COMMON: &str = r#"
+struct Dimensions {
+    window_size: vec2<f32>,
+    image_size: vec2<f32>,
+    // Array layer holding the current frame
+    layer: u32,
+    // Factor applied to the whole overlay's alpha
+    opacity: f32,
+    // Whether the frame is mirrored horizontally and vertically
+    flip: vec2<u32>,
+    // Offset and size of the current frame within the texture
+    uv_rect: vec4<f32>,
+    // Offset and size in window pixels the frame is drawn at, before it is
+    // rotated about the rectangle's center
+    draw_rect: vec4<f32>,
+    // Rows of the matrix rotating window offsets back into the frame
+    rotation: vec4<f32>,
+    // Linear color multiplied into every texel, white leaves frames as they are
+    tint: vec4<f32>,
+    // Linear key color made transparent, alpha is 1 when keying is enabled
+    chroma_key: vec4<f32>,
+    // Distance from the key below which texels are transparent, and the
+    // width of the falloff beyond it
+    chroma_range: vec2<f32>,
+    // Non-zero when the surface isn't sRGB, so shaders encode their output
+    encode_srgb: u32,
+    // How the surface composites alpha, one of the ALPHA_* constants
+    alpha_output: u32,
+    // Linear straight-alpha color behind frames, on opaque surfaces blended
+    // over black
+    background: vec4<f32>,
+    // Array layer of the frame crossfaded into, and how far, 0 shows only
+    // the current frame
+    next_layer: u32,
+    blend: f32,
+    // Outline thickness in frame texels
+    outline_width: f32,
+    // Which color effects apply, a combination of the EFFECT_* flags
+    effects: u32,
+    // Linear outline color, alpha is 1 when the outline is drawn
+    outline: vec4<f32>,
+    // Hue rotation in degrees and saturation factor of the color effects
+    hue_shift: f32,
+    saturation: f32,
+}
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: Some("Overlay Device"),
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                memory_hints: wgpu::MemoryHints::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await?;
+const ALPHA_PREMULTIPLIED: u32 = 0u;
+const ALPHA_POSTMULTIPLIED: u32 = 1u;
+const ALPHA_OPAQUE: u32 = 2u;
 
-        let device_arc = Arc::new(device);
-        let queue_arc = Arc::new(queue);
+const EFFECT_HUE_SHIFT: u32 = 1u;
+const EFFECT_SATURATION: u32 = 2u;
+const EFFECT_GRAYSCALE: u32 = 4u;
+const EFFECT_INVERT: u32 = 8u;
 
-        let size = window.inner_size();
-        let surface_caps = surface.get_capabilities(&adapter);
+@group(1) @binding(0)
+var s_diffuse: sampler;
+@group(1) @binding(1)
+var<uniform> dimensions: Dimensions;
 
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
+// Position of a window pixel within the drawn frame, 0..1 inside it
+fn draw_coords(pos: vec2<f32>) -> vec2<f32> {
+    let offset = pos - dimensions.draw_rect.xy - dimensions.draw_rect.zw * 0.5;
+    let unrotated = vec2<f32>(dot(dimensions.rotation.xy, offset), dot(dimensions.rotation.zw, offset));
+    let coords = unrotated / dimensions.draw_rect.zw + 0.5;
+    return select(coords, 1.0 - coords, dimensions.flip != vec2<u32>(0u));
+}
 
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-            desired_maximum_frame_latency: 2,
-            alpha_mode: wgpu::CompositeAlphaMode::PreMultiplied,
-            view_formats: vec![],
-        };
+fn inside_frame(coords: vec2<f32>) -> bool {
+    return all(coords >= vec2<f32>(0.0)) && all(coords < vec2<f32>(1.0));
+}
 
-        surface.configure(&device_arc, &config);
+// Alpha factor hiding texels close to the chroma key color
+fn chroma_alpha(color: vec3<f32>) -> f32 {
+    if dimensions.chroma_key.a == 0.0 {
+        return 1.0;
+    }
+    let distance = length(color - dimensions.chroma_key.rgb);
+    let falloff = max(dimensions.chroma_range.y, 1e-5);
+    return clamp((distance - dimensions.chroma_range.x) / falloff, 0.0, 1.0);
+}
 
-        // Initialize the dimensions
-        let current_dimensions = Dimensions {
-            window_width: size.width as f32,
-            window_height: size.height as f32,
-            image_width: size.width as f32,
-            image_height: size.height as f32,
-        };
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
 
-        // Create dimensions buffer
-        let dimensions_buffer = device_arc.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Dimensions Buffer"),
-            contents: bytemuck::cast_slice(&[current_dimensions]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let rgb = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = rgb * 12.92;
+    let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
 
-        let bind_group_layout =
-            device_arc.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Texture Bind Group Layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
-            });
+// Relative luminance of a linear color
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
 
-        let pipeline_layout = device_arc.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
+// Apply the enabled color effects to a linear color, in this order: hue
+// rotation about the gray axis, saturation scaling, grayscale, then
+// inverting the sRGB encoded color. Mirrors `apply_color_effects`.
+fn apply_effects(color: vec3<f32>) -> vec3<f32> {
+    let effects = dimensions.effects;
+    if effects == 0u {
+        return color;
+    }
 
-        let vertex_shader = device_arc.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Vertex Shader"),
-            source: wgpu::ShaderSource::Wgsl(VERTEX_SHADER.into()),
-        });
+    var rgb = color;
+    if (effects & EFFECT_HUE_SHIFT) != 0u {
+        let angle = radians(dimensions.hue_shift);
+        let axis = vec3<f32>(inverseSqrt(3.0));
+        rgb = rgb * cos(angle) + cross(axis, rgb) * sin(angle) + axis * dot(axis, rgb) * (1.0 - cos(angle));
+    }
+    if (effects & EFFECT_SATURATION) != 0u {
+        rgb = mix(vec3<f32>(luma(rgb)), rgb, dimensions.saturation);
+    }
+    if (effects & EFFECT_GRAYSCALE) != 0u {
+        rgb = vec3<f32>(luma(rgb));
+    }
+    rgb = clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (effects & EFFECT_INVERT) != 0u {
+        rgb = srgb_to_linear(1.0 - linear_to_srgb(rgb));
+    }
+    return rgb;
+}
 
-        let fragment_shader = device_arc.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Fragment Shader"),
-            source: wgpu::ShaderSource::Wgsl(FRAGMENT_SHADER.into()),
-        });
+// Coverage of the outline at a window pixel: the highest alpha among the
+// frame's texels within the outline width of it, 0 when there is no outline
+fn outline_alpha(coords: vec2<f32>, size: vec2<f32>) -> f32 {
+    if dimensions.outline.a == 0.0 {
+        return 0.0;
+    }
 
-        let pipeline = device_arc.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &vertex_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fragment_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::One,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            multiview: None,
-            cache: None,
-        });
+    // Neighbours outside the frame's rectangle belong to other atlas frames
+    let rect_min = vec2<i32>(round(dimensions.uv_rect.xy * size));
+    let rect_max = vec2<i32>(round((dimensions.uv_rect.xy + dimensions.uv_rect.zw) * size)) - 1;
+    let center = vec2<i32>(floor((dimensions.uv_rect.xy + coords * dimensions.uv_rect.zw) * size));
+    let width = dimensions.outline_width;
+    let reach = i32(ceil(width));
 
-        // Create reusable sampler
-        let sampler = device_arc.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+    var alpha = 0.0;
+    for (var y = -reach; y <= reach; y++) {
+        for (var x = -reach; x <= reach; x++) {
+            let texel = center + vec2<i32>(x, y);
+            if f32(x * x + y * y) > width * width || any(texel < rect_min) || any(texel > rect_max) {
+                continue;
+            }
+            let sampled = load_frame(texel);
+            alpha = max(alpha, sampled.a * chroma_alpha(sampled.rgb));
+        }
+    }
+    return alpha;
+}
 
-        // Initialize delta compressor
-        let delta_compressor = Some(DeltaCompressor::new(device_arc.clone(), queue_arc.clone())?);
+// Turn a premultiplied linear color into what the surface expects: straight
+// alpha or the background blended in for surfaces that don't composite
+// premultiplied colors, then sRGB encoded for surfaces that only offer
+// linear formats
+fn encode_output(premultiplied: vec4<f32>) -> vec4<f32> {
+    var color = premultiplied;
+    if dimensions.alpha_output == ALPHA_POSTMULTIPLIED {
+        color = vec4<f32>(color.rgb / max(color.a, 1e-5), color.a);
+    } else if dimensions.alpha_output == ALPHA_OPAQUE {
+        let background = dimensions.background.rgb * dimensions.background.a;
+        color = vec4<f32>(color.rgb + background * (1.0 - color.a), 1.0);
+    }
 
-        Ok(Self {
-            device: device_arc,
-            queue: queue_arc,
-            surface: Some(surface), // Wrap in Option
-            pipeline,
-            bind_group_layout,
-            sequence_type: None,
-            current_texture_index: 0,
-            config,
-            dimensions_buffer,
-            current_dimensions,
-            delta_compressor,
-            sampler,
-        })
+    if dimensions.encode_srgb == 0u {
+        return color;
     }
+    return vec4<f32>(linear_to_srgb(color.rgb), color.a);
+}
 
-    /// Properly cleanup the renderer resources
-    pub fn cleanup(&mut self) {
-        log::info!("Cleaning up renderer resources");
+// Map frame-relative coordinates into the frame's rectangle, staying half a
+// texel inside so filtering never picks up a neighbouring atlas frame
+fn frame_coords(coords: vec2<f32>, size: vec2<f32>) -> vec2<f32> {
+    let uv = dimensions.uv_rect.xy + coords * dimensions.uv_rect.zw;
+    let half_texel = 0.5 / size;
+    return clamp(uv, dimensions.uv_rect.xy + half_texel, dimensions.uv_rect.xy + dimensions.uv_rect.zw - half_texel);
+}
+"#;
 
-        // Clear sequence type which contains GPU resources
-        self.sequence_type = None;
+const FRAGMENT_SHADER: &str = r#"
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    // Calculate texture coordinates based on actual dimensions
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let coords = draw_coords(pos.xy);
+    let tex_coords = frame_coords(coords, size);
+    
+    // Sample the texture
+    let sampled = sample_frame(tex_coords);
+    let keyed = vec4<f32>(sampled.rgb, sampled.a * chroma_alpha(sampled.rgb));
+    let color = vec4<f32>(apply_effects(keyed.rgb) * dimensions.tint.rgb, keyed.a);
 
-        // Clear delta compressor
-        self.delta_compressor = None;
+    // Frames hold straight alpha but the surface composites premultiplied
+    let premultiplied = vec4<f32>(color.rgb * color.a, color.a);
+    let frame = select(vec4<f32>(0.0), premultiplied, inside_frame(coords));
+    let outline = dimensions.outline * outline_alpha(coords, size);
+    return encode_output((frame + outline * (1.0 - frame.a)) * dimensions.opacity);
+}
+"#;
 
-        // Drop the surface before the window is destroyed
-        if let Some(surface) = self.surface.take() {
-            drop(surface);
-            log::debug!("Surface dropped");
-        }
+/// What a fragment shader loaded with `load_fragment_shader` has to provide,
+/// shown when it fails to compile
+const CUSTOM_SHADER_INTERFACE: &str = "\
+A custom fragment shader must define the entry point
+    @fragment fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32>
+returning premultiplied alpha passed through encode_output. These
+declarations are added to it:
+    @group(0) @binding(0) var t_diffuse: texture_2d<f32> or texture_2d_array<f32>
+    @group(1) @binding(0) var s_diffuse: sampler
+    @group(1) @binding(1) var<uniform> dimensions: Dimensions
+    fn sample_frame(uv: vec2<f32>) -> vec4<f32>
+    fn load_frame(texel: vec2<i32>) -> vec4<f32>
+    fn draw_coords(pos: vec2<f32>) -> vec2<f32>
+    fn inside_frame(coords: vec2<f32>) -> bool
+    fn frame_coords(coords: vec2<f32>, size: vec2<f32>) -> vec2<f32>
+    fn chroma_alpha(color: vec3<f32>) -> f32
+    fn outline_alpha(coords: vec2<f32>, size: vec2<f32>) -> f32
+    fn apply_effects(color: vec3<f32>) -> vec3<f32>
+    fn srgb_to_linear(color: vec3<f32>) -> vec3<f32>
+    fn linear_to_srgb(color: vec3<f32>) -> vec3<f32>
+    fn luma(color: vec3<f32>) -> f32
+    fn encode_output(color: vec4<f32>) -> vec4<f32>
+so the shader must not declare them again. Use sample_frame and load_frame
+rather than t_diffuse, which is an array for multi-frame sequences.";
 
-        log::info!("Renderer cleanup complete");
+/// Helpers for reading frames packed by `pack_rgba4444`, prepended to the
+/// fragment shaders drawing them
+const PACKED_COMMON: &str = r#"
+// Split the two bytes of a texel back into four 4-bit channels
+fn unpack(texel: vec4<f32>) -> vec4<f32> {
+    let bytes = vec2<u32>(round(texel.rg * 255.0));
+    let channels = vec4<u32>(bytes.x >> 4u, bytes.x & 15u, bytes.y >> 4u, bytes.y & 15u);
+    return vec4<f32>(channels) / 15.0;
+}
+
+// Like outline_alpha, unpacking the neighbouring texels
+fn packed_outline_alpha(coords: vec2<f32>, size: vec2<f32>) -> f32 {
+    if dimensions.outline.a == 0.0 {
+        return 0.0;
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        if width == 0 || height == 0 {
-            return;
+    let rect_min = vec2<i32>(round(dimensions.uv_rect.xy * size));
+    let rect_max = vec2<i32>(round((dimensions.uv_rect.xy + dimensions.uv_rect.zw) * size)) - 1;
+    let center = vec2<i32>(floor((dimensions.uv_rect.xy + coords * dimensions.uv_rect.zw) * size));
+    let width = dimensions.outline_width;
+    let reach = i32(ceil(width));
+
+    var alpha = 0.0;
+    for (var y = -reach; y <= reach; y++) {
+        for (var x = -reach; x <= reach; x++) {
+            let texel = center + vec2<i32>(x, y);
+            if f32(x * x + y * y) > width * width || any(texel < rect_min) || any(texel > rect_max) {
+                continue;
+            }
+            let color = unpack(load_frame(texel));
+            alpha = max(alpha, color.a * chroma_alpha(srgb_to_linear(color.rgb)));
         }
+    }
+    return alpha;
+}
+"#;
 
-        self.config.width = width;
-        self.config.height = height;
+/// Fragment shader for frames packed by `pack_rgba4444`
+const PACKED_FRAGMENT_SHADER: &str = r#"
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let coords = draw_coords(pos.xy);
+    let outline = dimensions.outline * packed_outline_alpha(coords, size);
+    if !inside_frame(coords) {
+        return encode_output(outline * dimensions.opacity);
+    }
 
-        if let Some(ref surface) = self.surface {
-            surface.configure(&self.device, &self.config);
-        }
+    // Filtering would blend the packed bytes, so take the nearest texel
+    let texel = vec2<i32>(frame_coords(coords, size) * size);
+    let color = unpack(load_frame(texel));
 
-        // Update dimensions
-        self.current_dimensions.window_width = width as f32;
-        self.current_dimensions.window_height = height as f32;
+    // The texture holds sRGB values but isn't an sRGB format
+    let linear = srgb_to_linear(color.rgb);
+    let alpha = color.a * chroma_alpha(linear);
+    let frame = vec4<f32>(apply_effects(linear) * dimensions.tint.rgb * alpha, alpha);
+    return encode_output((frame + outline * (1.0 - alpha)) * dimensions.opacity);
+}
+"#;
 
-        // Update the buffer
-        self.queue.write_buffer(
-            &self.dimensions_buffer,
-            0,
-            bytemuck::cast_slice(&[self.current_dimensions]),
-        );
+/// Fragment shader drawing the coverage of full-depth frames into the
+/// drop shadow's silhouette
+const SILHOUETTE_FRAGMENT_SHADER: &str = r#"
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let coords = draw_coords(pos.xy);
+    let sampled = sample_frame(frame_coords(coords, size));
+    let alpha = sampled.a * chroma_alpha(sampled.rgb) * dimensions.opacity;
+    return vec4<f32>(select(0.0, alpha, inside_frame(coords)));
+}
+"#;
 
-        log::info!("Resized to {}x{}", width, height);
+/// Fragment shader drawing the coverage of packed frames into the drop
+/// shadow's silhouette
+const PACKED_SILHOUETTE_FRAGMENT_SHADER: &str = r#"
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let coords = draw_coords(pos.xy);
+    if !inside_frame(coords) {
+        return vec4<f32>(0.0);
     }
 
-    // New method to preload all images at once
-    pub fn preload_images(&mut self, images: &[RgbaImage]) {
-        if images.is_empty() {
-            log::warn!("No images to preload");
-            return;
-        }
-
-        // Clear any existing sequence
-        self.sequence_type = None;
+    let color = unpack(load_frame(vec2<i32>(frame_coords(coords, size) * size)));
+    return vec4<f32>(color.a * chroma_alpha(srgb_to_linear(color.rgb)) * dimensions.opacity);
+}
+"#;
 
-        // Use first image dimensions for the window
-        let first_dims = images[0].dimensions();
-        self.current_dimensions.image_width = first_dims.0 as f32;
-        self.current_dimensions.image_height = first_dims.1 as f32;
+/// Tint that leaves frames unchanged
+const WHITE: [f32; 4] = [1.0; 4];
 
-        // Update the dimensions buffer
-        self.queue.write_buffer(
-            &self.dimensions_buffer,
-            0,
-            bytemuck::cast_slice(&[self.current_dimensions]),
-        );
+/// Default background, opaque surfaces show it as black
+const TRANSPARENT: [f32; 4] = [0.0; 4];
 
-        log::info!(
-            "Preloading {} images to GPU memory (uncompressed)",
-            images.len()
-        );
+/// Texture coordinates of a frame that covers its whole texture
+const FULL_UV_RECT: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
 
-        let mut texture_bind_groups = Vec::new();
+/// Bits of `Dimensions::effects`, matching the constants in
+/// `FRAGMENT_COMMON`
+const EFFECT_HUE_SHIFT: u32 = 1;
+const EFFECT_SATURATION: u32 = 2;
+const EFFECT_GRAYSCALE: u32 = 4;
+const EFFECT_INVERT: u32 = 8;
 
-        for (i, image) in images.iter().enumerate() {
-            let dimensions = image.dimensions();
+/// Most copies of a sequence one window can show
+pub const MAX_INSTANCES: usize = 16;
 
-            let texture_size = wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            };
+/// Thickest outline in frame texels, the shaders check every texel within
+/// this distance of each pixel
+const MAX_OUTLINE_WIDTH: f32 = 16.0;
 
-            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-                label: Some(&format!("Image Texture {}", i)),
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
+/// Largest atlas page, smaller devices use their maximum texture size
+const ATLAS_MAX_PAGE_SIZE: u32 = 4096;
 
-            self.queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                image,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * dimensions.0),
-                    rows_per_image: Some(dimensions.1),
-                },
-                texture_size,
-            );
+/// Threshold for every position of a 4x4 tile in ordered dithering
+const BAYER_4X4: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
 
-            let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Dimensions {
+    window_width: f32,
+    window_height: f32,
+    image_width: f32,
+    image_height: f32,
+    /// Array layer of the current frame, always 0 for single-layer textures
+    layer: u32,
+    /// Multiplies the premultiplied output, 1 for fully opaque frames
+    opacity: f32,
+    /// Non-zero to mirror the frame horizontally and vertically, applied
+    /// within the frame's own rectangle before it is rotated
+    flip: [u32; 2],
+    /// Offset and size of the current frame in texture coordinates, only
+    /// smaller than the whole texture for atlas pages
+    uv_rect: [f32; 4],
+    /// Offset and size in window pixels the frame is drawn at, see
+    /// `draw_rect`
+    draw_rect: [f32; 4],
+    /// Rows of the inverse of the frame's rotation, see `rotation_rows`
+    rotation: [f32; 4],
+    /// Linear color multiplied into frames before premultiplying, alpha is
+    /// always 1
+    tint: [f32; 4],
+    /// Linear key color, alpha 1 when chroma keying is on and 0 when off
+    chroma_key: [f32; 4],
+    /// Tolerance and softness of the chroma key
+    chroma_range: [f32; 2],
+    /// Non-zero when the surface format isn't sRGB and the shaders have to
+    /// encode their output themselves
+    encode_srgb: u32,
+    /// How the surface composites alpha, see `alpha_output`
+    alpha_output: u32,
+    /// Linear straight-alpha color behind frames, on opaque surfaces
+    /// blended over black
+    background: [f32; 4],
+    /// Array layer of the frame being crossfaded into
+    next_layer: u32,
+    /// How far the crossfade into `next_layer` is, 0 shows `layer` alone
+    blend: f32,
+    /// Outline thickness in frame texels
+    outline_width: f32,
+    /// Which color effects apply, see `ColorEffects::flags`
+    effects: u32,
+    /// Linear outline color, alpha 1 when the outline is on and 0 when off
+    outline: [f32; 4],
+    /// Hue rotation in degrees, for `EFFECT_HUE_SHIFT`
+    hue_shift: f32,
+    /// Saturation factor, for `EFFECT_SATURATION`
+    saturation: f32,
+    _padding: [u32; 2],
+}
 
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some(&format!("Texture Bind Group {}", i)),
-                layout: &self.bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture_view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: self.dimensions_buffer.as_entire_binding(),
-                    },
-                ],
-            });
+/// A background color to make transparent in frames without alpha
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChromaKey {
+    /// sRGB key color
+    pub color: [u8; 3],
+    /// Distance from the key color, with channels from 0 to 1, within which
+    /// texels become fully transparent
+    pub tolerance: f32,
+    /// Width of the falloff beyond `tolerance` over which texels fade back
+    /// in, to avoid hard fringes
+    pub softness: f32,
+}
 
-            texture_bind_groups.push(bind_group);
-        }
+/// A colored border around the opaque parts of frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    /// sRGB outline color
+    pub color: [u8; 3],
+    /// Thickness in frame pixels, so it scales with the frame
+    pub width: f32,
+}
 
-        self.sequence_type = Some(SequenceType::Uncompressed {
-            texture_bind_groups,
-        });
+/// Color adjustments applied to frames before the tint, in the order of the
+/// fields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorEffects {
+    /// Rotation of every color's hue in degrees
+    pub hue_shift: f32,
+    /// Factor scaling saturation, 0 for gray and above 1 for more vivid
+    pub saturation: f32,
+    /// Replace colors by their luminance
+    pub grayscale: bool,
+    /// Invert colors as they are seen, in sRGB
+    pub invert: bool,
+}
 
-        self.current_texture_index = 0;
-        log::info!(
-            "Preloaded {} images to GPU memory (uncompressed)",
-            images.len()
-        );
+impl Default for ColorEffects {
+    fn default() -> Self {
+        Self {
+            hue_shift: 0.0,
+            saturation: 1.0,
+            grayscale: false,
+            invert: false,
+        }
     }
+}
 
-    pub async fn preload_images_compressed(&mut self, images: &[RgbaImage]) -> Result<()> {
-        if images.is_empty() {
-            log::warn!("No images to compress");
-            return Ok(());
+impl ColorEffects {
+    /// The `EFFECT_*` bits of the effects that change colors, the shaders
+    /// skip the others
+    fn flags(&self) -> u32 {
+        let mut flags = 0;
+        if self.hue_shift.rem_euclid(360.0) != 0.0 {
+            flags |= EFFECT_HUE_SHIFT;
         }
+        if self.saturation != 1.0 {
+            flags |= EFFECT_SATURATION;
+        }
+        if self.grayscale {
+            flags |= EFFECT_GRAYSCALE;
+        }
+        if self.invert {
+            flags |= EFFECT_INVERT;
+        }
+        flags
+    }
+}
 
-        // Calculate original memory usage
-        let original_size: usize = images.iter().map(|img| img.as_raw().len()).sum();
-        log::info!(
-            "Original sequence size: {:.2} MB",
-            original_size as f64 / (1024.0 * 1024.0)
-        );
-
-        // Clear any existing sequence
-        self.sequence_type = None;
+/// How frame textures are sampled when drawn at a different size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Filtering {
+    /// Smooth interpolation between texels
+    #[default]
+    Linear,
+    /// Hard texel edges for pixel art, drawn at whole multiples of the frame
+    /// size
+    Nearest,
+}
 
-        // Use first image dimensions for the window
-        let first_dims = images[0].dimensions();
-        self.current_dimensions.image_width = first_dims.0 as f32;
-        self.current_dimensions.image_height = first_dims.1 as f32;
+/// When finished frames are shown on screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PresentMode {
+    /// Wait for vertical blank, supported everywhere
+    #[default]
+    Fifo,
+    /// Wait for vertical blank but replace queued frames, for lower latency
+    Mailbox,
+    /// Present right away, possibly tearing, for benchmarking
+    Immediate,
+}
 
-        // Update the dimensions buffer
-        self.queue.write_buffer(
-            &self.dimensions_buffer,
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+/// Which GPU the renderer runs on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterSelection {
+    /// Position in `list_adapters` or part of the adapter's name, matched
+    /// case-insensitively
+    pub gpu: Option<String>,
+    /// Prefer an integrated GPU over a discrete one when no adapter is named
+    pub low_power: bool,
+    /// Render in software, e.g. with llvmpipe or WARP, when no GPU adapter
+    /// is available instead of failing
+    pub software_fallback: bool,
+}
+
+impl Default for AdapterSelection {
+    fn default() -> Self {
+        Self {
+            gpu: None,
+            low_power: false,
+            software_fallback: true,
+        }
+    }
+}
+
+/// The adapter and device frames are drawn with. The first window's
+/// renderer opens it and the renderers of the other windows share it, so
+/// frames are uploaded once however many windows show them.
+pub struct Gpu {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    /// Shared so frame bind groups fit the pipelines of every renderer
+    layouts: FrameLayouts,
+}
+
+impl Gpu {
+    /// Open a device on the adapter `selection` asks for that can present
+    /// to `surface`, or on any adapter without one
+    async fn new(
+        instance: wgpu::Instance,
+        surface: Option<&wgpu::Surface<'_>>,
+        selection: &AdapterSelection,
+    ) -> Result<Self> {
+        let adapter = request_adapter(&instance, surface, selection).await?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("Overlay Device"),
+                // Used for block-compressed frames where available
+                required_features: adapter.features() & wgpu::Features::TEXTURE_COMPRESSION_BC,
+                // Raise the texture size limits to what the adapter supports so
+                // large frames don't need downscaling unnecessarily
+                required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+                memory_hints: wgpu::MemoryHints::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await?;
+
+        let layouts = FrameLayouts {
+            single_layer: create_bind_group_layout(&device, wgpu::TextureViewDimension::D2),
+            array: create_bind_group_layout(&device, wgpu::TextureViewDimension::D2Array),
+            uniforms: create_uniforms_layout(&device),
+        };
+
+        Ok(Self {
+            instance,
+            adapter,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            layouts,
+        })
+    }
+}
+
+/// Video memory the renderer holds, as estimated by
+/// `Renderer::gpu_memory_estimate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuMemoryEstimate {
+    /// Bytes of every frame texture, mip levels and spare layers included
+    pub texture_bytes: u64,
+    pub texture_count: usize,
+    /// Bytes of the surface's swapchain images, 0 without a surface
+    pub surface_bytes: u64,
+}
+
+/// Bind group sampling a frame texture
+#[derive(Clone)]
+pub struct TextureBinding {
+    bind_group: wgpu::BindGroup,
+    /// The bound texture, kept to tell how much memory it takes
+    texture: wgpu::Texture,
+    /// Whether the texture has several layers and is bound as an array
+    array: bool,
+}
+
+/// A frame pipeline for each way a frame texture can be bound
+struct FramePipeline {
+    single_layer: wgpu::RenderPipeline,
+    array: wgpu::RenderPipeline,
+}
+
+impl FramePipeline {
+    fn new(
+        device: &wgpu::Device,
+        layouts: &FrameLayouts,
+        format: wgpu::TextureFormat,
+        fragment_source: &str,
+    ) -> Self {
+        Self {
+            single_layer: create_pipeline(
+                device,
+                &layouts.single_layer,
+                &layouts.uniforms,
+                format,
+                SINGLE_LAYER_BINDING,
+                fragment_source,
+            ),
+            array: create_pipeline(
+                device,
+                &layouts.array,
+                &layouts.uniforms,
+                format,
+                ARRAY_BINDING,
+                fragment_source,
+            ),
+        }
+    }
+
+    /// The pipeline matching how `binding` binds its texture
+    fn for_binding(&self, binding: &TextureBinding) -> &wgpu::RenderPipeline {
+        if binding.array {
+            &self.array
+        } else {
+            &self.single_layer
+        }
+    }
+}
+
+/// Pipelines drawing a frame's silhouette, and the passes turning it into
+/// a drop shadow
+struct ShadowEffect {
+    /// The shadow as configured, `pass` draws it scaled to the render size
+    shadow: Shadow,
+    silhouette: FramePipeline,
+    packed_silhouette: FramePipeline,
+    pass: ShadowPass,
+}
+
+/// Bind group layouts for single-layer frame textures and texture arrays,
+/// and for each renderer's sampler and uniforms
+struct FrameLayouts {
+    single_layer: wgpu::BindGroupLayout,
+    array: wgpu::BindGroupLayout,
+    uniforms: wgpu::BindGroupLayout,
+}
+
+/// How a frame is placed in a window that doesn't match its size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ScaleMode {
+    /// Cover the whole window, distorting the frame if the aspect ratios differ
+    #[default]
+    Stretch,
+    /// Scale to fit inside the window, leaving transparent bars on two sides
+    Fit,
+    /// Scale to cover the window, cropping what doesn't fit
+    Fill,
+    /// Draw at the frame's own size in the middle of the window, clipped
+    Center,
+    /// Fit inside the window at the largest whole multiple of the frame size,
+    /// with nearest sampling so texels stay sharp
+    IntegerScale,
+}
+
+/// Frames of one sequence resident on the GPU
+#[derive(Clone)]
+pub struct FrameSet {
+    storage: FrameStorage,
+    /// Slot in `storage` for every frame in playback order, identical frames
+    /// share a slot
+    frame_textures: Vec<usize>,
+    image_size: (u32, u32),
+}
+
+/// Where the distinct frames of a sequence live
+#[derive(Clone)]
+enum FrameStorage {
+    /// Every distinct frame is a layer of one texture that stays bound,
+    /// switching frames only rewrites the layer in the uniform buffer
+    Array {
+        texture: wgpu::Texture,
+        binding: TextureBinding,
+        /// Layers holding frames, the texture may have spare ones
+        layer_count: u32,
+    },
+    /// One single-layer texture and bind group per distinct frame, for frames
+    /// of differing sizes or more distinct frames than an array can hold
+    Separate(Vec<TextureBinding>),
+    /// Distinct frames packed side by side into a few large pages
+    Atlas {
+        /// Texture and bind group of every page
+        pages: Vec<(wgpu::Texture, TextureBinding)>,
+        /// Where each distinct frame was placed
+        placements: Vec<AtlasPlacement>,
+        /// Continues where the last frame was placed, for frames added later
+        packer: ShelfPacker,
+    },
+}
+
+/// Position of a frame within an atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AtlasPlacement {
+    page: usize,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl AtlasPlacement {
+    fn origin(&self) -> wgpu::Origin3d {
+        wgpu::Origin3d {
+            x: self.x,
+            y: self.y,
+            z: 0,
+        }
+    }
+}
+
+/// Places rectangles left to right in rows ("shelves") as tall as the
+/// tallest rectangle in them, starting a new page once a page is full
+#[derive(Debug, Clone)]
+struct ShelfPacker {
+    page_size: (u32, u32),
+    page: usize,
+    /// Where the next rectangle goes on the current shelf
+    cursor: (u32, u32),
+    shelf_height: u32,
+}
+
+impl ShelfPacker {
+    fn new(page_size: (u32, u32)) -> Self {
+        Self {
+            page_size,
+            page: 0,
+            cursor: (0, 0),
+            shelf_height: 0,
+        }
+    }
+
+    /// Reserve room for a rectangle of `size`, `None` when it is larger than
+    /// a page
+    fn place(&mut self, (width, height): (u32, u32)) -> Option<AtlasPlacement> {
+        let (page_width, page_height) = self.page_size;
+        if width > page_width || height > page_height {
+            return None;
+        }
+
+        if self.cursor.0 + width > page_width {
+            self.cursor = (0, self.cursor.1 + self.shelf_height);
+            self.shelf_height = 0;
+        }
+        if self.cursor.1 + height > page_height {
+            self.page += 1;
+            self.cursor = (0, 0);
+            self.shelf_height = 0;
+        }
+
+        let placement = AtlasPlacement {
+            page: self.page,
+            x: self.cursor.0,
+            y: self.cursor.1,
+            width,
+            height,
+        };
+        self.cursor.0 += width;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(placement)
+    }
+}
+
+impl FrameSet {
+    /// Every texture holding frames of the sequence
+    fn textures(&self) -> Vec<&wgpu::Texture> {
+        match &self.storage {
+            FrameStorage::Array { texture, .. } => vec![texture],
+            FrameStorage::Separate(bind_groups) => {
+                bind_groups.iter().map(|binding| &binding.texture).collect()
+            }
+            FrameStorage::Atlas { pages, .. } => pages.iter().map(|(texture, _)| texture).collect(),
+        }
+    }
+
+    /// Whether the frames are packed by `pack_rgba4444`, as they are when
+    /// uploaded in low memory mode
+    fn is_packed(&self) -> bool {
+        self.textures()
+            .first()
+            .is_some_and(|texture| texture.format() == wgpu::TextureFormat::Rg8Unorm)
+    }
+
+    /// Number of distinct frames stored
+    fn slot_count(&self) -> usize {
+        match &self.storage {
+            FrameStorage::Array { layer_count, .. } => *layer_count as usize,
+            FrameStorage::Separate(bind_groups) => bind_groups.len(),
+            FrameStorage::Atlas { placements, .. } => placements.len(),
+        }
+    }
+
+    /// Bind group, array layer and texture coordinates showing the frame at
+    /// `index`
+    fn frame(&self, index: usize) -> Option<(&TextureBinding, u32, [f32; 4])> {
+        let slot = *self.frame_textures.get(index)?;
+        match &self.storage {
+            FrameStorage::Array { binding, .. } => Some((binding, slot as u32, FULL_UV_RECT)),
+            FrameStorage::Separate(bind_groups) => Some((bind_groups.get(slot)?, 0, FULL_UV_RECT)),
+            FrameStorage::Atlas {
+                pages, placements, ..
+            } => {
+                let placement = placements.get(slot)?;
+                let (texture, binding) = pages.get(placement.page)?;
+                let (page_width, page_height) = (texture.width() as f32, texture.height() as f32);
+                Some((
+                    binding,
+                    0,
+                    [
+                        placement.x as f32 / page_width,
+                        placement.y as f32 / page_height,
+                        placement.width as f32 / page_width,
+                        placement.height as f32 / page_height,
+                    ],
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum SequenceType {
+    Uncompressed {
+        /// Every preloaded sequence keeps its own bind groups so switching
+        /// between them needs no upload
+        sequences: Vec<FrameSet>,
+    },
+    Compressed {
+        compressed_sequence: Arc<CompressedSequence>,
+        current_frame_bind_group: TextureBinding,
+        /// Shared by the renderers drawing the same texture, so each frame
+        /// is reconstructed once
+        reconstructed_frame: Arc<Mutex<ReconstructedFrame>>,
+    },
+}
+
+/// The frame of a delta-compressed sequence its texture holds
+pub struct ReconstructedFrame {
+    index: usize,
+    image: Arc<RgbaImage>,
+}
+
+/// Frames a renderer holds on the GPU, see `Renderer::frames`
+#[derive(Clone)]
+pub struct SharedFrames {
+    gpu: Arc<Gpu>,
+    sequence_type: Option<SequenceType>,
+}
+
+impl SharedFrames {
+    /// Whether renderers drawing with `gpu` can share these frames
+    pub fn is_on(&self, gpu: &Arc<Gpu>) -> bool {
+        Arc::ptr_eq(&self.gpu, gpu)
+    }
+}
+
+pub struct Renderer {
+    /// Shared with the renderers of the other windows, its instance also
+    /// recreates the surface after a suspend
+    gpu: Arc<Gpu>,
+    surface: Option<wgpu::Surface<'static>>,
+    /// What `surface` presents through on Windows, see `Composition`
+    #[cfg(target_os = "windows")]
+    composition: Option<Composition>,
+    /// Created by `new_headless`, frames are only drawn by `capture_frame`
+    headless: bool,
+    pipeline: FramePipeline,
+    /// Pipeline for frames stored with 4 bits per channel
+    packed_pipeline: FramePipeline,
+    low_memory: bool,
+    /// Pack frames into shared atlas pages instead of one layer each
+    atlas: bool,
+    /// Store block-aligned frames BC1 or BC7 compressed, only set when the
+    /// device supports it
+    block_compression: bool,
+    /// Fade into the next frame over the current one's display time
+    crossfade: bool,
+    /// Drop shadow drawn behind frames, if enabled
+    shadow: Option<ShadowEffect>,
+    color_effects: ColorEffects,
+    /// Whether the user was told their translucent background shows over
+    /// black
+    background_warned: bool,
+    /// Requested ratio of the size frames are drawn at to the window's size
+    render_scale: f32,
+    /// Window pixels per pixel of shadow offsets, blur and instance spacing
+    scale_factor: f32,
+    /// Offscreen target frames are drawn into when rendering larger than
+    /// the window
+    supersampler: Option<Supersampler>,
+    /// Draws the context menu over frames, created when one first opens
+    menu: Option<MenuPainter>,
+    /// Give full-depth frame textures a mip chain for smooth downscaling
+    mipmaps: bool,
+    mipmap_generator: MipmapGenerator,
+    filter: Filtering,
+    scale_mode: ScaleMode,
+    /// Clockwise rotation of frames in degrees
+    rotation: f32,
+    sequence_type: Option<SequenceType>,
+    active_sequence: usize,
+    current_texture_index: usize,
+    config: wgpu::SurfaceConfiguration,
+    /// Present modes the surface supports
+    present_modes: Vec<wgpu::PresentMode>,
+    /// Holds the Dimensions of every instance, `dimensions_stride` apart
+    dimensions_buffer: wgpu::Buffer,
+    dimensions_stride: u64,
+    /// Dimensions of the first instance, the others differ in where they
+    /// are drawn and which frame they show
+    current_dimensions: Dimensions,
+    /// Copies of the sequence drawn side by side, see `set_instances`
+    instance_count: usize,
+    /// Gap between instances in window pixels
+    instance_spacing: f32,
+    /// Frame index shown by each instance after the first
+    instance_frames: Vec<usize>,
+    /// Frame the current one is crossfading into, if it can be blended
+    crossfade_next: Option<usize>,
+
+    delta_compressor: Option<DeltaCompressor>,
+    /// Bind group of the sampler and the Dimensions buffer, the frame
+    /// bind groups only hold textures so renderers can share them
+    uniforms: wgpu::BindGroup,
+}
+
+impl Renderer {
+    /// Create a renderer drawing into `window` with `gpu`, the one of
+    /// another window's renderer, or on the adapter `selection` asks for
+    /// without one. Full-depth frames are drawn with the fragment shader at
+    /// `fragment_shader` instead of the built-in one if given, see
+    /// `load_fragment_shader`.
+    pub async fn new(
+        window: Arc<dyn WindowBackend>,
+        fragment_shader: Option<&Path>,
+        gpu: Option<Arc<Gpu>>,
+        selection: &AdapterSelection,
+    ) -> Result<Self> {
+        let fragment_source = match fragment_shader {
+            Some(path) => load_fragment_shader(path)?,
+            None => FRAGMENT_SHADER.to_string(),
+        };
+
+        let instance = gpu
+            .as_ref()
+            .map_or_else(create_instance, |gpu| gpu.instance.clone());
+        #[cfg(target_os = "windows")]
+        let (surface, composition) = crate::composition::create_surface(&instance, window.clone())?;
+        #[cfg(not(target_os = "windows"))]
+        let surface = instance.create_surface(window.clone())?;
+        let gpu = match gpu {
+            Some(gpu) if gpu.adapter.is_surface_supported(&surface) => gpu,
+            Some(_) => {
+                return Err(anyhow!(
+                    "The GPU of the other windows can't present to this one"
+                ));
+            }
+            None => Arc::new(Gpu::new(instance, Some(&surface), selection).await?),
+        };
+
+        let surface_caps = surface.get_capabilities(&gpu.adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(surface_caps.formats[0]);
+        if !surface_format.is_srgb() {
+            log::info!(
+                "Surface has no sRGB format, encoding {:?} output in the shaders",
+                surface_format
+            );
+        }
+
+        let size = window.inner_size();
+        let mut renderer = Self::with_target(
+            gpu,
+            (size.width, size.height),
+            surface_format,
+            &fragment_source,
+        )?;
+
+        renderer.present_modes = surface_caps.present_modes;
+        // Configuring a mode the surface doesn't list panics
+        let alpha_mode = choose_alpha_mode(&surface_caps.alpha_modes);
+        match alpha_mode {
+            wgpu::CompositeAlphaMode::PreMultiplied => {
+                log::info!("Compositing with premultiplied alpha")
+            }
+            wgpu::CompositeAlphaMode::Opaque => log::warn!(
+                "The surface can't be transparent, drawing frames over a solid background"
+            ),
+            mode => log::info!(
+                "Surface has no premultiplied alpha, compositing with {:?}",
+                mode
+            ),
+        }
+        renderer.config.alpha_mode = alpha_mode;
+        renderer.current_dimensions.alpha_output = alpha_output(alpha_mode);
+        renderer.write_dimensions();
+        surface.configure(&renderer.gpu.device, &renderer.config);
+        renderer.surface = Some(surface);
+        #[cfg(target_os = "windows")]
+        renderer.attach_composition(composition);
+        Ok(renderer)
+    }
+
+    /// Create a renderer without a window, for tests and tools that run
+    /// without a display server, with `gpu` or any adapter without one. It
+    /// draws into an offscreen texture of the given size instead, read back
+    /// with `capture_frame`.
+    #[cfg(test)]
+    pub async fn new_headless(width: u32, height: u32, gpu: Option<Arc<Gpu>>) -> Result<Self> {
+        let gpu = match gpu {
+            Some(gpu) => gpu,
+            None => {
+                Arc::new(Gpu::new(create_instance(), None, &AdapterSelection::default()).await?)
+            }
+        };
+        let mut renderer = Self::with_target(
+            gpu,
+            (width, height),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            FRAGMENT_SHADER,
+        )?;
+        renderer.headless = true;
+        Ok(renderer)
+    }
+
+    /// Set up the pipelines and buffers for drawing frames with `gpu` into
+    /// `format` targets of `size`, with no surface attached yet
+    fn with_target(
+        gpu: Arc<Gpu>,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        fragment_source: &str,
+    ) -> Result<Self> {
+        let device = &gpu.device;
+        let (width, height) = size;
+        let max_dimension = device.limits().max_texture_dimension_2d;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: width.min(max_dimension),
+            height: height.min(max_dimension),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::PreMultiplied,
+            view_formats: vec![],
+        };
+
+        // Initialize the dimensions
+        let current_dimensions = Dimensions {
+            window_width: width as f32,
+            window_height: height as f32,
+            image_width: width as f32,
+            image_height: height as f32,
+            layer: 0,
+            opacity: 1.0,
+            flip: [0; 2],
+            uv_rect: FULL_UV_RECT,
+            draw_rect: [0.0, 0.0, width as f32, height as f32],
+            rotation: rotation_rows(0.0),
+            tint: WHITE,
+            chroma_key: [0.0; 4],
+            chroma_range: [0.0; 2],
+            encode_srgb: !format.is_srgb() as u32,
+            alpha_output: alpha_output(wgpu::CompositeAlphaMode::PreMultiplied),
+            background: TRANSPARENT,
+            next_layer: 0,
+            blend: 0.0,
+            outline_width: 0.0,
+            effects: 0,
+            outline: [0.0; 4],
+            hue_shift: 0.0,
+            saturation: 1.0,
+            _padding: [0; 2],
+        };
+
+        // One Dimensions per instance, each at an offset the device can bind
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let dimensions_stride = (size_of::<Dimensions>() as u64).next_multiple_of(alignment);
+        let dimensions_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Dimensions Buffer"),
+            size: dimensions_stride * MAX_INSTANCES as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue.write_buffer(
+            &dimensions_buffer,
             0,
-            bytemuck::cast_slice(&[self.current_dimensions]),
+            bytemuck::cast_slice(&[current_dimensions]),
+        );
+
+        let pipeline = FramePipeline::new(device, &gpu.layouts, config.format, fragment_source);
+        let packed_pipeline = FramePipeline::new(
+            device,
+            &gpu.layouts,
+            config.format,
+            &format!("{}{}", PACKED_COMMON, PACKED_FRAGMENT_SHADER),
+        );
+
+        let uniforms = create_uniforms(
+            device,
+            &gpu.layouts,
+            &create_sampler(device, Filtering::default()),
+            &dimensions_buffer,
+        );
+
+        let mipmap_generator = MipmapGenerator::new(gpu.device.clone(), gpu.queue.clone());
+
+        // Initialize delta compressor
+        let delta_compressor = Some(DeltaCompressor::new(gpu.device.clone(), gpu.queue.clone())?);
+
+        Ok(Self {
+            gpu,
+            surface: None,
+            #[cfg(target_os = "windows")]
+            composition: None,
+            headless: false,
+            pipeline,
+            packed_pipeline,
+            low_memory: false,
+            atlas: false,
+            block_compression: false,
+            crossfade: false,
+            shadow: None,
+            color_effects: ColorEffects::default(),
+            background_warned: false,
+            render_scale: 1.0,
+            scale_factor: 1.0,
+            supersampler: None,
+            menu: None,
+            mipmaps: true,
+            mipmap_generator,
+            filter: Filtering::default(),
+            scale_mode: ScaleMode::default(),
+            rotation: 0.0,
+            sequence_type: None,
+            active_sequence: 0,
+            current_texture_index: 0,
+            config,
+            present_modes: vec![wgpu::PresentMode::Fifo],
+            dimensions_buffer,
+            dimensions_stride,
+            current_dimensions,
+            instance_count: 1,
+            instance_spacing: 0.0,
+            instance_frames: Vec::new(),
+            crossfade_next: None,
+            delta_compressor,
+            uniforms,
+        })
+    }
+
+    /// Properly cleanup the renderer resources
+    pub fn cleanup(&mut self) {
+        log::info!("Cleaning up renderer resources");
+
+        // Clear sequence type which contains GPU resources
+        self.sequence_type = None;
+
+        // Clear delta compressor
+        self.delta_compressor = None;
+
+        // Drop the surface before the window is destroyed
+        if let Some(surface) = self.surface.take() {
+            drop(surface);
+            log::debug!("Surface dropped");
+        }
+
+        log::info!("Renderer cleanup complete");
+    }
+
+    /// Drop the surface while the application is suspended. Frame textures
+    /// stay on the device, so `resume` needs no new upload.
+    pub fn suspend(&mut self) {
+        if self.surface.take().is_some() {
+            log::info!("Surface dropped for suspend");
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.composition = None;
+        }
+    }
+
+    /// Recreate the surface for `window` after a suspend, sized to the
+    /// window's current size
+    pub fn resume(&mut self, window: Arc<dyn WindowBackend>) -> Result<()> {
+        let size = window.inner_size();
+        #[cfg(target_os = "windows")]
+        let (surface, composition) =
+            crate::composition::create_surface(&self.gpu.instance, window)?;
+        #[cfg(not(target_os = "windows"))]
+        let surface = self.gpu.instance.create_surface(window)?;
+        self.surface = Some(surface);
+
+        if size.width > 0 && size.height > 0 {
+            self.resize(size.width, size.height);
+        } else if let Some(surface) = &self.surface {
+            surface.configure(&self.gpu.device, &self.config);
+        }
+        #[cfg(target_os = "windows")]
+        self.attach_composition(composition);
+        log::info!("Surface recreated after resume");
+        Ok(())
+    }
+
+    /// Keep the composition the surface presents through, showing what it
+    /// presents now that it is configured
+    #[cfg(target_os = "windows")]
+    fn attach_composition(&mut self, composition: Option<Composition>) {
+        if let Some(composition) = &composition
+            && let Err(e) = composition.commit()
+        {
+            log::error!("Failed to show the DirectComposition visual: {}", e);
+        }
+        self.composition = composition;
+    }
+
+    /// Present frames with `mode`, or with Fifo if the surface doesn't
+    /// support it
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        let requested = wgpu::PresentMode::from(mode);
+        let present_mode = if self.present_modes.contains(&requested) {
+            requested
+        } else {
+            log::warn!(
+                "{:?} presentation isn't supported by this surface, falling back to Fifo",
+                requested
+            );
+            wgpu::PresentMode::Fifo
+        };
+        log::info!("Presenting with {:?}", present_mode);
+
+        self.config.present_mode = present_mode;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.gpu.device, &self.config);
+        }
+    }
+
+    /// Store uncompressed frames uploaded from now on with 4 bits per
+    /// channel, halving their memory at the cost of color depth
+    pub fn set_low_memory(&mut self, enabled: bool) {
+        self.low_memory = enabled;
+    }
+
+    /// Pack the distinct frames of sequences uploaded from now on into a few
+    /// large atlas pages, which suits many small frames
+    pub fn set_atlas(&mut self, enabled: bool) {
+        self.atlas = enabled;
+    }
+
+    /// Store frames uploaded from now on block-compressed, BC1 for opaque
+    /// sequences and BC7 for ones with alpha, if the device supports it.
+    /// Frames whose size isn't a multiple of 4, and atlas pages, stay
+    /// uncompressed. Compressed frames get no mipmaps.
+    pub fn set_block_compression(&mut self, enabled: bool) {
+        let supported = self
+            .gpu
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_COMPRESSION_BC);
+        if enabled && !supported {
+            log::info!("The GPU has no BC texture compression, storing frames uncompressed");
+        }
+        self.block_compression = enabled && supported;
+    }
+
+    /// Whether frame textures uploaded from now on get a mip chain. Without
+    /// one, shrinking the window far below the frame size shimmers, but
+    /// uploads are faster.
+    pub fn set_mipmaps(&mut self, enabled: bool) {
+        self.mipmaps = enabled;
+    }
+
+    /// Sample frames with `filter`. Nearest filtering also draws frames at
+    /// whole multiples of their size.
+    pub fn set_filter(&mut self, filter: Filtering) {
+        self.filter = filter;
+        self.update_sampler();
+        self.write_dimensions();
+    }
+
+    /// Place frames in the window according to `mode`
+    pub fn set_scale_mode(&mut self, mode: ScaleMode) {
+        self.scale_mode = mode;
+        self.update_sampler();
+        self.write_dimensions();
+    }
+
+    /// Bind a sampler filtering as `sampling` says
+    fn update_sampler(&mut self) {
+        let sampler = create_sampler(&self.gpu.device, self.sampling());
+        self.uniforms = create_uniforms(
+            &self.gpu.device,
+            &self.gpu.layouts,
+            &sampler,
+            &self.dimensions_buffer,
         );
+    }
+
+    /// Rotate frames clockwise by `degrees` about their center. The scale
+    /// mode places the rotated frame's bounding box in the window.
+    pub fn set_rotation(&mut self, degrees: f32) {
+        self.rotation = degrees;
+        self.write_dimensions();
+    }
+
+    /// Mirror frames horizontally and/or vertically
+    pub fn set_flip(&mut self, horizontal: bool, vertical: bool) {
+        self.current_dimensions.flip = [horizontal as u32, vertical as u32];
+        self.write_dimensions();
+    }
+
+    /// Fade the whole overlay, from 0 for invisible to 1 for the frames'
+    /// own alpha. Values outside that range are clamped.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.current_dimensions.opacity = opacity.clamp(0.0, 1.0);
+        self.write_dimensions();
+    }
+
+    /// Draw `quads` of a context menu over frames when presenting, nothing
+    /// once they are empty. Captures leave the menu out.
+    pub fn set_menu(&mut self, quads: &[MenuQuad]) {
+        // Quad colors are sRGB, which sRGB targets expect linear
+        let linear = self.config.format.is_srgb();
+        let quads: Vec<MenuQuad> = quads
+            .iter()
+            .map(|quad| {
+                let [r, g, b, a] = quad.color;
+                let color = if linear {
+                    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+                } else {
+                    quad.color
+                };
+                MenuQuad { color, ..*quad }
+            })
+            .collect();
+
+        let (device, queue, format) = (&self.gpu.device, &self.gpu.queue, self.config.format);
+        self.menu
+            .get_or_insert_with(|| MenuPainter::new(device, queue, format))
+            .set_quads(device, &quads);
+    }
+
+    /// Multiply frames by an sRGB color with channels from 0 to 1, white
+    /// shows them unchanged
+    pub fn set_tint(&mut self, r: f32, g: f32, b: f32) {
+        self.current_dimensions.tint = [
+            srgb_to_linear(r.clamp(0.0, 1.0)),
+            srgb_to_linear(g.clamp(0.0, 1.0)),
+            srgb_to_linear(b.clamp(0.0, 1.0)),
+            1.0,
+        ];
+        self.write_dimensions();
+    }
+
+    /// Fade from each frame into the next instead of cutting, driven by
+    /// `set_crossfade_target`. Only full-depth sequences stored as a single
+    /// texture array can fade, others keep cutting between frames.
+    pub fn set_crossfade(&mut self, enabled: bool) {
+        self.crossfade = enabled;
+        if !enabled {
+            self.set_crossfade_target(None, 0.0);
+        }
+    }
+
+    /// Blend `progress` of the way from the current frame into the frame at
+    /// `next` in playback order. `None`, or a frame that can't be blended
+    /// with the current one, shows the current frame alone.
+    pub fn set_crossfade_target(&mut self, next: Option<usize>, progress: f32) {
+        let target = match (&self.sequence_type, next) {
+            (Some(SequenceType::Uncompressed { sequences }), Some(next))
+                if self.crossfade && !self.low_memory =>
+            {
+                sequences
+                    .get(self.active_sequence)
+                    .filter(|frame_set| matches!(frame_set.storage, FrameStorage::Array { .. }))
+                    .and_then(|frame_set| frame_set.frame(next))
+                    .map(|(_, layer, _)| (next, layer))
+            }
+            _ => None,
+        };
+
+        let dimensions = &mut self.current_dimensions;
+        let (next_layer, blend) = match target {
+            Some((_, layer)) => (layer, progress.clamp(0.0, 1.0)),
+            None => (dimensions.layer, 0.0),
+        };
+        let crossfade_next = target.map(|(next, _)| next);
+        if (next_layer, blend) != (dimensions.next_layer, dimensions.blend)
+            || crossfade_next != self.crossfade_next
+        {
+            dimensions.next_layer = next_layer;
+            dimensions.blend = blend;
+            self.crossfade_next = crossfade_next;
+            self.write_dimensions();
+        }
+    }
+
+    /// Color the window is cleared to behind frames, with sRGB channels and
+    /// alpha from 0 to 1. Surfaces that can't be transparent show it over
+    /// black.
+    pub fn set_background(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        let [r, g, b] = [r, g, b].map(|channel| srgb_to_linear(channel.clamp(0.0, 1.0)));
+        let a = a.clamp(0.0, 1.0);
+        let transparent = [r, g, b, a] == TRANSPARENT;
+        if a < 1.0
+            && !transparent
+            && self.config.alpha_mode == wgpu::CompositeAlphaMode::Opaque
+            && !self.background_warned
+        {
+            log::warn!("The surface can't be transparent, showing the background over black");
+            self.background_warned = true;
+        }
+        self.current_dimensions.background = [r, g, b, a];
+        self.write_dimensions();
+    }
+
+    /// The background as `encode_output` would write it to the surface,
+    /// for clearing targets with
+    fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.current_dimensions.background;
+        let (color, alpha) = match self.config.alpha_mode {
+            wgpu::CompositeAlphaMode::PostMultiplied => ([r, g, b], a),
+            wgpu::CompositeAlphaMode::Opaque => ([r * a, g * a, b * a], 1.0),
+            _ => ([r * a, g * a, b * a], a),
+        };
+        let [r, g, b] = if self.current_dimensions.encode_srgb != 0 {
+            color.map(linear_to_srgb)
+        } else {
+            color
+        };
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: alpha as f64,
+        }
+    }
+
+    /// Apply every color effect in `effects`, replacing the current ones
+    pub fn set_color_effects(&mut self, effects: ColorEffects) {
+        self.color_effects = effects;
+        let dimensions = &mut self.current_dimensions;
+        dimensions.effects = effects.flags();
+        dimensions.hue_shift = effects.hue_shift;
+        dimensions.saturation = effects.saturation.max(0.0);
+        self.write_dimensions();
+    }
+
+    // The single effect setters are for control interfaces animating one
+    // effect at a time, nothing calls them yet
+
+    /// Rotate the hue of frames by `degrees`, 0 leaves it unchanged
+    #[allow(dead_code)]
+    pub fn set_hue_shift(&mut self, degrees: f32) {
+        self.set_color_effects(ColorEffects {
+            hue_shift: degrees,
+            ..self.color_effects
+        });
+    }
+
+    /// Scale the saturation of frames, 1 leaves it unchanged and 0 is gray
+    #[allow(dead_code)]
+    pub fn set_saturation(&mut self, saturation: f32) {
+        self.set_color_effects(ColorEffects {
+            saturation,
+            ..self.color_effects
+        });
+    }
+
+    /// Show frames in shades of gray
+    #[allow(dead_code)]
+    pub fn set_grayscale(&mut self, enabled: bool) {
+        self.set_color_effects(ColorEffects {
+            grayscale: enabled,
+            ..self.color_effects
+        });
+    }
+
+    /// Invert the colors of frames
+    #[allow(dead_code)]
+    pub fn set_invert(&mut self, enabled: bool) {
+        self.set_color_effects(ColorEffects {
+            invert: enabled,
+            ..self.color_effects
+        });
+    }
+
+    /// Draw `count` copies of the sequence side by side, `spacing` window
+    /// pixels apart, each in an equal share of the window's width. Capped
+    /// at `MAX_INSTANCES`. One instance draws exactly as before.
+    pub fn set_instances(&mut self, count: usize, spacing: f32) {
+        self.instance_count = count.clamp(1, MAX_INSTANCES);
+        self.instance_spacing = spacing.max(0.0);
+        self.write_dimensions();
+    }
+
+    /// Show frame `frames[i]` in instance `i + 1`, the first instance always
+    /// shows the current frame. Instances of delta-compressed sequences all
+    /// show the current frame, only it is reconstructed.
+    pub fn set_instance_frames(&mut self, frames: &[usize]) {
+        if self.instance_frames != frames {
+            self.instance_frames = frames.to_vec();
+            self.write_dimensions();
+        }
+    }
+
+    /// Draw a border around the opaque parts of frames, or none with `None`.
+    /// The width is capped at `MAX_OUTLINE_WIDTH` texels.
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
+        let dimensions = &mut self.current_dimensions;
+        match outline {
+            Some(outline) if outline.width > 0.0 => {
+                let [r, g, b] = outline
+                    .color
+                    .map(|channel| srgb_to_linear(channel as f32 / 255.0));
+                dimensions.outline = [r, g, b, 1.0];
+                dimensions.outline_width = outline.width.min(MAX_OUTLINE_WIDTH);
+            }
+            _ => {
+                dimensions.outline = [0.0; 4];
+                dimensions.outline_width = 0.0;
+            }
+        }
+        self.write_dimensions();
+    }
+
+    /// Draw a blurred silhouette of frames behind them, or no shadow with
+    /// `None`. Frames are inset by `Shadow::margin` on every side to leave
+    /// room for it. Shadows need a surface compositing premultiplied alpha.
+    pub fn set_shadow(&mut self, shadow: Option<Shadow>) {
+        self.shadow = match shadow {
+            Some(_) if self.config.alpha_mode != wgpu::CompositeAlphaMode::PreMultiplied => {
+                log::warn!("Drop shadows need a transparent surface, drawing without one");
+                None
+            }
+            Some(shadow) => {
+                let silhouette_source =
+                    format!("{}{}", PACKED_COMMON, PACKED_SILHOUETTE_FRAGMENT_SHADER);
+                Some(ShadowEffect {
+                    shadow,
+                    silhouette: FramePipeline::new(
+                        &self.gpu.device,
+                        &self.gpu.layouts,
+                        SILHOUETTE_FORMAT,
+                        SILHOUETTE_FRAGMENT_SHADER,
+                    ),
+                    packed_silhouette: FramePipeline::new(
+                        &self.gpu.device,
+                        &self.gpu.layouts,
+                        SILHOUETTE_FORMAT,
+                        &silhouette_source,
+                    ),
+                    pass: ShadowPass::new(
+                        &self.gpu.device,
+                        &self.gpu.queue,
+                        shadow.scaled(self.pixel_factor()),
+                        self.render_size(),
+                        self.config.format,
+                    ),
+                })
+            }
+            None => None,
+        };
+        self.write_dimensions();
+    }
+
+    /// Make texels close to the key's color transparent, or turn keying off
+    /// with `None`
+    pub fn set_chroma_key(&mut self, key: Option<ChromaKey>) {
+        let dimensions = &mut self.current_dimensions;
+        match key {
+            Some(key) => {
+                let [r, g, b] = key
+                    .color
+                    .map(|channel| srgb_to_linear(channel as f32 / 255.0));
+                dimensions.chroma_key = [r, g, b, 1.0];
+                dimensions.chroma_range = [key.tolerance.max(0.0), key.softness.max(0.0)];
+            }
+            None => {
+                dimensions.chroma_key = [0.0; 4];
+                dimensions.chroma_range = [0.0; 2];
+            }
+        }
+        self.write_dimensions();
+    }
+
+    /// Draw full-depth frames with the fragment shader at `path` from the
+    /// next render on. If it fails to compile the current shader stays.
+    pub fn set_fragment_shader(&mut self, path: &Path) -> Result<()> {
+        let fragment_source = load_fragment_shader(path)?;
+        self.pipeline = FramePipeline::new(
+            &self.gpu.device,
+            &self.gpu.layouts,
+            self.config.format,
+            &fragment_source,
+        );
+        Ok(())
+    }
+
+    /// Filtering frames are sampled with, integer scaling always magnifies
+    /// with nearest sampling
+    fn sampling(&self) -> Filtering {
+        if self.scale_mode == ScaleMode::IntegerScale {
+            Filtering::Nearest
+        } else {
+            self.filter
+        }
+    }
+
+    /// Whether frames are drawn by a software rasterizer rather than a GPU
+    pub fn is_software(&self) -> bool {
+        self.gpu.adapter.get_info().device_type == wgpu::DeviceType::Cpu
+    }
+
+    /// Largest width or height a frame texture may have on this device
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.gpu.device.limits().max_texture_dimension_2d
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let max_dimension = self.max_texture_dimension();
+        let (width, height) = (width.min(max_dimension), height.min(max_dimension));
+
+        self.config.width = width;
+        self.config.height = height;
+
+        if let Some(ref surface) = self.surface {
+            surface.configure(&self.gpu.device, &self.config);
+        }
+
+        self.update_render_size();
+
+        log::info!("Resized to {}x{}", width, height);
+    }
+
+    /// Draw frames at `scale` times the window's size and filter them down
+    /// to it, smoothing edges and detail lost when frames are shrunk. The
+    /// scale is limited to `MAX_RENDER_SCALE` and the device's texture size,
+    /// at 1 frames are drawn straight into the window.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale;
+        self.update_render_size();
+        let factor = self.render_factor();
+        if (factor - scale).abs() > 0.01 {
+            log::warn!(
+                "Render scale {} is out of range, drawing at {:.2}",
+                scale,
+                factor
+            );
+        }
+        // The shadow's offset and blur are in window pixels
+        if let Some(shadow) = self.shadow.as_ref().map(|effect| effect.shadow) {
+            self.set_shadow(Some(shadow));
+        }
+    }
+
+    /// Treat shadow offsets, blur and instance spacing as logical pixels of
+    /// a display at `factor`, for windows sized in logical pixels
+    pub fn set_scale_factor(&mut self, factor: f64) {
+        self.scale_factor = factor as f32;
+        if let Some(shadow) = self.shadow.as_ref().map(|effect| effect.shadow) {
+            self.set_shadow(Some(shadow));
+        }
+        self.write_dimensions();
+    }
+
+    /// Size frames are drawn at, larger than the window when supersampling
+    fn render_size(&self) -> (u32, u32) {
+        self.supersampler
+            .as_ref()
+            .map_or((self.config.width, self.config.height), Supersampler::size)
+    }
+
+    /// How many render pixels make up one window pixel
+    fn render_factor(&self) -> f32 {
+        self.render_size().0 as f32 / self.config.width.max(1) as f32
+    }
+
+    /// How many render pixels make up one pixel of shadow offsets, blur and
+    /// instance spacing
+    fn pixel_factor(&self) -> f32 {
+        self.render_factor() * self.scale_factor
+    }
+
+    /// Size the offscreen targets for the window's size and render scale
+    fn update_render_size(&mut self) {
+        let window = (self.config.width, self.config.height);
+        let size = scaled_size(window, self.render_scale, self.max_texture_dimension());
+        if size == window {
+            self.supersampler = None;
+        } else if let Some(supersampler) = &mut self.supersampler {
+            supersampler.resize(&self.gpu.device, &self.gpu.queue, size, window);
+        } else {
+            self.supersampler = Some(Supersampler::new(
+                &self.gpu.device,
+                &self.gpu.queue,
+                self.config.format,
+                size,
+                window,
+            ));
+        }
+
+        if let Some(shadow) = &mut self.shadow {
+            shadow.pass.resize(&self.gpu.device, &self.gpu.queue, size);
+        }
+
+        self.current_dimensions.window_width = size.0 as f32;
+        self.current_dimensions.window_height = size.1 as f32;
+        self.write_dimensions();
+    }
+
+    /// Upload the frames of every sequence to GPU memory, replacing whatever
+    /// was loaded before. The first sequence becomes the active one.
+    ///
+    /// Distinct frames of a sequence share one array texture when they all
+    /// have the same size and fit within the device's layer limit, otherwise
+    /// every distinct frame gets its own texture. `progress` is told about
+    /// every frame uploaded.
+    pub fn preload_sequences(&mut self, sequences: &[&[&RgbaImage]], progress: &ProgressFn) {
+        if sequences.iter().all(|images| images.is_empty()) {
+            log::warn!("No images to preload");
+            return;
+        }
+
+        // Clear any existing sequence
+        self.sequence_type = None;
+
+        let frame_count: usize = sequences.iter().map(|images| images.len()).sum();
+        log::info!(
+            "Preloading {} images of {} sequences to GPU memory (uncompressed)",
+            frame_count,
+            sequences.len()
+        );
+
+        let deduped: Vec<_> = sequences
+            .iter()
+            .map(|images| dedup_frames(images))
+            .collect();
+        let texture_count: usize = deduped
+            .iter()
+            .map(|(unique_frames, _)| unique_frames.len())
+            .sum();
+
+        let mut uploaded = 0;
+        let mut texel_count = 0;
+        let sequences: Vec<FrameSet> = sequences
+            .iter()
+            .zip(deduped)
+            .enumerate()
+            .map(|(sequence, (images, (unique_frames, frame_textures)))| {
+                let unique_images: Vec<&RgbaImage> =
+                    unique_frames.iter().map(|&i| images[i]).collect();
+                texel_count += unique_images
+                    .iter()
+                    .map(|image| image.width() as u64 * image.height() as u64)
+                    .sum::<u64>();
+
+                let mut report = || {
+                    uploaded += 1;
+                    progress(uploaded, texture_count, "GPU upload");
+                };
+
+                FrameSet {
+                    storage: self.create_frame_storage(sequence, &unique_images, &mut report),
+                    frame_textures,
+                    image_size: images.first().map_or((1, 1), |image| image.dimensions()),
+                }
+            })
+            .collect();
+
+        self.sequence_type = Some(SequenceType::Uncompressed { sequences });
+        self.active_sequence = 0;
+        self.current_texture_index = 0;
+        self.update_image_dimensions();
+
+        log::info!(
+            "Preloaded {} images to GPU memory as {} unique textures (uncompressed)",
+            frame_count,
+            texture_count
+        );
+
+        if self.low_memory {
+            // Two bytes per texel instead of four
+            let saved = texel_count * 2;
+            log::info!(
+                "Low-memory textures save about {:.2} MB",
+                saved as f64 / (1024.0 * 1024.0)
+            );
+        }
+
+        self.log_gpu_memory();
+    }
+
+    /// Estimate the video memory taken by the uploaded frames and the
+    /// surface. Drivers add alignment and bookkeeping on top, so the real
+    /// figure is somewhat higher.
+    pub fn gpu_memory_estimate(&self) -> GpuMemoryEstimate {
+        let textures = match &self.sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => {
+                sequences.iter().flat_map(FrameSet::textures).collect()
+            }
+            Some(SequenceType::Compressed {
+                current_frame_bind_group,
+                ..
+            }) => vec![&current_frame_bind_group.texture],
+            None => Vec::new(),
+        };
+
+        // Besides the image being shown, one per frame that may be queued
+        let surface_bytes = if self.surface.is_some() {
+            let swapchain_images = self.config.desired_maximum_frame_latency as u64 + 1;
+            let texel_size = self.config.format.block_copy_size(None).unwrap_or(4) as u64;
+            self.config.width as u64 * self.config.height as u64 * texel_size * swapchain_images
+        } else {
+            0
+        };
+
+        GpuMemoryEstimate {
+            texture_bytes: textures.iter().map(|texture| texture_memory(texture)).sum(),
+            texture_count: textures.len(),
+            surface_bytes,
+        }
+    }
+
+    fn log_gpu_memory(&self) {
+        let estimate = self.gpu_memory_estimate();
+        log::info!(
+            "Frames take about {} of GPU memory across {} textures",
+            format_memory(estimate.texture_bytes),
+            estimate.texture_count
+        );
+        if estimate.surface_bytes > 0 {
+            log::info!(
+                "The surface takes about {} more",
+                format_memory(estimate.surface_bytes)
+            );
+        }
+    }
+
+    /// Upload the distinct frames of one sequence, as layers of a single
+    /// texture when possible. `report` is called after every frame.
+    fn create_frame_storage(
+        &self,
+        sequence: usize,
+        images: &[&RgbaImage],
+        report: &mut dyn FnMut(),
+    ) -> FrameStorage {
+        if self.atlas
+            && let Some(storage) = self.create_atlas(sequence, images, report)
+        {
+            return storage;
+        }
+
+        let same_size = images
+            .windows(2)
+            .all(|pair| pair[0].dimensions() == pair[1].dimensions());
+        let max_layers = self.gpu.device.limits().max_texture_array_layers as usize;
+
+        if images.is_empty() || !same_size || images.len() > max_layers {
+            if images.len() > max_layers {
+                log::info!(
+                    "Sequence {} has {} distinct frames but texture arrays hold at most {}, \
+                     using one texture per frame",
+                    sequence,
+                    images.len(),
+                    max_layers
+                );
+            }
+
+            let format = self.frame_format(images);
+            self.log_block_compression(sequence, images, format);
+            return FrameStorage::Separate(
+                images
+                    .iter()
+                    .enumerate()
+                    .map(|(index, image)| {
+                        let label = format!("Frame {}", index);
+                        let texture =
+                            self.create_frame_texture(&label, image.dimensions(), 1, format);
+                        self.write_frame(&texture, wgpu::Origin3d::ZERO, image);
+                        self.mipmap_generator.generate(&texture, 0..1);
+                        report();
+                        self.create_texture_bind_group(&label, &texture)
+                    })
+                    .collect(),
+            );
+        }
+
+        let label = format!("Sequence {} Frames", sequence);
+        let format = self.frame_format(images);
+        self.log_block_compression(sequence, images, format);
+        let texture =
+            self.create_frame_texture(&label, images[0].dimensions(), images.len() as u32, format);
+        for (layer, image) in images.iter().enumerate() {
+            self.write_frame(&texture, array_layer(layer as u32), image);
+            report();
+        }
+        self.mipmap_generator
+            .generate(&texture, 0..images.len() as u32);
+
+        FrameStorage::Array {
+            binding: self.create_texture_bind_group(&label, &texture),
+            texture,
+            layer_count: images.len() as u32,
+        }
+    }
+
+    /// Pack the distinct frames of one sequence into as few atlas pages as
+    /// possible, `None` when a frame is larger than a page
+    fn create_atlas(
+        &self,
+        sequence: usize,
+        images: &[&RgbaImage],
+        report: &mut dyn FnMut(),
+    ) -> Option<FrameStorage> {
+        let max_page = self.max_texture_dimension().min(ATLAS_MAX_PAGE_SIZE);
+        let sizes: Vec<_> = images.iter().map(|image| image.dimensions()).collect();
+        let page_size = atlas_page_size(&sizes, max_page)?;
+
+        let mut packer = ShelfPacker::new(page_size);
+        let placements: Vec<AtlasPlacement> = sizes
+            .iter()
+            .map(|&size| packer.place(size))
+            .collect::<Option<_>>()?;
+
+        let page_count = placements.last().map_or(0, |placement| placement.page + 1);
+        let pages: Vec<_> = (0..page_count)
+            .map(|page| self.create_atlas_page(page, page_size))
+            .collect();
+
+        for (image, placement) in images.iter().zip(&placements) {
+            self.write_frame(&pages[placement.page].0, placement.origin(), image);
+            report();
+        }
+        for (texture, _) in &pages {
+            self.mipmap_generator.generate(texture, 0..1);
+        }
+
+        log::info!(
+            "Packed {} frames of sequence {} into {} atlas pages of {}x{}",
+            images.len(),
+            sequence,
+            page_count,
+            page_size.0,
+            page_size.1
+        );
+
+        Some(FrameStorage::Atlas {
+            pages,
+            placements,
+            packer,
+        })
+    }
+
+    /// Create an empty atlas page and the bind group sampling it
+    fn create_atlas_page(&self, page: usize, size: (u32, u32)) -> (wgpu::Texture, TextureBinding) {
+        let label = format!("Atlas Page {}", page);
+        let texture = self.create_frame_texture(&label, size, 1, self.uncompressed_format());
+        let binding = self.create_texture_bind_group(&label, &texture);
+        (texture, binding)
+    }
+
+    /// The GPU this renderer draws with, for the renderers of other windows
+    /// to share
+    pub fn gpu(&self) -> &Arc<Gpu> {
+        &self.gpu
+    }
+
+    /// Handles to the frames this renderer holds on the GPU. Renderers
+    /// drawing with the same `Gpu` draw them with `share_frames` rather than
+    /// uploading them again.
+    pub fn frames(&self) -> SharedFrames {
+        SharedFrames {
+            gpu: self.gpu.clone(),
+            sequence_type: self.sequence_type.clone(),
+        }
+    }
+
+    /// Draw `frames`, taken from another renderer on the same GPU, in place
+    /// of the frames held so far. The active sequence and frame stay.
+    pub fn share_frames(&mut self, frames: SharedFrames) -> Result<()> {
+        if !frames.is_on(&self.gpu) {
+            return Err(anyhow!("Frames on another GPU can't be shared"));
+        }
+        self.sequence_type = frames.sequence_type;
+        self.update_image_dimensions();
+        Ok(())
+    }
+
+    /// Show the sequence at `index` from its first frame, all of its frames
+    /// are already on the GPU
+    pub fn select_sequence(&mut self, index: usize) -> Result<()> {
+        let sequence_count = match &self.sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => sequences.len(),
+            Some(SequenceType::Compressed { .. }) => 1,
+            None => 0,
+        };
+
+        if index >= sequence_count {
+            return Err(anyhow::anyhow!(
+                "Sequence {} is not loaded, only {} are",
+                index,
+                sequence_count
+            ));
+        }
+
+        self.active_sequence = index;
+        self.current_texture_index = 0;
+        self.update_image_dimensions();
+        Ok(())
+    }
+
+    /// Point the Dimensions uniform at the frame size of the active sequence
+    fn update_image_dimensions(&mut self) {
+        let (width, height) = match &self.sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => {
+                let Some(frame_set) = sequences.get(self.active_sequence) else {
+                    return;
+                };
+                frame_set.image_size
+            }
+            Some(SequenceType::Compressed {
+                current_frame_bind_group,
+                ..
+            }) => {
+                let texture = &current_frame_bind_group.texture;
+                (texture.width(), texture.height())
+            }
+            None => return,
+        };
+        self.current_dimensions.image_width = width as f32;
+        self.current_dimensions.image_height = height as f32;
+        self.update_frame_layer();
+    }
+
+    /// Point the Dimensions uniform at the array layer and atlas rectangle of
+    /// the current frame
+    fn update_frame_layer(&mut self) {
+        let (layer, uv_rect) = match &self.sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => sequences
+                .get(self.active_sequence)
+                .and_then(|frame_set| frame_set.frame(self.current_texture_index))
+                .map_or((0, FULL_UV_RECT), |(_, layer, uv_rect)| (layer, uv_rect)),
+            _ => (0, FULL_UV_RECT),
+        };
+
+        self.current_dimensions.layer = layer;
+        self.current_dimensions.uv_rect = uv_rect;
+        self.write_dimensions();
+    }
+
+    /// Place the frame of every instance within its cell of the window,
+    /// inside the shadow's margin, and upload their Dimensions uniforms
+    fn write_dimensions(&mut self) {
+        let cells = self.instance_cells();
+        self.current_dimensions.draw_rect = self.place_in_cell(cells[0]);
+        self.current_dimensions.rotation = rotation_rows(self.rotation);
+        self.gpu.queue.write_buffer(
+            &self.dimensions_buffer,
+            0,
+            bytemuck::cast_slice(&[self.current_dimensions]),
+        );
+
+        for (instance, cell) in cells.iter().enumerate().skip(1) {
+            let mut dimensions = self.current_dimensions;
+            dimensions.draw_rect = self.place_in_cell(*cell);
+            if let Some((layer, uv_rect, next_layer)) = self.instance_layers(instance) {
+                dimensions.layer = layer;
+                dimensions.uv_rect = uv_rect;
+                dimensions.next_layer = next_layer;
+            }
+            self.gpu.queue.write_buffer(
+                &self.dimensions_buffer,
+                instance as u64 * self.dimensions_stride,
+                bytemuck::cast_slice(&[dimensions]),
+            );
+        }
+    }
+
+    /// Rectangle in render pixels the current frame is drawn at within
+    /// `cell`, before rotation
+    fn place_in_cell(&self, [cell_x, cell_y, cell_width, cell_height]: [f32; 4]) -> [f32; 4] {
+        let dimensions = &self.current_dimensions;
+        let [x, y, width, height] = rotated_draw_rect(
+            (cell_width, cell_height),
+            (dimensions.image_width, dimensions.image_height),
+            self.rotation,
+            self.scale_mode,
+            self.sampling() == Filtering::Nearest,
+        );
+        [cell_x + x, cell_y + y, width, height]
+    }
+
+    /// Rectangles as x, y, width and height in window pixels the current
+    /// frame of every instance is drawn at, before rotation
+    pub fn frame_rects(&self) -> Vec<[f32; 4]> {
+        let factor = self.render_factor();
+        self.instance_cells()
+            .into_iter()
+            .map(|cell| self.place_in_cell(cell).map(|value| value / factor))
+            .collect()
+    }
+
+    /// Cells of the window the instances are placed in, see
+    /// `instance_cells`
+    fn instance_cells(&self) -> Vec<[f32; 4]> {
+        let margin = self
+            .shadow
+            .as_ref()
+            .map_or(0.0, |shadow| shadow.pass.shadow().margin() as f32);
+        instance_cells(
+            (
+                self.current_dimensions.window_width,
+                self.current_dimensions.window_height,
+            ),
+            margin,
+            self.instance_count,
+            self.instance_spacing * self.pixel_factor(),
+        )
+    }
+
+    /// Frame index `instance` shows
+    fn instance_frame(&self, instance: usize) -> usize {
+        instance
+            .checked_sub(1)
+            .and_then(|index| self.instance_frames.get(index))
+            .copied()
+            .unwrap_or(self.current_texture_index)
+    }
+
+    /// Array layer, atlas rectangle and crossfade layer of the frame
+    /// `instance` shows, which only differ between instances of
+    /// uncompressed sequences
+    fn instance_layers(&self, instance: usize) -> Option<(u32, [f32; 4], u32)> {
+        let Some(SequenceType::Uncompressed { sequences }) = &self.sequence_type else {
+            return None;
+        };
+        let frame_set = sequences.get(self.active_sequence)?;
+        let count = frame_set.frame_textures.len();
+        if count == 0 {
+            return None;
+        }
+
+        let frame = self.instance_frame(instance) % count;
+        let (_, layer, uv_rect) = frame_set.frame(frame)?;
+        // Every instance fades as far ahead as the first one
+        let next_layer = self
+            .crossfade_next
+            .map(|next| (frame + next + count - self.current_texture_index % count) % count)
+            .and_then(|next| frame_set.frame(next))
+            .map_or(layer, |(_, next_layer, _)| next_layer);
+        Some((layer, uv_rect, next_layer))
+    }
+
+    /// Replace frame `index` of sequence `sequence`, or append it when it is
+    /// the next frame of that sequence
+    pub fn upload_image(&mut self, sequence: usize, index: usize, image: &RgbaImage) -> Result<()> {
+        // Moved out so the texture helpers can borrow the renderer
+        let mut sequence_type = self.sequence_type.take();
+        let result = match &mut sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => match sequences.get_mut(sequence) {
+                Some(frame_set) => self.replace_frame(frame_set, index, image),
+                None => Err(anyhow::anyhow!("Sequence {} is not loaded", sequence)),
+            },
+            Some(SequenceType::Compressed { .. }) => Err(anyhow::anyhow!(
+                "Cannot upload individual frames to a delta-compressed sequence"
+            )),
+            None => Err(anyhow::anyhow!("No sequence has been preloaded")),
+        };
+        self.sequence_type = sequence_type;
+
+        if result.is_ok() {
+            self.update_frame_layer();
+        }
+        result
+    }
+
+    /// Store `image` as frame `index` of `frame_set`, see `upload_image`
+    fn replace_frame(
+        &self,
+        frame_set: &mut FrameSet,
+        index: usize,
+        image: &RgbaImage,
+    ) -> Result<()> {
+        let frame_count = frame_set.frame_textures.len();
+        let slot = if index < frame_count {
+            let slot = frame_set.frame_textures[index];
+            let shared = frame_set
+                .frame_textures
+                .iter()
+                .filter(|&&other| other == slot)
+                .count()
+                > 1;
+
+            // Other frames still show the old texture
+            if shared { frame_set.slot_count() } else { slot }
+        } else if index == frame_count {
+            frame_set.slot_count()
+        } else {
+            return Err(anyhow::anyhow!(
+                "Frame {} uploaded before frame {}",
+                index,
+                frame_count
+            ));
+        };
+
+        if let FrameStorage::Array {
+            texture,
+            layer_count,
+            ..
+        } = &frame_set.storage
+        {
+            let size = (texture.width(), texture.height());
+            let max_layers = self.gpu.device.limits().max_texture_array_layers;
+
+            // BC1 would drop the new frame's transparency
+            let loses_alpha =
+                texture.format() == wgpu::TextureFormat::Bc1RgbaUnormSrgb && !is_opaque(image);
+
+            if image.dimensions() != size || slot as u32 >= max_layers || loses_alpha {
+                log::info!(
+                    "Frame {} no longer fits the texture array, splitting it",
+                    index
+                );
+                frame_set.storage = self.split_array(texture, *layer_count);
+            } else if slot as u32 >= texture.depth_or_array_layers() {
+                let capacity = (*layer_count * 2).clamp(slot as u32 + 1, max_layers);
+                frame_set.storage = self.grow_array(texture, *layer_count, capacity);
+            }
+        }
+
+        match &mut frame_set.storage {
+            FrameStorage::Array {
+                texture,
+                layer_count,
+                ..
+            } => {
+                self.write_frame(texture, array_layer(slot as u32), image);
+                self.mipmap_generator
+                    .generate(texture, slot as u32..slot as u32 + 1);
+                *layer_count = (*layer_count).max(slot as u32 + 1);
+            }
+            FrameStorage::Separate(bind_groups) => {
+                let label = format!("Frame {}", index);
+                let texture = self.create_frame_texture(
+                    &label,
+                    image.dimensions(),
+                    1,
+                    self.frame_format(&[image]),
+                );
+                self.write_frame(&texture, wgpu::Origin3d::ZERO, image);
+                self.mipmap_generator.generate(&texture, 0..1);
+                let bind_group = self.create_texture_bind_group(&label, &texture);
+
+                if slot < bind_groups.len() {
+                    bind_groups[slot] = bind_group;
+                } else {
+                    bind_groups.push(bind_group);
+                }
+            }
+            FrameStorage::Atlas {
+                pages,
+                placements,
+                packer,
+            } => {
+                // A replaced frame's space is only reused by one of the same size
+                let placement = match placements.get(slot) {
+                    Some(placement)
+                        if (placement.width, placement.height) == image.dimensions() =>
+                    {
+                        *placement
+                    }
+                    _ => packer.place(image.dimensions()).ok_or_else(|| {
+                        anyhow::anyhow!("Frame {} is larger than an atlas page", index)
+                    })?,
+                };
+
+                if placement.page == pages.len() {
+                    pages.push(self.create_atlas_page(placement.page, packer.page_size));
+                }
+                self.write_frame(&pages[placement.page].0, placement.origin(), image);
+                self.mipmap_generator
+                    .generate(&pages[placement.page].0, 0..1);
+
+                if slot < placements.len() {
+                    placements[slot] = placement;
+                } else {
+                    placements.push(placement);
+                }
+            }
+        }
+
+        if index < frame_count {
+            frame_set.frame_textures[index] = slot;
+        } else {
+            frame_set.frame_textures.push(slot);
+        }
+
+        Ok(())
+    }
+
+    /// Copy the first `layer_count` layers of `texture` into a new array
+    /// texture with room for `capacity` layers
+    fn grow_array(&self, texture: &wgpu::Texture, layer_count: u32, capacity: u32) -> FrameStorage {
+        let grown = self.create_frame_texture(
+            "Grown Frames",
+            (texture.width(), texture.height()),
+            capacity,
+            texture.format(),
+        );
+
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Grow Frame Array Encoder"),
+            });
+        for mip_level in 0..texture.mip_level_count() {
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo {
+                    mip_level,
+                    ..texture.as_image_copy()
+                },
+                wgpu::TexelCopyTextureInfo {
+                    mip_level,
+                    ..grown.as_image_copy()
+                },
+                mip_extent(texture, mip_level, layer_count),
+            );
+        }
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        FrameStorage::Array {
+            binding: self.create_texture_bind_group("Grown Frames", &grown),
+            texture: grown,
+            layer_count,
+        }
+    }
+
+    /// Copy each of the first `layer_count` layers of `texture` into a
+    /// texture of its own
+    fn split_array(&self, texture: &wgpu::Texture, layer_count: u32) -> FrameStorage {
+        let size = (texture.width(), texture.height());
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Split Frame Array Encoder"),
+            });
+
+        let bind_groups = (0..layer_count)
+            .map(|layer| {
+                let label = format!("Frame {}", layer);
+                let frame = self.create_frame_texture(&label, size, 1, texture.format());
+                for mip_level in 0..texture.mip_level_count() {
+                    encoder.copy_texture_to_texture(
+                        wgpu::TexelCopyTextureInfo {
+                            texture,
+                            mip_level,
+                            origin: array_layer(layer),
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        wgpu::TexelCopyTextureInfo {
+                            mip_level,
+                            ..frame.as_image_copy()
+                        },
+                        mip_extent(texture, mip_level, 1),
+                    );
+                }
+                self.create_texture_bind_group(&label, &frame)
+            })
+            .collect();
+
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+        FrameStorage::Separate(bind_groups)
+    }
+
+    /// Format of frame textures when block compression doesn't apply
+    fn uncompressed_format(&self) -> wgpu::TextureFormat {
+        if self.low_memory {
+            wgpu::TextureFormat::Rg8Unorm
+        } else {
+            MIPMAP_FORMAT
+        }
+    }
+
+    /// Format to store `images` in under the current memory mode
+    fn frame_format(&self, images: &[&RgbaImage]) -> wgpu::TextureFormat {
+        if !self.block_compression
+            || self.low_memory
+            || images.is_empty()
+            || !images
+                .iter()
+                .all(|image| is_block_aligned(image.dimensions()))
+        {
+            return self.uncompressed_format();
+        }
+
+        if images.iter().all(|image| is_opaque(image)) {
+            wgpu::TextureFormat::Bc1RgbaUnormSrgb
+        } else {
+            wgpu::TextureFormat::Bc7RgbaUnormSrgb
+        }
+    }
+
+    /// Report what block compression saves for the frames of `sequence`
+    fn log_block_compression(
+        &self,
+        sequence: usize,
+        images: &[&RgbaImage],
+        format: wgpu::TextureFormat,
+    ) {
+        let bytes_per_texel = match format {
+            wgpu::TextureFormat::Bc1RgbaUnormSrgb => 0.5,
+            wgpu::TextureFormat::Bc7RgbaUnormSrgb => 1.0,
+            _ => {
+                if self.block_compression {
+                    log::info!(
+                        "Sequence {} can't be block-compressed, frame sizes must be multiples of 4",
+                        sequence
+                    );
+                }
+                return;
+            }
+        };
+
+        let texels: u64 = images
+            .iter()
+            .map(|image| image.width() as u64 * image.height() as u64)
+            .sum();
+        let megabytes = |bytes: f64| bytes / (1024.0 * 1024.0);
+        log::info!(
+            "Sequence {} stored as {:?}: {:.2} MB instead of {:.2} MB",
+            sequence,
+            format,
+            megabytes(texels as f64 * bytes_per_texel),
+            megabytes(texels as f64 * 4.0)
+        );
+    }
+
+    /// Create a texture holding `layers` frames of the given size in
+    /// `format`, with a mip chain if mipmaps are on and `format` can have
+    /// them generated
+    fn create_frame_texture(
+        &self,
+        label: &str,
+        size: (u32, u32),
+        layers: u32,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::Texture {
+        // Averaging packed texels would mix up their channels, and
+        // compressed textures can't be drawn into
+        let mip_level_count = if self.mipmaps && format == MIPMAP_FORMAT {
+            mip_level_count(size.0, size.1)
+        } else {
+            1
+        };
+
+        // Mip levels are filled by drawing into them
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        // The GL backend guesses a square texture with a multiple of six
+        // layers is a cube map, so such arrays get one unused layer more
+        let max_layers = self.gpu.device.limits().max_texture_array_layers;
+        let layers =
+            if layers > 1 && size.0 == size.1 && layers.is_multiple_of(6) && layers < max_layers {
+                layers + 1
+            } else {
+                layers
+            };
+
+        self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Copied from when an array has to grow or be split
+            usage,
+            view_formats: &[],
+        })
+    }
+
+    /// Write `image` into a texture from `create_frame_texture`, with its
+    /// top-left corner at `origin`
+    fn write_frame(&self, texture: &wgpu::Texture, origin: wgpu::Origin3d, image: &RgbaImage) {
+        let dimensions = image.dimensions();
+
+        // Rows of compressed data hold a row of 4x4 blocks
+        let encoded;
+        let (data, bytes_per_row, rows): (&[u8], u32, u32) = match texture.format() {
+            wgpu::TextureFormat::Rg8Unorm => {
+                encoded = pack_rgba4444(image);
+                (&encoded, 2 * dimensions.0, dimensions.1)
+            }
+            wgpu::TextureFormat::Bc1RgbaUnormSrgb => {
+                encoded = encode_bc1(image);
+                (&encoded, 2 * dimensions.0, dimensions.1 / 4)
+            }
+            wgpu::TextureFormat::Bc7RgbaUnormSrgb => {
+                encoded = encode_bc7(image);
+                (&encoded, 4 * dimensions.0, dimensions.1 / 4)
+            }
+            _ => (image, 4 * dimensions.0, dimensions.1),
+        };
+
+        self.gpu.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(rows),
+            },
+            wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Bind every layer of `texture` as a texture array
+    fn create_texture_bind_group(&self, label: &str, texture: &wgpu::Texture) -> TextureBinding {
+        // The GL backend binds single-layer textures as plain 2D textures and
+        // cannot view them as arrays, so only real arrays get an array view
+        let array = texture.depth_or_array_layers() > 1;
+        let (dimension, layout) = if array {
+            (wgpu::TextureViewDimension::D2Array, &self.gpu.layouts.array)
+        } else {
+            (
+                wgpu::TextureViewDimension::D2,
+                &self.gpu.layouts.single_layer,
+            )
+        };
+
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(dimension),
+            ..Default::default()
+        });
+
+        let bind_group = self
+            .gpu
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} Bind Group", label)),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                }],
+            });
+
+        TextureBinding {
+            bind_group,
+            texture: texture.clone(),
+            array,
+        }
+    }
+
+    pub async fn preload_images_compressed(&mut self, images: &[&RgbaImage]) -> Result<()> {
+        if images.is_empty() {
+            log::warn!("No images to compress");
+            return Ok(());
+        }
+
+        // Calculate original memory usage
+        let original_size: usize = images.iter().map(|img| img.as_raw().len()).sum();
+        log::info!(
+            "Original sequence size: {:.2} MB",
+            original_size as f64 / (1024.0 * 1024.0)
+        );
+
+        // Clear any existing sequence
+        self.sequence_type = None;
+        let first_dims = images[0].dimensions();
+
+        log::info!("Compressing {} images with delta compression", images.len());
+
+        // Compress the sequence
+        let compressed_sequence = if let Some(ref mut compressor) = self.delta_compressor {
+            compressor.compress_sequence(images).await?
+        } else {
+            return Err(anyhow::anyhow!("Delta compressor not initialized"));
+        };
+
+        // Log compression statistics
+        let compressed_size = compressed_sequence.memory_usage();
+        let compression_ratio = compressed_sequence.compression_ratio(original_size);
+        log::info!(
+            "Compressed sequence size: {:.2} MB",
+            compressed_size as f64 / (1024.0 * 1024.0)
+        );
+        log::info!("Compression ratio: {:.2}x", compression_ratio);
+
+        // Create texture for current frame
+        let texture_size = wgpu::Extent3d {
+            width: first_dims.0,
+            height: first_dims.1,
+            depth_or_array_layers: 1,
+        };
+
+        let current_frame_texture = self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Current Frame Texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Upload base frame initially
+        self.gpu.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &current_frame_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &compressed_sequence.base_frame,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * first_dims.0),
+                rows_per_image: Some(first_dims.1),
+            },
+            texture_size,
+        );
+
+        let current_frame_bind_group =
+            self.create_texture_bind_group("Current Frame", &current_frame_texture);
+
+        self.sequence_type = Some(SequenceType::Compressed {
+            compressed_sequence: Arc::new(compressed_sequence),
+            current_frame_bind_group,
+            reconstructed_frame: Arc::new(Mutex::new(ReconstructedFrame {
+                index: 0,
+                image: Arc::new(images[0].to_owned()),
+            })),
+        });
+
+        self.active_sequence = 0;
+        self.current_texture_index = 0;
+        // The window takes the first image's size
+        self.update_image_dimensions();
+        log::info!("Successfully set up delta-compressed sequence");
+        self.log_gpu_memory();
+
+        Ok(())
+    }
+
+    pub async fn set_current_texture_index(&mut self, index: usize) -> Result<()> {
+        match &mut self.sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => {
+                if let Some(frame_set) = sequences.get(self.active_sequence)
+                    && !frame_set.frame_textures.is_empty()
+                {
+                    self.current_texture_index = index % frame_set.frame_textures.len();
+                    self.update_frame_layer();
+                }
+            }
+            Some(SequenceType::Compressed {
+                compressed_sequence,
+                current_frame_bind_group,
+                reconstructed_frame,
+            }) => {
+                if index >= compressed_sequence.frame_count {
+                    return Ok(());
+                }
+
+                self.current_texture_index = index;
+
+                // Another renderer drawing the same texture may have shown
+                // the frame already
+                let base_frame = {
+                    let shown = reconstructed_frame.lock().unwrap();
+                    if shown.index == index {
+                        return Ok(());
+                    }
+                    shown.image.clone()
+                };
+
+                // Reconstruct the frame if it's not the base frame
+                let new_frame = if index == 0 {
+                    compressed_sequence.base_frame.clone()
+                } else {
+                    let delta_index = index - 1;
+                    if delta_index < compressed_sequence.deltas.len() {
+                        if let Some(ref mut compressor) = self.delta_compressor {
+                            compressor
+                                .reconstruct_frame(
+                                    &base_frame,
+                                    &compressed_sequence.deltas[delta_index],
+                                )
+                                .await?
+                        } else {
+                            return Err(anyhow::anyhow!("Delta compressor not available"));
+                        }
+                    } else {
+                        return Ok(());
+                    }
+                };
+
+                // Upload the new frame to the current frame texture
+                let (width, height) = new_frame.dimensions();
+                self.gpu.queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &current_frame_bind_group.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &new_frame,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * width),
+                        rows_per_image: Some(height),
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+
+                // Update the reconstructed frame for next iteration
+                *reconstructed_frame.lock().unwrap() = ReconstructedFrame {
+                    index,
+                    image: Arc::new(new_frame),
+                };
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Draw the current frame. Returns whether it was presented, a surface
+    /// that was lost or timed out skips the frame and should be retried on
+    /// the next redraw. Only running out of memory is an error.
+    pub fn render(&mut self) -> Result<bool> {
+        let surface = match &self.surface {
+            Some(surface) => surface,
+            None if self.headless => return Ok(false),
+            None => {
+                log::warn!("Cannot render: surface has been dropped");
+                return Ok(false);
+            }
+        };
+
+        let frame = match surface.get_current_texture() {
+            Ok(frame) => frame,
+            // Resolution changes and compositor restarts invalidate the
+            // swapchain, it works again once configured
+            Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                log::info!("Reconfiguring surface: {}", e);
+                surface.configure(&self.gpu.device, &self.config);
+                return Ok(false);
+            }
+            Err(e @ wgpu::SurfaceError::OutOfMemory) => return Err(e.into()),
+            Err(e) => {
+                log::warn!("Skipping frame: {}", e);
+                return Ok(false);
+            }
+        };
+        let suboptimal = frame.suboptimal;
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        self.draw_frame(&mut encoder, &view);
+        if let Some(menu) = &self.menu {
+            menu.draw(
+                &self.gpu.queue,
+                &mut encoder,
+                &view,
+                (self.config.width, self.config.height),
+            );
+        }
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        frame.present();
+
+        // Still presentable, but the next frame gets a matching swapchain
+        if suboptimal {
+            surface.configure(&self.gpu.device, &self.config);
+        }
+
+        Ok(true)
+    }
+
+    /// Draw the current frame as `render` presents it into `view`, which has
+    /// the surface's format
+    fn draw_frame(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // Delta-compressed frames are always reconstructed at full depth
+        let (bindings, packed): (Vec<Option<&TextureBinding>>, bool) = match &self.sequence_type {
+            Some(SequenceType::Uncompressed { sequences }) => {
+                let Some(frame_set) = sequences.get(self.active_sequence) else {
+                    return;
+                };
+                let count = frame_set.frame_textures.len().max(1);
+                let bindings = (0..self.instance_count)
+                    .map(|instance| {
+                        frame_set
+                            .frame(self.instance_frame(instance) % count)
+                            .map(|(binding, _, _)| binding)
+                    })
+                    .collect();
+                (bindings, frame_set.is_packed())
+            }
+            Some(SequenceType::Compressed {
+                current_frame_bind_group,
+                ..
+            }) => (
+                vec![Some(current_frame_bind_group); self.instance_count],
+                false,
+            ),
+            None => return,
+        };
+        if bindings.first().is_none_or(|binding| binding.is_none()) {
+            return;
+        }
+
+        // Supersampled frames are drawn offscreen and filtered into `view`
+        // at the end
+        let target = self.supersampler.as_ref().map_or(view, Supersampler::view);
+        let (width, height) = self.render_size();
+
+        // Each instance only draws into its own column, so surfaces without
+        // transparency get every pixel's background exactly once
+        let columns =
+            (self.instance_count > 1).then(|| instance_columns(&self.instance_cells(), width));
+        let draws: Vec<InstanceDraw> = bindings
+            .into_iter()
+            .enumerate()
+            .filter_map(|(instance, binding)| {
+                Some(InstanceDraw {
+                    binding: binding?,
+                    offset: (instance as u64 * self.dimensions_stride) as u32,
+                    scissor: columns.as_ref().map(|columns| (columns[instance], height)),
+                })
+            })
+            .collect();
+
+        // The background and shadow fill the target first and the frame
+        // blends over them
+        let load = match &self.shadow {
+            Some(shadow) => {
+                let silhouette = if packed {
+                    &shadow.packed_silhouette
+                } else {
+                    &shadow.silhouette
+                };
+                draw_pass(
+                    encoder,
+                    shadow.pass.silhouette_view(),
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    silhouette,
+                    &self.uniforms,
+                    &draws,
+                );
+                shadow.pass.draw(encoder, target, self.clear_color());
+                wgpu::LoadOp::Load
+            }
+            None => wgpu::LoadOp::Clear(self.clear_color()),
+        };
+
+        let pipeline = if packed {
+            &self.packed_pipeline
+        } else {
+            &self.pipeline
+        };
+        draw_pass(encoder, target, load, pipeline, &self.uniforms, &draws);
+
+        if let Some(supersampler) = &self.supersampler {
+            supersampler.draw(encoder, view);
+        }
+    }
+
+    /// Draw the current frame the way it is presented, scaling and effects
+    /// included, into an offscreen texture of the window's size and read it
+    /// back. The surface isn't touched, so presenting carries on unaffected.
+    pub fn capture_frame(&self) -> Result<RgbaImage> {
+        let target = self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // The pipelines only draw into the surface's format
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+        self.draw_frame(&mut encoder, &target.create_view(&Default::default()));
+
+        read_texture(&self.gpu.device, &self.gpu.queue, encoder, &target)
+    }
+}
+
+/// Read the WGSL fragment shader at `path` to use in place of
+/// `FRAGMENT_SHADER`, checking that it compiles with the declarations of
+/// `FRAGMENT_COMMON` and either texture binding
+pub fn load_fragment_shader(path: &Path) -> Result<String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read shader {}: {}", path.display(), e))?;
+
+    for binding in [SINGLE_LAYER_BINDING, ARRAY_BINDING] {
+        // Module scope declarations may come in any order, putting the file
+        // first keeps line numbers in errors matching it
+        let full_source = format!("{}{}{}", source, FRAGMENT_COMMON, binding);
+        let module =
+            validate_wgsl(&full_source, &path.display().to_string()).map_err(|message| {
+                anyhow!(
+                    "Shader {} failed to compile:\n{}\n{}",
+                    path.display(),
+                    message,
+                    CUSTOM_SHADER_INTERFACE
+                )
+            })?;
+
+        if !module.entry_points.iter().any(|entry_point| {
+            entry_point.name == "fs_main" && entry_point.stage == wgpu::naga::ShaderStage::Fragment
+        }) {
+            return Err(anyhow!(
+                "Shader {} has no fragment entry point named fs_main\n{}",
+                path.display(),
+                CUSTOM_SHADER_INTERFACE
+            ));
+        }
+    }
+
+    Ok(source)
+}
+
+/// Bytes taken by every mip level and layer of `texture`
+fn texture_memory(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let size = mip_extent(texture, level, texture.depth_or_array_layers());
+            size.width.div_ceil(block_width) as u64
+                * size.height.div_ceil(block_height) as u64
+                * size.depth_or_array_layers as u64
+                * block_size
+        })
+        .sum()
+}
+
+/// Byte count in the largest binary unit it reaches, e.g. "312 MiB"
+fn format_memory(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 || value >= 100.0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn create_instance() -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    })
+}
+
+/// Find the adapter `selection` asks for that can present to `surface`, or
+/// any adapter without one. A named adapter that doesn't exist or can't
+/// present is an error rather than a reason to pick another.
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface<'_>>,
+    selection: &AdapterSelection,
+) -> Result<wgpu::Adapter> {
+    let Some(gpu) = &selection.gpu else {
+        let power_preference = if selection.low_power {
+            wgpu::PowerPreference::LowPower
+        } else {
+            wgpu::PowerPreference::default()
+        };
+        let mut options = wgpu::RequestAdapterOptions {
+            power_preference,
+            force_fallback_adapter: false,
+            compatible_surface: surface,
+        };
+        if let Ok(adapter) = instance.request_adapter(&options).await {
+            return Ok(adapter);
+        }
+        if !selection.software_fallback {
+            return Err(anyhow!("Failed to find an appropriate adapter"));
+        }
+
+        // Virtual machines without GPU passthrough usually still have one
+        options.force_fallback_adapter = true;
+        let adapter = instance.request_adapter(&options).await.map_err(|_| {
+            anyhow!("Failed to find an appropriate adapter, not even a software one")
+        })?;
+        log::warn!(
+            "No GPU adapter found, rendering in software with {}",
+            adapter.get_info().name
+        );
+        return Ok(adapter);
+    };
+
+    let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+    let names: Vec<String> = adapters
+        .iter()
+        .map(|adapter| adapter.get_info().name)
+        .collect();
+    let Some(index) = find_adapter(&names, gpu) else {
+        let candidates: Vec<String> = adapters.iter().enumerate().map(describe_adapter).collect();
+        return Err(anyhow!(
+            "No GPU matches '{}', available adapters:\n{}",
+            gpu,
+            candidates.join("\n")
+        ));
+    };
+
+    let adapter = adapters.into_iter().nth(index).unwrap();
+    if let Some(surface) = surface
+        && !adapter.is_surface_supported(surface)
+    {
+        return Err(anyhow!(
+            "{} can't present to this window",
+            describe_adapter((index, &adapter))
+        ));
+    }
+    log::info!("Using {}", describe_adapter((index, &adapter)));
+    Ok(adapter)
+}
+
+/// Position of the adapter `gpu` refers to among `names`, either its index
+/// or the first name containing it, ignoring case
+fn find_adapter(names: &[String], gpu: &str) -> Option<usize> {
+    if let Ok(index) = gpu.parse::<usize>() {
+        return (index < names.len()).then_some(index);
+    }
+
+    let gpu = gpu.to_lowercase();
+    names
+        .iter()
+        .position(|name| name.to_lowercase().contains(&gpu))
+}
+
+/// One line naming the adapter at `index`, as listed by `list_adapters`
+fn describe_adapter((index, adapter): (usize, &wgpu::Adapter)) -> String {
+    let info = adapter.get_info();
+    format!(
+        "{}: {} ({:?}, {:?})",
+        index, info.name, info.device_type, info.backend
+    )
+}
+
+/// Every adapter `--gpu` can choose from, one line each
+pub fn list_adapters() -> Vec<String> {
+    create_instance()
+        .enumerate_adapters(wgpu::Backends::all())
+        .iter()
+        .enumerate()
+        .map(describe_adapter)
+        .collect()
+}
+
+/// Copy `texture` after the commands in `encoder` have run into an image,
+/// waiting for the GPU. Takes 8-bit RGBA or BGRA textures, whose texels
+/// hold sRGB values whether or not the format is marked as such.
+fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mut encoder: wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+) -> Result<RgbaImage> {
+    let bgra = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(anyhow!("Cannot read back {:?} textures", format)),
+    };
+
+    // Buffer rows have to be aligned, the padding is dropped again below
+    let (width, height) = (texture.width(), texture.height());
+    let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &readback,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = readback.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    let _ = device.poll(wgpu::MaintainBase::Wait);
+    receiver.recv()??;
+
+    let data = slice.get_mapped_range();
+    let mut pixels: Vec<u8> = data
+        .chunks(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..width as usize * 4])
+        .copied()
+        .collect();
+    if bgra {
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|texel| texel.swap(0, 2));
+    }
+
+    RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| anyhow!("Readback of {}x{} texture was cut short", width, height))
+}
+
+/// Parse and validate WGSL, describing the first problem found with
+/// snippets of the source labelled `name`. wgpu would otherwise panic on an
+/// invalid shader module.
+pub fn validate_wgsl(source: &str, name: &str) -> Result<wgpu::naga::Module, String> {
+    use wgpu::naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    let module = wgpu::naga::front::wgsl::parse_str(source)
+        .map_err(|e| e.emit_to_string_with_path(source, name))?;
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|e| e.emit_to_string_with_path(source, name))?;
+    Ok(module)
+}
+
+/// Layout of a frame bind group, holding only the frame texture viewed as
+/// `view_dimension` so renderers on the same device can share it
+fn create_bind_group_layout(
+    device: &wgpu::Device,
+    view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Texture Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        }],
+    })
+}
+
+/// Layout of a renderer's own bind group: the sampler frames are drawn
+/// with and the Dimensions uniform
+fn create_uniforms_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Frame Uniforms Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                // Each instance reads its own Dimensions from the buffer
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(size_of::<Dimensions>() as u64),
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+/// Build the pipeline drawing the frame quad with `fragment_source`, which
+/// may use the declarations of `FRAGMENT_COMMON` and of the texture
+/// `binding_source` matching `bind_group_layout`
+fn create_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    uniforms_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+    binding_source: &str,
+    fragment_source: &str,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout, uniforms_layout],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Vertex Shader"),
+        source: wgpu::ShaderSource::Wgsl(VERTEX_SHADER.into()),
+    });
+
+    let fragment_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Fragment Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            format!("{}{}{}", FRAGMENT_COMMON, binding_source, fragment_source).into(),
+        ),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &vertex_shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &fragment_shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                // The fragment shader outputs premultiplied color
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// One instance's draw of its frame
+struct InstanceDraw<'a> {
+    binding: &'a TextureBinding,
+    /// Offset of the instance's Dimensions in the uniform buffer
+    offset: u32,
+    /// Column of the target the instance is limited to, as x and width, and
+    /// the target's height, `None` to draw over the whole target
+    scissor: Option<((u32, u32), u32)>,
+}
+
+/// Draw the frame of every instance in `draws` into `view` with `pipeline`,
+/// reading their Dimensions from `uniforms`
+fn draw_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+    pipeline: &FramePipeline,
+    uniforms: &wgpu::BindGroup,
+    draws: &[InstanceDraw],
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+
+    for draw in draws {
+        if let Some(((x, width), height)) = draw.scissor {
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_scissor_rect(x, 0, width, height);
+        }
+        render_pass.set_pipeline(pipeline.for_binding(draw.binding));
+        render_pass.set_bind_group(0, &draw.binding.bind_group, &[]);
+        render_pass.set_bind_group(1, uniforms, &[draw.offset]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
+
+/// Bind group of a renderer's `sampler` and the Dimensions uniforms in
+/// `dimensions_buffer`
+fn create_uniforms(
+    device: &wgpu::Device,
+    layouts: &FrameLayouts,
+    sampler: &wgpu::Sampler,
+    dimensions_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Frame Uniforms Bind Group"),
+        layout: &layouts.uniforms,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: dimensions_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(size_of::<Dimensions>() as u64),
+                }),
+            },
+        ],
+    })
+}
+
+/// Sampler for frame textures filtering with `filtering`
+fn create_sampler(device: &wgpu::Device, filtering: Filtering) -> wgpu::Sampler {
+    let filter_mode = match filtering {
+        Filtering::Linear => wgpu::FilterMode::Linear,
+        Filtering::Nearest => wgpu::FilterMode::Nearest,
+    };
+
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter_mode,
+        min_filter: filter_mode,
+        mipmap_filter: filter_mode,
+        ..Default::default()
+    })
+}
+
+/// Physical pixels per logical pixel to lay frames out at on a display
+/// scaled by `scale_factor`. Frames drawn at whole multiples of their size
+/// (`whole`) take the nearest whole factor instead, so 125% and 150%
+/// displays neither blur them nor pad them with a border of empty pixels.
+pub fn display_scale(scale_factor: f64, whole: bool) -> f64 {
+    if whole {
+        scale_factor.round().max(1.0)
+    } else {
+        scale_factor
+    }
+}
+
+/// Rectangle in window pixels a frame of `image` size is drawn at, as x, y,
+/// width and height, centered in the window. With `integer_scale` the scale
+/// `mode` picks is rounded down to a whole factor of at least 1, so every
+/// texel covers the same number of pixels.
+fn draw_rect(
+    window: (f32, f32),
+    image: (f32, f32),
+    mode: ScaleMode,
+    integer_scale: bool,
+) -> [f32; 4] {
+    if image.0 <= 0.0 || image.1 <= 0.0 {
+        return [0.0, 0.0, window.0, window.1];
+    }
+
+    let (scale_x, scale_y) = (window.0 / image.0, window.1 / image.1);
+    let (scale_x, scale_y) = match mode {
+        ScaleMode::Stretch => (scale_x, scale_y),
+        ScaleMode::Fit | ScaleMode::IntegerScale => (scale_x.min(scale_y), scale_x.min(scale_y)),
+        ScaleMode::Fill => (scale_x.max(scale_y), scale_x.max(scale_y)),
+        ScaleMode::Center => (1.0, 1.0),
+    };
+    let (scale_x, scale_y) = if integer_scale || mode == ScaleMode::IntegerScale {
+        (scale_x.floor().max(1.0), scale_y.floor().max(1.0))
+    } else {
+        (scale_x, scale_y)
+    };
+
+    let (width, height) = (image.0 * scale_x, image.1 * scale_y);
+    [
+        ((window.0 - width) / 2.0).round(),
+        ((window.1 - height) / 2.0).round(),
+        width,
+        height,
+    ]
+}
+
+/// Rectangles as x, y, width and height that `count` instances are placed
+/// in, side by side with `spacing` between them and `margin` around them
+/// all, splitting the window's width equally
+fn instance_cells(window: (f32, f32), margin: f32, count: usize, spacing: f32) -> Vec<[f32; 4]> {
+    let count = count.max(1);
+    let gaps = spacing * (count - 1) as f32;
+    let width = ((window.0 - 2.0 * margin - gaps) / count as f32).max(0.0);
+    let height = (window.1 - 2.0 * margin).max(0.0);
+    (0..count)
+        .map(|instance| {
+            [
+                margin + instance as f32 * (width + spacing),
+                margin,
+                width,
+                height,
+            ]
+        })
+        .collect()
+}
+
+/// Columns of a `width` wide window each instance draws into, meeting
+/// halfway between neighbouring cells so every pixel is drawn exactly once
+fn instance_columns(cells: &[[f32; 4]], width: u32) -> Vec<(u32, u32)> {
+    let boundary = |instance: usize| -> u32 {
+        match instance {
+            0 => 0,
+            instance if instance >= cells.len() => width,
+            instance => {
+                let [left_x, _, left_width, _] = cells[instance - 1];
+                let middle = (left_x + left_width + cells[instance][0]) / 2.0;
+                (middle.round().max(0.0) as u32).min(width)
+            }
+        }
+    };
+    (0..cells.len())
+        .map(|instance| {
+            let (start, end) = (boundary(instance), boundary(instance + 1));
+            (start, end.saturating_sub(start))
+        })
+        .collect()
+}
+
+/// Like `draw_rect`, but for a frame rotated clockwise by `degrees` about
+/// its center: the scale mode places the rotated frame's bounding box, and
+/// the result is the unrotated frame at that scale sharing the box's center
+fn rotated_draw_rect(
+    window: (f32, f32),
+    image: (f32, f32),
+    degrees: f32,
+    mode: ScaleMode,
+    integer_scale: bool,
+) -> [f32; 4] {
+    let bounds = rotated_bounds(image, degrees);
+    let [x, y, width, height] = draw_rect(window, bounds, mode, integer_scale);
+    if bounds.0 <= 0.0 || bounds.1 <= 0.0 {
+        return [x, y, width, height];
+    }
+
+    let (scaled_width, scaled_height) = (image.0 * width / bounds.0, image.1 * height / bounds.1);
+    [
+        x + (width - scaled_width) / 2.0,
+        y + (height - scaled_height) / 2.0,
+        scaled_width,
+        scaled_height,
+    ]
+}
+
+/// Sine and cosine of a clockwise rotation, exact for quarter turns so
+/// rotated sizes don't pick up rounding errors
+fn rotation_sin_cos(degrees: f32) -> (f32, f32) {
+    match degrees.rem_euclid(360.0) {
+        0.0 => (0.0, 1.0),
+        90.0 => (1.0, 0.0),
+        180.0 => (0.0, -1.0),
+        270.0 => (-1.0, 0.0),
+        degrees => degrees.to_radians().sin_cos(),
+    }
+}
+
+/// Width and height of the bounding box of a `size` rectangle rotated by
+/// `degrees`
+fn rotated_bounds(size: (f32, f32), degrees: f32) -> (f32, f32) {
+    let (sin, cos) = rotation_sin_cos(degrees);
+    (
+        size.0 * cos.abs() + size.1 * sin.abs(),
+        size.0 * sin.abs() + size.1 * cos.abs(),
+    )
+}
+
+/// Window size fitting a frame of `size` rotated by `degrees`
+pub fn rotated_size(size: (u32, u32), degrees: f32) -> (u32, u32) {
+    let (width, height) = rotated_bounds((size.0 as f32, size.1 as f32), degrees);
+    (width.round() as u32, height.round() as u32)
+}
+
+/// Rows of the matrix undoing a clockwise rotation by `degrees` in window
+/// coordinates, where y points down
+fn rotation_rows(degrees: f32) -> [f32; 4] {
+    let (sin, cos) = rotation_sin_cos(degrees);
+    [cos, sin, -sin, cos]
+}
+
+/// The best of the surface's alpha modes for an overlay: premultiplied like
+/// the shaders' output, then straight alpha, then no transparency at all.
+/// Surfaces listing none of these get whatever they list first.
+fn choose_alpha_mode(supported: &[wgpu::CompositeAlphaMode]) -> wgpu::CompositeAlphaMode {
+    [
+        wgpu::CompositeAlphaMode::PreMultiplied,
+        wgpu::CompositeAlphaMode::PostMultiplied,
+        wgpu::CompositeAlphaMode::Opaque,
+    ]
+    .into_iter()
+    .find(|mode| supported.contains(mode))
+    .or(supported.first().copied())
+    .unwrap_or(wgpu::CompositeAlphaMode::Auto)
+}
+
+/// What `encode_output` turns the premultiplied shader output into for a
+/// surface compositing with `mode`, matching the ALPHA_* shader constants
+fn alpha_output(mode: wgpu::CompositeAlphaMode) -> u32 {
+    match mode {
+        wgpu::CompositeAlphaMode::PostMultiplied => 1,
+        wgpu::CompositeAlphaMode::Opaque => 2,
+        _ => 0,
+    }
+}
+
+/// Convert an sRGB channel to linear light, like the shaders see texels of
+/// sRGB textures
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear channel as sRGB, what `encode_output` does in the shaders
+fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// What `apply_effects` in the shaders does to a linear color, to check
+/// them against
+#[cfg(test)]
+fn apply_color_effects(color: [f32; 3], effects: &ColorEffects) -> [f32; 3] {
+    let flags = effects.flags();
+    let luma = |[r, g, b]: [f32; 3]| 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let mut rgb = color;
+
+    if flags & EFFECT_HUE_SHIFT != 0 {
+        // Rodrigues' rotation about the normalized gray axis
+        let (sin, cos) = effects.hue_shift.to_radians().sin_cos();
+        let axis = 1.0 / 3f32.sqrt();
+        let [r, g, b] = rgb;
+        let cross = [axis * (b - g), axis * (r - b), axis * (g - r)];
+        let along = axis * axis * (r + g + b) * (1.0 - cos);
+        rgb = std::array::from_fn(|i| rgb[i] * cos + cross[i] * sin + along);
+    }
+    if flags & EFFECT_SATURATION != 0 {
+        let gray = luma(rgb);
+        rgb = rgb.map(|channel| gray + (channel - gray) * effects.saturation);
+    }
+    if flags & EFFECT_GRAYSCALE != 0 {
+        rgb = [luma(rgb); 3];
+    }
+    rgb = rgb.map(|channel| channel.clamp(0.0, 1.0));
+    if flags & EFFECT_INVERT != 0 {
+        rgb = rgb.map(|channel| srgb_to_linear(1.0 - linear_to_srgb(channel)));
+    }
+    rgb
+}
+
+/// Size of `layers` layers of `texture` at mip level `mip_level`
+fn mip_extent(texture: &wgpu::Texture, mip_level: u32, layers: u32) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: (texture.width() >> mip_level).max(1),
+        height: (texture.height() >> mip_level).max(1),
+        depth_or_array_layers: layers,
+    }
+}
+
+/// Copy origin of an array layer
+fn array_layer(layer: u32) -> wgpu::Origin3d {
+    wgpu::Origin3d {
+        x: 0,
+        y: 0,
+        z: layer,
+    }
+}
+
+/// Smallest square power-of-two page, at most `max_page` wide, that holds
+/// every frame of `sizes` on a single page, or `max_page` when they need
+/// several. `None` when a frame is larger than `max_page`.
+fn atlas_page_size(sizes: &[(u32, u32)], max_page: u32) -> Option<(u32, u32)> {
+    let largest = sizes
+        .iter()
+        .map(|&(width, height)| width.max(height))
+        .max()?;
+    if largest > max_page {
+        return None;
+    }
+
+    let mut side = largest.next_power_of_two().min(max_page);
+    while side < max_page {
+        let mut packer = ShelfPacker::new((side, side));
+        if sizes.iter().all(|&size| {
+            packer
+                .place(size)
+                .is_some_and(|placement| placement.page == 0)
+        }) {
+            break;
+        }
+        side = (side * 2).min(max_page);
+    }
+
+    Some((side, side))
+}
+
+/// Quantize a frame to 4 bits per channel and pack it into the two bytes per
+/// texel of an Rg8Unorm texture: red and green in the first, blue and alpha
+/// in the second. Color is dithered so gradients don't band.
+fn pack_rgba4444(image: &RgbaImage) -> Vec<u8> {
+    let mut packed = Vec::with_capacity(image.as_raw().len() / 2);
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let threshold = BAYER_4X4[y as usize % 4][x as usize % 4] * 16 + 8;
+        let [r, g, b, a] = pixel.0.map(u32::from);
+        let dither = |channel: u32| (channel * 15 + threshold) / 255;
+        let alpha = (a * 15 + 127) / 255;
+
+        packed.push((dither(r) << 4 | dither(g)) as u8);
+        packed.push((dither(b) << 4 | alpha) as u8);
+    }
+
+    packed
+}
+
+/// Find byte-identical frames so they can share a texture.
+///
+/// Returns the index of the first occurrence of every distinct frame, and for
+/// each frame the position of its distinct frame in that list.
+fn dedup_frames(images: &[&RgbaImage]) -> (Vec<usize>, Vec<usize>) {
+    let mut unique_frames: Vec<usize> = Vec::new();
+    let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+
+    let frame_textures = images
+        .iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let mut hasher = DefaultHasher::new();
+            image.dimensions().hash(&mut hasher);
+            image.as_raw().hash(&mut hasher);
+
+            // Compare the bytes too so a hash collision can't merge frames
+            let candidates = by_hash.entry(hasher.finish()).or_default();
+            if let Some(&texture) = candidates
+                .iter()
+                .find(|&&texture| images[unique_frames[texture]] == *image)
+            {
+                return texture;
+            }
+
+            let texture = unique_frames.len();
+            unique_frames.push(index);
+            candidates.push(texture);
+            texture
+        })
+        .collect();
+
+    (unique_frames, frame_textures)
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        log::debug!("Dropping Renderer");
+        self.cleanup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use image::Rgba;
+    use wgpu::util::DeviceExt;
+
+    #[test]
+    fn test_dedup_frames() {
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        let wide_red = RgbaImage::from_pixel(4, 1, Rgba([255, 0, 0, 255]));
+
+        let images = [&red, &red, &blue, &red, &wide_red, &blue];
+        let (unique_frames, frame_textures) = dedup_frames(&images);
+
+        assert_eq!(unique_frames, vec![0, 2, 4]);
+        assert_eq!(frame_textures, vec![0, 0, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn test_draw_rect() {
+        let window = (300.0, 100.0);
+        let image = (100.0, 100.0);
+
+        // Stretched over the window, as frames always were
+        assert_eq!(
+            draw_rect(window, image, ScaleMode::Stretch, false),
+            [0.0, 0.0, 300.0, 100.0]
+        );
+        assert_eq!(
+            draw_rect(window, image, ScaleMode::Fit, false),
+            [100.0, 0.0, 100.0, 100.0]
+        );
+        assert_eq!(
+            draw_rect(window, image, ScaleMode::Fill, false),
+            [0.0, -100.0, 300.0, 300.0]
+        );
+        assert_eq!(
+            draw_rect((80.0, 60.0), image, ScaleMode::Center, false),
+            [-10.0, -20.0, 100.0, 100.0]
+        );
+
+        // Whole multiples per axis, centered
+        assert_eq!(
+            draw_rect((350.0, 250.0), image, ScaleMode::Stretch, true),
+            [25.0, 25.0, 300.0, 200.0]
+        );
+        // Never below the frame's own size
+        assert_eq!(
+            draw_rect((50.0, 100.0), image, ScaleMode::Stretch, true),
+            [-25.0, 0.0, 100.0, 100.0]
+        );
+
+        // One whole factor for both axes, the remainder letterboxed
+        assert_eq!(
+            draw_rect((350.0, 250.0), image, ScaleMode::IntegerScale, false),
+            [75.0, 25.0, 200.0, 200.0]
+        );
+        assert_eq!(
+            draw_rect((300.0, 300.0), image, ScaleMode::IntegerScale, false),
+            [0.0, 0.0, 300.0, 300.0]
+        );
+        assert_eq!(
+            draw_rect((80.0, 60.0), image, ScaleMode::IntegerScale, false),
+            [-10.0, -20.0, 100.0, 100.0]
+        );
+    }
+
+    #[test]
+    fn test_display_scale() {
+        let window = |scale: f64| {
+            let size: winit::dpi::PhysicalSize<u32> =
+                winit::dpi::LogicalSize::new(100, 60).to_physical(scale);
+            (size.width, size.height)
+        };
+
+        // Fractional scales keep the logical size, to the nearest pixel
+        assert_eq!(window(display_scale(1.25, false)), (125, 75));
+        assert_eq!(window(display_scale(1.5, false)), (150, 90));
+        assert_eq!(window(display_scale(1.75, false)), (175, 105));
+
+        // Whole multiples round to the nearest factor, never below 1
+        assert_eq!(display_scale(1.25, true), 1.0);
+        assert_eq!(display_scale(1.5, true), 2.0);
+        assert_eq!(display_scale(0.75, true), 1.0);
+        let (width, height) = window(display_scale(1.5, true));
+        assert_eq!(
+            draw_rect(
+                (width as f32, height as f32),
+                (100.0, 60.0),
+                ScaleMode::IntegerScale,
+                false
+            ),
+            [0.0, 0.0, 200.0, 120.0]
+        );
+    }
+
+    #[test]
+    fn test_rotated_draw_rect() {
+        assert_eq!(rotated_size((200, 100), 90.0), (100, 200));
+        assert_eq!(rotated_size((200, 100), -180.0), (200, 100));
+        assert_eq!(rotated_size((100, 100), 45.0), (141, 141));
+
+        // A quarter turn in a window sized for it fills the window once
+        // turned back
+        assert_eq!(
+            rotated_draw_rect(
+                (100.0, 200.0),
+                (200.0, 100.0),
+                90.0,
+                ScaleMode::Stretch,
+                false
+            ),
+            [-50.0, 50.0, 200.0, 100.0]
+        );
+        // Fit scales the rotated bounding box, not the frame
+        assert_eq!(
+            rotated_draw_rect((200.0, 200.0), (200.0, 100.0), 270.0, ScaleMode::Fit, false),
+            [0.0, 50.0, 200.0, 100.0]
+        );
+        assert_eq!(
+            rotated_draw_rect((300.0, 100.0), (100.0, 100.0), 0.0, ScaleMode::Fit, false),
+            draw_rect((300.0, 100.0), (100.0, 100.0), ScaleMode::Fit, false)
+        );
+    }
+
+    /// Device on the first available adapter, `None` on machines without one
+    fn test_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_limits: adapter.limits(),
+            ..Default::default()
+        }))
+        .ok()
+    }
+
+    /// Uniform drawing a frame of `image` size into `window` with `mode`
+    fn test_dimensions(image: &RgbaImage, window: (u32, u32), mode: ScaleMode) -> Dimensions {
+        let image_size = (image.width() as f32, image.height() as f32);
+        let window_size = (window.0 as f32, window.1 as f32);
+        Dimensions {
+            window_width: window_size.0,
+            window_height: window_size.1,
+            image_width: image_size.0,
+            image_height: image_size.1,
+            layer: 0,
+            opacity: 1.0,
+            flip: [0; 2],
+            uv_rect: FULL_UV_RECT,
+            draw_rect: draw_rect(window_size, image_size, mode, false),
+            rotation: rotation_rows(0.0),
+            tint: WHITE,
+            chroma_key: [0.0; 4],
+            chroma_range: [0.0; 2],
+            encode_srgb: 0,
+            alpha_output: alpha_output(wgpu::CompositeAlphaMode::PreMultiplied),
+            background: TRANSPARENT,
+            next_layer: 0,
+            blend: 0.0,
+            outline_width: 0.0,
+            effects: 0,
+            outline: [0.0; 4],
+            hue_shift: 0.0,
+            saturation: 1.0,
+            _padding: [0; 2],
+        }
+    }
+
+    /// Draw `image` with the frame pipeline into a `target_format` target
+    /// of the window size in `dimensions` and read the result back
+    fn render_offscreen(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &RgbaImage,
+        dimensions: Dimensions,
+        target_format: wgpu::TextureFormat,
+    ) -> RgbaImage {
+        let window = (
+            dimensions.window_width as u32,
+            dimensions.window_height as u32,
+        );
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let frame = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: image.width(),
+                    height: image.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            image,
+        );
+
+        let dimensions_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[dimensions]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let layout = create_bind_group_layout(device, wgpu::TextureViewDimension::D2);
+        let uniforms_layout = create_uniforms_layout(device);
+        let pipeline = create_pipeline(
+            device,
+            &layout,
+            &uniforms_layout,
+            target_format,
+            SINGLE_LAYER_BINDING,
+            FRAGMENT_SHADER,
+        );
+        let sampler = create_sampler(device, Filtering::Linear);
+        let frame_view = frame.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&frame_view),
+            }],
+        });
+        let uniforms = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &uniforms_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: dimensions_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: window.0,
+                height: window.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: target_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let view = target.create_view(&Default::default());
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_bind_group(1, &uniforms, &[0]);
+            render_pass.draw(0..4, 0..1);
+        }
+        read_texture(device, queue, encoder, &target).unwrap()
+    }
+
+    #[test]
+    fn test_fit_letterboxes_readback() {
+        let Some((device, queue)) = test_device() else {
+            eprintln!("No GPU adapter available, skipping");
+            return;
+        };
+
+        // A 2:1 frame in a square window is scaled up 4x and gets bars above
+        // and below
+        let red = Rgba([255, 0, 0, 255]);
+        let image = RgbaImage::from_pixel(4, 2, red);
+        let output = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            test_dimensions(&image, (16, 16), ScaleMode::Fit),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let expected = if (4..12).contains(&y) {
+                red
+            } else {
+                Rgba([0, 0, 0, 0])
+            };
+            assert_eq!(*pixel, expected, "pixel at {}, {}", x, y);
+        }
+
+        // Stretching covers the whole window like before
+        let output = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            test_dimensions(&image, (16, 16), ScaleMode::Stretch),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        assert!(output.pixels().all(|pixel| *pixel == red));
+    }
+
+    /// Renderer without a window holding `frames` as its only sequence
+    fn headless_renderer(size: (u32, u32), frames: &[&RgbaImage]) -> Option<Renderer> {
+        let mut renderer = pollster::block_on(Renderer::new_headless(size.0, size.1, None))
+            .map_err(|e| eprintln!("No GPU adapter available, skipping: {}", e))
+            .ok()?;
+        renderer.preload_sequences(&[frames], &|_, _, _| {});
+        Some(renderer)
+    }
+
+    #[test]
+    fn test_headless_frames() {
+        let red = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+        let Some(mut renderer) = headless_renderer((4, 4), &[&red, &blue]) else {
+            return;
+        };
+
+        assert_eq!(renderer.capture_frame().unwrap(), red);
+        pollster::block_on(renderer.set_current_texture_index(1)).unwrap();
+        assert_eq!(renderer.capture_frame().unwrap(), blue);
+        // The index wraps around to the first frame
+        pollster::block_on(renderer.set_current_texture_index(2)).unwrap();
+        assert_eq!(renderer.capture_frame().unwrap(), red);
+    }
+
+    #[test]
+    fn test_headless_block_compression() {
+        let Some(mut renderer) = headless_renderer((8, 4), &[]) else {
+            return;
+        };
+        renderer.set_block_compression(true);
+        if !renderer.block_compression {
+            eprintln!("No BC texture compression, skipping");
+            return;
+        }
+
+        // A sprite's edge stored in mode 5 next to a gradient of sixteen
+        // steps stored in mode 6, the GPU decoding them checks how both
+        // are laid out
+        let frame = RgbaImage::from_fn(8, 4, |x, y| {
+            if x < 4 {
+                let alpha = if x + y >= 3 { 255 } else { 0 };
+                Rgba([40 + 60 * x as u8, 200, 250 - 60 * x as u8, alpha])
+            } else {
+                let step = (x - 4 + 4 * y) as u8;
+                Rgba([30 + step * 12, 200 - step * 9, 90, 255])
+            }
+        });
+        renderer.preload_sequences(&[&[&frame]], &|_, _, _| {});
+        let output = renderer.capture_frame().unwrap();
+        let visible = frame.pixels().zip(output.pixels());
+        for (input, output) in visible.filter(|(input, _)| input[3] > 0) {
+            for channel in 0..3 {
+                assert!(
+                    input[channel].abs_diff(output[channel]) <= 4,
+                    "{:?} became {:?}",
+                    input,
+                    output
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_headless_resize_while_rendering() {
+        let red = Rgba([255, 0, 0, 255]);
+        let frame = RgbaImage::from_pixel(4, 4, red);
+        let Some(mut renderer) = headless_renderer((4, 4), &[&frame]) else {
+            return;
+        };
+        // Supersampling resizes its offscreen targets along with the window
+        renderer.set_render_scale(2.0);
+
+        for step in 0..64 {
+            let (width, height) = (1 + step * 7 % 61, 1 + step * 13 % 47);
+            renderer.resize(width, height);
+            assert!(renderer.render().is_ok(), "{}x{}", width, height);
+            let output = renderer.capture_frame().unwrap();
+            assert_eq!(output.dimensions(), (width, height));
+            assert!(output.pixels().all(|pixel| *pixel == red));
+        }
+
+        // Minimized windows report a zero size, which keeps the last one
+        renderer.resize(0, 16);
+        assert!(renderer.render().is_ok());
+        assert!(renderer.capture_frame().is_ok());
+    }
+
+    #[test]
+    fn test_headless_shared_frames() {
+        let red = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+        let green = RgbaImage::from_pixel(4, 4, Rgba([0, 255, 0, 255]));
+        let Some(mut first) = headless_renderer((4, 4), &[&red, &blue]) else {
+            return;
+        };
+        let mut second =
+            pollster::block_on(Renderer::new_headless(4, 4, Some(first.gpu().clone()))).unwrap();
+        second.share_frames(first.frames()).unwrap();
+        assert_eq!(second.capture_frame().unwrap(), red);
+
+        // Each renderer shows a frame of its own
+        pollster::block_on(second.set_current_texture_index(1)).unwrap();
+        assert_eq!(second.capture_frame().unwrap(), blue);
+        assert_eq!(first.capture_frame().unwrap(), red);
+
+        // Frames uploaded through one renderer reach the other
+        first.upload_image(0, 1, &green).unwrap();
+        second.share_frames(first.frames()).unwrap();
+        assert_eq!(second.capture_frame().unwrap(), green);
+
+        // Another device can't draw them
+        let Some(mut other) = headless_renderer((4, 4), &[&blue]) else {
+            return;
+        };
+        assert!(other.share_frames(first.frames()).is_err());
+        assert_eq!(other.capture_frame().unwrap(), blue);
+    }
+
+    #[test]
+    fn test_headless_crossfade() {
+        let red = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+        let Some(mut renderer) = headless_renderer((4, 4), &[&red, &blue]) else {
+            return;
+        };
+
+        // Without crossfading the target is ignored
+        renderer.set_crossfade_target(Some(1), 0.5);
+        assert_eq!(renderer.capture_frame().unwrap(), red);
+
+        // Halfway between the frames in linear light
+        renderer.set_crossfade(true);
+        renderer.set_crossfade_target(Some(1), 0.5);
+        let pixel = *renderer.capture_frame().unwrap().get_pixel(2, 2);
+        assert!(
+            pixel[0].abs_diff(188) <= 1 && pixel[2].abs_diff(188) <= 1,
+            "{:?}",
+            pixel
+        );
+        assert_eq!(pixel[3], 255);
+
+        renderer.set_crossfade_target(Some(1), 1.0);
+        assert_eq!(renderer.capture_frame().unwrap(), blue);
+        renderer.set_crossfade_target(None, 0.5);
+        assert_eq!(renderer.capture_frame().unwrap(), red);
+    }
+
+    #[test]
+    fn test_instance_cells() {
+        assert_eq!(
+            instance_cells((100.0, 50.0), 0.0, 1, 10.0),
+            vec![[0.0, 0.0, 100.0, 50.0]]
+        );
+
+        // Two gaps of 10 and a margin of 5 leave 3 cells of 30
+        let cells = instance_cells((120.0, 50.0), 5.0, 3, 10.0);
+        assert_eq!(
+            cells,
+            vec![
+                [5.0, 5.0, 30.0, 40.0],
+                [45.0, 5.0, 30.0, 40.0],
+                [85.0, 5.0, 30.0, 40.0],
+            ]
+        );
+
+        // Columns split the gaps and cover the whole width
+        assert_eq!(
+            instance_columns(&cells, 120),
+            vec![(0, 40), (40, 40), (80, 40)]
+        );
+    }
+
+    #[test]
+    fn test_headless_instances() {
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let green = RgbaImage::from_pixel(2, 2, Rgba([0, 255, 0, 255]));
+        let blue = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        let Some(mut renderer) = headless_renderer((8, 2), &[&red, &green, &blue]) else {
+            return;
+        };
+
+        renderer.set_instances(3, 1.0);
+        renderer.set_instance_frames(&[1, 2]);
+        let output = renderer.capture_frame().unwrap();
+        let colors: Vec<Rgba<u8>> = (0..8).map(|x| *output.get_pixel(x, 1)).collect();
+        assert_eq!(colors[0], *red.get_pixel(0, 0));
+        assert_eq!(colors[1], *red.get_pixel(0, 0));
+        assert_eq!(colors[2][3], 0);
+        assert_eq!(colors[3], *green.get_pixel(0, 0));
+        assert_eq!(colors[5][3], 0);
+        assert_eq!(colors[7], *blue.get_pixel(0, 0));
+
+        // Instances follow the current frame when it advances
+        pollster::block_on(renderer.set_current_texture_index(1)).unwrap();
+        renderer.set_instance_frames(&[2, 0]);
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(0, 0), green.get_pixel(0, 0));
+        assert_eq!(output.get_pixel(3, 0), blue.get_pixel(0, 0));
+        assert_eq!(output.get_pixel(6, 0), red.get_pixel(0, 0));
+
+        // Spacing is in logical pixels on a scaled display
+        renderer.set_scale_factor(2.0);
+        let cells = renderer.instance_cells();
+        assert!(
+            (cells[1][0] - cells[0][2] - 2.0).abs() < 1e-5,
+            "{:?}",
+            cells
+        );
+    }
+
+    #[test]
+    fn test_headless_outline() {
+        // A red square with a transparent border of two texels
+        let image = RgbaImage::from_fn(8, 8, |x, y| {
+            if (2..6).contains(&x) && (2..6).contains(&y) {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let Some(mut renderer) = headless_renderer((8, 8), &[&image]) else {
+            return;
+        };
+
+        renderer.set_outline(Some(Outline {
+            color: [255, 255, 255],
+            width: 1.0,
+        }));
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(3, 3), &Rgba([255, 0, 0, 255]));
+        assert_eq!(output.get_pixel(1, 3), &Rgba([255, 255, 255, 255]));
+        assert_eq!(output.get_pixel(0, 3)[3], 0);
+        // Diagonal neighbours are further away than the width
+        assert_eq!(output.get_pixel(1, 1)[3], 0);
+
+        renderer.set_outline(None);
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(1, 3)[3], 0);
+    }
+
+    #[test]
+    fn test_headless_shadow() {
+        let red = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let Some(mut renderer) = headless_renderer((16, 16), &[&red]) else {
+            return;
+        };
+
+        // A margin of 6 leaves the frame its own size in the middle
+        renderer.set_shadow(Some(Shadow {
+            color: [0; 3],
+            offset: (4.0, 0.0),
+            blur_radius: 2.0,
+            opacity: 1.0,
+        }));
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(7, 7), &Rgba([255, 0, 0, 255]));
+        assert_eq!(output.get_pixel(5, 7)[3], 0);
+
+        // Right of the frame, where the offset silhouette is
+        let shadow = output.get_pixel(12, 7);
+        assert!(shadow[3] > 128, "{:?}", shadow);
+        assert_eq!(&shadow.0[..3], &[0, 0, 0]);
+        assert_eq!(output.get_pixel(3, 7)[3], 0);
 
-        log::info!("Compressing {} images with delta compression", images.len());
+        renderer.set_shadow(None);
+        let output = renderer.capture_frame().unwrap();
+        assert!(output.pixels().all(|pixel| pixel[3] == 255));
+    }
 
-        // Compress the sequence
-        let compressed_sequence = if let Some(ref mut compressor) = self.delta_compressor {
-            compressor.compress_sequence(images).await?
-        } else {
-            return Err(anyhow::anyhow!("Delta compressor not initialized"));
+    #[test]
+    fn test_headless_render_scale() {
+        // Window pixels land on single texels of the checkerboard, so only
+        // supersampling averages their neighbours in
+        let checkerboard = RgbaImage::from_fn(10, 10, |x, y| {
+            let value = if (x + y) % 2 == 0 { 255 } else { 0 };
+            Rgba([value, value, value, 255])
+        });
+        let Some(mut renderer) = headless_renderer((2, 2), &[&checkerboard]) else {
+            return;
         };
+        renderer.set_mipmaps(false);
+        renderer.preload_sequences(&[&[&checkerboard]], &|_, _, _| {});
 
-        // Log compression statistics
-        let compressed_size = compressed_sequence.memory_usage();
-        let compression_ratio = compressed_sequence.compression_ratio(original_size);
-        log::info!(
-            "Compressed sequence size: {:.2} MB",
-            compressed_size as f64 / (1024.0 * 1024.0)
+        let pixel = *renderer.capture_frame().unwrap().get_pixel(0, 0);
+        assert!(pixel[0] == 0 || pixel[0] == 255, "{:?}", pixel);
+
+        renderer.set_render_scale(4.0);
+        assert_eq!(renderer.render_size(), (8, 8));
+        let pixel = *renderer.capture_frame().unwrap().get_pixel(0, 0);
+        assert!((100..230).contains(&pixel[0]), "{:?}", pixel);
+        assert_eq!(pixel[3], 255);
+
+        // Back to drawing straight into the target
+        renderer.set_render_scale(1.0);
+        assert!(renderer.supersampler.is_none());
+        let pixel = *renderer.capture_frame().unwrap().get_pixel(0, 0);
+        assert!(pixel[0] == 0 || pixel[0] == 255, "{:?}", pixel);
+    }
+
+    #[test]
+    fn test_headless_background() {
+        let clear = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0]));
+        let Some(mut renderer) = headless_renderer((4, 4), &[&clear]) else {
+            return;
+        };
+
+        // Transparent unless set
+        assert_eq!(renderer.capture_frame().unwrap(), clear);
+
+        // Half of linear blue, premultiplied and stored as sRGB
+        renderer.set_background(0.0, 0.0, 1.0, 0.5);
+        let pixel = *renderer.capture_frame().unwrap().get_pixel(1, 1);
+        assert!(
+            pixel[2].abs_diff(188) <= 1 && pixel[3].abs_diff(128) <= 1,
+            "{:?}",
+            pixel
         );
-        log::info!("Compression ratio: {:.2}x", compression_ratio);
+        assert_eq!(&pixel.0[..2], &[0, 0]);
 
-        // Create texture for current frame
-        let texture_size = wgpu::Extent3d {
-            width: first_dims.0,
-            height: first_dims.1,
-            depth_or_array_layers: 1,
+        // The shadow pass clears to the background too
+        renderer.set_shadow(Some(Shadow::default()));
+        let pixel = *renderer.capture_frame().unwrap().get_pixel(0, 0);
+        assert!(pixel[2].abs_diff(188) <= 1, "{:?}", pixel);
+    }
+
+    #[test]
+    fn test_gpu_memory_estimate() {
+        let red = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+        let Some(renderer) = headless_renderer((4, 4), &[&red, &blue]) else {
+            return;
         };
 
-        let current_frame_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Current Frame Texture"),
-            size: texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
+        // Two layers of 4x4, 2x2 and 1x1 texels
+        assert_eq!(
+            renderer.gpu_memory_estimate(),
+            GpuMemoryEstimate {
+                texture_bytes: 2 * (16 + 4 + 1) * 4,
+                texture_count: 1,
+                surface_bytes: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_adapter() {
+        let names = [
+            "Intel(R) Iris(R) Xe Graphics".to_string(),
+            "NVIDIA GeForce RTX 3060 Laptop GPU".to_string(),
+        ];
+        assert_eq!(find_adapter(&names, "1"), Some(1));
+        assert_eq!(find_adapter(&names, "2"), None);
+        assert_eq!(find_adapter(&names, "intel"), Some(0));
+        assert_eq!(find_adapter(&names, "RTX"), Some(1));
+        assert_eq!(find_adapter(&names, "radeon"), None);
+    }
+
+    #[test]
+    fn test_format_memory() {
+        assert_eq!(format_memory(512), "512 B");
+        assert_eq!(format_memory(1536), "1.5 KiB");
+        assert_eq!(format_memory(312 * 1024 * 1024), "312 MiB");
+        assert_eq!(format_memory(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_headless_alpha() {
+        let image = RgbaImage::from_fn(4, 4, |x, _| {
+            if x < 2 {
+                Rgba([0, 0, 0, 0])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
         });
+        let Some(mut renderer) = headless_renderer((4, 4), &[&image]) else {
+            return;
+        };
 
-        // Upload base frame initially
-        self.queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &current_frame_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &compressed_sequence.base_frame,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * first_dims.0),
-                rows_per_image: Some(first_dims.1),
-            },
-            texture_size,
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(0, 0)[3], 0);
+        assert_eq!(output.get_pixel(3, 3), &Rgba([255, 255, 255, 255]));
+
+        // Effects apply to what is read back like to what is presented
+        renderer.set_opacity(0.5);
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(0, 0)[3], 0);
+        assert!(output.get_pixel(3, 3)[3].abs_diff(128) <= 1);
+    }
+
+    #[test]
+    fn test_headless_premultiplied_edges() {
+        // An opaque white column fading out through half and quarter alpha,
+        // as at the antialiased edge of a sprite
+        let alphas = [255, 128, 64, 0];
+        let image = RgbaImage::from_fn(4, 1, |x, _| Rgba([255, 255, 255, alphas[x as usize]]));
+        let Some(renderer) = headless_renderer((4, 1), &[&image]) else {
+            return;
+        };
+
+        // Each color is multiplied by its alpha exactly once: half of linear
+        // white stored as sRGB is 188, where multiplying twice would give 137
+        let output = renderer.capture_frame().unwrap();
+        let expected = [
+            [255, 255, 255, 255],
+            [188, 188, 188, 128],
+            [137, 137, 137, 64],
+            [0; 4],
+        ];
+        for (x, expected) in expected.into_iter().enumerate() {
+            let pixel = output.get_pixel(x as u32, 0);
+            assert!(
+                (0..4).all(|channel| pixel[channel].abs_diff(expected[channel]) <= 1),
+                "pixel {} is {:?}, expected {:?}",
+                x,
+                pixel,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_headless_scale_mode() {
+        let image = RgbaImage::from_pixel(2, 1, Rgba([0, 255, 0, 255]));
+        let Some(mut renderer) = headless_renderer((8, 8), &[&image]) else {
+            return;
+        };
+
+        // Stretched over the whole target by default
+        let output = renderer.capture_frame().unwrap();
+        assert!(output.pixels().all(|pixel| pixel[3] == 255));
+
+        // Fit leaves transparent bars above and below
+        renderer.set_scale_mode(ScaleMode::Fit);
+        let output = renderer.capture_frame().unwrap();
+        assert_eq!(output.get_pixel(4, 0)[3], 0);
+        assert_eq!(output.get_pixel(4, 4), &Rgba([0, 255, 0, 255]));
+        assert_eq!(output.get_pixel(4, 7)[3], 0);
+    }
+
+    #[test]
+    fn test_bgra_readback() {
+        let Some((device, queue)) = test_device() else {
+            eprintln!("No GPU adapter available, skipping");
+            return;
+        };
+
+        // Most surfaces are BGRA, captures still come out as RGBA
+        let image = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 100, y as u8 * 200, 50, 255]));
+        let output = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            test_dimensions(&image, (3, 2), ScaleMode::Stretch),
+            wgpu::TextureFormat::Bgra8UnormSrgb,
         );
+        assert_eq!(output, image);
+    }
 
-        let texture_view =
-            current_frame_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    #[test]
+    fn test_flip_readback() {
+        let Some((device, queue)) = test_device() else {
+            eprintln!("No GPU adapter available, skipping");
+            return;
+        };
 
-        let current_frame_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Current Frame Bind Group"),
-            layout: &self.bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&self.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: self.dimensions_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+        let image = RgbaImage::from_fn(2, 2, |x, _| if x == 0 { red } else { blue });
 
-        self.sequence_type = Some(SequenceType::Compressed {
-            compressed_sequence,
-            current_frame_texture,
-            current_frame_bind_group,
-            reconstructed_frame: Some(images[0].clone()),
-        });
+        let output = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            test_dimensions(&image, (2, 2), ScaleMode::Stretch),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        assert_eq!(output, image);
 
-        self.current_texture_index = 0;
-        log::info!("Successfully set up delta-compressed sequence");
+        // Mirrored horizontally, unchanged vertically
+        let mut dimensions = test_dimensions(&image, (2, 2), ScaleMode::Stretch);
+        dimensions.flip = [1, 0];
+        let output = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            dimensions,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        assert_eq!(output.get_pixel(0, 0), &blue);
+        assert_eq!(output.get_pixel(1, 1), &red);
 
-        Ok(())
+        let mut dimensions = test_dimensions(&image, (2, 2), ScaleMode::Stretch);
+        dimensions.flip = [0, 1];
+        let output = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            dimensions,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        assert_eq!(output, image);
     }
 
-    pub async fn set_current_texture_index(&mut self, index: usize) -> Result<()> {
-        match &mut self.sequence_type {
-            Some(SequenceType::Uncompressed {
-                texture_bind_groups,
-            }) => {
-                if !texture_bind_groups.is_empty() {
-                    self.current_texture_index = index % texture_bind_groups.len();
-                }
-            }
-            Some(SequenceType::Compressed {
-                compressed_sequence,
-                current_frame_texture,
-                reconstructed_frame,
-                ..
-            }) => {
-                if index >= compressed_sequence.frame_count {
-                    return Ok(());
-                }
+    #[test]
+    fn test_linear_to_srgb() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-6);
+        assert!((linear_to_srgb(0.5) - 0.7354).abs() < 1e-4);
+        assert_eq!(linear_to_srgb(2.0), linear_to_srgb(1.0));
 
-                self.current_texture_index = index;
+        // Inverse of the decoding applied to tints and packed frames
+        for byte in 0..=255u8 {
+            let value = byte as f32 / 255.0;
+            let round_trip = linear_to_srgb(srgb_to_linear(value));
+            assert!(
+                (round_trip - value).abs() < 1e-5,
+                "{} became {}",
+                value,
+                round_trip
+            );
+        }
+    }
 
-                // Reconstruct the frame if it's not the base frame
-                let new_frame = if index == 0 {
-                    compressed_sequence.base_frame.clone()
-                } else {
-                    let delta_index = index - 1;
-                    if delta_index < compressed_sequence.deltas.len() {
-                        let base_frame = reconstructed_frame
-                            .as_ref()
-                            .ok_or_else(|| anyhow::anyhow!("No reconstructed frame available"))?;
+    #[test]
+    fn test_color_effects() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        let red = [1.0, 0.0, 0.0];
 
-                        if let Some(ref mut compressor) = self.delta_compressor {
-                            compressor
-                                .reconstruct_frame(
-                                    base_frame,
-                                    &compressed_sequence.deltas[delta_index],
-                                )
-                                .await?
-                        } else {
-                            return Err(anyhow::anyhow!("Delta compressor not available"));
-                        }
-                    } else {
-                        return Ok(());
-                    }
-                };
+        assert_eq!(ColorEffects::default().flags(), 0);
+        assert_eq!(apply_color_effects(red, &ColorEffects::default()), red);
 
-                // Update the reconstructed frame for next iteration
-                *reconstructed_frame = Some(new_frame.clone());
+        // A third of a turn moves each primary onto the next
+        let hue = |degrees| ColorEffects {
+            hue_shift: degrees,
+            ..ColorEffects::default()
+        };
+        assert!(close(
+            apply_color_effects(red, &hue(120.0)),
+            [0.0, 1.0, 0.0]
+        ));
+        assert!(close(
+            apply_color_effects(red, &hue(-120.0)),
+            [0.0, 0.0, 1.0]
+        ));
+        assert_eq!(hue(360.0).flags(), 0);
 
-                // Upload the new frame to the current frame texture
-                let (width, height) = new_frame.dimensions();
-                self.queue.write_texture(
-                    wgpu::TexelCopyTextureInfo {
-                        texture: current_frame_texture,
-                        mip_level: 0,
-                        origin: wgpu::Origin3d::ZERO,
-                        aspect: wgpu::TextureAspect::All,
-                    },
-                    &new_frame,
-                    wgpu::TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row: Some(4 * width),
-                        rows_per_image: Some(height),
-                    },
-                    wgpu::Extent3d {
-                        width,
-                        height,
-                        depth_or_array_layers: 1,
-                    },
+        // No saturation is the same as grayscale
+        let gray = apply_color_effects(
+            red,
+            &ColorEffects {
+                grayscale: true,
+                ..ColorEffects::default()
+            },
+        );
+        assert!(close(gray, [0.2126; 3]));
+        let desaturated = ColorEffects {
+            saturation: 0.0,
+            ..ColorEffects::default()
+        };
+        assert!(close(apply_color_effects(red, &desaturated), gray));
+
+        // Inverting mid gray in sRGB leaves it where it is
+        let invert = ColorEffects {
+            invert: true,
+            ..ColorEffects::default()
+        };
+        assert!(close(apply_color_effects([1.0; 3], &invert), [0.0; 3]));
+        let mid_gray = [srgb_to_linear(0.5); 3];
+        assert!(close(apply_color_effects(mid_gray, &invert), mid_gray));
+    }
+
+    #[test]
+    fn test_color_effects_readback() {
+        let pixels = [[255, 0, 0], [40, 160, 220], [250, 200, 30], [128, 128, 128]];
+        let image = RgbaImage::from_fn(4, 1, |x, _| {
+            let [r, g, b] = pixels[x as usize];
+            Rgba([r, g, b, 255])
+        });
+        let Some(mut renderer) = headless_renderer((4, 1), &[&image]) else {
+            return;
+        };
+
+        let effects = [
+            ColorEffects {
+                hue_shift: 75.0,
+                ..ColorEffects::default()
+            },
+            ColorEffects {
+                saturation: 1.6,
+                ..ColorEffects::default()
+            },
+            ColorEffects {
+                hue_shift: -30.0,
+                saturation: 0.5,
+                grayscale: false,
+                invert: true,
+            },
+            ColorEffects {
+                grayscale: true,
+                invert: true,
+                ..ColorEffects::default()
+            },
+        ];
+        for effects in effects {
+            renderer.set_color_effects(effects);
+            let output = renderer.capture_frame().unwrap();
+            for (x, pixel) in pixels.iter().enumerate() {
+                let linear = pixel.map(|channel| srgb_to_linear(channel as f32 / 255.0));
+                let expected = apply_color_effects(linear, &effects)
+                    .map(|channel| (linear_to_srgb(channel) * 255.0).round() as u8);
+                let actual = output.get_pixel(x as u32, 0);
+                assert!(
+                    (0..3).all(|i| actual[i].abs_diff(expected[i]) <= 2),
+                    "{:?}: {:?} became {:?}, expected {:?}",
+                    effects,
+                    pixel,
+                    actual,
+                    expected
                 );
             }
-            None => {}
         }
+    }
 
-        Ok(())
+    #[test]
+    fn test_choose_alpha_mode() {
+        use wgpu::CompositeAlphaMode::*;
+        assert_eq!(choose_alpha_mode(&[Opaque, PreMultiplied]), PreMultiplied);
+        assert_eq!(choose_alpha_mode(&[Opaque, PostMultiplied]), PostMultiplied);
+        assert_eq!(choose_alpha_mode(&[Opaque]), Opaque);
+        assert_eq!(choose_alpha_mode(&[Inherit]), Inherit);
     }
 
-    pub fn render(&mut self) -> Result<()> {
-        let surface = match &self.surface {
-            Some(surface) => surface,
-            None => {
-                log::warn!("Cannot render: surface has been dropped");
-                return Ok(());
+    #[test]
+    fn test_alpha_output_readback() {
+        let Some((device, queue)) = test_device() else {
+            eprintln!("No GPU adapter available, skipping");
+            return;
+        };
+
+        let image = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([255, 0, 0, 128])
+            } else {
+                Rgba([0, 0, 0, 0])
             }
+        });
+        let render = |alpha_mode| {
+            let mut dimensions = test_dimensions(&image, (2, 1), ScaleMode::Stretch);
+            dimensions.alpha_output = alpha_output(alpha_mode);
+            dimensions.background = [0.0, 0.0, 1.0, 1.0];
+            render_offscreen(
+                &device,
+                &queue,
+                &image,
+                dimensions,
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+            )
+        };
+        let close = |pixel: &Rgba<u8>, expected: [u8; 4]| {
+            (0..4).all(|channel| pixel[channel].abs_diff(expected[channel]) <= 1)
         };
 
-        let frame = surface.get_current_texture()?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Half of linear red, stored as sRGB
+        let output = render(wgpu::CompositeAlphaMode::PreMultiplied);
+        assert!(
+            close(output.get_pixel(0, 0), [188, 0, 0, 128]),
+            "{:?}",
+            output
+        );
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        // Straight alpha gives back the frame's own color
+        let output = render(wgpu::CompositeAlphaMode::PostMultiplied);
+        assert!(
+            close(output.get_pixel(0, 0), [255, 0, 0, 128]),
+            "{:?}",
+            output
+        );
 
-        let bind_group = match &self.sequence_type {
-            Some(SequenceType::Uncompressed {
-                texture_bind_groups,
-            }) => {
-                if !texture_bind_groups.is_empty() {
-                    Some(&texture_bind_groups[self.current_texture_index])
-                } else {
-                    None
-                }
+        // Opaque surfaces show the background through transparent texels
+        let output = render(wgpu::CompositeAlphaMode::Opaque);
+        assert!(
+            close(output.get_pixel(0, 0), [188, 0, 188, 255]),
+            "{:?}",
+            output
+        );
+        assert_eq!(output.get_pixel(1, 0), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_encode_srgb_readback() {
+        let Some((device, queue)) = test_device() else {
+            eprintln!("No GPU adapter available, skipping");
+            return;
+        };
+
+        let image = RgbaImage::from_fn(16, 16, |x, y| {
+            Rgba([(x * 16) as u8, (y * 16) as u8, 200, (255 - x * y) as u8])
+        });
+        let mut dimensions = test_dimensions(&image, (16, 16), ScaleMode::Stretch);
+
+        // Encoding in the shader for a linear target matches what an sRGB
+        // target stores, within rounding
+        let srgb = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            dimensions,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        dimensions.encode_srgb = 1;
+        let encoded = render_offscreen(
+            &device,
+            &queue,
+            &image,
+            dimensions,
+            wgpu::TextureFormat::Rgba8Unorm,
+        );
+
+        for (x, y, pixel) in srgb.enumerate_pixels() {
+            let other = encoded.get_pixel(x, y);
+            for channel in 0..4 {
+                assert!(
+                    pixel[channel].abs_diff(other[channel]) <= 1,
+                    "pixel at {}, {}: {:?} and {:?}",
+                    x,
+                    y,
+                    pixel,
+                    other
+                );
             }
-            Some(SequenceType::Compressed {
-                current_frame_bind_group,
-                ..
-            }) => Some(current_frame_bind_group),
-            None => None,
+        }
+    }
+
+    #[test]
+    fn test_shelf_packer() {
+        let mut packer = ShelfPacker::new((100, 100));
+        let place = |packer: &mut ShelfPacker, size| {
+            let placement = packer.place(size).unwrap();
+            (placement.page, placement.x, placement.y)
         };
 
-        if let Some(bind_group) = bind_group {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        assert_eq!(place(&mut packer, (40, 30)), (0, 0, 0));
+        assert_eq!(place(&mut packer, (40, 50)), (0, 40, 0));
+        // No room left on the first shelf, the next starts below its tallest frame
+        assert_eq!(place(&mut packer, (40, 10)), (0, 0, 50));
+        assert_eq!(place(&mut packer, (60, 50)), (0, 40, 50));
+        // The page is full
+        assert_eq!(place(&mut packer, (10, 10)), (1, 0, 0));
+        assert!(packer.place((101, 10)).is_none());
+    }
 
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, bind_group, &[]);
-            render_pass.draw(0..4, 0..1);
+    #[test]
+    fn test_atlas_page_size() {
+        // Four 200x200 frames fit a 512 page, twenty need 1024
+        assert_eq!(atlas_page_size(&[(200, 200); 4], 4096), Some((512, 512)));
+        assert_eq!(atlas_page_size(&[(200, 200); 20], 4096), Some((1024, 1024)));
+        // Too many for a single page, spill at the maximum size
+        assert_eq!(
+            atlas_page_size(&[(200, 200); 400], 2048),
+            Some((2048, 2048))
+        );
+        assert_eq!(atlas_page_size(&[(300, 10)], 256), None);
+        assert_eq!(atlas_page_size(&[], 256), None);
+    }
+
+    #[test]
+    fn test_shaders_validate() {
+        let mut sources = vec![VERTEX_SHADER.to_string()];
+        for binding in [SINGLE_LAYER_BINDING, ARRAY_BINDING] {
+            for fragment in [FRAGMENT_SHADER, SILHOUETTE_FRAGMENT_SHADER] {
+                sources.push(format!("{}{}{}", FRAGMENT_COMMON, binding, fragment));
+            }
+            for fragment in [PACKED_FRAGMENT_SHADER, PACKED_SILHOUETTE_FRAGMENT_SHADER] {
+                sources.push(format!(
+                    "{}{}{}{}",
+                    FRAGMENT_COMMON, binding, PACKED_COMMON, fragment
+                ));
+            }
+        }
+        for source in &sources {
+            if let Err(message) = validate_wgsl(source, "wgsl") {
+                panic!("{}", message);
+            }
         }
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+    #[test]
+    fn test_load_fragment_shader() {
+        let directory = TestDir::new("shader");
 
-        frame.present();
+        // The built-in shader works as a custom one
+        let path = directory.join("builtin.wgsl");
+        std::fs::write(&path, FRAGMENT_SHADER).unwrap();
+        assert_eq!(load_fragment_shader(&path).unwrap(), FRAGMENT_SHADER);
 
-        Ok(())
+        // Errors name the file, point at its own lines and explain the
+        // interface
+        let path = directory.join("broken.wgsl");
+        std::fs::write(
+            &path,
+            "@fragment\nfn fs_main() -> @location(0) vec4<f32> {\n    return 1;\n}\n",
+        )
+        .unwrap();
+        let message = load_fragment_shader(&path).unwrap_err().to_string();
+        assert!(message.contains("broken.wgsl:3:"), "{}", message);
+        assert!(message.contains("sample_frame"), "{}", message);
+
+        let path = directory.join("no_entry_point.wgsl");
+        std::fs::write(&path, "fn helper() {}").unwrap();
+        let message = load_fragment_shader(&path).unwrap_err().to_string();
+        assert!(message.contains("fs_main"), "{}", message);
+
+        assert!(load_fragment_shader(&directory.join("missing.wgsl")).is_err());
     }
-}
 
-impl Drop for Renderer {
-    fn drop(&mut self) {
-        log::debug!("Dropping Renderer");
-        self.cleanup();
+    #[test]
+    fn test_pack_rgba4444() {
+        let white = RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 255]));
+        assert!(pack_rgba4444(&white).iter().all(|&byte| byte == 0xff));
+
+        let clear = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0]));
+        assert!(pack_rgba4444(&clear).iter().all(|&byte| byte == 0));
+
+        // Dithering keeps the average of a tile close to the original value
+        let gray = RgbaImage::from_pixel(4, 4, Rgba([128, 128, 128, 255]));
+        let packed = pack_rgba4444(&gray);
+        assert_eq!(packed.len(), 32);
+        let average = packed
+            .chunks(2)
+            .map(|texel| (texel[0] >> 4) as f64 * 17.0)
+            .sum::<f64>()
+            / 16.0;
+        assert!((average - 128.0).abs() < 2.0, "average {}", average);
+        assert!(packed.chunks(2).all(|texel| texel[1] & 0x0f == 0x0f));
     }
 }
@@ -0,0 +1,496 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::renderer::FULLSCREEN_VERTEX_SHADER;
+
+/// Declarations shared by the blur and composite fragment shaders
+const SHADOW_COMMON: &str = r#"
+struct ShadowParams {
+    // Linear shadow color, alpha is the shadow's opacity
+    color: vec4<f32>,
+    // Offset of the shadow from the frame in window pixels
+    offset: vec2<f32>,
+    // One texel along the direction a blur pass averages
+    texel_step: vec2<f32>,
+    radius: f32,
+    encode_srgb: u32,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> params: ShadowParams;
+"#;
+
+const BLUR_FRAGMENT_SHADER: &str = r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Gaussian falling to about 2% at the radius
+    let sigma = max(params.radius / 2.0, 0.5);
+    var total = textureSampleLevel(t_source, s_source, in.tex_coords, 0.0).r;
+    var weights = 1.0;
+    for (var i = 1.0; i <= params.radius; i += 1.0) {
+        let weight = exp(-(i * i) / (2.0 * sigma * sigma));
+        let step = params.texel_step * i;
+        total += weight * textureSampleLevel(t_source, s_source, in.tex_coords + step, 0.0).r;
+        total += weight * textureSampleLevel(t_source, s_source, in.tex_coords - step, 0.0).r;
+        weights += 2.0 * weight;
+    }
+    return vec4<f32>(total / weights);
+}
+"#;
+
+const COMPOSITE_FRAGMENT_SHADER: &str = r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_source));
+    let uv = (in.position.xy - params.offset) / size;
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    let coverage = select(0.0, textureSampleLevel(t_source, s_source, uv, 0.0).r, inside);
+
+    let alpha = coverage * params.color.a;
+    let rgb = params.color.rgb * alpha;
+    if params.encode_srgb == 0u {
+        return vec4<f32>(rgb, alpha);
+    }
+    let low = rgb * 12.92;
+    let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return vec4<f32>(select(high, low, rgb <= vec3<f32>(0.0031308)), alpha);
+}
+"#;
+
+/// Format of the silhouette and blur targets, only coverage is kept
+pub const SILHOUETTE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// Largest blur radius in pixels, each pass samples twice as many texels
+const MAX_BLUR_RADIUS: f32 = 64.0;
+
+/// A blurred, offset silhouette of the frame drawn behind it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    /// sRGB shadow color
+    pub color: [u8; 3],
+    /// Offset of the shadow from the frame in window pixels, positive
+    /// values move it right and down
+    pub offset: (f32, f32),
+    /// How far in pixels the shadow's edge is blurred
+    pub blur_radius: f32,
+    /// Opacity of the shadow where the frame is opaque, from 0 to 1
+    pub opacity: f32,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Self {
+            color: [0; 3],
+            offset: (4.0, 4.0),
+            blur_radius: 6.0,
+            opacity: 0.5,
+        }
+    }
+}
+
+impl Shadow {
+    /// Space in pixels to leave around the frame on every side so the shadow
+    /// isn't clipped by the window
+    pub fn margin(&self) -> u32 {
+        let offset = self.offset.0.abs().max(self.offset.1.abs());
+        (self.blur_radius() + offset).ceil() as u32
+    }
+
+    /// The shadow as it looks when drawn `factor` times larger
+    pub fn scaled(self, factor: f32) -> Self {
+        Self {
+            offset: (self.offset.0 * factor, self.offset.1 * factor),
+            blur_radius: self.blur_radius * factor,
+            ..self
+        }
+    }
+
+    fn blur_radius(&self) -> f32 {
+        self.blur_radius.clamp(0.0, MAX_BLUR_RADIUS).round()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ShadowParams {
+    /// Linear color, alpha is the shadow's opacity
+    color: [f32; 4],
+    offset: [f32; 2],
+    /// One texel along the blur direction in texture coordinates, zero for
+    /// the composite pass
+    texel_step: [f32; 2],
+    radius: f32,
+    encode_srgb: u32,
+    _padding: [u32; 2],
+}
+
+/// Blurs a frame's silhouette and composites it as a shadow. The renderer
+/// draws the silhouette into `silhouette_view`, then `draw` blurs it
+/// horizontally and vertically and fills the target with the shadow, ready
+/// for the frame to be drawn on top.
+pub struct ShadowPass {
+    shadow: Shadow,
+    /// Whether the target format isn't sRGB, so the shader encodes the
+    /// shadow color itself
+    encode_srgb: bool,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Parameters of the horizontal blur, vertical blur and composite passes
+    params: [wgpu::Buffer; 3],
+    targets: ShadowTargets,
+}
+
+/// Window-sized textures the shadow is drawn through, recreated on resize
+struct ShadowTargets {
+    silhouette: wgpu::TextureView,
+    /// Holds the horizontal blur, the vertical one goes back into
+    /// `silhouette`
+    blurred: wgpu::TextureView,
+    /// Bind groups of the horizontal blur, vertical blur and composite
+    /// passes
+    bind_groups: [wgpu::BindGroup; 3],
+    size: (u32, u32),
+}
+
+impl ShadowTargets {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        params: &[wgpu::Buffer; 3],
+        size: (u32, u32),
+    ) -> Self {
+        let silhouette = create_target(device, size);
+        let blurred = create_target(device, size);
+        let sources = [&silhouette, &blurred, &silhouette];
+        let bind_groups = std::array::from_fn(|pass| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Shadow Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(sources[pass]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params[pass].as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        Self {
+            silhouette,
+            blurred,
+            bind_groups,
+            size,
+        }
+    }
+}
+
+impl ShadowPass {
+    /// Set up drawing `shadow` into `format` targets of `size`
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shadow: Shadow,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let blur_pipeline = create_pipeline(
+            device,
+            &bind_group_layout,
+            BLUR_FRAGMENT_SHADER,
+            wgpu::ColorTargetState::from(SILHOUETTE_FORMAT),
+        );
+        let composite_pipeline = create_pipeline(
+            device,
+            &bind_group_layout,
+            COMPOSITE_FRAGMENT_SHADER,
+            wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = std::array::from_fn(|_| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Params Buffer"),
+                contents: bytemuck::cast_slice(&[ShadowParams::zeroed()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+
+        let targets = ShadowTargets::new(device, &bind_group_layout, &sampler, &params, size);
+
+        let pass = Self {
+            shadow,
+            encode_srgb: !format.is_srgb(),
+            blur_pipeline,
+            composite_pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+            targets,
+        };
+        pass.write_params(queue);
+        pass
+    }
+
+    pub fn shadow(&self) -> Shadow {
+        self.shadow
+    }
+
+    /// Where the renderer draws the frame's coverage each frame
+    pub fn silhouette_view(&self) -> &wgpu::TextureView {
+        &self.targets.silhouette
+    }
+
+    /// Match the targets to a window of `size`
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: (u32, u32)) {
+        if size != self.targets.size {
+            self.targets = ShadowTargets::new(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                &self.params,
+                size,
+            );
+            self.write_params(queue);
+        }
+    }
+
+    /// Upload the parameters of every pass for the current target size
+    fn write_params(&self, queue: &wgpu::Queue) {
+        let shadow = &self.shadow;
+        let (width, height) = self.targets.size;
+        let composite = ShadowParams {
+            color: [
+                srgb_to_linear(shadow.color[0]),
+                srgb_to_linear(shadow.color[1]),
+                srgb_to_linear(shadow.color[2]),
+                shadow.opacity.clamp(0.0, 1.0),
+            ],
+            offset: [shadow.offset.0, shadow.offset.1],
+            texel_step: [0.0; 2],
+            radius: shadow.blur_radius(),
+            encode_srgb: self.encode_srgb as u32,
+            _padding: [0; 2],
+        };
+        let steps = [[1.0 / width as f32, 0.0], [0.0, 1.0 / height as f32]];
+
+        for (buffer, texel_step) in self.params.iter().zip(steps.into_iter().chain([[0.0; 2]])) {
+            let params = ShadowParams {
+                texel_step,
+                ..composite
+            };
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[params]));
+        }
+    }
+
+    /// Blur the silhouette drawn since the last call and fill `target` with
+    /// the shadow over `background`, clearing what was there
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        background: wgpu::Color,
+    ) {
+        let targets = &self.targets;
+        let passes = [
+            (&targets.blurred, &self.blur_pipeline),
+            (&targets.silhouette, &self.blur_pipeline),
+            (target, &self.composite_pipeline),
+        ];
+
+        let clears = [
+            wgpu::Color::TRANSPARENT,
+            wgpu::Color::TRANSPARENT,
+            background,
+        ];
+        for (((view, pipeline), bind_group), clear) in
+            passes.into_iter().zip(&targets.bind_groups).zip(clears)
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+fn create_target(device: &wgpu::Device, (width, height): (u32, u32)) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SILHOUETTE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    fragment_source: &str,
+    target: wgpu::ColorTargetState,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shadow Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            format!(
+                "{}{}{}",
+                FULLSCREEN_VERTEX_SHADER, SHADOW_COMMON, fragment_source
+            )
+            .into(),
+        ),
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Shadow Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Shadow Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(target)],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn srgb_to_linear(channel: u8) -> f32 {
+    let value = channel as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::validate_wgsl;
+
+    #[test]
+    fn test_shadow_margin() {
+        assert_eq!(Shadow::default().margin(), 10);
+        let shadow = Shadow {
+            offset: (-3.0, 7.5),
+            blur_radius: 2.2,
+            ..Shadow::default()
+        };
+        assert_eq!(shadow.margin(), 10);
+        // The radius is capped, so is the margin
+        let shadow = Shadow {
+            offset: (0.0, 0.0),
+            blur_radius: 1000.0,
+            ..Shadow::default()
+        };
+        assert_eq!(shadow.margin(), MAX_BLUR_RADIUS as u32);
+    }
+
+    #[test]
+    fn test_shadow_shaders_validate() {
+        for fragment_source in [BLUR_FRAGMENT_SHADER, COMPOSITE_FRAGMENT_SHADER] {
+            let source = format!(
+                "{}{}{}",
+                FULLSCREEN_VERTEX_SHADER, SHADOW_COMMON, fragment_source
+            );
+            if let Err(message) = validate_wgsl(&source, "shadow") {
+                panic!("{}", message);
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Changed whenever fields change meaning, files of other versions are
+/// ignored
+const STATE_VERSION: u32 = 1;
+
+/// Where the overlay was and how it was set up when it last exited, restored
+/// on the next start unless command line flags say otherwise
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedState {
+    pub version: u32,
+    /// Top left corner of the window on the desktop, unknown on Wayland
+    pub position: Option<(i32, i32)>,
+    /// Name of the monitor the window was on
+    pub monitor: Option<String>,
+    pub window_scale: f64,
+    pub opacity: f32,
+    pub speed: f32,
+    /// Path or preset that was playing, `frame` only applies to it
+    pub source: Option<String>,
+    /// Frame of the main sequence that was showing
+    pub frame: usize,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            position: None,
+            monitor: None,
+            window_scale: 1.0,
+            opacity: 1.0,
+            speed: 1.0,
+            source: None,
+            frame: 0,
+        }
+    }
+}
+
+impl SavedState {
+    /// Read the state saved by the last run. A missing file is no state, a
+    /// file that can't be read, is corrupt or from another version is
+    /// ignored with a warning.
+    pub fn load() -> Option<Self> {
+        let path = get_state_path()
+            .inspect_err(|e| log::warn!("No saved state: {}", e))
+            .ok()?;
+        if !path.exists() {
+            log::debug!("State file not found at {}", path.display());
+            return None;
+        }
+
+        log::info!("Restoring state from {}", path.display());
+        fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read it: {}", e))
+            .and_then(|content| Self::parse(&content))
+            .inspect_err(|e| log::warn!("Ignoring the state file {}: {}", path.display(), e))
+            .ok()
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let state: Self = toml::from_str(content).map_err(|e| anyhow!("{}", e))?;
+        if state.version != STATE_VERSION {
+            return Err(anyhow!(
+                "It is version {}, this build reads version {}",
+                state.version,
+                STATE_VERSION
+            ));
+        }
+        // Hand edited values like nan would reach the playback math
+        if !state.window_scale.is_finite() || !state.opacity.is_finite() || !state.speed.is_finite()
+        {
+            return Err(anyhow!(
+                "It holds a window scale, opacity or speed that isn't a number"
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Write the state for the next run to restore
+    pub fn save(&self) -> Result<()> {
+        let path = get_state_path()?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)
+                .map_err(|e| anyhow!("Failed to create {}: {}", directory.display(), e))?;
+        }
+
+        // Written next to it and renamed over it, so an interrupted write
+        // never leaves half a file
+        let content = toml::to_string(self)?;
+        let partial = path.with_extension("toml.partial");
+        fs::write(&partial, content)
+            .and_then(|()| fs::rename(&partial, &path))
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        log::debug!("Saved state to {}", path.display());
+        Ok(())
+    }
+
+    /// Delete the saved state. Returns whether there was any.
+    pub fn clear() -> Result<bool> {
+        let path = get_state_path()?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(anyhow!("Failed to delete {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Where the state is saved: the XDG state directory on Linux, the local
+/// application data directory elsewhere
+pub fn get_state_path() -> Result<PathBuf> {
+    let directory = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+
+    Ok(directory.join("anibuddy").join("state.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trip() {
+        let state = SavedState {
+            position: Some((-1280, 40)),
+            monitor: Some("DP-2".to_string()),
+            window_scale: 1.5,
+            speed: 0.5,
+            source: Some("konata".to_string()),
+            frame: 12,
+            ..SavedState::default()
+        };
+        let content = toml::to_string(&state).unwrap();
+        assert_eq!(SavedState::parse(&content).unwrap(), state);
+
+        // Nothing remembered yet
+        let content = toml::to_string(&SavedState::default()).unwrap();
+        assert_eq!(SavedState::parse(&content).unwrap(), SavedState::default());
+    }
+
+    #[test]
+    fn test_state_rejected() {
+        assert!(SavedState::parse("").is_err());
+        assert!(SavedState::parse("version = 1\nposition = [").is_err());
+        assert!(SavedState::parse("version = 1\nspeed = \"fast\"").is_err());
+
+        let other_version = SavedState {
+            version: STATE_VERSION + 1,
+            ..SavedState::default()
+        };
+        let content = toml::to_string(&other_version).unwrap();
+        assert!(SavedState::parse(&content).is_err());
+
+        // Not a number, or infinite
+        for field in ["window_scale", "opacity", "speed"] {
+            for value in ["nan", "inf", "-inf"] {
+                let content = toml::to_string(&SavedState::default())
+                    .unwrap()
+                    .lines()
+                    .map(|line| {
+                        if line.starts_with(&format!("{} =", field)) {
+                            format!("{} = {}", field, value)
+                        } else {
+                            line.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                assert!(
+                    SavedState::parse(&content).is_err(),
+                    "{} = {}",
+                    field,
+                    value
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::renderer::FULLSCREEN_VERTEX_SHADER;
+
+const DOWNSAMPLE_FRAGMENT_SHADER: &str = r#"
+struct DownsampleParams {
+    target_size: vec2<f32>,
+    // Samples along each axis of a target pixel's footprint
+    taps: u32,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> params: DownsampleParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Average evenly spaced samples over the source texels this pixel
+    // covers, each filtered linearly between its neighbours
+    let pixel = floor(in.position.xy);
+    let taps = f32(params.taps);
+    var total = vec4<f32>(0.0);
+    for (var y = 0u; y < params.taps; y++) {
+        for (var x = 0u; x < params.taps; x++) {
+            let offset = (vec2<f32>(f32(x), f32(y)) + 0.5) / taps;
+            total += textureSampleLevel(t_source, s_source, (pixel + offset) / params.target_size, 0.0);
+        }
+    }
+    return total / (taps * taps);
+}
+"#;
+
+/// Largest render scale, beyond it the extra detail isn't visible
+pub const MAX_RENDER_SCALE: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct DownsampleParams {
+    target_size: [f32; 2],
+    taps: u32,
+    _padding: u32,
+}
+
+/// An offscreen target larger than the window that frames are drawn into,
+/// box filtered down to the window's size afterwards
+pub struct Supersampler {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    /// Size of the offscreen target and of the window it is reduced to
+    sizes: ((u32, u32), (u32, u32)),
+}
+
+impl Supersampler {
+    /// Set up drawing at `size` for a `format` target of `target_size`
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        target_size: (u32, u32),
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Downsample Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Downsample Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{}{}", FULLSCREEN_VERTEX_SHADER, DOWNSAMPLE_FRAGMENT_SHADER).into(),
+            ),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Downsample Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Downsample Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(format.into())],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Downsample Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Downsample Params Buffer"),
+            contents: bytemuck::cast_slice(&[DownsampleParams::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let (view, bind_group) =
+            create_target(device, &bind_group_layout, &sampler, &params, format, size);
+
+        let supersampler = Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+            format,
+            view,
+            bind_group,
+            sizes: (size, target_size),
+        };
+        supersampler.write_params(queue);
+        supersampler
+    }
+
+    /// Where frames are drawn before `draw` reduces them
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Size frames are drawn at
+    pub fn size(&self) -> (u32, u32) {
+        self.sizes.0
+    }
+
+    /// Draw at `size` for a target of `target_size` from now on
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: (u32, u32),
+        target_size: (u32, u32),
+    ) {
+        if (size, target_size) == self.sizes {
+            return;
+        }
+        if size != self.sizes.0 {
+            (self.view, self.bind_group) = create_target(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                &self.params,
+                self.format,
+                size,
+            );
+        }
+        self.sizes = (size, target_size);
+        self.write_params(queue);
+    }
+
+    fn write_params(&self, queue: &wgpu::Queue) {
+        let ((width, height), (target_width, target_height)) = self.sizes;
+        let scale = (width as f32 / target_width.max(1) as f32)
+            .max(height as f32 / target_height.max(1) as f32);
+        let params = DownsampleParams {
+            target_size: [target_width as f32, target_height as f32],
+            taps: scale.ceil().max(1.0) as u32,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    /// Reduce what was drawn into `view` onto `target`, replacing its
+    /// contents
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Downsample Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// The offscreen texture frames are drawn into and the bind group sampling
+/// it
+fn create_target(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    params: &wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    (width, height): (u32, u32),
+) -> (wgpu::TextureView, wgpu::BindGroup) {
+    let view = device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Supersampled Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Downsample Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params.as_entire_binding(),
+            },
+        ],
+    });
+
+    (view, bind_group)
+}
+
+/// Size to draw at for a window of `size` at `scale`, the scale reduced so
+/// neither side exceeds `max_dimension` and never below the window's size
+pub fn scaled_size(size: (u32, u32), scale: f32, max_dimension: u32) -> (u32, u32) {
+    let largest = size.0.max(size.1).max(1) as f32;
+    let scale = scale
+        .min(MAX_RENDER_SCALE)
+        .min(max_dimension as f32 / largest)
+        .max(1.0);
+    (
+        (size.0 as f32 * scale).round() as u32,
+        (size.1 as f32 * scale).round() as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::validate_wgsl;
+
+    #[test]
+    fn test_scaled_size() {
+        assert_eq!(scaled_size((300, 200), 1.0, 8192), (300, 200));
+        assert_eq!(scaled_size((300, 200), 2.5, 8192), (750, 500));
+        // Capped by the render scale limit and the texture size limit
+        assert_eq!(scaled_size((300, 200), 10.0, 8192), (1200, 800));
+        assert_eq!(scaled_size((3000, 2000), 4.0, 8192), (8192, 5461));
+        // Never smaller than the window
+        assert_eq!(scaled_size((300, 200), 0.5, 8192), (300, 200));
+        assert_eq!(scaled_size((9000, 100), 2.0, 8192), (9000, 100));
+    }
+
+    #[test]
+    fn test_downsample_shader_validates() {
+        let source = format!("{}{}", FULLSCREEN_VERTEX_SHADER, DOWNSAMPLE_FRAGMENT_SHADER);
+        if let Err(message) = validate_wgsl(&source, "downsample") {
+            panic!("{}", message);
+        }
+    }
+}
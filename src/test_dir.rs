@@ -0,0 +1,62 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tests that have created a directory so far in this process
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Empty directory under the temp directory for one test, removed with
+/// everything in it when dropped. Its name holds the process id and a
+/// counter, so tests running at the same time never share one.
+#[derive(Debug)]
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// Create a directory named after `name`
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "anibuddy-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Left over by an earlier run with the same process id
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_and_removed() {
+        let first = TestDir::new("same");
+        let second = TestDir::new("same");
+        assert_ne!(first.to_path_buf(), second.to_path_buf());
+
+        std::fs::write(first.join("file"), b"contents").unwrap();
+        let path = first.to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.is_dir());
+    }
+}
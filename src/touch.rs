@@ -0,0 +1,163 @@
+use winit::dpi::PhysicalPosition;
+use winit::event::TouchPhase;
+
+/// What fingers on the overlay ask for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// One finger rests on the window at this position, where dragging
+    /// starts from, as the first one down or the last one left
+    Hold(PhysicalPosition<f64>),
+    /// The finger holding the window moved to this position
+    Drag(PhysicalPosition<f64>),
+    /// The fingers stopped dragging, by lifting or by a second one
+    /// joining in
+    Release,
+    /// The two fingers pinching moved apart or together by this factor
+    Pinch(f64),
+}
+
+/// Fingers on the overlay by their ids, in the order they touched it
+#[derive(Debug, Clone)]
+pub struct Touches<K> {
+    fingers: Vec<(K, PhysicalPosition<f64>)>,
+}
+
+impl<K> Default for Touches<K> {
+    fn default() -> Self {
+        Self {
+            fingers: Vec::new(),
+        }
+    }
+}
+
+impl<K: PartialEq> Touches<K> {
+    /// Follow finger `id` through `phase` at `position`. One finger drags,
+    /// the first two of several pinch, however many come and go.
+    pub fn update(
+        &mut self,
+        id: K,
+        phase: TouchPhase,
+        position: PhysicalPosition<f64>,
+    ) -> Option<Gesture> {
+        let index = self.fingers.iter().position(|(finger, _)| *finger == id);
+        match (phase, index) {
+            (TouchPhase::Started, Some(index)) => {
+                self.fingers[index].1 = position;
+                None
+            }
+            (TouchPhase::Started, None) => {
+                self.fingers.push((id, position));
+                match self.fingers.len() {
+                    1 => Some(Gesture::Hold(position)),
+                    2 => Some(Gesture::Release),
+                    _ => None,
+                }
+            }
+            (TouchPhase::Moved, Some(index)) => {
+                let distance = self.pinch_distance();
+                self.fingers[index].1 = position;
+                match (self.fingers.len(), distance, self.pinch_distance()) {
+                    (1, _, _) => Some(Gesture::Drag(position)),
+                    (_, Some(before), Some(after)) if index < 2 && before > 0.0 => {
+                        Some(Gesture::Pinch(after / before)).filter(|_| after > 0.0)
+                    }
+                    _ => None,
+                }
+            }
+            (TouchPhase::Ended | TouchPhase::Cancelled, Some(index)) => {
+                self.fingers.remove(index);
+                match self.fingers.as_slice() {
+                    [] => Some(Gesture::Release),
+                    [(_, position)] => Some(Gesture::Hold(*position)),
+                    _ => None,
+                }
+            }
+            // Fingers that went down elsewhere
+            (_, None) => None,
+        }
+    }
+
+    /// Forget every finger, as when the window they were on went away
+    pub fn clear(&mut self) {
+        self.fingers.clear();
+    }
+
+    /// Distance between the first two fingers
+    fn pinch_distance(&self) -> Option<f64> {
+        match self.fingers.as_slice() {
+            [(_, first), (_, second), ..] => Some((first.x - second.x).hypot(first.y - second.y)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f64, y: f64) -> PhysicalPosition<f64> {
+        PhysicalPosition::new(x, y)
+    }
+
+    #[test]
+    fn test_drag() {
+        let mut touches = Touches::default();
+        assert_eq!(
+            touches.update(1, TouchPhase::Started, at(10.0, 10.0)),
+            Some(Gesture::Hold(at(10.0, 10.0)))
+        );
+        assert_eq!(
+            touches.update(1, TouchPhase::Moved, at(15.0, 12.0)),
+            Some(Gesture::Drag(at(15.0, 12.0)))
+        );
+        // Fingers never seen going down are left alone
+        assert_eq!(touches.update(7, TouchPhase::Moved, at(0.0, 0.0)), None);
+        assert_eq!(touches.update(7, TouchPhase::Ended, at(0.0, 0.0)), None);
+        assert_eq!(
+            touches.update(1, TouchPhase::Cancelled, at(15.0, 12.0)),
+            Some(Gesture::Release)
+        );
+        assert_eq!(touches.update(1, TouchPhase::Moved, at(20.0, 20.0)), None);
+    }
+
+    #[test]
+    fn test_pinch() {
+        let mut touches = Touches::default();
+        touches.update(1, TouchPhase::Started, at(0.0, 0.0));
+        // A second finger stops the drag and starts pinching
+        assert_eq!(
+            touches.update(2, TouchPhase::Started, at(100.0, 0.0)),
+            Some(Gesture::Release)
+        );
+        assert_eq!(
+            touches.update(2, TouchPhase::Moved, at(150.0, 0.0)),
+            Some(Gesture::Pinch(1.5))
+        );
+        assert_eq!(
+            touches.update(1, TouchPhase::Moved, at(75.0, 0.0)),
+            Some(Gesture::Pinch(0.5))
+        );
+
+        // A third finger takes no part until one of the first two lifts
+        assert_eq!(
+            touches.update(3, TouchPhase::Started, at(150.0, 100.0)),
+            None
+        );
+        assert_eq!(touches.update(3, TouchPhase::Moved, at(150.0, 200.0)), None);
+        assert_eq!(touches.update(1, TouchPhase::Ended, at(75.0, 0.0)), None);
+        assert_eq!(
+            touches.update(3, TouchPhase::Moved, at(150.0, 400.0)),
+            Some(Gesture::Pinch(2.0))
+        );
+
+        // The finger left over holds the window where it is
+        assert_eq!(
+            touches.update(2, TouchPhase::Ended, at(150.0, 0.0)),
+            Some(Gesture::Hold(at(150.0, 400.0)))
+        );
+        assert_eq!(
+            touches.update(3, TouchPhase::Moved, at(160.0, 400.0)),
+            Some(Gesture::Drag(at(160.0, 400.0)))
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use ksni::blocking::{Handle, TrayMethods};
+use ksni::menu::{MenuItem, StandardItem};
+use winit::event_loop::EventLoopProxy;
+
+use crate::overlay::OverlayCommand;
+
+/// Tray icon whose menu sends commands to the overlay's event loop
+pub struct OverlayTray {
+    proxy: EventLoopProxy<OverlayCommand>,
+    title: String,
+}
+
+impl OverlayTray {
+    /// Show the tray icon, labeled `title`, until the handle is dropped
+    /// or shut down. Fails when no tray host is running.
+    pub fn spawn(proxy: EventLoopProxy<OverlayCommand>, title: String) -> Result<Handle<Self>> {
+        Ok(Self { proxy, title }.spawn()?)
+    }
+
+    fn item(label: &str, command: OverlayCommand) -> MenuItem<Self> {
+        StandardItem {
+            label: label.to_string(),
+            activate: Box::new(move |tray: &mut Self| {
+                // Fails only once the event loop is gone
+                let _ = tray.proxy.send_event(command);
+            }),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl ksni::Tray for OverlayTray {
+    // A click opens the menu, there is nothing else to do
+    const MENU_ON_ACTIVATE: bool = true;
+
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").to_string()
+    }
+
+    fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    fn icon_name(&self) -> String {
+        "applications-multimedia".to_string()
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        vec![
+            Self::item("Show/Hide", OverlayCommand::ToggleVisible),
+            Self::item("Pause/Resume", OverlayCommand::TogglePause),
+            Self::item("Stop", OverlayCommand::Stop),
+            Self::item("Open config", OverlayCommand::OpenConfig),
+            MenuItem::Separator,
+            Self::item("Quit", OverlayCommand::Quit),
+        ]
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{Result, anyhow};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How long the directory has to stay quiet before changes are reported, so
+/// a bulk export results in a single reload
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Collects file changes inside an image directory
+pub struct DirectoryWatcher {
+    // Dropping the watcher stops the events
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    changed: HashSet<PathBuf>,
+    last_event: Option<Instant>,
+}
+
+impl DirectoryWatcher {
+    pub fn new(directory: &Path, recursive: bool) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .map_err(|e| anyhow!("Failed to create a watcher: {}", e))?;
+
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher
+            .watch(directory, mode)
+            .map_err(|e| anyhow!("Failed to watch {}: {}", directory.display(), e))?;
+
+        log::info!("Watching {} for changes", directory.display());
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            changed: HashSet::new(),
+            last_event: None,
+        })
+    }
+
+    /// Paths changed since the last report, once no new change has arrived
+    /// for the debounce period
+    pub fn poll(&mut self) -> Option<Vec<PathBuf>> {
+        let now = Instant::now();
+
+        for event in self.events.try_iter() {
+            match event {
+                // Reading the frames ourselves shows up as access events
+                Ok(event) if is_content_change(&event.kind) => {
+                    self.changed.extend(event.paths);
+                    self.last_event = Some(now);
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("File watcher error: {}", e),
+            }
+        }
+
+        match self.last_event {
+            Some(last_event) if now.duration_since(last_event) >= DEBOUNCE => {
+                self.last_event = None;
+                Some(self.changed.drain().collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_content_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
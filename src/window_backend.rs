@@ -0,0 +1,844 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use winit::application::ApplicationHandler;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, EventLoop, EventLoopBuilder};
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey};
+use winit::monitor::MonitorHandle;
+use winit::window::{CursorIcon, Fullscreen, Window, WindowAttributes, WindowId};
+
+/// What the overlay needs from the surface it is drawn on. Winit windows
+/// provide it for every platform, `LayerShellWindow` for Wayland
+/// compositors supporting wlr-layer-shell.
+pub trait WindowBackend: wgpu::WindowHandle {
+    /// Identifies the window among the overlay's others
+    fn id(&self) -> WindowId;
+
+    fn inner_size(&self) -> PhysicalSize<u32>;
+
+    /// Physical pixels per logical pixel of the display showing the window
+    fn scale_factor(&self) -> f64 {
+        1.0
+    }
+
+    /// Monitor showing most of the window, if the platform tells it
+    fn current_monitor(&self) -> Option<MonitorHandle> {
+        None
+    }
+
+    /// Ask for a new size, which applies once the platform confirms it
+    fn request_inner_size(&self, size: PhysicalSize<u32>);
+
+    fn set_visible(&self, visible: bool);
+
+    fn set_title(&self, title: &str);
+
+    fn request_redraw(&self);
+
+    /// Position of the top left corner on the desktop, if the platform
+    /// tells it
+    fn outer_position(&self) -> Option<PhysicalPosition<i32>>;
+
+    fn set_outer_position(&self, position: PhysicalPosition<i32>);
+
+    /// Let the platform move the window with the pointer until the button
+    /// is released. Returns false where it can't, the window then has to
+    /// be moved by hand.
+    fn drag_window(&self) -> bool {
+        false
+    }
+
+    /// Fill the monitor showing the window without decorations, or go back
+    /// to the window's own size. Returns false where it can't.
+    fn set_fullscreen(&self, _fullscreen: bool) -> bool {
+        false
+    }
+
+    /// Let clicks pass through to the windows underneath or take them
+    fn set_click_through(&self, _click_through: bool) {}
+
+    /// Change how the pointer looks while over the window
+    fn set_cursor(&self, _style: CursorStyle) {}
+
+    /// Take clicks only within `rectangles`, given as x, y, width and
+    /// height, or anywhere in the window without them. Returns false where
+    /// the platform can't.
+    fn set_input_shape(&self, _rectangles: Option<&[[i32; 4]]>) -> bool {
+        false
+    }
+
+    /// Events that arrived outside winit's event loop since the last call
+    fn poll_events(&self) -> Vec<BackendEvent> {
+        Vec::new()
+    }
+
+    /// Whether `poll_events` has to be called regularly, because nothing
+    /// wakes the event loop up for them
+    fn needs_polling(&self) -> bool {
+        false
+    }
+}
+
+/// Window events of backends that winit doesn't deliver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub enum BackendEvent {
+    Resized(PhysicalSize<u32>),
+    RedrawRequested,
+    CloseRequested,
+}
+
+/// Screen corner the overlay is anchored to, or the screen's center
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+/// How X11 window managers are asked to treat the overlay window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum X11Mode {
+    /// An ordinary window the window manager places, lists and may tile
+    #[default]
+    Managed,
+    /// Bypass the window manager entirely, nothing lists, moves or
+    /// decorates the window
+    OverrideRedirect,
+    /// A dock, which window managers keep above others and leave out of
+    /// taskbars, pagers and alt-tab
+    Dock,
+    /// A floating utility window, kept out of tiling
+    Utility,
+}
+
+/// Key that has to be held to drag the overlay with the mouse
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DragModifier {
+    /// Any left click drags
+    None,
+    #[default]
+    Alt,
+    Ctrl,
+    Shift,
+    Super,
+}
+
+impl DragModifier {
+    /// Whether `modifiers` include this one
+    pub fn is_held(self, modifiers: ModifiersState) -> bool {
+        match self {
+            DragModifier::None => true,
+            DragModifier::Alt => modifiers.alt_key(),
+            DragModifier::Ctrl => modifiers.control_key(),
+            DragModifier::Shift => modifiers.shift_key(),
+            DragModifier::Super => modifiers.super_key(),
+        }
+    }
+}
+
+/// How the pointer looks over an interactive overlay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CursorStyle {
+    /// The platform's arrow
+    #[default]
+    Default,
+    /// No pointer at all
+    Hidden,
+    /// A hand while the drag modifier is held, showing that the overlay
+    /// can be dragged
+    Grab,
+}
+
+/// Modifiers among `keys` held down, either the left or the right one
+pub fn held_modifiers(keys: &HashSet<KeyCode>) -> ModifiersState {
+    [
+        (
+            KeyCode::ShiftLeft,
+            KeyCode::ShiftRight,
+            ModifiersState::SHIFT,
+        ),
+        (
+            KeyCode::ControlLeft,
+            KeyCode::ControlRight,
+            ModifiersState::CONTROL,
+        ),
+        (KeyCode::AltLeft, KeyCode::AltRight, ModifiersState::ALT),
+        (
+            KeyCode::SuperLeft,
+            KeyCode::SuperRight,
+            ModifiersState::SUPER,
+        ),
+    ]
+    .into_iter()
+    .filter(|(left, right, _)| keys.contains(left) || keys.contains(right))
+    .fold(ModifiersState::empty(), |held, (_, _, modifier)| {
+        held | modifier
+    })
+}
+
+/// Key the overlay's controls know `code` as on a US layout, for keys
+/// that arrive without the layout applied
+pub fn key_for_code(code: KeyCode, shift: bool) -> Option<Key> {
+    let text = match code {
+        KeyCode::Escape => return Some(Key::Named(NamedKey::Escape)),
+        KeyCode::Space => return Some(Key::Named(NamedKey::Space)),
+        KeyCode::Tab => return Some(Key::Named(NamedKey::Tab)),
+        KeyCode::ArrowLeft => return Some(Key::Named(NamedKey::ArrowLeft)),
+        KeyCode::ArrowRight => return Some(Key::Named(NamedKey::ArrowRight)),
+        KeyCode::F11 => return Some(Key::Named(NamedKey::F11)),
+        KeyCode::F12 => return Some(Key::Named(NamedKey::F12)),
+        KeyCode::KeyQ if !shift => "q",
+        KeyCode::KeyH if !shift => "h",
+        KeyCode::Equal | KeyCode::NumpadAdd if shift => "+",
+        KeyCode::Equal => "=",
+        KeyCode::NumpadAdd => "+",
+        KeyCode::Minus | KeyCode::NumpadSubtract => "-",
+        KeyCode::Digit0 | KeyCode::Numpad0 => "0",
+        KeyCode::BracketLeft => "[",
+        KeyCode::BracketRight => "]",
+        _ => return None,
+    };
+    Some(Key::Character(text.into()))
+}
+
+/// Where the overlay is placed on its monitor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Placement {
+    pub corner: Corner,
+    /// Horizontal and vertical distance from the corner's edges in pixels
+    pub margin: (u32, u32),
+}
+
+/// `attributes` with the hints making X11 window managers treat the window
+/// as `mode`. Wayland ignores them.
+#[cfg(target_os = "linux")]
+pub fn with_x11_mode(attributes: WindowAttributes, mode: X11Mode) -> WindowAttributes {
+    use winit::platform::x11::{WindowAttributesExtX11, WindowType};
+
+    match mode {
+        X11Mode::Managed => attributes,
+        X11Mode::OverrideRedirect => attributes.with_override_redirect(true),
+        X11Mode::Dock => attributes.with_x11_window_type(vec![WindowType::Dock]),
+        X11Mode::Utility => attributes.with_x11_window_type(vec![WindowType::Utility]),
+    }
+}
+
+/// Other platforms have no X11 window managers to hint
+#[cfg(not(target_os = "linux"))]
+pub fn with_x11_mode(attributes: WindowAttributes, _mode: X11Mode) -> WindowAttributes {
+    attributes
+}
+
+/// `attributes` with `app_id` as the Wayland application id and the X11
+/// WM_CLASS, which compositors and window managers match rules against
+#[cfg(target_os = "linux")]
+pub fn with_app_id(attributes: WindowAttributes, app_id: &str) -> WindowAttributes {
+    use winit::platform::x11::WindowAttributesExtX11;
+
+    // Winit keeps one name for both
+    attributes.with_name(app_id, app_id)
+}
+
+/// Other platforms tell windows apart by their executable
+#[cfg(not(target_os = "linux"))]
+pub fn with_app_id(attributes: WindowAttributes, _app_id: &str) -> WindowAttributes {
+    attributes
+}
+
+/// `attributes` for a borderless panel floating above other windows, with
+/// neither titlebar nor the shadow macOS draws around window contents
+#[cfg(target_os = "macos")]
+pub fn with_macos_style(attributes: WindowAttributes) -> WindowAttributes {
+    use winit::platform::macos::WindowAttributesExtMacOS;
+    use winit::window::WindowLevel;
+
+    attributes
+        .with_window_level(WindowLevel::AlwaysOnTop)
+        .with_titlebar_transparent(true)
+        .with_title_hidden(true)
+        .with_fullsize_content_view(true)
+        .with_has_shadow(false)
+}
+
+/// Other platforms style windows through their own options
+#[cfg(not(target_os = "macos"))]
+pub fn with_macos_style(attributes: WindowAttributes) -> WindowAttributes {
+    attributes
+}
+
+/// `builder` for an application without Dock icon or menu bar on macOS,
+/// like other desktop decorations, which only takes over from the active
+/// application on launch if `activate`
+#[cfg(target_os = "macos")]
+pub fn as_accessory<T>(
+    builder: &mut EventLoopBuilder<T>,
+    activate: bool,
+) -> &mut EventLoopBuilder<T> {
+    use winit::platform::macos::{ActivationPolicy, EventLoopBuilderExtMacOS};
+
+    builder
+        .with_activation_policy(ActivationPolicy::Accessory)
+        .with_activate_ignoring_other_apps(activate)
+}
+
+/// Only macOS lists applications by more than their windows
+#[cfg(not(target_os = "macos"))]
+pub fn as_accessory<T>(
+    builder: &mut EventLoopBuilder<T>,
+    _activate: bool,
+) -> &mut EventLoopBuilder<T> {
+    builder
+}
+
+/// Never give `window` the keyboard focus, neither when it is shown nor
+/// when it is clicked. X11 window managers read the hints when the window
+/// is mapped, so this has to happen while it is still hidden. Wayland
+/// compositors hand out the focus as they see fit.
+#[cfg(target_os = "linux")]
+pub fn prevent_focus(window: &Window) -> Result<()> {
+    use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use x11rb::connection::Connection;
+    use x11rb::properties::WmHints;
+
+    let id = match window.window_handle()?.as_raw() {
+        RawWindowHandle::Xlib(handle) => handle.window as u32,
+        RawWindowHandle::Xcb(handle) => handle.window.get(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Wayland compositors decide which window has the focus"
+            ));
+        }
+    };
+
+    let (connection, _) = x11rb::connect(None)?;
+    let mut hints = WmHints::get(&connection, id)?
+        .reply()?
+        .unwrap_or_else(WmHints::new);
+    hints.input = Some(false);
+    hints.set(&connection, id)?.check()?;
+    connection.flush()?;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn prevent_focus(window: &Window) -> Result<()> {
+    no_activate::set(window)
+}
+
+/// macOS only makes windows key within the active application, which an
+/// accessory application doesn't become unless clicked
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn prevent_focus(_window: &Window) -> Result<()> {
+    Ok(())
+}
+
+/// Take clicks on `window` only within `rectangles` through the X shape
+/// extension. Winit doesn't expose its Wayland surfaces to set an input
+/// region on.
+#[cfg(target_os = "linux")]
+fn set_input_shape(window: &Window, rectangles: Option<&[[i32; 4]]>) -> Result<()> {
+    use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::shape::{ConnectionExt, SK, SO};
+    use x11rb::protocol::xproto::{ClipOrdering, Rectangle};
+
+    let id = match window.window_handle()?.as_raw() {
+        RawWindowHandle::Xlib(handle) => handle.window as u32,
+        RawWindowHandle::Xcb(handle) => handle.window.get(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Winit's Wayland windows have no input region to set"
+            ));
+        }
+    };
+
+    let (connection, _) = x11rb::connect(None)?;
+    match rectangles {
+        Some(rectangles) => {
+            let rectangles: Vec<Rectangle> = rectangles
+                .iter()
+                .map(|&[x, y, width, height]| Rectangle {
+                    x: x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                    y: y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                    width: width.clamp(0, u16::MAX as i32) as u16,
+                    height: height.clamp(0, u16::MAX as i32) as u16,
+                })
+                .collect();
+            connection.shape_rectangles(
+                SO::SET,
+                SK::INPUT,
+                ClipOrdering::UNSORTED,
+                id,
+                0,
+                0,
+                &rectangles,
+            )?
+        }
+        // No mask restores the default shape
+        None => connection.shape_mask(SO::SET, SK::INPUT, id, 0, 0, x11rb::NONE)?,
+    }
+    .check()?;
+    connection.flush()?;
+    Ok(())
+}
+
+/// Other platforms have no input regions apart from the visible shape
+#[cfg(not(target_os = "linux"))]
+fn set_input_shape(_window: &Window, _rectangles: Option<&[[i32; 4]]>) -> Result<()> {
+    Err(anyhow::anyhow!("Input shapes need X11 or Wayland"))
+}
+
+/// Show `window` without taking the focus from the window that has it
+#[cfg(target_os = "windows")]
+pub fn show_inactive(window: &dyn WindowBackend) {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, SetForegroundWindow};
+
+    // Winit only shows a window without activating it the first time,
+    // afterwards the previous window gets the focus back. Being in the
+    // foreground then, the process is allowed to hand it over.
+    let previous = unsafe { GetForegroundWindow() };
+    window.set_visible(true);
+    let hwnd = no_activate::hwnd(window);
+    if previous != 0 && Some(previous) != hwnd && hwnd == Some(unsafe { GetForegroundWindow() }) {
+        unsafe { SetForegroundWindow(previous) };
+    }
+}
+
+/// Other platforms show windows without activating them once they were
+/// created inactive
+#[cfg(not(target_os = "windows"))]
+pub fn show_inactive(window: &dyn WindowBackend) {
+    window.set_visible(true);
+}
+
+/// The WS_EX_NOACTIVATE style, which keeps clicks from activating windows
+#[cfg(target_os = "windows")]
+mod no_activate {
+    use anyhow::{Result, anyhow};
+    use windows_sys::Win32::Foundation::HWND;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GWL_EXSTYLE, GetWindowLongPtrW, SetWindowLongPtrW, WS_EX_NOACTIVATE,
+    };
+    use winit::raw_window_handle::RawWindowHandle;
+
+    use super::WindowBackend;
+
+    pub fn hwnd(window: &dyn WindowBackend) -> Option<HWND> {
+        match window.window_handle().ok()?.as_raw() {
+            RawWindowHandle::Win32(handle) => Some(handle.hwnd.get()),
+            _ => None,
+        }
+    }
+
+    /// Whether `window` has the style
+    pub fn get(window: &dyn WindowBackend) -> bool {
+        // Reading the style of a valid window has no side effects
+        hwnd(window).is_some_and(|hwnd| unsafe {
+            GetWindowLongPtrW(hwnd, GWL_EXSTYLE) & WS_EX_NOACTIVATE as isize != 0
+        })
+    }
+
+    pub fn set(window: &dyn WindowBackend) -> Result<()> {
+        let hwnd = hwnd(window).ok_or_else(|| anyhow!("Not a Win32 window"))?;
+        // Adds to the styles winit set, the window stays valid meanwhile
+        unsafe {
+            let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_NOACTIVATE as isize);
+        }
+        Ok(())
+    }
+}
+
+/// Keep `window` out of taskbars, pagers and alt-tab. X11 window managers
+/// only read the hints when the window is mapped, so this has to happen
+/// while it is still hidden. Wayland has no way to ask for it.
+#[cfg(target_os = "linux")]
+pub fn hide_from_taskbar(window: &Window) -> Result<()> {
+    use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, PropMode};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    let id = match window.window_handle()?.as_raw() {
+        RawWindowHandle::Xlib(handle) => handle.window as u32,
+        RawWindowHandle::Xcb(handle) => handle.window.get(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Wayland windows can't leave the taskbar, layer surfaces are never in it"
+            ));
+        }
+    };
+
+    let (connection, _) = x11rb::connect(None)?;
+    let atom = |name: &str| -> Result<u32> {
+        Ok(connection
+            .intern_atom(false, name.as_bytes())?
+            .reply()?
+            .atom)
+    };
+    let state = atom("_NET_WM_STATE")?;
+    let skip = [
+        atom("_NET_WM_STATE_SKIP_TASKBAR")?,
+        atom("_NET_WM_STATE_SKIP_PAGER")?,
+    ];
+    connection
+        .change_property32(PropMode::APPEND, id, state, AtomEnum::ATOM, &skip)?
+        .check()?;
+    connection.flush()?;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn hide_from_taskbar(window: &Window) -> Result<()> {
+    use winit::platform::windows::WindowExtWindows;
+
+    window.set_skip_taskbar(true);
+    Ok(())
+}
+
+/// Windows on macOS are listed by their application, not on their own
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn hide_from_taskbar(_window: &Window) -> Result<()> {
+    Ok(())
+}
+
+impl WindowBackend for Window {
+    fn id(&self) -> WindowId {
+        Window::id(self)
+    }
+
+    fn inner_size(&self) -> PhysicalSize<u32> {
+        Window::inner_size(self)
+    }
+
+    fn scale_factor(&self) -> f64 {
+        Window::scale_factor(self)
+    }
+
+    fn current_monitor(&self) -> Option<MonitorHandle> {
+        Window::current_monitor(self)
+    }
+
+    fn request_inner_size(&self, size: PhysicalSize<u32>) {
+        let _ = Window::request_inner_size(self, size);
+    }
+
+    fn set_visible(&self, visible: bool) {
+        Window::set_visible(self, visible);
+    }
+
+    fn set_title(&self, title: &str) {
+        Window::set_title(self, title);
+    }
+
+    fn request_redraw(&self) {
+        Window::request_redraw(self);
+    }
+
+    fn outer_position(&self) -> Option<PhysicalPosition<i32>> {
+        Window::outer_position(self).ok()
+    }
+
+    fn set_outer_position(&self, position: PhysicalPosition<i32>) {
+        Window::set_outer_position(self, position);
+    }
+
+    fn drag_window(&self) -> bool {
+        Window::drag_window(self).is_ok()
+    }
+
+    fn set_fullscreen(&self, fullscreen: bool) -> bool {
+        Window::set_fullscreen(
+            self,
+            fullscreen.then(|| Fullscreen::Borderless(self.current_monitor())),
+        );
+        true
+    }
+
+    fn set_click_through(&self, click_through: bool) {
+        // Winit replaces the extended style on Windows, dropping what
+        // prevent_focus added to it
+        #[cfg(target_os = "windows")]
+        let no_activate = no_activate::get(self);
+        if let Err(e) = self.set_cursor_hittest(!click_through) {
+            log::warn!("Can't change whether clicks pass through: {}", e);
+        }
+        #[cfg(target_os = "windows")]
+        if no_activate && let Err(e) = no_activate::set(self) {
+            log::warn!("The overlay may take the focus again: {}", e);
+        }
+    }
+
+    fn set_input_shape(&self, rectangles: Option<&[[i32; 4]]>) -> bool {
+        match set_input_shape(self, rectangles) {
+            Ok(()) => true,
+            Err(e) => {
+                log::debug!("No input shape: {}", e);
+                false
+            }
+        }
+    }
+
+    fn set_cursor(&self, style: CursorStyle) {
+        self.set_cursor_visible(style != CursorStyle::Hidden);
+        Window::set_cursor(
+            self,
+            if style == CursorStyle::Grab {
+                CursorIcon::Grab
+            } else {
+                CursorIcon::Default
+            },
+        );
+    }
+}
+
+/// Name of `monitor` as `--monitor` matches it
+pub fn monitor_name(monitor: &MonitorHandle) -> String {
+    monitor.name().unwrap_or_default()
+}
+
+/// Position of the monitor `query` refers to among `names`, either its
+/// index or the first name containing it, ignoring case
+pub fn find_monitor(names: &[String], query: &str) -> Option<usize> {
+    if let Ok(index) = query.parse::<usize>() {
+        return (index < names.len()).then_some(index);
+    }
+
+    let query = query.to_lowercase();
+    names
+        .iter()
+        .position(|name| name.to_lowercase().contains(&query))
+}
+
+/// Top left corner of a window of `size` centered in the area at `origin`
+/// of `area`
+pub fn centered_position(
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    PhysicalPosition::new(
+        origin.x + (area.width as i32 - size.width as i32) / 2,
+        origin.y + (area.height as i32 - size.height as i32) / 2,
+    )
+}
+
+/// Where a window of `size` goes when asked for `requested` with the
+/// monitor at `origin` of `area` chosen. Negative coordinates count from
+/// the monitor's right and bottom edges to the window's, others are
+/// absolute.
+pub fn resolve_position(
+    requested: PhysicalPosition<i32>,
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let resolve = |requested: i32, origin: i32, area: u32, size: u32| {
+        if requested < 0 {
+            origin + area as i32 - size as i32 + requested
+        } else {
+            requested
+        }
+    };
+    PhysicalPosition::new(
+        resolve(requested.x, origin.x, area.width, size.width),
+        resolve(requested.y, origin.y, area.height, size.height),
+    )
+}
+
+/// Top left corner of a window of `size` placed as `placement` asks in the
+/// monitor at `origin` of `area`
+pub fn anchored_position(
+    placement: Placement,
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let (margin_x, margin_y) = (placement.margin.0 as i32, placement.margin.1 as i32);
+    let left = origin.x + margin_x;
+    let right = origin.x + area.width as i32 - size.width as i32 - margin_x;
+    let top = origin.y + margin_y;
+    let bottom = origin.y + area.height as i32 - size.height as i32 - margin_y;
+    match placement.corner {
+        Corner::TopLeft => PhysicalPosition::new(left, top),
+        Corner::TopRight => PhysicalPosition::new(right, top),
+        Corner::BottomLeft => PhysicalPosition::new(left, bottom),
+        Corner::BottomRight => PhysicalPosition::new(right, bottom),
+        Corner::Center => centered_position(origin, area, size),
+    }
+}
+
+/// Whether winit windows are Wayland windows, which can't choose where
+/// they are shown
+#[cfg(target_os = "linux")]
+pub fn is_wayland(event_loop: &ActiveEventLoop) -> bool {
+    use winit::platform::wayland::ActiveEventLoopExtWayland;
+
+    event_loop.is_wayland()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_wayland(_event_loop: &ActiveEventLoop) -> bool {
+    false
+}
+
+/// One line describing the monitor at `index`, as listed by `list_monitors`
+fn describe_monitor((index, monitor): (usize, MonitorHandle)) -> String {
+    let size = monitor.size();
+    format!(
+        "{}: {} ({}x{}, scale {})",
+        index,
+        monitor.name().as_deref().unwrap_or("unnamed"),
+        size.width,
+        size.height,
+        monitor.scale_factor()
+    )
+}
+
+/// Collects the monitors once the event loop starts, then stops it
+#[derive(Default)]
+struct MonitorLister {
+    monitors: Vec<String>,
+}
+
+impl ApplicationHandler for MonitorLister {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.monitors = event_loop
+            .available_monitors()
+            .enumerate()
+            .map(describe_monitor)
+            .collect();
+        event_loop.exit();
+    }
+
+    fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, _: WindowEvent) {}
+}
+
+/// Every monitor `--monitor` can choose from, one line each. Monitors are
+/// only known to a running event loop, so this runs one briefly.
+pub fn list_monitors() -> Result<Vec<String>> {
+    let mut lister = MonitorLister::default();
+    EventLoop::new()?.run_app(&mut lister)?;
+    Ok(lister.monitors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_monitor() {
+        let names = [
+            "eDP-1".to_string(),
+            "DP-2".to_string(),
+            "HDMI-A-1".to_string(),
+        ];
+        assert_eq!(find_monitor(&names, "2"), Some(2));
+        assert_eq!(find_monitor(&names, "3"), None);
+        assert_eq!(find_monitor(&names, "hdmi"), Some(2));
+        assert_eq!(find_monitor(&names, "DP"), Some(0));
+        assert_eq!(find_monitor(&names, "VGA"), None);
+    }
+
+    #[test]
+    fn test_drag_modifier() {
+        assert!(DragModifier::None.is_held(ModifiersState::empty()));
+        assert!(!DragModifier::Alt.is_held(ModifiersState::empty()));
+        assert!(DragModifier::Alt.is_held(ModifiersState::ALT | ModifiersState::SHIFT));
+        assert!(!DragModifier::Ctrl.is_held(ModifiersState::ALT));
+        assert!(DragModifier::Super.is_held(ModifiersState::SUPER));
+    }
+
+    #[test]
+    fn test_raw_keys() {
+        let keys = HashSet::from([KeyCode::ControlRight, KeyCode::KeyA, KeyCode::AltLeft]);
+        assert_eq!(
+            held_modifiers(&keys),
+            ModifiersState::CONTROL | ModifiersState::ALT
+        );
+        assert_eq!(held_modifiers(&HashSet::new()), ModifiersState::empty());
+
+        assert_eq!(
+            key_for_code(KeyCode::Escape, true),
+            Some(Key::Named(NamedKey::Escape))
+        );
+        let text =
+            |code, shift| key_for_code(code, shift).and_then(|key| key.to_text().map(String::from));
+        assert_eq!(text(KeyCode::KeyQ, false).as_deref(), Some("q"));
+        assert_eq!(text(KeyCode::KeyQ, true), None);
+        assert_eq!(text(KeyCode::Equal, false).as_deref(), Some("="));
+        assert_eq!(text(KeyCode::Equal, true).as_deref(), Some("+"));
+        assert_eq!(text(KeyCode::Numpad0, false).as_deref(), Some("0"));
+        assert_eq!(key_for_code(KeyCode::KeyA, false), None);
+    }
+
+    #[test]
+    fn test_centered_position() {
+        let position = centered_position(
+            PhysicalPosition::new(1920, 0),
+            PhysicalSize::new(2560, 1440),
+            PhysicalSize::new(200, 100),
+        );
+        assert_eq!(position, PhysicalPosition::new(1920 + 1180, 670));
+
+        // Windows larger than the monitor overhang it evenly
+        let position = centered_position(
+            PhysicalPosition::new(0, 0),
+            PhysicalSize::new(100, 100),
+            PhysicalSize::new(300, 100),
+        );
+        assert_eq!(position, PhysicalPosition::new(-100, 0));
+    }
+
+    #[test]
+    fn test_anchored_position() {
+        let origin = PhysicalPosition::new(1920, 0);
+        let area = PhysicalSize::new(2560, 1440);
+        let size = PhysicalSize::new(200, 100);
+        let anchor = |corner| {
+            let placement = Placement {
+                corner,
+                margin: (24, 16),
+            };
+            anchored_position(placement, origin, area, size)
+        };
+
+        assert_eq!(
+            anchor(Corner::TopLeft),
+            PhysicalPosition::new(1920 + 24, 16)
+        );
+        assert_eq!(
+            anchor(Corner::BottomRight),
+            PhysicalPosition::new(1920 + 2560 - 200 - 24, 1440 - 100 - 16)
+        );
+        assert_eq!(
+            anchor(Corner::TopRight),
+            PhysicalPosition::new(1920 + 2560 - 200 - 24, 16)
+        );
+        assert_eq!(
+            anchor(Corner::Center),
+            centered_position(origin, area, size)
+        );
+    }
+
+    #[test]
+    fn test_resolve_position() {
+        let origin = PhysicalPosition::new(1920, 0);
+        let area = PhysicalSize::new(2560, 1440);
+        let size = PhysicalSize::new(200, 100);
+        let resolve = |x, y| resolve_position(PhysicalPosition::new(x, y), origin, area, size);
+
+        assert_eq!(resolve(100, 50), PhysicalPosition::new(100, 50));
+        assert_eq!(
+            resolve(-20, -20),
+            PhysicalPosition::new(1920 + 2560 - 200 - 20, 1440 - 100 - 20)
+        );
+        assert_eq!(resolve(0, -1), PhysicalPosition::new(0, 1440 - 100 - 1));
+    }
+}